@@ -165,6 +165,103 @@ async fn handle_content_uri(
     }
 }
 
+/// A picked file, mapped to its entry in the collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PickedEntry {
+    /// Name of the file in the collection.
+    name: String,
+    /// The path or content URI to read the file from.
+    source: String,
+    /// Whether `source` is a content URI that can't be read with `std::fs`.
+    is_content_uri: bool,
+}
+
+/// Map picked files to collection entries.
+///
+/// Names are taken from the picker (falling back to the last path component),
+/// sanitized so they can't escape the export directory, and made unique by
+/// appending ` (n)` before the extension.
+fn plan_picked_files(files: &[PickerFileInfo]) -> Vec<PickedEntry> {
+    let mut used = std::collections::HashSet::new();
+    let mut entries = Vec::with_capacity(files.len());
+    for file in files {
+        let source = if file.path.is_empty() {
+            file.uri.clone()
+        } else {
+            file.path.clone()
+        };
+        let name = if file.name.is_empty() {
+            std::path::Path::new(&source)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            file.name.clone()
+        };
+        let mut name = name.replace(['/', '\\', '\0'], "_");
+        if name.is_empty() || name == "." || name == ".." {
+            name = "file".to_string();
+        }
+
+        let mut unique = name.clone();
+        let mut n = 1;
+        while !used.insert(unique.clone()) {
+            unique = match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+                _ => format!("{} ({})", name, n),
+            };
+            n += 1;
+        }
+
+        entries.push(PickedEntry {
+            name: unique,
+            is_content_uri: source.starts_with("content://"),
+            source,
+        });
+    }
+    entries
+}
+
+/// Turn picked files into import entries for a single collection.
+///
+/// Regular paths (desktop and iOS) are imported in place. Content URIs
+/// (Android) are opened through `tauri_plugin_fs` one at a time as they are
+/// imported, and streamed into the store without a temporary file.
+fn import_picked(
+    app: &AppHandle,
+    files: &[PickerFileInfo],
+) -> Result<Vec<sendme_lib::ImportEntry>, String> {
+    use std::str::FromStr;
+    use tauri_plugin_fs::FilePath;
+
+    let mut entries = Vec::with_capacity(files.len());
+    for picked in plan_picked_files(files) {
+        log_info!("📄 Picked {} -> {}", picked.source, picked.name);
+        let entry = if picked.is_content_uri {
+            let file_path = FilePath::from_str(&picked.source)
+                .map_err(|e| format!("Failed to parse file path: {:?}", e))?;
+            let app = app.clone();
+            let open = sendme_lib::OpenFile::new(move || {
+                let mut options = tauri_plugin_fs::OpenOptions::new();
+                options.read(true);
+                app.fs().open(file_path.clone(), options)
+            });
+            sendme_lib::ImportEntry::Open {
+                name: picked.name,
+                open,
+            }
+        } else {
+            sendme_lib::ImportEntry::Path {
+                name: picked.name,
+                path: std::path::PathBuf::from(picked.source),
+            }
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
 /// Copy exported files from temp_dir to a content URI on Android.
 ///
 /// Uses JNI to call Android's ContentResolver to create and write files
@@ -415,6 +512,16 @@ pub struct SendFileRequest {
     pub filename: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendFilesRequest {
+    /// Files returned by the picker, sent together as one collection.
+    pub files: Vec<PickerFileInfo>,
+    pub ticket_type: String,
+    /// Put the files in a folder with this name on the receiver.
+    #[serde(default)]
+    pub root: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReceiveFileRequest {
     pub ticket: String,
@@ -443,6 +550,100 @@ type Transfers = Arc<RwLock<HashMap<String, TransferState>>>;
 struct TransferState {
     info: TransferInfo,
     abort_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    progress: ProgressSnapshot,
+    /// The progress events of a running transfer, see [`replay_progress`].
+    broadcast: Option<ProgressBroadcast>,
+}
+
+/// How often the progress of a receive is saved for crash recovery.
+const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The last known state of a receive, saved to disk while it runs.
+///
+/// If the app exits without finishing the receive, the checkpoint is still
+/// there on the next start, and the transfer is shown as interrupted. It can
+/// then be resumed, which picks up the data already downloaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProgressCheckpoint {
+    info: TransferInfo,
+    output_dir: Option<String>,
+    progress: ProgressSnapshot,
+}
+
+impl ProgressCheckpoint {
+    fn path(dir: &std::path::Path, id: &str) -> std::path::PathBuf {
+        dir.join(format!("{}.json", id))
+    }
+
+    /// Save the checkpoint, replacing the previous one atomically.
+    fn save(&self, dir: &std::path::Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let path = Self::path(dir, &self.info.id);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    fn remove(dir: &std::path::Path, id: &str) {
+        let _ = std::fs::remove_file(Self::path(dir, id));
+    }
+
+    /// Load all checkpoints in `dir`, skipping unreadable ones.
+    fn load_all(dir: &std::path::Path) -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                let data = std::fs::read(entry.path()).ok()?;
+                serde_json::from_slice(&data).ok()
+            })
+            .collect()
+    }
+
+    /// The state of the transfer after the app was restarted.
+    fn into_interrupted(self) -> TransferState {
+        let mut info = self.info;
+        info.status = "interrupted".to_string();
+        TransferState {
+            info,
+            abort_tx: None,
+            progress: self.progress,
+            broadcast: None,
+        }
+    }
+}
+
+fn checkpoint_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("checkpoints"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Save a checkpoint of a running receive.
+async fn save_checkpoint(
+    transfers: &Transfers,
+    dir: &std::path::Path,
+    id: &str,
+    output_dir: &Option<String>,
+) {
+    let checkpoint = {
+        let transfers_guard = transfers.read().await;
+        let Some(state) = transfers_guard.get(id) else {
+            return;
+        };
+        ProgressCheckpoint {
+            info: state.info.clone(),
+            output_dir: output_dir.clone(),
+            progress: state.progress.clone(),
+        }
+    };
+    if let Err(e) = checkpoint.save(dir) {
+        log_warn!("Failed to save progress checkpoint: {}", e);
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -499,16 +700,28 @@ pub fn run() {
 
     builder
         .setup(move |app| {
+            // Show receives that were interrupted by a crash, so they can be resumed
+            if let Ok(dir) = checkpoint_dir(app.handle()) {
+                let mut transfers_guard = transfers.blocking_write();
+                for checkpoint in ProgressCheckpoint::load_all(&dir) {
+                    let id = checkpoint.info.id.clone();
+                    transfers_guard.insert(id, checkpoint.into_interrupted());
+                }
+            }
             // Store transfers in app state
             app.manage(transfers.clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             send_file,
+            send_files,
             receive_file,
+            resume_transfer,
             cancel_transfer,
             get_transfers,
             get_transfer_status,
+            get_transfer_progress,
+            replay_progress,
             clear_transfers,
             get_hostname,
             get_device_model,
@@ -516,12 +729,36 @@ pub fn run() {
             open_received_file,
             list_received_files,
             pick_file,
-            pick_directory
+            pick_directory,
+            ticket_qr
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+/// A ticket to show as a QR code, see [`ticket_qr`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TicketQr {
+    /// The ticket to encode, with less address information if needed.
+    pub ticket: String,
+    /// Whether the full ticket fits, i.e. `ticket` is unchanged.
+    pub fits: bool,
+}
+
+/// The ticket to encode in a QR code with error correction level `ecl`.
+///
+/// Tickets too long for a code that scans reliably are shortened by
+/// dropping address information, see [`sendme_lib::qr::fitting_ticket`].
+#[tauri::command]
+fn ticket_qr(ticket: String, ecl: sendme_lib::qr::Ecl) -> Result<TicketQr, String> {
+    let parsed = sendme_lib::links::parse_ticket(&ticket).map_err(|e| e.to_string())?;
+    let fitting = sendme_lib::qr::fitting_ticket(&parsed, ecl);
+    Ok(TicketQr {
+        fits: fitting == parsed,
+        ticket: fitting.to_string(),
+    })
+}
+
 #[tauri::command]
 async fn send_file(
     app: AppHandle,
@@ -542,33 +779,12 @@ async fn send_file(
     let transfer_id = Uuid::new_v4().to_string();
     log_info!("📝 Generated transfer_id: {}", transfer_id);
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+    let (broadcast, tx) = ProgressBroadcast::new(ProgressOptions {
+        latch_metadata: true,
+    });
     let (abort_tx, abort_rx) = tokio::sync::oneshot::channel();
 
-    // Parse ticket type
-    let ticket_type = match request.ticket_type.as_str() {
-        "id" => {
-            log_info!("🎫 Ticket type: ID only");
-            Ok(sendme_lib::types::AddrInfoOptions::Id)
-        }
-        "relay" => {
-            log_info!("🎫 Ticket type: Relay");
-            Ok(sendme_lib::types::AddrInfoOptions::Relay)
-        }
-        "addresses" => {
-            log_info!("🎫 Ticket type: Addresses (local-only)");
-            Ok(sendme_lib::types::AddrInfoOptions::Addresses)
-        }
-        "relay_and_addresses" => {
-            log_info!("🎫 Ticket type: Relay + Addresses");
-            Ok(sendme_lib::types::AddrInfoOptions::RelayAndAddresses)
-        }
-        _ => {
-            let err = format!("Invalid ticket type: {}", request.ticket_type);
-            log_error!("❌ {}", err);
-            Err(err)
-        }
-    }?;
+    let ticket_type = parse_ticket_type(&request.ticket_type)?;
 
     // Get temp directory for macOS sandbox compatibility
     log_info!("📁 Getting temp directory...");
@@ -589,12 +805,12 @@ async fn send_file(
     log_info!("✅ Display name: {}", display_name);
 
     let args = SendArgs {
-        path: file_path,
         ticket_type,
         common: CommonConfig {
             temp_dir: Some(temp_dir),
             ..Default::default()
         },
+        ..SendArgs::new(file_path)
     };
     log_info!("⚙️  SendArgs created successfully");
 
@@ -624,22 +840,236 @@ async fn send_file(
         TransferState {
             info: transfer_info.clone(),
             abort_tx: Some(abort_tx),
+            progress: ProgressSnapshot::default(),
+            broadcast: Some(broadcast.clone()),
         },
     );
     drop(transfers_guard);
     log_info!("✅ Transfer stored with id: {}", transfer_id);
 
-    let app_clone = app.clone();
-    let transfers_clone = transfers.inner().clone();
-    let transfer_id_clone = transfer_id.clone();
+    spawn_send_progress_task(
+        app.clone(),
+        transfers.inner().clone(),
+        transfer_id.clone(),
+        broadcast.subscribe(),
+        abort_rx,
+    );
+
+    log_info!("🚀 Calling sendme_lib::send_with_progress...");
+    match sendme_lib::send_with_progress(args, tx).await {
+        Ok(result) => {
+            log_info!("═══════════════════════════════════════════════════");
+            log_info!("✅ SEND COMPLETED SUCCESSFULLY");
+            log_info!("═══════════════════════════════════════════════════");
+            log_info!("🎫 Ticket: {}", result.ticket.to_string());
+            log_info!("📊 Transfer ID: {}", transfer_id);
+            update_transfer_status(transfers.inner(), &transfer_id, "serving").await;
+            Ok(result.ticket.to_string())
+        }
+        Err(e) => {
+            log_error!("═══════════════════════════════════════════════════");
+            log_error!("❌ SEND FAILED");
+            log_error!("═══════════════════════════════════════════════════");
+            log_error!("Error: {}", e);
+            log_error!("Transfer ID: {}", transfer_id);
+            update_transfer_status(transfers.inner(), &transfer_id, &format!("error: {}", e)).await;
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Send several picked files as a single collection with one ticket.
+#[tauri::command]
+async fn send_files(
+    app: AppHandle,
+    transfers: tauri::State<'_, Transfers>,
+    request: SendFilesRequest,
+) -> Result<String, String> {
+    log_info!("📤 SEND_FILES STARTED with {} files", request.files.len());
+    if request.files.is_empty() {
+        return Err("No files selected".to_string());
+    }
+
+    let transfer_id = Uuid::new_v4().to_string();
+    let (broadcast, tx) = ProgressBroadcast::new(ProgressOptions {
+        latch_metadata: true,
+    });
+    let (abort_tx, abort_rx) = tokio::sync::oneshot::channel();
+
+    let ticket_type = parse_ticket_type(&request.ticket_type)?;
+    let temp_dir = app
+        .path()
+        .temp_dir()
+        .map_err(|e| format!("Failed to get temp directory: {}", e))?;
+    let entries = import_picked(&app, &request.files)?;
+
+    let display_name = match (&request.root, entries.as_slice()) {
+        (Some(root), _) => root.clone(),
+        (None, [entry]) => entry.name().to_string(),
+        (None, _) => format!("{} files", entries.len()),
+    };
+    let args = SendManyArgs {
+        entries,
+        root: request.root,
+        ticket_type,
+        common: CommonConfig {
+            temp_dir: Some(temp_dir),
+            ..Default::default()
+        },
+        on_connect: None,
+    };
+
+    let transfer_info = TransferInfo {
+        id: transfer_id.clone(),
+        transfer_type: "send".to_string(),
+        path: display_name,
+        status: "initializing".to_string(),
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64,
+    };
+    transfers.write().await.insert(
+        transfer_id.clone(),
+        TransferState {
+            info: transfer_info,
+            abort_tx: Some(abort_tx),
+            progress: ProgressSnapshot::default(),
+            broadcast: Some(broadcast.clone()),
+        },
+    );
+
+    spawn_send_progress_task(
+        app.clone(),
+        transfers.inner().clone(),
+        transfer_id.clone(),
+        broadcast.subscribe(),
+        abort_rx,
+    );
+
+    match sendme_lib::send_many_with_progress(args, tx).await {
+        Ok(result) => {
+            log_info!("✅ SEND_FILES COMPLETED, ticket: {}", result.ticket);
+            update_transfer_status(transfers.inner(), &transfer_id, "serving").await;
+            Ok(result.ticket.to_string())
+        }
+        Err(e) => {
+            log_error!("❌ SEND_FILES FAILED: {}", e);
+            update_transfer_status(transfers.inner(), &transfer_id, &format!("error: {}", e)).await;
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Parse the ticket type sent by the frontend.
+fn parse_ticket_type(ticket_type: &str) -> Result<AddrInfoOptions, String> {
+    match ticket_type {
+        "id" => {
+            log_info!("🎫 Ticket type: ID only");
+            Ok(sendme_lib::types::AddrInfoOptions::Id)
+        }
+        "relay" => {
+            log_info!("🎫 Ticket type: Relay");
+            Ok(sendme_lib::types::AddrInfoOptions::Relay)
+        }
+        "addresses" => {
+            log_info!("🎫 Ticket type: Addresses (local-only)");
+            Ok(sendme_lib::types::AddrInfoOptions::Addresses)
+        }
+        "relay_and_addresses" => {
+            log_info!("🎫 Ticket type: Relay + Addresses");
+            Ok(sendme_lib::types::AddrInfoOptions::RelayAndAddresses)
+        }
+        _ => {
+            let err = format!("Invalid ticket type: {}", ticket_type);
+            log_error!("❌ {}", err);
+            Err(err)
+        }
+    }
+}
+
+/// The status of a transfer after `event`, if the event changes it.
+fn progress_status(event: &ProgressEvent) -> Option<String> {
+    match event {
+        ProgressEvent::Import(name, _) => Some(format!("importing: {}", name)),
+        ProgressEvent::Export(name, _) => Some(format!("exporting: {}", name)),
+        ProgressEvent::Download(_) => Some("downloading".to_string()),
+        ProgressEvent::Connection(ConnectionStatus::ServerReady { .. }) => None,
+        ProgressEvent::Connection(status) => Some(format!("connection: {:?}", status)),
+        ProgressEvent::SendReady { .. } => Some("serving".to_string()),
+    }
+}
+
+/// The `progress` event to emit to the frontend for `event`.
+fn progress_update(transfer_id: &str, event: ProgressEvent) -> ProgressUpdate {
+    match event {
+        ProgressEvent::Import(name, progress) => ProgressUpdate {
+            event_type: "import".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "name": name,
+                "progress": serialize_import_progress(&progress),
+            }),
+        },
+        ProgressEvent::Export(name, progress) => ProgressUpdate {
+            event_type: "export".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "name": name,
+                "progress": serialize_export_progress(&progress),
+            }),
+        },
+        ProgressEvent::Download(progress) => ProgressUpdate {
+            event_type: "download".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "progress": serialize_download_progress(&progress),
+            }),
+        },
+        ProgressEvent::Connection(ConnectionStatus::ServerReady { reachable }) => ProgressUpdate {
+            event_type: "server_ready".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "reachable": reachable,
+            }),
+        },
+        ProgressEvent::Connection(status) => ProgressUpdate {
+            event_type: "connection".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "status": format!("{:?}", status),
+            }),
+        },
+        ProgressEvent::SendReady {
+            ticket,
+            hash,
+            total_size,
+        } => ProgressUpdate {
+            event_type: "ready".to_string(),
+            data: serde_json::json!({
+                "transfer_id": transfer_id,
+                "ticket": ticket.to_string(),
+                "hash": hash.to_hex(),
+                "total_size": total_size,
+            }),
+        },
+    }
+}
+
+/// Forward the progress events of a send to the frontend and keep the
+/// transfer status up to date.
+fn spawn_send_progress_task(
+    app: AppHandle,
+    transfers: Transfers,
+    transfer_id: String,
+    mut rx: ProgressReceiverRx,
+    abort_rx: tokio::sync::oneshot::Receiver<()>,
+) {
     let transfer_id_for_abort = transfer_id.clone();
 
     log_info!("🔄 Spawning progress listener task...");
     tokio::spawn(async move {
-        log_info!(
-            "  [Progress Task] Started for transfer: {}",
-            transfer_id_clone
-        );
+        log_info!("  [Progress Task] Started for transfer: {}", transfer_id);
 
         // Listen for abort signal
         tokio::spawn(async move {
@@ -661,99 +1091,20 @@ async fn send_file(
                     ProgressEvent::Export(name, _) => format!("Export({})", name),
                     ProgressEvent::Download(_) => "Download".to_string(),
                     ProgressEvent::Connection(status) => format!("Connection({:?})", status),
+                    ProgressEvent::SendReady { hash, .. } => format!("SendReady({})", hash),
                 }
             );
-
-            let update = match event {
-                ProgressEvent::Import(name, progress) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("importing: {}", name),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "import".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "name": name,
-                            "progress": serialize_import_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Export(name, progress) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("exporting: {}", name),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "export".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "name": name,
-                            "progress": serialize_export_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Download(progress) => {
-                    update_transfer_status(&transfers_clone, &transfer_id_clone, "downloading")
-                        .await;
-                    ProgressUpdate {
-                        event_type: "download".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "progress": serialize_download_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Connection(status) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("connection: {:?}", status),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "connection".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "status": format!("{:?}", status),
-                        }),
-                    }
-                }
-            };
-
-            let _ = app_clone.emit("progress", update);
+            update_transfer_progress(&transfers, &transfer_id, &event).await;
+            if let Some(status) = progress_status(&event) {
+                update_transfer_status(&transfers, &transfer_id, &status).await;
+            }
+            let _ = app.emit("progress", progress_update(&transfer_id, event));
         }
 
         log_info!("  [Progress Task] Completed. Total events: {}", event_count);
         // Mark transfer as complete
-        update_transfer_status(&transfers_clone, &transfer_id_clone, "completed").await;
+        update_transfer_status(&transfers, &transfer_id, "completed").await;
     });
-
-    log_info!("🚀 Calling sendme_lib::send_with_progress...");
-    match sendme_lib::send_with_progress(args, tx).await {
-        Ok(result) => {
-            log_info!("═══════════════════════════════════════════════════");
-            log_info!("✅ SEND COMPLETED SUCCESSFULLY");
-            log_info!("═══════════════════════════════════════════════════");
-            log_info!("🎫 Ticket: {}", result.ticket.to_string());
-            log_info!("📊 Transfer ID: {}", transfer_id);
-            update_transfer_status(transfers.inner(), &transfer_id, "serving").await;
-            Ok(result.ticket.to_string())
-        }
-        Err(e) => {
-            log_error!("═══════════════════════════════════════════════════");
-            log_error!("❌ SEND FAILED");
-            log_error!("═══════════════════════════════════════════════════");
-            log_error!("Error: {}", e);
-            log_error!("Transfer ID: {}", transfer_id);
-            update_transfer_status(transfers.inner(), &transfer_id, &format!("error: {}", e)).await;
-            Err(e.to_string())
-        }
-    }
 }
 
 #[tauri::command]
@@ -768,7 +1119,9 @@ async fn receive_file(
     let transfer_id = Uuid::new_v4().to_string();
     log_info!("Transfer ID: {}", transfer_id);
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+    let (broadcast, tx) = ProgressBroadcast::new(ProgressOptions {
+        latch_metadata: true,
+    });
     let (abort_tx, _abort_rx) = tokio::sync::oneshot::channel();
 
     // On Android, set_current_dir doesn't work with public directories due to sandboxing.
@@ -862,7 +1215,6 @@ async fn receive_file(
     );
 
     let args = ReceiveArgs {
-        ticket,
         common: CommonConfig {
             format: Format::Hex,
             relay: RelayModeOption::Default,
@@ -870,8 +1222,18 @@ async fn receive_file(
             magic_ipv4_addr: None,
             magic_ipv6_addr: None,
             temp_dir: Some(temp_dir.clone()),
+            priority: Priority::default(),
+            bind_port: None,
+            normalize_names: true,
+            import_batch_threshold: sendme_lib::import::DEFAULT_BATCH_THRESHOLD,
+            store_backend: StoreBackend::default(),
+            timeout: None,
+            max_upload_bps: None,
+            max_download_bps: None,
+            temp_prefix: None,
         },
         export_dir,
+        ..ReceiveArgs::new(ticket)
     };
 
     // Create transfer info
@@ -895,14 +1257,19 @@ async fn receive_file(
         TransferState {
             info: transfer_info.clone(),
             abort_tx: Some(abort_tx),
+            progress: ProgressSnapshot::default(),
+            broadcast: Some(broadcast.clone()),
         },
     );
     drop(transfers_guard);
     log_info!("✅ Transfer stored with id: {}", transfer_id);
 
+    let mut rx = broadcast.subscribe();
     let app_clone = app.clone();
     let transfers_clone = transfers.inner().clone();
     let transfer_id_clone = transfer_id.clone();
+    let checkpoints = checkpoint_dir(&app).ok();
+    let output_dir = request.output_dir.clone();
 
     log_info!("🔄 Spawning progress listener task...");
     tokio::spawn(async move {
@@ -911,6 +1278,7 @@ async fn receive_file(
             transfer_id_clone
         );
         let mut event_count = 0;
+        let mut last_checkpoint: Option<std::time::Instant> = None;
         while let Some(event) = rx.recv().await {
             event_count += 1;
             log_info!(
@@ -921,74 +1289,28 @@ async fn receive_file(
                     ProgressEvent::Export(name, _) => format!("Export({})", name),
                     ProgressEvent::Download(_) => "Download".to_string(),
                     ProgressEvent::Connection(status) => format!("Connection({:?})", status),
+                    ProgressEvent::SendReady { hash, .. } => format!("SendReady({})", hash),
                 }
             );
-
-            let update = match event {
-                ProgressEvent::Import(name, progress) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("importing: {}", name),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "import".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "name": name,
-                            "progress": serialize_import_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Export(name, progress) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("exporting: {}", name),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "export".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "name": name,
-                            "progress": serialize_export_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Download(progress) => {
-                    update_transfer_status(&transfers_clone, &transfer_id_clone, "downloading")
-                        .await;
-                    ProgressUpdate {
-                        event_type: "download".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "progress": serialize_download_progress(&progress),
-                        }),
-                    }
-                }
-                ProgressEvent::Connection(status) => {
-                    update_transfer_status(
-                        &transfers_clone,
-                        &transfer_id_clone,
-                        &format!("connection: {:?}", status),
-                    )
-                    .await;
-                    ProgressUpdate {
-                        event_type: "connection".to_string(),
-                        data: serde_json::json!({
-                            "transfer_id": transfer_id_clone,
-                            "status": format!("{:?}", status),
-                        }),
-                    }
+            update_transfer_progress(&transfers_clone, &transfer_id_clone, &event).await;
+            if let Some(ref dir) = checkpoints {
+                if last_checkpoint.map_or(true, |t| t.elapsed() >= CHECKPOINT_INTERVAL) {
+                    save_checkpoint(&transfers_clone, dir, &transfer_id_clone, &output_dir).await;
+                    last_checkpoint = Some(std::time::Instant::now());
                 }
-            };
+            }
 
-            let _ = app_clone.emit("progress", update);
+            if let Some(status) = progress_status(&event) {
+                update_transfer_status(&transfers_clone, &transfer_id_clone, &status).await;
+            }
+            let _ = app_clone.emit("progress", progress_update(&transfer_id_clone, event));
         }
 
         log_info!("  [Progress Task] Completed. Total events: {}", event_count);
+        // The receive returned, so there is nothing left to recover
+        if let Some(ref dir) = checkpoints {
+            ProgressCheckpoint::remove(dir, &transfer_id_clone);
+        }
         // Mark transfer as complete
         update_transfer_status(&transfers_clone, &transfer_id_clone, "completed").await;
     });
@@ -999,9 +1321,11 @@ async fn receive_file(
         Ok(result) => {
             log_info!("✅ RECEIVE COMPLETED");
             log_info!(
-                "Files: {}, Bytes: {}",
+                "Files: {}, Bytes: {}, {:.0} B/s (peak {:.0} B/s)",
                 result.total_files,
-                result.stats.total_bytes_read()
+                result.stats.total_bytes_read(),
+                result.average_throughput(),
+                result.peak_throughput
             );
 
             // If output was a content URI, copy files from temp_dir to the content URI
@@ -1025,12 +1349,14 @@ async fn receive_file(
             }
 
             update_transfer_status(transfers.inner(), &transfer_id, "completed").await;
-            Ok(format!(
-                "{{\"transfer_id\": \"{}\", \"files\": {}, \"bytes\": {}}}",
-                transfer_id,
-                result.total_files,
-                result.stats.total_bytes_read()
-            ))
+            Ok(serde_json::json!({
+                "transfer_id": transfer_id,
+                "files": result.total_files,
+                "bytes": result.stats.total_bytes_read(),
+                "average_throughput": result.average_throughput(),
+                "peak_throughput": result.peak_throughput,
+            })
+            .to_string())
         }
         Err(e) => {
             log_error!("❌ RECEIVE FAILED: {}", e);
@@ -1060,6 +1386,31 @@ async fn cancel_transfer(
     }
 }
 
+/// Resume a receive that was interrupted by a crash.
+///
+/// Data that was already downloaded is kept, so only the rest is fetched.
+/// Returns the id of the new transfer.
+#[tauri::command]
+async fn resume_transfer(
+    app: AppHandle,
+    transfers: tauri::State<'_, Transfers>,
+    id: String,
+) -> Result<String, String> {
+    let dir = checkpoint_dir(&app)?;
+    let checkpoint = ProgressCheckpoint::load_all(&dir)
+        .into_iter()
+        .find(|checkpoint| checkpoint.info.id == id)
+        .ok_or_else(|| "No interrupted transfer with this id".to_string())?;
+    transfers.write().await.remove(&id);
+    ProgressCheckpoint::remove(&dir, &id);
+
+    let request = ReceiveFileRequest {
+        ticket: checkpoint.info.path,
+        output_dir: checkpoint.output_dir,
+    };
+    receive_file(app, transfers, request).await
+}
+
 #[tauri::command]
 async fn get_transfers(
     transfers: tauri::State<'_, Transfers>,
@@ -1084,7 +1435,57 @@ async fn get_transfer_status(
     }
 }
 
+/// Get a snapshot of the progress of a transfer.
+///
+/// Unlike the `progress` events, this can be polled at any time, e.g. after
+/// the UI was in the background and missed some events.
+#[tauri::command]
+async fn get_transfer_progress(
+    transfers: tauri::State<'_, Transfers>,
+    id: String,
+) -> Result<ProgressSnapshot, String> {
+    let transfers_guard = transfers.read().await;
+    if let Some(state) = transfers_guard.get(&id) {
+        Ok(state.progress.clone())
+    } else {
+        Err("Transfer not found".to_string())
+    }
+}
+
+/// The latest progress events of a running transfer: the file list and the
+/// event after it.
+///
+/// A frontend that starts listening to `progress` events after the transfer
+/// started calls this to catch up, since it missed the `metadata` event.
+#[tauri::command]
+async fn replay_progress(
+    transfers: tauri::State<'_, Transfers>,
+    id: String,
+) -> Result<Vec<ProgressUpdate>, String> {
+    let broadcast = {
+        let transfers_guard = transfers.read().await;
+        let state = transfers_guard.get(&id).ok_or("Transfer not found")?;
+        state.broadcast.clone()
+    };
+    let mut updates = Vec::new();
+    if let Some(broadcast) = broadcast {
+        // the latched events are queued by the time `subscribe` returns
+        let mut rx = broadcast.subscribe();
+        while let Ok(event) = rx.try_recv() {
+            updates.push(progress_update(&id, event));
+        }
+    }
+    Ok(updates)
+}
+
 // Helper functions
+async fn update_transfer_progress(transfers: &Transfers, id: &str, event: &ProgressEvent) {
+    let mut transfers_guard = transfers.write().await;
+    if let Some(state) = transfers_guard.get_mut(id) {
+        state.progress.update(event);
+    }
+}
+
 async fn update_transfer_status(transfers: &Transfers, id: &str, status: &str) {
     let mut transfers_guard = transfers.write().await;
     if let Some(state) = transfers_guard.get_mut(id) {
@@ -1106,9 +1507,21 @@ fn serialize_import_progress(progress: &ImportProgress) -> serde_json::Value {
         ImportProgress::FileCompleted { name } => {
             serde_json::json!({"type": "file_completed", "name": name})
         }
+        ImportProgress::FileChanged { name } => {
+            serde_json::json!({"type": "file_changed", "name": name})
+        }
+        ImportProgress::FileSkipped { name } => {
+            serde_json::json!({"type": "file_skipped", "name": name})
+        }
+        ImportProgress::BatchProgress { completed, total } => {
+            serde_json::json!({"type": "batch_progress", "completed": completed, "total": total})
+        }
         ImportProgress::Completed { total_size: _ } => {
             serde_json::json!({"type": "completed"})
         }
+        ImportProgress::Cancelled => {
+            serde_json::json!({"type": "cancelled"})
+        }
     }
 }
 
@@ -1134,6 +1547,12 @@ fn serialize_export_progress(progress: &ExportProgress) -> serde_json::Value {
 
 fn serialize_download_progress(progress: &DownloadProgress) -> serde_json::Value {
     match progress {
+        DownloadProgress::EndpointReady { online } => {
+            serde_json::json!({"type": "endpoint_ready", "online": online})
+        }
+        DownloadProgress::Throttled { bytes_per_sec } => {
+            serde_json::json!({"type": "throttled", "bytes_per_sec": bytes_per_sec})
+        }
         DownloadProgress::Connecting => {
             serde_json::json!({"type": "connecting"})
         }
@@ -1155,36 +1574,51 @@ fn serialize_download_progress(progress: &DownloadProgress) -> serde_json::Value
         DownloadProgress::Downloading { offset, total } => {
             serde_json::json!({"type": "downloading", "offset": offset, "total": total})
         }
+        DownloadProgress::Verifying { offset, total } => {
+            serde_json::json!({"type": "verifying", "offset": offset, "total": total})
+        }
         DownloadProgress::Completed => {
             serde_json::json!({"type": "completed"})
         }
+        DownloadProgress::Restarted { reason } => {
+            serde_json::json!({"type": "restarted", "reason": reason})
+        }
+        DownloadProgress::Stalled { attempt, reason } => {
+            serde_json::json!({"type": "stalled", "attempt": attempt, "reason": reason})
+        }
+        DownloadProgress::Resumed { attempt } => {
+            serde_json::json!({"type": "resumed", "attempt": attempt})
+        }
     }
 }
 
 #[tauri::command]
-async fn clear_transfers(transfers: tauri::State<'_, Transfers>) -> Result<(), String> {
+async fn clear_transfers(
+    app: AppHandle,
+    transfers: tauri::State<'_, Transfers>,
+) -> Result<(), String> {
+    let checkpoints = checkpoint_dir(&app).ok();
     // Cancel all active transfers
     let mut transfers_guard = transfers.write().await;
-    for (_id, mut state) in transfers_guard.drain() {
+    for (id, mut state) in transfers_guard.drain() {
         // Send abort signal
         if let Some(abort_tx) = state.abort_tx.take() {
             let _ = abort_tx.send(());
         }
+        if let Some(ref dir) = checkpoints {
+            ProgressCheckpoint::remove(dir, &id);
+        }
     }
     drop(transfers_guard);
 
     // Clean up temporary sendme directories
-    let temp_dirs = std::fs::read_dir(".")
-        .map_err(|e| e.to_string())?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| entry.file_name().to_string_lossy().starts_with(".sendme-"))
-        .filter(|entry| entry.path().is_dir())
-        .map(|entry| entry.path())
-        .collect::<Vec<_>>();
-
-    for path in temp_dirs {
-        log_info!("Removing temporary directory: {:?}", path);
-        let _ = std::fs::remove_dir_all(&path);
+    let common = CommonConfig {
+        temp_dir: Some(".".into()),
+        ..Default::default()
+    };
+    let removed = sendme_lib::remove_temp_dirs(&common).map_err(|e| e.to_string())?;
+    for path in removed {
+        log_info!("Removed temporary directory: {:?}", path);
     }
 
     Ok(())
@@ -1991,3 +2425,72 @@ fn pick_directory(
 ) -> Result<PickerDirectoryInfo, String> {
     Err("Directory picking is only available on mobile platforms. Use tauri-plugin-dialog on desktop.".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picked(path: &str, name: &str) -> PickerFileInfo {
+        PickerFileInfo {
+            uri: path.to_string(),
+            path: path.to_string(),
+            name: name.to_string(),
+            size: 0,
+            mime_type: "application/octet-stream".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_picked_files() {
+        let files = vec![
+            picked("/photos/a.jpg", ""),
+            picked("content://media/external/1", "a.jpg"),
+            picked("content://media/external/2", "a.jpg"),
+            picked("content://media/external/3", "../notes"),
+            picked("content://media/external/4", ""),
+        ];
+        let entries = plan_picked_files(&files);
+        let names = entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.jpg", "a (1).jpg", "a (2).jpg", ".._notes", "4"]);
+        assert!(!entries[0].is_content_uri);
+        assert_eq!(entries[0].source, "/photos/a.jpg");
+        assert!(entries[1..].iter().all(|e| e.is_content_uri));
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("sendme-checkpoints-{}", Uuid::new_v4()));
+        let mut progress = ProgressSnapshot::default();
+        progress.update(&ProgressEvent::Download(DownloadProgress::Downloading {
+            offset: 40,
+            total: 100,
+        }));
+        let checkpoint = ProgressCheckpoint {
+            info: TransferInfo {
+                id: "abc".to_string(),
+                transfer_type: "receive".to_string(),
+                path: "blobticket".to_string(),
+                status: "downloading".to_string(),
+                created_at: 1_700_000_000,
+            },
+            output_dir: Some("/downloads".to_string()),
+            progress,
+        };
+        checkpoint.save(&dir).unwrap();
+
+        let loaded = ProgressCheckpoint::load_all(&dir);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].output_dir.as_deref(), Some("/downloads"));
+        let state = loaded[0].clone().into_interrupted();
+        assert_eq!(state.info.id, "abc");
+        assert_eq!(state.info.path, "blobticket");
+        assert_eq!(state.info.status, "interrupted");
+        assert_eq!(state.progress.bytes, 40);
+        assert_eq!(state.progress.total_bytes, 100);
+        assert!(state.abort_tx.is_none());
+
+        ProgressCheckpoint::remove(&dir, "abc");
+        assert!(ProgressCheckpoint::load_all(&dir).is_empty());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
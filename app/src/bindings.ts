@@ -9,6 +9,14 @@ export interface SendFileRequest {
   filename?: string;
 }
 
+export interface SendFilesRequest {
+  /** Files returned by the picker, sent together as one collection. */
+  files: FileInfo[];
+  ticket_type: string;
+  /** Put the files in a folder with this name on the receiver. */
+  root?: string;
+}
+
 export interface ReceiveFileRequest {
   ticket: string;
   output_dir?: string;
@@ -22,6 +30,22 @@ export interface TransferInfo {
   created_at: number;
 }
 
+export interface ProgressSnapshot {
+  bytes: number;
+  total_bytes: number;
+  files: number;
+  total_files: number;
+  percent: number;
+  bytes_per_sec: number;
+}
+
+export interface TicketQr {
+  /** The ticket to encode, with less address information if needed. */
+  ticket: string;
+  /** Whether the full ticket fits, i.e. `ticket` is unchanged. */
+  fits: boolean;
+}
+
 export interface ProgressUpdate {
   event_type: string;
   data: any;
@@ -34,6 +58,23 @@ export async function send_file(request: SendFileRequest): Promise<string> {
   return await invoke("send_file", { request });
 }
 
+/**
+ * The ticket to show as a QR code that still scans reliably
+ */
+export async function ticket_qr(
+  ticket: string,
+  ecl: "Low" | "Medium" | "Quartile" | "High",
+): Promise<TicketQr> {
+  return await invoke("ticket_qr", { ticket, ecl });
+}
+
+/**
+ * Send several picked files as one collection and return the ticket
+ */
+export async function send_files(request: SendFilesRequest): Promise<string> {
+  return await invoke("send_files", { request });
+}
+
 /**
  * Receive a file or directory using a ticket
  */
@@ -43,6 +84,14 @@ export async function receive_file(
   return await invoke("receive_file", { request });
 }
 
+/**
+ * Resume a receive that was interrupted when the app exited.
+ * Returns the id of the new transfer.
+ */
+export async function resume_transfer(id: string): Promise<string> {
+  return await invoke("resume_transfer", { id });
+}
+
 /**
  * Cancel an active transfer
  */
@@ -64,6 +113,23 @@ export async function get_transfer_status(id: string): Promise<string> {
   return await invoke("get_transfer_status", { id });
 }
 
+/**
+ * Get a snapshot of the progress of a specific transfer
+ */
+export async function get_transfer_progress(
+  id: string,
+): Promise<ProgressSnapshot> {
+  return await invoke("get_transfer_progress", { id });
+}
+
+/**
+ * Get the latest progress events of a running transfer, to catch up on the
+ * events sent before listening to `progress`
+ */
+export async function replay_progress(id: string): Promise<ProgressUpdate[]> {
+  return await invoke("replay_progress", { id });
+}
+
 /**
  * Clear all transfers and clean up temporary directories
  */
@@ -129,7 +195,7 @@ export interface FileInfo {
   path: string;
   name: string;
   size: number;
-  mime_type: string;
+  mimeType: string;
 }
 
 /**
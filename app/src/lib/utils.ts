@@ -69,6 +69,13 @@ export function getTransferStatus(status: string): {
   const s = status.toLowerCase();
   if (s.includes("error"))
     return { label: "Error", color: "text-red-500", icon: "X", pulse: false };
+  if (s.includes("interrupted"))
+    return {
+      label: "Interrupted",
+      color: "text-yellow-500",
+      icon: "Download",
+      pulse: false,
+    };
   if (s.includes("cancel"))
     return {
       label: "Cancelled",
@@ -9,12 +9,18 @@ import {
 } from "solid-js";
 import {
   send_file,
+  send_files,
   receive_file,
   cancel_transfer,
+  resume_transfer,
   get_transfers,
+  replay_progress,
   clear_transfers,
   open_received_file,
   pick_directory,
+  pick_file,
+  ticket_qr,
+  type FileInfo,
 } from "~/bindings";
 import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
@@ -107,6 +113,8 @@ export default function Home() {
 
   // Send state
   const [sendPath, setSendPath] = createSignal("");
+  // Files picked together are sent as one collection instead of `sendPath`
+  const [sendPicked, setSendPicked] = createSignal<FileInfo[]>([]);
   const [sendTicketType, setSendTicketType] = createSignal(
     "relay_and_addresses",
   );
@@ -207,13 +215,36 @@ export default function Home() {
   // File picker functions
   async function selectFile() {
     try {
-      const selected = await open({
-        multiple: false,
-        directory: false,
-      });
-      if (selected && typeof selected === "string") {
-        setSendPath(selected);
+      let picked: FileInfo[];
+      if (isMobile()) {
+        picked = await pick_file({ allowMultiple: true });
+      } else {
+        const selected = await open({
+          multiple: true,
+          directory: false,
+        });
+        const paths = Array.isArray(selected)
+          ? selected
+          : selected
+            ? [selected]
+            : [];
+        picked = paths.map((path) => ({
+          uri: path,
+          path,
+          name: "",
+          size: 0,
+          mimeType: "",
+        }));
+      }
+      if (picked.length === 0) {
+        return;
       }
+      setSendPicked(picked);
+      setSendPath(
+        picked.length === 1
+          ? picked[0].name || picked[0].path || picked[0].uri
+          : `${picked.length} files`,
+      );
     } catch (e) {
       console.error("Failed to select file:", e);
     }
@@ -226,6 +257,7 @@ export default function Home() {
         directory: true,
       });
       if (selected && typeof selected === "string") {
+        setSendPicked([]);
         setSendPath(selected);
       }
     } catch (e) {
@@ -267,14 +299,26 @@ export default function Home() {
     setSendTicketQrCode("");
 
     try {
-      const result = await send_file({
-        path: sendPath(),
-        ticket_type: sendTicketType(),
-      });
+      const result =
+        sendPicked().length > 0
+          ? await send_files({
+              files: sendPicked(),
+              ticket_type: sendTicketType(),
+            })
+          : await send_file({
+              path: sendPath(),
+              ticket_type: sendTicketType(),
+            });
       setSendTicket(result);
-      // Generate QR code for the ticket
+      // Generate QR code for the ticket, shortened if it would be too dense
+      const qr = await ticket_qr(result, "High");
+      if (!qr.fits) {
+        toast.warning(
+          "The ticket is too long for a reliable QR code, the code leaves out some addresses",
+        );
+      }
       setSendTicketQrCode(
-        await QRCode.toDataURL(result, {
+        await QRCode.toDataURL(qr.ticket, {
           errorCorrectionLevel: "H",
           width: 300,
         }),
@@ -355,6 +399,17 @@ export default function Home() {
     }
   }
 
+  async function handleResume(id: string) {
+    try {
+      await resume_transfer(id);
+      await loadTransfers();
+      toast.info("Transfer resumed");
+    } catch (e) {
+      console.error("Resume failed:", e);
+      toast.error(`Failed to resume transfer: ${e}`);
+    }
+  }
+
   async function handleClearTransfers() {
     try {
       await clear_transfers();
@@ -470,8 +525,8 @@ export default function Home() {
     window.addEventListener("mousemove", handleMouseMove);
 
     // Listen for progress events
-    const unlisten = await listen<ProgressUpdate>("progress", (event) => {
-      const { transfer_id, ...data } = event.payload.data;
+    const handleProgress = (update: ProgressUpdate) => {
+      const { transfer_id, ...data } = update.data;
       setProgressData((prev) => ({
         ...prev,
         [transfer_id]: { transfer_id, ...data },
@@ -502,7 +557,19 @@ export default function Home() {
           [transfer_id]: data.progress,
         }));
       }
-    });
+    };
+    const unlisten = await listen<ProgressUpdate>("progress", (event) =>
+      handleProgress(event.payload),
+    );
+
+    // Catch up on the file lists of transfers that started before listening
+    for (const transfer of transfers()) {
+      try {
+        (await replay_progress(transfer.id)).forEach(handleProgress);
+      } catch (e) {
+        console.error("Failed to replay progress:", e);
+      }
+    }
 
     // Cleanup on unmount
     onCleanup(() => {
@@ -1126,11 +1193,21 @@ export default function Home() {
                                 </Show>
                               </div>
 
+                              <Show when={transfer.status === "interrupted"}>
+                                <button
+                                  onClick={() => handleResume(transfer.id)}
+                                  class="p-2 text-white/20 transition-colors hover:text-blue-400"
+                                >
+                                  <RefreshCw size={18} />
+                                </button>
+                              </Show>
+
                               <Show
                                 when={
                                   !transfer.status.includes("complete") &&
                                   !transfer.status.includes("error") &&
-                                  !transfer.status.includes("cancel")
+                                  !transfer.status.includes("cancel") &&
+                                  transfer.status !== "interrupted"
                                 }
                               >
                                 <button
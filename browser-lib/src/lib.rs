@@ -1,4 +1,4 @@
 mod node;
-pub use node::SendmeNode;
+pub use node::{Cancelled, SendmeNode};
 
 pub mod wasm;
@@ -10,10 +10,27 @@ use iroh::{discovery::static_provider::StaticProvider, protocol::Router, Endpoin
 use iroh_blobs::{
     api::{blobs::BlobStatus, Store},
     format::collection::Collection,
+    get::request::get_hash_seq_and_sizes,
     ticket::BlobTicket,
     BlobFormat, Hash,
 };
 
+/// Maximum size of the hash sequence of a collection that will be fetched.
+const MAX_HASH_SEQ_SIZE: u64 = 1024 * 1024 * 32;
+
+/// Error returned when a download is aborted, see
+/// [`SendmeNode::get_collection_with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "download cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
 /// Sendme node for browser/WebAssembly environments
 ///
 /// Uses in-memory storage and WebAssembly-compatible networking.
@@ -229,6 +246,21 @@ impl SendmeNode {
     /// Returns all files in the collection as a vector of (filename, data) tuples.
     /// Useful for downloading folders/multiple files.
     pub async fn get_collection(&self, ticket_str: String) -> Result<Vec<(String, Bytes)>> {
+        self.get_collection_with_progress(ticket_str, |_, _| {}, || false).await
+    }
+
+    /// Get all files from a collection, reporting overall progress
+    ///
+    /// `on_progress` is called with `(offset, total)` in bytes across all files,
+    /// where the total is known before the download starts. `is_aborted` is
+    /// checked between chunks; once it returns true, the download stops with a
+    /// [`Cancelled`] error.
+    pub async fn get_collection_with_progress(
+        &self,
+        ticket_str: String,
+        mut on_progress: impl FnMut(u64, u64),
+        is_aborted: impl Fn() -> bool,
+    ) -> Result<Vec<(String, Bytes)>> {
         // Parse the ticket
         let ticket: BlobTicket = ticket_str.parse()?;
         let hash_and_format = ticket.hash_and_format();
@@ -251,6 +283,13 @@ impl SendmeNode {
 
             tracing::info!("Connected to peer, starting download");
 
+            // Get the sizes of all blobs first, so progress has a total
+            let (_hash_seq, sizes) =
+                get_hash_seq_and_sizes(&connection, &collection_hash, MAX_HASH_SEQ_SIZE, None)
+                    .await?;
+            let total = sizes.iter().sum::<u64>();
+            on_progress(0, total);
+
             // Get the local blob state
             let local = self.blobs.remote().local(hash_and_format).await?;
 
@@ -260,12 +299,19 @@ impl SendmeNode {
 
             // Consume the stream to download all data
             while let Some(item) = stream.next().await {
+                // Dropping the stream aborts the request
+                if is_aborted() {
+                    tracing::info!("Download cancelled");
+                    return Err(Cancelled.into());
+                }
                 match item {
                     iroh_blobs::api::remote::GetProgressItem::Progress(offset) => {
                         tracing::debug!("Downloaded {} bytes", offset);
+                        on_progress(offset.min(total), total);
                     }
                     iroh_blobs::api::remote::GetProgressItem::Done(_stats) => {
                         tracing::info!("Download complete");
+                        on_progress(total, total);
                         break;
                     }
                     iroh_blobs::api::remote::GetProgressItem::Error(cause) => {
@@ -284,6 +330,9 @@ impl SendmeNode {
         // Get all files from the collection
         let mut result = Vec::new();
         for (filename, blob_hash) in collection.iter() {
+            if is_aborted() {
+                return Err(Cancelled.into());
+            }
             tracing::info!("Fetching blob: {} ({})", filename, blob_hash);
             let bytes = self.blobs.get_bytes(*blob_hash).await?;
             result.push((filename.to_string(), bytes));
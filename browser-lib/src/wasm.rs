@@ -2,7 +2,7 @@
 //!
 //! This module exports SendmeNode functions to JavaScript via wasm-bindgen.
 
-use crate::SendmeNode;
+use crate::{Cancelled, SendmeNode};
 use js_sys::{Array, Uint8Array};
 use tracing::level_filters::LevelFilter;
 use wasm_bindgen::{prelude::wasm_bindgen, JsError, JsValue};
@@ -174,25 +174,54 @@ impl SendmeNodeWasm {
                 .get_collection(ticket)
                 .await
                 .map_err(|e: anyhow::Error| JsError::new(&e.to_string()))?;
+            files_to_array(files)
+        });
 
-            // Create a JS array of file objects
-            let result = Array::new_with_length(files.len() as u32);
-            for (i, (filename, data)) in files.into_iter().enumerate() {
-                let obj = js_sys::Object::new();
-                js_sys::Reflect::set(&obj, &JsValue::from("filename"), &JsValue::from(filename))
-                    .map_err(|e| JsError::new(&format!("Failed to set filename: {:?}", e)))?;
+        Ok(promise)
+    }
 
-                js_sys::Reflect::set(
-                    &obj,
-                    &JsValue::from("data"),
-                    &JsValue::from(bytes_to_uint8array(&data)),
-                )
-                .map_err(|e| JsError::new(&format!("Failed to set data: {:?}", e)))?;
+    /// Get all files from a collection, with progress and cancellation
+    ///
+    /// `on_progress` is called with { offset: number, total: number } in bytes
+    /// across all files. If `abort_signal` is aborted, the download stops and
+    /// the promise rejects with an error named "AbortError", like `fetch`.
+    ///
+    /// Resolves to the same array as `get_collection`.
+    pub fn get_collection_with_progress(
+        &self,
+        ticket: String,
+        on_progress: js_sys::Function,
+        abort_signal: Option<web_sys::AbortSignal>,
+    ) -> Result<js_sys::Promise, JsError> {
+        let node = self.0.clone();
 
-                result.set(i as u32, JsValue::from(obj));
-            }
+        let promise = future_to_promise(async move {
+            let report = |offset: u64, total: u64| {
+                let obj = js_sys::Object::new();
+                // plain numbers instead of BigInts, which are exact up to 8 PiB
+                let (offset, total) = (offset as f64, total as f64);
+                js_sys::Reflect::set(&obj, &JsValue::from("offset"), &JsValue::from(offset))
+                    .ok();
+                js_sys::Reflect::set(&obj, &JsValue::from("total"), &JsValue::from(total)).ok();
+                if let Err(e) = on_progress.call1(&JsValue::NULL, &obj) {
+                    tracing::warn!("progress callback failed: {:?}", e);
+                }
+            };
+            let is_aborted = || abort_signal.as_ref().is_some_and(|signal| signal.aborted());
 
-            Ok(JsValue::from(result))
+            let files = match node
+                .get_collection_with_progress(ticket, report, is_aborted)
+                .await
+            {
+                Ok(files) => files,
+                Err(e) if e.is::<Cancelled>() => {
+                    let error = js_sys::Error::new(&e.to_string());
+                    error.set_name("AbortError");
+                    return Err(error.into());
+                }
+                Err(e) => return Err(JsError::new(&e.to_string()).into()),
+            };
+            files_to_array(files)
         });
 
         Ok(promise)
@@ -232,6 +261,27 @@ impl SendmeNodeWasm {
     }
 }
 
+/// Convert downloaded files to a JS array of { filename, data } objects
+fn files_to_array(files: Vec<(String, bytes::Bytes)>) -> Result<JsValue, JsValue> {
+    let result = Array::new_with_length(files.len() as u32);
+    for (i, (filename, data)) in files.into_iter().enumerate() {
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &JsValue::from("filename"), &JsValue::from(filename))
+            .map_err(|e| JsError::new(&format!("Failed to set filename: {:?}", e)))?;
+
+        js_sys::Reflect::set(
+            &obj,
+            &JsValue::from("data"),
+            &JsValue::from(bytes_to_uint8array(&data)),
+        )
+        .map_err(|e| JsError::new(&format!("Failed to set data: {:?}", e)))?;
+
+        result.set(i as u32, JsValue::from(obj));
+    }
+
+    Ok(JsValue::from(result))
+}
+
 /// Convert Uint8Array to Bytes
 fn uint8array_to_bytes(data: &Uint8Array) -> bytes::Bytes {
     let mut buffer = vec![0u8; data.length() as usize];
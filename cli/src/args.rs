@@ -0,0 +1,469 @@
+//! Command line arguments for the non-interactive sendme commands.
+
+use std::{
+    net::{SocketAddrV4, SocketAddrV6},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use sendme_lib::{types::*, BlobTicket, RelayUrl};
+
+/// Send a file or directory between two machines, using blake3 verified streaming.
+///
+/// Running without a subcommand starts the interactive TUI.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Send a file or directory.
+    #[command(visible_alias = "serve")]
+    Send(SendArgsCli),
+
+    /// Receive a file or directory.
+    #[command(visible_alias = "recv")]
+    Receive(ReceiveArgsCli),
+
+    /// Check that local files match the data of a ticket.
+    VerifyFile(VerifyFileArgsCli),
+
+    /// Work with tickets without connecting to anyone.
+    Ticket(TicketArgsCli),
+}
+
+/// Arguments shared by all non-interactive commands.
+#[derive(Parser, Debug, Clone)]
+pub struct CommonArgsCli {
+    /// The IPv4 address that magicsocket will listen on.
+    ///
+    /// If None, defaults to a random free port, but it can be useful to specify a fixed
+    /// port, e.g. to configure a firewall rule.
+    #[clap(long, default_value = None)]
+    pub magic_ipv4_addr: Option<SocketAddrV4>,
+
+    /// The IPv6 address that magicsocket will listen on.
+    ///
+    /// If None, defaults to a random free port, but it can be useful to specify a fixed
+    /// port, e.g. to configure a firewall rule.
+    #[clap(long, default_value = None)]
+    pub magic_ipv6_addr: Option<SocketAddrV6>,
+
+    /// Fixed UDP port to listen on for both IPv4 and IPv6.
+    ///
+    /// Falls back to a random port if it is taken. The magic addresses take
+    /// precedence for their address family.
+    #[clap(long, value_name = "PORT")]
+    pub bind_port: Option<u16>,
+
+    /// Output format for hashes.
+    #[clap(long, default_value_t = Format::Hex)]
+    pub format: Format,
+
+    /// The relay URL to use as a home relay, or `disabled` / `default`.
+    #[clap(long, default_value_t = RelayModeOption::Default)]
+    pub relay: RelayModeOption,
+
+    /// Print the secret key of the endpoint.
+    #[clap(long)]
+    pub show_secret: bool,
+
+    /// Suppress progress bars.
+    #[clap(long)]
+    pub no_progress: bool,
+
+    /// Print a single JSON object on stdout instead of human readable output.
+    ///
+    /// Implies `--no-progress` and `--no-qr`.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Write statistics about the transfer as JSON to FILE.
+    #[clap(long, value_name = "FILE")]
+    pub stats_out: Option<PathBuf>,
+
+    /// Priority for import/export work when other transfers run in the same process.
+    #[clap(long, default_value_t = Priority::Normal)]
+    pub priority: Priority,
+
+    /// Keep file names exactly as the file system reports them, instead of
+    /// normalizing them to Unicode NFC.
+    #[clap(long)]
+    pub no_normalize_names: bool,
+
+    /// Where to keep the data while transferring: `fs` for a temporary
+    /// directory, or `mem` to keep everything in memory.
+    #[clap(long, default_value_t = StoreBackend::Fs)]
+    pub store_backend: StoreBackend,
+
+    /// Give up receiving if no data arrives for this many seconds.
+    #[clap(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Limit the upload rate when sending, e.g. `2MB` for two megabytes per
+    /// second.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate)]
+    pub max_upload: Option<u64>,
+
+    /// Write every progress event as a line of JSON to FILE, for programs
+    /// that wrap sendme. Works with and without the progress bars.
+    #[clap(long, value_name = "FILE")]
+    pub progress_json: Option<PathBuf>,
+
+    /// Like `--progress-json`, but write to the already open file descriptor N.
+    #[cfg(unix)]
+    #[clap(long, value_name = "N", conflicts_with = "progress_json")]
+    pub progress_fd: Option<std::os::fd::RawFd>,
+}
+
+impl CommonArgsCli {
+    /// Open the target of `--progress-json` or `--progress-fd`, if one is given.
+    pub fn progress_json_out(&self) -> anyhow::Result<Option<std::fs::File>> {
+        if let Some(path) = &self.progress_json {
+            let file = std::fs::File::create(path)
+                .map_err(|e| anyhow::anyhow!("failed to create {}: {}", path.display(), e))?;
+            return Ok(Some(file));
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.progress_fd {
+            use std::os::fd::BorrowedFd;
+            anyhow::ensure!(fd >= 0, "invalid --progress-fd {}", fd);
+            // Write to a duplicate, so the caller's descriptor, which may be
+            // stdout or stderr, is never closed.
+            // SAFETY: the descriptor is only borrowed for the duplication,
+            // which fails with EBADF if it isn't open.
+            let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+            let owned = borrowed
+                .try_clone_to_owned()
+                .map_err(|e| anyhow::anyhow!("invalid --progress-fd {}: {}", fd, e))?;
+            return Ok(Some(std::fs::File::from(owned)));
+        }
+        Ok(None)
+    }
+
+    /// Convert the command line arguments into the library configuration.
+    pub fn config(&self) -> CommonConfig {
+        CommonConfig {
+            magic_ipv4_addr: self.magic_ipv4_addr,
+            magic_ipv6_addr: self.magic_ipv6_addr,
+            format: self.format,
+            relay: self.relay.clone(),
+            show_secret: self.show_secret,
+            temp_dir: None,
+            priority: self.priority,
+            bind_port: self.bind_port,
+            normalize_names: !self.no_normalize_names,
+            import_batch_threshold: sendme_lib::import::DEFAULT_BATCH_THRESHOLD,
+            store_backend: self.store_backend,
+            timeout: self.timeout.map(Duration::from_secs),
+            max_upload_bps: self.max_upload,
+            max_download_bps: None,
+            temp_prefix: None,
+        }
+    }
+}
+
+/// Parse a `--max-upload`, `--max-download-rate` or `--estimate` rate, see
+/// [`sendme_lib::throttle::parse_rate`].
+fn parse_rate(s: &str) -> Result<u64, String> {
+    sendme_lib::throttle::parse_rate(s).map_err(|e| e.to_string())
+}
+
+/// Arguments for the `send` command.
+#[derive(Parser, Debug)]
+pub struct SendArgsCli {
+    /// Paths to the files or directories to send.
+    ///
+    /// The last component of each path will be used as the name of the data
+    /// being shared. More than one path are sent together with a single
+    /// ticket, and paths with the same name get their position as a prefix,
+    /// e.g. `2-notes.txt`.
+    #[clap(required_unless_present = "url")]
+    pub paths: Vec<PathBuf>,
+
+    /// Send the content of an HTTP(S) URL instead of a local path.
+    ///
+    /// The download is streamed into the store without saving it to disk.
+    #[clap(long, conflicts_with = "paths")]
+    pub url: Option<String>,
+
+    /// The name to use for the data from `--url`.
+    ///
+    /// Defaults to the last path segment of the URL.
+    #[clap(long, requires = "url")]
+    pub name: Option<String>,
+
+    /// What type of ticket to use.
+    #[clap(long, default_value_t = AddrInfoOptions::RelayAndAddresses)]
+    pub ticket_type: AddrInfoOptions,
+
+    /// Send this text along with the data, as a `SENDME_NOTE.txt` file.
+    #[clap(long, value_name = "TEXT", conflicts_with = "url")]
+    pub note: Option<String>,
+
+    /// If the path is a directory with a single file, send just the file
+    /// under its own name.
+    #[clap(long, conflicts_with = "url")]
+    pub unwrap_single_file: bool,
+
+    /// Read every file a second time after importing it, and fail if the
+    /// content differs, e.g. because the disk is failing. Doubles the reads.
+    #[clap(long, conflicts_with = "url")]
+    pub double_read_verify: bool,
+
+    /// Wait for the endpoint to be online before importing, so the ticket
+    /// has a relay address and setup time is reported separately.
+    #[clap(long)]
+    pub prewarm: bool,
+
+    /// Always use the requested ticket type. By default, relay URLs are
+    /// added to an `addresses` ticket if direct connections are unlikely.
+    #[clap(long)]
+    pub strict_ticket_type: bool,
+
+    /// Import a file again if it changed while it was read, e.g. because it
+    /// is still being written. Changed files are always reported.
+    #[clap(long, conflicts_with = "url")]
+    pub reimport_changed: bool,
+
+    /// What to do with files whose names are not valid UTF-8: fail (error),
+    /// leave them out (skip), or replace the invalid bytes (lossy).
+    #[clap(long, value_name = "POLICY", default_value_t = NonUtf8Policy::Error)]
+    pub non_utf8_names: NonUtf8Policy,
+
+    /// Leave out what `.gitignore` files in the sent directories ignore, and
+    /// `.git` directories, e.g. `target/` or `node_modules/`.
+    #[clap(long, conflicts_with = "url")]
+    pub gitignore: bool,
+
+    /// Leave out files matching GLOB, which can be given more than once.
+    /// Globs match names in the collection, e.g. `data/cache/**` or `*.tmp`.
+    #[clap(long, value_name = "GLOB", conflicts_with = "url")]
+    pub exclude: Vec<String>,
+
+    /// Send the files that symlinks point to, and the contents of linked
+    /// directories, instead of leaving symlinks out.
+    #[clap(long, conflicts_with = "url")]
+    pub follow_symlinks: bool,
+
+    /// Record the modification times and permissions of all files, so
+    /// receivers can restore them with `--apply-sender-metadata`.
+    #[clap(long, conflicts_with = "url")]
+    pub send_metadata: bool,
+
+    /// Keep the store in DIR and serve the files in place instead of copying
+    /// them. Serving the same unchanged path again starts without importing.
+    /// The files must not be modified while they are served.
+    #[clap(long, value_name = "DIR", conflicts_with = "url")]
+    pub store: Option<PathBuf>,
+
+    /// Register the ticket with the short link service at URL and print the
+    /// short link as well. The service is self-hosted, see the docs of
+    /// `sendme_lib::shortlink` for its API.
+    #[clap(long, value_name = "URL", conflicts_with = "url")]
+    pub shortlink_service: Option<String>,
+
+    /// Don't print the ticket as a QR code.
+    #[clap(long)]
+    pub no_qr: bool,
+
+    /// Print a caption with the name, size and ticket type below the QR
+    /// code, so a printed or forwarded code tells what it is.
+    #[clap(long, conflicts_with = "no_qr")]
+    pub qr_caption: bool,
+
+    /// Print how long receiving the data takes at RATE, e.g. `500KB` for a
+    /// slow uplink.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate)]
+    pub estimate: Option<u64>,
+
+    /// Serve Prometheus metrics on this port at `/metrics`.
+    #[cfg(feature = "metrics")]
+    #[clap(long, value_name = "PORT")]
+    pub metrics_port: Option<u16>,
+
+    #[clap(flatten)]
+    pub common: CommonArgsCli,
+}
+
+/// Arguments for the `receive` command.
+#[derive(Parser, Debug)]
+pub struct ReceiveArgsCli {
+    /// The ticket to use to connect to the sender, or a short link to it.
+    /// Several tickets are received one after the other, see `--parallel`.
+    #[clap(required_unless_present = "from_plan")]
+    pub ticket: Vec<String>,
+
+    /// How many of several tickets to receive at the same time. The others
+    /// wait until one of them is done.
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    pub parallel: usize,
+
+    /// Only fetch the file names and sizes, and write them as a JSON plan to FILE.
+    #[clap(long, value_name = "FILE", conflicts_with = "from_plan")]
+    pub plan_only: Option<PathBuf>,
+
+    /// Print the files of the collection as a tree with their sizes before
+    /// downloading. With `--plan-only`, nothing is downloaded.
+    #[clap(long, conflicts_with_all = ["json", "test_connect"])]
+    pub tree: bool,
+
+    /// Receive the files listed in a plan written by `--plan-only`.
+    #[clap(long, value_name = "FILE", conflicts_with = "ticket")]
+    pub from_plan: Option<PathBuf>,
+
+    /// Only receive the named files from the plan. Can be repeated.
+    #[clap(long, value_name = "NAME", requires = "from_plan")]
+    pub only: Vec<String>,
+
+    /// Only connect through this relay, ignoring other relays in the ticket.
+    /// Can be repeated.
+    #[clap(long = "allowed-relay", value_name = "URL")]
+    pub allowed_relays: Vec<RelayUrl>,
+
+    /// Only check that the sender can be reached, and report the path and
+    /// round trip time. No file content is downloaded.
+    #[clap(long, conflicts_with_all = ["plan_only", "from_plan"])]
+    pub test_connect: bool,
+
+    /// Keep going if a file can't be written, e.g. because its name is not
+    /// allowed on this file system. Skipped files are listed at the end.
+    #[clap(long)]
+    pub continue_on_export_error: bool,
+
+    /// Fail unless the sender shows this verification code, e.g. `482-193`.
+    /// Compare it with the sender over a channel you trust.
+    #[clap(long, value_name = "CODE")]
+    pub expect_code: Option<String>,
+
+    /// Don't download files that are already in the current directory with
+    /// the same content, e.g. from an earlier receive of the same share.
+    #[clap(long)]
+    pub merge_with_existing: bool,
+
+    /// How many times to reconnect when the connection to the sender drops
+    /// during the download. Data that was already received is kept.
+    #[clap(long, default_value_t = sendme_lib::receive::DEFAULT_STREAM_RECONNECTS)]
+    pub stream_reconnects: u32,
+
+    /// Write the file into this named pipe while it downloads, e.g. for a
+    /// media player. Only works for shares with a single file. A pipe that
+    /// already exists at the file's export path is used automatically.
+    #[clap(long, value_name = "PATH")]
+    pub fifo: Option<PathBuf>,
+
+    /// Wait for the endpoint to be online before connecting, so the time to
+    /// set up relays and DNS is not counted as connecting.
+    #[clap(long)]
+    pub prewarm: bool,
+
+    /// Export into this directory first, and move the files to their place
+    /// only after checking them. Nothing is written on a failed check.
+    #[clap(long, value_name = "DIR", conflicts_with = "fifo")]
+    pub staging_dir: Option<PathBuf>,
+
+    /// Extract a received `.tar` or `.zip` file into the export directory,
+    /// and remove the archive.
+    #[clap(long, conflicts_with = "fifo")]
+    pub auto_extract: bool,
+
+    /// Sync each received file to disk before reporting it as done. Slower,
+    /// but the files survive a crash or power loss right after the receive.
+    #[clap(long)]
+    pub fsync: bool,
+
+    /// Give the received files the modification times and permissions the
+    /// sender recorded with `--send-metadata`.
+    #[clap(long)]
+    pub apply_sender_metadata: bool,
+
+    /// What to do with files that already exist in the export directory:
+    /// replace them (overwrite), keep them and leave the received ones out
+    /// (skip), or add a number to the name of the received ones (rename).
+    #[clap(long, value_name = "POLICY", default_value_t = ExportConflict::Overwrite)]
+    pub on_conflict: ExportConflict,
+
+    /// Write `.sendme-manifest.txt` into the export directory, listing the
+    /// BLAKE3 hash of each received file in the format of `b3sum`.
+    #[clap(long)]
+    pub manifest: bool,
+
+    /// Limit the download rate, e.g. `2MB` for two megabytes per second, so
+    /// a receive doesn't take all of a shared link.
+    #[clap(long, value_name = "RATE", value_parser = parse_rate)]
+    pub max_download_rate: Option<u64>,
+
+    /// Which relay of the ticket to use: `first` leaves the choice to iroh,
+    /// `lowestlatency` measures each relay first and uses the fastest.
+    #[clap(long, value_name = "SELECTION", default_value_t = RelaySelection::First)]
+    pub relay_selection: RelaySelection,
+
+    /// Only receive the files that were added or changed since an earlier
+    /// share of the same data, given as its ticket or a plan written by
+    /// `--plan-only`. Files that were removed since are deleted locally.
+    #[clap(
+        long,
+        value_name = "TICKET_OR_PLAN",
+        requires = "ticket",
+        conflicts_with_all = ["plan_only", "from_plan", "test_connect"]
+    )]
+    pub since: Option<String>,
+
+    #[clap(flatten)]
+    pub common: CommonArgsCli,
+}
+
+/// Arguments for the `verify-file` command.
+#[derive(Parser, Debug)]
+pub struct VerifyFileArgsCli {
+    /// The ticket to check against. The file names and hashes are fetched
+    /// from the sender.
+    #[clap(required_unless_present = "plan")]
+    pub ticket: Option<BlobTicket>,
+
+    /// The file to check, or the directory the data was received into.
+    pub path: PathBuf,
+
+    /// Check against a plan written by `receive --plan-only` instead of
+    /// contacting the sender.
+    #[clap(long, value_name = "FILE", conflicts_with = "ticket")]
+    pub plan: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub common: CommonArgsCli,
+}
+
+/// Arguments for the `ticket` command.
+#[derive(Parser, Debug)]
+pub struct TicketArgsCli {
+    #[command(subcommand)]
+    pub command: TicketCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TicketCommand {
+    /// Print a ticket with less address information, e.g. a relay-only
+    /// ticket that doesn't reveal the sender's IP addresses.
+    ///
+    /// Information can only be removed, not added.
+    Convert(TicketConvertArgsCli),
+}
+
+/// Arguments for the `ticket convert` command.
+#[derive(Parser, Debug)]
+pub struct TicketConvertArgsCli {
+    /// The ticket to convert, or a link or command containing it.
+    pub ticket: String,
+
+    /// The address information to keep.
+    #[clap(long)]
+    pub to: AddrInfoOptions,
+
+    /// Print a single JSON object on stdout.
+    #[clap(long)]
+    pub json: bool,
+}
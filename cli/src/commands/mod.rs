@@ -0,0 +1,188 @@
+//! Non-interactive `send` / `receive` / `verify-file` / `ticket` commands.
+//!
+//! These print plain output that is easy to script against, in contrast to
+//! the interactive TUI that runs when no subcommand is given.
+
+use std::{future::Future, path::Path, time::Duration};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use sendme_lib::{
+    progress::{ProgressReceiverRx, ProgressSenderTx},
+    types::Format,
+    Hash,
+};
+use tokio::{io::AsyncWriteExt, sync::mpsc, task::JoinHandle};
+
+mod receive;
+mod send;
+mod ticket;
+mod verify;
+
+pub use receive::receive;
+pub use send::send;
+pub use ticket::ticket;
+pub use verify::verify_file;
+
+/// Which decorative output the commands are allowed to produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputMode {
+    /// Print the ticket as a QR code.
+    pub qr: bool,
+    /// Draw progress bars on stderr.
+    pub progress: bool,
+    /// Print a single JSON object on stdout and nothing else.
+    pub json: bool,
+}
+
+impl OutputMode {
+    /// Resolve the output mode from the command line flags.
+    ///
+    /// QR codes and progress bars are only drawn when stdout is a terminal, so
+    /// piping the output or running in CI yields plain text without escapes.
+    pub fn new(no_qr: bool, no_progress: bool, json: bool) -> Self {
+        Self::with_terminal(no_qr, no_progress, json, console::Term::stdout().is_term())
+    }
+
+    fn with_terminal(no_qr: bool, no_progress: bool, json: bool, is_term: bool) -> Self {
+        let decorate = is_term && !json;
+        Self {
+            qr: decorate && !no_qr,
+            progress: decorate && !no_progress,
+            json,
+        }
+    }
+}
+
+/// Print a ticket as a QR code on stdout with an optional caption below it, if
+/// the output mode allows it.
+pub fn print_qr_code(ticket: &str, caption: Option<&str>, output: OutputMode) {
+    use fast_qr::{QRBuilder, ECL};
+
+    if !output.qr {
+        return;
+    }
+    if !sendme_lib::qr::fits_comfortably(ticket, sendme_lib::qr::Ecl::Medium) {
+        eprintln!(
+            "warning: the ticket is too long for a QR code that scans reliably, \
+             send with `--ticket-type id` or use `sendme ticket convert` for a shorter one"
+        );
+    }
+    match QRBuilder::new(ticket).ecl(ECL::M).build() {
+        Ok(qr) => {
+            println!("{}", qr.to_str());
+            if let Some(caption) = caption {
+                println!("{caption}");
+            }
+        }
+        Err(e) => eprintln!("failed to render QR code: {:?}", e),
+    }
+}
+
+/// Write transfer statistics as pretty JSON, if a stats file was requested.
+pub fn write_stats(path: Option<&Path>, stats: &serde_json::Value) -> anyhow::Result<()> {
+    if let Some(path) = path {
+        std::fs::write(path, serde_json::to_vec_pretty(stats)?)
+            .map_err(|e| anyhow::anyhow!("failed to write stats {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Start consuming the progress events of a transfer, if anything wants them:
+/// the progress bars drawn by `show`, or the `json_out` of `--progress-json`.
+///
+/// Returns the sender to pass to the transfer, and the task that ends once
+/// the sender is dropped and all events are handled.
+pub fn spawn_progress<F>(
+    output: OutputMode,
+    json_out: Option<std::fs::File>,
+    show: impl FnOnce(ProgressReceiverRx) -> F,
+) -> Option<(ProgressSenderTx, JoinHandle<()>)>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    if !output.progress && json_out.is_none() {
+        return None;
+    }
+    let (progress_tx, mut progress_rx) = mpsc::channel(32);
+    if let Some(out) = json_out {
+        progress_rx = tee_progress_json(progress_rx, out);
+    }
+    let task = if output.progress {
+        tokio::spawn(show(progress_rx))
+    } else {
+        tokio::spawn(async move { while progress_rx.recv().await.is_some() {} })
+    };
+    Some((progress_tx, task))
+}
+
+/// Write each event of `progress_rx` as a line of JSON to `out`, and pass it
+/// on to the returned receiver.
+fn tee_progress_json(
+    mut progress_rx: ProgressReceiverRx,
+    out: std::fs::File,
+) -> ProgressReceiverRx {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        let mut out = tokio::fs::File::from_std(out);
+        let mut failed = false;
+        while let Some(event) = progress_rx.recv().await {
+            if let (false, Ok(mut line)) = (failed, serde_json::to_vec(&event)) {
+                line.push(b'\n');
+                // flush every line, so a wrapper sees it right away
+                let written = match out.write_all(&line).await {
+                    Ok(()) => out.flush().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = written {
+                    eprintln!("warning: failed to write progress: {e}");
+                    failed = true;
+                }
+            }
+            let _ = tx.send(event).await;
+        }
+    });
+    rx
+}
+
+/// Format a hash according to the requested output format.
+pub fn print_hash(hash: &Hash, format: Format) -> String {
+    match format {
+        Format::Hex => hash.to_hex().to_string(),
+        Format::Cid => hash.to_string(),
+    }
+}
+
+/// Create a spinner for a step without a known length.
+fn make_spinner(msg: &'static str) -> ProgressBar {
+    let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+    pb.set_style(ProgressStyle::with_template("{spinner:.green} {msg}").unwrap());
+    pb.set_message(msg);
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// Create a progress bar measured in bytes.
+fn make_bytes_bar(msg: &'static str, total: u64) -> ProgressBar {
+    let pb = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr());
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{msg} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, eta {eta})",
+        )
+        .unwrap()
+        .progress_chars("#>-"),
+    );
+    pb.set_message(msg);
+    pb
+}
+
+/// Create a progress bar measured in files.
+fn make_files_bar(msg: &'static str, total: u64) -> ProgressBar {
+    let pb = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr());
+    pb.set_style(
+        ProgressStyle::with_template("{msg} [{wide_bar:.cyan/blue}] {pos}/{len} files")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message(msg);
+    pb
+}
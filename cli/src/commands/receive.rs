@@ -0,0 +1,384 @@
+//! The `receive` command.
+
+use indicatif::{HumanBytes, HumanDuration, ProgressBar};
+use sendme_lib::{
+    progress::{DownloadProgress, ExportProgress, ProgressEvent, ProgressReceiverRx, TicketState},
+    types::ReceiveArgs,
+    ConnectPath, ReceivePlan,
+};
+use tokio::sync::mpsc;
+
+use super::{
+    make_bytes_bar, make_files_bar, make_spinner, spawn_progress, write_stats, OutputMode,
+};
+use crate::args::ReceiveArgsCli;
+
+/// Download the data for a ticket (or a saved plan) into the current directory.
+pub async fn receive(mut args: ReceiveArgsCli) -> anyhow::Result<()> {
+    let output = OutputMode::new(true, args.common.no_progress, args.common.json);
+    let stats_out = args.common.stats_out.clone();
+    if args.ticket.len() > 1 {
+        return receive_tickets(args, output).await;
+    }
+    // the plan of the collection, if it is loaded or fetched anyway
+    let mut plan = None;
+    let mut lib_args = match (args.ticket.pop(), &args.from_plan) {
+        (_, Some(path)) => {
+            let mut loaded = ReceivePlan::load(path)?;
+            if !args.only.is_empty() {
+                loaded.retain(&args.only)?;
+            }
+            let lib_args = loaded.receive_args(args.common.config(), None)?;
+            plan = Some(loaded);
+            lib_args
+        }
+        (Some(ticket), None) => ticket_args(&ticket, &args).await?,
+        (None, None) => anyhow::bail!("either a ticket or --from-plan is required"),
+    };
+    apply_options(&mut lib_args, &args);
+
+    if args.test_connect {
+        let report = sendme_lib::test_connect(lib_args).await?;
+        if output.json {
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            let path = match report.path {
+                ConnectPath::Direct => "direct",
+                ConnectPath::Relay => "relay",
+                ConnectPath::Mixed => "direct and relay",
+                ConnectPath::Unknown => "unknown",
+            };
+            println!(
+                "reached {} via {} path, rtt {:?}. {} available",
+                report.sender.fmt_short(),
+                path,
+                report.rtt,
+                HumanBytes(report.total_size)
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = args.plan_only {
+        let plan = sendme_lib::fetch_plan(lib_args).await?;
+        if args.tree {
+            print!("{}", plan.tree(|size| HumanBytes(size).to_string()));
+        }
+        plan.save(&path)?;
+        if output.json {
+            println!("{}", serde_json::to_string(&plan)?);
+        } else {
+            println!(
+                "wrote plan for {} files, {} to {}",
+                plan.files.len(),
+                HumanBytes(plan.total_size),
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if args.tree {
+        if plan.is_none() {
+            plan = Some(sendme_lib::fetch_plan(lib_args.clone()).await?);
+        }
+        if let Some(ref plan) = plan {
+            print!("{}", plan.tree(|size| HumanBytes(size).to_string()));
+        }
+    }
+
+    let mut diff = None;
+    if let Some(since) = args.since {
+        let old = previous_plan(&since, &lib_args).await?;
+        let new = match plan {
+            Some(plan) => plan,
+            None => sendme_lib::fetch_plan(lib_args.clone()).await?,
+        };
+        let changes = sendme_lib::collection_diff(&old.collection(), &new.collection());
+        if changes.to_fetch().is_empty() {
+            let removed = changes.remove_files(&std::env::current_dir()?)?;
+            if output.json {
+                let json = serde_json::json!({
+                    "files": 0,
+                    "removed": removed.deleted,
+                    "kept": removed.kept,
+                });
+                println!("{json}");
+            } else {
+                println!(
+                    "nothing to download, removed {} files",
+                    removed.deleted.len()
+                );
+                print_kept(&removed.kept);
+            }
+            return Ok(());
+        }
+        lib_args.only_files = Some(changes.to_fetch());
+        diff = Some(changes);
+    }
+
+    let json_out = args.common.progress_json_out()?;
+    let result = match spawn_progress(output, json_out, show_download_progress) {
+        Some((progress_tx, progress)) => {
+            let result = sendme_lib::receive_with_progress(lib_args, progress_tx).await;
+            // The sender is dropped once the receive returns, which ends the task.
+            let _ = progress.await;
+            result?
+        }
+        None => sendme_lib::receive(lib_args).await?,
+    };
+
+    // only delete files once the new data is safely on disk
+    let removed = match diff {
+        Some(diff) => diff.remove_files(&std::env::current_dir()?)?,
+        None => Default::default(),
+    };
+
+    let elapsed = result.stats.elapsed;
+    let bytes_read = result.stats.total_bytes_read();
+    if output.json {
+        let names = result
+            .collection
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "files": result.total_files,
+            "size": result.payload_size,
+            "bytes_read": bytes_read,
+            "elapsed_secs": elapsed.as_secs_f64(),
+            "throughput_bytes_per_sec": result.average_throughput(),
+            "peak_throughput_bytes_per_sec": result.peak_throughput,
+            "names": names,
+            "failed_files": result.failed_files,
+            "removed": removed.deleted,
+            "kept": removed.kept,
+        });
+        println!("{json}");
+    } else {
+        println!(
+            "downloaded {} files, {}. took {} ({}/s, peak {}/s)",
+            result.total_files,
+            HumanBytes(result.payload_size),
+            HumanDuration(elapsed),
+            HumanBytes(result.average_throughput() as u64),
+            HumanBytes(result.peak_throughput as u64),
+        );
+        if !removed.deleted.is_empty() {
+            println!("removed {} files", removed.deleted.len());
+        }
+        print_kept(&removed.kept);
+        if !result.failed_files.is_empty() {
+            eprintln!(
+                "warning: {} files could not be exported:",
+                result.failed_files.len()
+            );
+            for name in &result.failed_files {
+                eprintln!("  {name}");
+            }
+        }
+    }
+    write_stats(stats_out.as_deref(), &result.to_stats_json())?;
+    Ok(())
+}
+
+/// The receive arguments for `ticket`, which may also be a short link.
+async fn ticket_args(ticket: &str, args: &ReceiveArgsCli) -> anyhow::Result<ReceiveArgs> {
+    let ticket = sendme_lib::shortlink::parse_or_resolve(ticket).await?;
+    Ok(ReceiveArgs {
+        common: args.common.config(),
+        ..ReceiveArgs::new(ticket)
+    })
+}
+
+/// Apply the options given on the command line to `lib_args`.
+fn apply_options(lib_args: &mut ReceiveArgs, args: &ReceiveArgsCli) {
+    if !args.allowed_relays.is_empty() {
+        lib_args.allowed_relays = Some(args.allowed_relays.clone());
+    }
+    lib_args.continue_on_export_error = args.continue_on_export_error;
+    lib_args.expect_code = args.expect_code.clone();
+    lib_args.merge_with_existing = args.merge_with_existing;
+    lib_args.stream_reconnects = args.stream_reconnects;
+    lib_args.fifo = args.fifo.clone();
+    lib_args.prewarm = args.prewarm;
+    lib_args.staging_dir = args.staging_dir.clone();
+    lib_args.auto_extract = args.auto_extract;
+    lib_args.fsync = args.fsync;
+    lib_args.relay_selection = args.relay_selection;
+    lib_args.apply_sender_metadata = args.apply_sender_metadata;
+    lib_args.on_conflict = args.on_conflict;
+    lib_args.write_manifest = args.manifest;
+    lib_args.common.max_download_bps = args.max_download_rate;
+}
+
+/// Receive several tickets, `--parallel` of them at a time.
+async fn receive_tickets(args: ReceiveArgsCli, output: OutputMode) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !args.test_connect && args.plan_only.is_none() && args.since.is_none() && !args.tree,
+        "--test-connect, --plan-only, --since and --tree take a single ticket"
+    );
+    anyhow::ensure!(args.fifo.is_none(), "--fifo takes a single ticket");
+    let progress_json = args.common.progress_json.is_some();
+    #[cfg(unix)]
+    let progress_json = progress_json || args.common.progress_fd.is_some();
+    anyhow::ensure!(
+        !progress_json,
+        "--progress-json and --progress-fd take a single ticket"
+    );
+    let mut lib_args = Vec::with_capacity(args.ticket.len());
+    for ticket in &args.ticket {
+        let mut ticket_args = ticket_args(ticket, &args).await?;
+        apply_options(&mut ticket_args, &args);
+        lib_args.push(ticket_args);
+    }
+
+    let (states_tx, mut states_rx) = mpsc::channel(32);
+    let show_states = output.progress;
+    let states = tokio::spawn(async move {
+        while let Some((index, state)) = states_rx.recv().await {
+            if show_states {
+                let state = match state {
+                    TicketState::Queued => "queued",
+                    TicketState::Active => "receiving",
+                    TicketState::Done => "done",
+                };
+                eprintln!("ticket {}: {state}", index + 1);
+            }
+        }
+    });
+    let results = sendme_lib::receive_many(lib_args, args.parallel, Some(states_tx)).await;
+    let _ = states.await;
+
+    let mut failed = 0;
+    let mut json = Vec::new();
+    for (index, result) in results.iter().enumerate() {
+        let n = index + 1;
+        match result {
+            Ok(result) if output.json => json.push(serde_json::json!({
+                "files": result.total_files,
+                "size": result.payload_size,
+                "failed_files": result.failed_files,
+            })),
+            Ok(result) => println!(
+                "ticket {n}: downloaded {} files, {}",
+                result.total_files,
+                HumanBytes(result.payload_size)
+            ),
+            Err(e) => {
+                failed += 1;
+                if output.json {
+                    json.push(serde_json::json!({ "error": format!("{e:#}") }));
+                } else {
+                    eprintln!("ticket {n}: failed: {e:#}");
+                }
+            }
+        }
+    }
+    if output.json {
+        println!("{}", serde_json::Value::Array(json));
+    }
+    anyhow::ensure!(failed == 0, "{failed} of {} tickets failed", results.len());
+    Ok(())
+}
+
+/// The plan of an earlier share for `--since`: a saved plan if `since` is a
+/// file, or otherwise fetched from the sender of the ticket.
+async fn previous_plan(since: &str, args: &ReceiveArgs) -> anyhow::Result<ReceivePlan> {
+    if std::path::Path::new(since).is_file() {
+        return ReceivePlan::load(since);
+    }
+    let ticket = sendme_lib::links::parse_ticket(since)?;
+    sendme_lib::fetch_plan(ReceiveArgs {
+        ticket,
+        only_files: None,
+        ..args.clone()
+    })
+    .await
+}
+
+/// Warn about files of an earlier share that `--since` did not delete because
+/// they were modified locally.
+fn print_kept(kept: &[std::path::PathBuf]) {
+    if kept.is_empty() {
+        return;
+    }
+    eprintln!(
+        "warning: kept {} removed files that were modified locally:",
+        kept.len()
+    );
+    for path in kept {
+        eprintln!("  {}", path.display());
+    }
+}
+
+/// Render download and export progress on stderr.
+async fn show_download_progress(mut progress_rx: ProgressReceiverRx) {
+    let mut bar: Option<ProgressBar> = None;
+    let replace = |bar: &mut Option<ProgressBar>, next: ProgressBar| {
+        if let Some(prev) = bar.replace(next) {
+            prev.finish_and_clear();
+        }
+    };
+
+    while let Some(event) = progress_rx.recv().await {
+        match event {
+            ProgressEvent::Download(DownloadProgress::EndpointReady { online: false }) => {
+                eprintln!("warning: endpoint is not online yet, connecting anyway");
+            }
+            ProgressEvent::Download(DownloadProgress::Throttled { bytes_per_sec }) => {
+                eprintln!("limiting download to {}/s", HumanBytes(bytes_per_sec));
+            }
+            ProgressEvent::Download(DownloadProgress::Connecting) => {
+                replace(&mut bar, make_spinner("[1/4] Connecting ..."));
+            }
+            ProgressEvent::Download(DownloadProgress::Restarted { reason }) => {
+                eprintln!("warning: discarding earlier partial download: {reason}");
+            }
+            ProgressEvent::Download(DownloadProgress::Stalled { attempt, reason }) => {
+                eprintln!("warning: connection lost, reconnecting (attempt {attempt}): {reason}");
+            }
+            ProgressEvent::Download(DownloadProgress::Resumed { .. }) => {
+                replace(&mut bar, make_spinner("[1/4] Reconnecting ..."));
+            }
+            ProgressEvent::Download(DownloadProgress::GettingSizes) => {
+                replace(&mut bar, make_spinner("[2/4] Getting sizes..."));
+            }
+            ProgressEvent::Download(DownloadProgress::Downloading { offset, total }) => {
+                if bar.as_ref().and_then(|bar| bar.length()) != Some(total) {
+                    replace(&mut bar, make_bytes_bar("[3/4] Downloading", total));
+                }
+                if let Some(ref bar) = bar {
+                    bar.set_position(offset);
+                }
+            }
+            ProgressEvent::Download(DownloadProgress::Verifying { offset, total }) => {
+                // the download bar can have the same length, so tell them apart by message
+                if bar.as_ref().map(|bar| bar.message()).as_deref() != Some("[4/4] Verifying") {
+                    replace(&mut bar, make_bytes_bar("[4/4] Verifying", total));
+                }
+                if let Some(ref bar) = bar {
+                    bar.set_position(offset);
+                }
+            }
+            ProgressEvent::Export(_, ExportProgress::Started { total_files }) => {
+                replace(&mut bar, make_files_bar("Exporting", total_files as u64));
+            }
+            ProgressEvent::Export(_, ExportProgress::FileCompleted { .. }) => {
+                if let Some(ref bar) = bar {
+                    bar.inc(1);
+                }
+            }
+            ProgressEvent::Export(_, ExportProgress::Completed)
+            | ProgressEvent::Download(DownloadProgress::Completed) => {
+                if let Some(bar) = bar.take() {
+                    bar.finish_and_clear();
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(bar) = bar.take() {
+        bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,204 @@
+//! The `send` command.
+
+use indicatif::{HumanBytes, HumanDuration, ProgressBar};
+use sendme_lib::{
+    progress::{ImportProgress, ProgressEvent, ProgressReceiverRx},
+    types::{SendArgs, SendManyArgs},
+    ImportEntry,
+};
+
+use super::{make_files_bar, print_hash, print_qr_code, spawn_progress, write_stats, OutputMode};
+use crate::args::SendArgsCli;
+
+/// Import and serve a file or directory until interrupted.
+pub async fn send(args: SendArgsCli) -> anyhow::Result<()> {
+    let output = OutputMode::new(args.no_qr, args.common.no_progress, args.common.json);
+    #[cfg(feature = "metrics")]
+    if let Some(port) = args.metrics_port {
+        metrics_exporter_prometheus::PrometheusBuilder::new()
+            .with_http_listener(([0, 0, 0, 0], port))
+            .install()?;
+    }
+    let format = args.common.format;
+    let stats_out = args.common.stats_out.clone();
+    let ticket_type = args.ticket_type;
+    let common = args.common.config();
+    let json_out = args.common.progress_json_out()?;
+    let mut paths = args.paths.into_iter();
+    let (name, is_dir, result) = match (paths.next(), args.url) {
+        (_, Some(url)) => {
+            let name = match args.name {
+                Some(name) => name,
+                None => sendme_lib::name_from_url(&url)?,
+            };
+            let lib_args = SendManyArgs {
+                entries: vec![ImportEntry::Url {
+                    name: name.clone(),
+                    url,
+                }],
+                root: None,
+                ticket_type,
+                common,
+                on_connect: None,
+            };
+            let result = match spawn_progress(output, json_out, show_import_progress) {
+                Some((progress_tx, _)) => {
+                    sendme_lib::send_many_with_progress(lib_args, progress_tx).await?
+                }
+                None => sendme_lib::send_many(lib_args).await?,
+            };
+            (name, false, result)
+        }
+        (Some(path), None) => {
+            let paths = paths.collect::<Vec<_>>();
+            let mut name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !paths.is_empty() {
+                name = format!("{name} and {} more", paths.len());
+            }
+            let is_dir = path.is_dir();
+            let lib_args = SendArgs {
+                paths,
+                ticket_type,
+                common,
+                attach_note: args.note,
+                unwrap_single_file: args.unwrap_single_file,
+                shortlink_service: args.shortlink_service,
+                double_read_verify: args.double_read_verify,
+                prewarm: args.prewarm,
+                strict_ticket_type: args.strict_ticket_type,
+                reimport_changed: args.reimport_changed,
+                non_utf8_names: args.non_utf8_names,
+                respect_gitignore: args.gitignore,
+                exclude: args.exclude,
+                follow_symlinks: args.follow_symlinks,
+                send_metadata: args.send_metadata,
+                ..SendArgs::new(path)
+            };
+            // The provider keeps reporting connection events after the import, so
+            // the progress task lives as long as the process.
+            let progress = spawn_progress(output, json_out, show_import_progress);
+            let result = match (args.store, progress) {
+                (Some(store), Some((progress_tx, _))) => {
+                    sendme_lib::send_serve_dir_with_progress(store, lib_args, progress_tx).await?
+                }
+                (None, Some((progress_tx, _))) => {
+                    sendme_lib::send_with_progress(lib_args, progress_tx).await?
+                }
+                (Some(store), None) => sendme_lib::send_serve_dir(store, lib_args).await?,
+                (None, None) => sendme_lib::send(lib_args).await?,
+            };
+            (name, is_dir, result)
+        }
+        (None, None) => anyhow::bail!("either a path or --url is required"),
+    };
+
+    if result.ticket_type != ticket_type {
+        eprintln!(
+            "warning: direct connections are unlikely to work, the ticket includes relays too"
+        );
+    }
+    let hash = print_hash(&result.hash, format);
+    let ticket = result.ticket.to_string();
+
+    if output.json {
+        let json = serde_json::json!({
+            "name": name,
+            "hash": hash,
+            "size": result.total_size,
+            "files": result.collection.len(),
+            "ticket": ticket,
+            "short_link": result.short_link,
+            "verification_code": result.verification_code(),
+            "estimated_secs": args
+                .estimate
+                .map(|rate| result.estimated_transfer_time(rate).as_secs_f64()),
+        });
+        println!("{json}");
+    } else {
+        let what = if is_dir { "directory" } else { "file" };
+        println!(
+            "imported {what} {name}, {}, hash {hash}",
+            HumanBytes(result.total_size)
+        );
+        if let Some(rate) = args.estimate {
+            let time = result.estimated_transfer_time(rate);
+            println!("≈ {:#} at {}/s", HumanDuration(time), HumanBytes(rate));
+        }
+        println!("to get this data, use");
+        println!("{}", sendme_lib::links::receive_command(&result.ticket));
+        if let Some(ref short_link) = result.short_link {
+            println!("or the short link");
+            println!("sendme receive {short_link}");
+        }
+        println!("verification code {}", result.verification_code());
+        let caption = args
+            .qr_caption
+            .then(|| sendme_lib::qr::caption(&name, result.total_size, result.ticket_type));
+        print_qr_code(&ticket, caption.as_deref(), output);
+    }
+    write_stats(stats_out.as_deref(), &result.to_stats_json())?;
+
+    // Without a terminal, e.g. under systemd, `kill -USR1` shows the transfers
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut usr1 = signal(SignalKind::user_defined1())?;
+        let handle = result.handle.clone();
+        tokio::spawn(async move {
+            while usr1.recv().await.is_some() {
+                eprintln!("{}", handle.status());
+            }
+        });
+    }
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Render import progress on stderr, then keep draining provider events.
+async fn show_import_progress(mut progress_rx: ProgressReceiverRx) {
+    let mut bar: Option<ProgressBar> = None;
+    while let Some(event) = progress_rx.recv().await {
+        let ProgressEvent::Import(_, progress) = event else {
+            continue;
+        };
+        match progress {
+            ImportProgress::Started { total_files } => {
+                bar = Some(make_files_bar("Importing", total_files as u64));
+            }
+            ImportProgress::FileCompleted { .. } => {
+                if let Some(ref bar) = bar {
+                    bar.inc(1);
+                }
+            }
+            ImportProgress::FileChanged { name } => {
+                let message = format!("warning: {name} changed while it was imported");
+                match bar {
+                    Some(ref bar) => bar.println(message),
+                    None => eprintln!("{message}"),
+                }
+            }
+            ImportProgress::FileSkipped { name } => {
+                let message = format!("warning: skipped {name}, its name is not valid UTF-8");
+                match bar {
+                    Some(ref bar) => bar.println(message),
+                    None => eprintln!("{message}"),
+                }
+            }
+            ImportProgress::BatchProgress { completed, .. } => {
+                if let Some(ref bar) = bar {
+                    bar.set_position(completed as u64);
+                }
+            }
+            ImportProgress::Completed { .. } => {
+                if let Some(bar) = bar.take() {
+                    bar.finish_and_clear();
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The `ticket` command.
+
+use crate::args::{TicketArgsCli, TicketCommand};
+
+/// Transform a ticket. This works offline, nothing is sent or fetched.
+pub fn ticket(args: TicketArgsCli) -> anyhow::Result<()> {
+    match args.command {
+        TicketCommand::Convert(args) => {
+            let ticket = sendme_lib::links::parse_ticket(&args.ticket)?;
+            let converted = sendme_lib::links::convert_ticket(&ticket, args.to);
+            if args.json {
+                let json = serde_json::json!({
+                    "ticket": converted.to_string(),
+                    "relay_urls": converted
+                        .addr()
+                        .relay_urls()
+                        .map(|url| url.to_string())
+                        .collect::<Vec<_>>(),
+                    "direct_addrs": converted
+                        .addr()
+                        .ip_addrs()
+                        .map(|addr| addr.to_string())
+                        .collect::<Vec<_>>(),
+                });
+                println!("{json}");
+            } else {
+                println!("{converted}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! The `verify-file` command.
+
+use sendme_lib::{types::ReceiveArgs, ReceivePlan};
+
+use super::OutputMode;
+use crate::args::VerifyFileArgsCli;
+
+/// Check local files against the hashes of a ticket or saved plan.
+///
+/// Fails if any file is missing or does not match.
+pub async fn verify_file(args: VerifyFileArgsCli) -> anyhow::Result<()> {
+    let output = OutputMode::new(true, args.common.no_progress, args.common.json);
+    let plan = match (args.ticket, args.plan) {
+        (_, Some(path)) => ReceivePlan::load(path)?,
+        (Some(ticket), None) => {
+            sendme_lib::fetch_plan(ReceiveArgs {
+                common: args.common.config(),
+                ..ReceiveArgs::new(ticket)
+            })
+            .await?
+        }
+        (None, None) => anyhow::bail!("either a ticket or --plan is required"),
+    };
+
+    let checks = plan.verify(&args.path)?;
+    let failed = checks.iter().filter(|check| !check.matches()).count();
+    if output.json {
+        println!("{}", serde_json::to_string(&checks)?);
+    } else {
+        for check in &checks {
+            let status = match check.actual {
+                None => "missing",
+                Some(_) if check.matches() => "ok",
+                Some(_) => "MISMATCH",
+            };
+            println!("{status:>8}  {}", check.name);
+        }
+    }
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} files do not match",
+        failed,
+        checks.len()
+    );
+    Ok(())
+}
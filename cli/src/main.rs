@@ -1,10 +1,12 @@
 //! Sendme CLI - Send files over the internet using iroh.
 //!
-//! Interactive TUI version with ratatui.
+//! Runs the interactive TUI (ratatui) by default, or the non-interactive
+//! `send` / `receive` commands when a subcommand is given.
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Result;
+use clap::Parser;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -14,15 +16,43 @@ use ratatui::{backend::CrosstermBackend, Terminal};
 use sendme_lib::{types::*, BlobTicket};
 use tokio::sync::mpsc;
 
+mod args;
+mod commands;
 mod tui;
 
-use tui::{app::TransferType, App, EventHandler, Transfer};
+use args::{Args, Commands};
+use tui::{app::TransferType, App, EventHandler, Transfer, TransferTasks};
 
 /// Tick rate for the event loop (ms).
 const TICK_RATE_MS: u64 = 250;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
+    match args.command {
+        Some(command) => {
+            // Keep stdout free for the command output (e.g. `--json`).
+            tracing_subscriber::fmt()
+                .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+                .with_writer(std::io::stderr)
+                .init();
+            match command {
+                Commands::Send(args) => match commands::send(args).await {
+                    // interrupted with Ctrl+C while importing
+                    Err(e) if e.is::<sendme_lib::send::Cancelled>() => std::process::exit(130),
+                    result => result,
+                },
+                Commands::Receive(args) => commands::receive(args).await,
+                Commands::VerifyFile(args) => commands::verify_file(args).await,
+                Commands::Ticket(args) => commands::ticket(args),
+            }
+        }
+        None => run_tui().await,
+    }
+}
+
+/// Run the interactive terminal UI.
+async fn run_tui() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     // Setup terminal in a blocking task
@@ -40,31 +70,54 @@ async fn main() -> Result<()> {
     // Create app
     let mut app = App::new();
 
+    // Background transfers, stopped and cleaned up when the TUI exits
+    let tasks = Arc::new(TransferTasks::new(std::env::temp_dir()));
+
     // Channels for async operations
     let (send_tx, mut send_rx) = mpsc::channel::<SendRequest>(32);
     let (receive_tx, mut receive_rx) = mpsc::channel::<ReceiveRequest>(32);
 
     // Spawn background tasks
     let send_event_handler = event_handler.clone();
+    let send_tasks = tasks.clone();
     tokio::spawn(async move {
         while let Some(event) = send_rx.recv().await {
-            if let Err(e) = handle_send_request(event, send_event_handler.clone()).await {
-                eprintln!("Send error: {}", e);
-            }
+            let event_handler = send_event_handler.clone();
+            let tasks = send_tasks.clone();
+            let handle = tokio::spawn(async move {
+                if let Err(e) = handle_send_request(event, &tasks, event_handler).await {
+                    eprintln!("Send error: {}", e);
+                }
+            });
+            // sends store their data with the shared server
+            send_tasks.track(send_tasks.server_dir(), handle);
         }
     });
 
     let receive_event_handler = event_handler.clone();
+    let receive_tasks = tasks.clone();
     tokio::spawn(async move {
         while let Some(event) = receive_rx.recv().await {
-            if let Err(e) = handle_receive_request(event, receive_event_handler.clone()).await {
-                eprintln!("Receive error: {}", e);
-            }
+            let temp_dir = match receive_tasks.temp_dir(&event.transfer_id) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    eprintln!("Receive error: {}", e);
+                    continue;
+                }
+            };
+            let event_handler = receive_event_handler.clone();
+            let dir = temp_dir.clone();
+            let handle = tokio::spawn(async move {
+                if let Err(e) = handle_receive_request(event, dir, event_handler).await {
+                    eprintln!("Receive error: {}", e);
+                }
+            });
+            receive_tasks.track(temp_dir, handle);
         }
     });
 
     // Run the event loop in a blocking task, then restore terminal
-    tokio::task::spawn_blocking(move || {
+    let mut app = tokio::task::spawn_blocking(move || {
         let mut terminal = Terminal::new(backend)?;
 
         loop {
@@ -82,18 +135,15 @@ async fn main() -> Result<()> {
                             if let crossterm::event::KeyCode::Enter = key.code {
                                 if !app.send_input_path.is_empty() {
                                     let path = app.send_input_path.clone();
-                                    let transfer_id = uuid::Uuid::new_v4().to_string();
 
-                                    let mut transfer =
-                                        Transfer::new(TransferType::Send, path.clone());
-                                    transfer.id = transfer_id.clone();
-                                    app.add_transfer(transfer.clone());
+                                    let transfer = Transfer::new(TransferType::Send, path.clone());
+                                    app.add_transfer(transfer);
 
                                     app.send_input_path.clear();
                                     app.send_message = format!("Initiating send for: {}", path);
 
                                     // Use try_send in spawn_blocking context
-                                    let _ = send_tx.try_send(SendRequest { path, transfer_id });
+                                    let _ = send_tx.try_send(SendRequest { path });
                                 }
                             }
                         }
@@ -154,7 +204,7 @@ async fn main() -> Result<()> {
                         // Store ticket in the transfer and show success view
                         if let Some(transfer) = app.transfers.last_mut() {
                             transfer.ticket = Some(ticket.clone());
-                            transfer.status = tui::app::TransferStatus::Serving;
+                            transfer.set_status(tui::app::TransferStatus::Serving);
                         }
                         app.set_send_success(ticket, path);
                     }
@@ -164,7 +214,8 @@ async fn main() -> Result<()> {
                     }
                     Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                         // Channel closed, exit
-                        return Ok(());
+                        app.running = false;
+                        break;
                     }
                 }
             }
@@ -186,71 +237,60 @@ async fn main() -> Result<()> {
         )?;
         terminal.show_cursor()?;
 
-        Ok::<(), anyhow::Error>(())
+        Ok::<_, anyhow::Error>(app)
     })
     .await??;
 
+    // Stop whatever is still running, so nothing is left behind in the temp dir
+    let summary = tasks.shutdown(&mut app).await;
+    println!("Exiting: {summary}");
+
     Ok(())
 }
 
 /// Parse a ticket string, handling various formats.
 fn parse_ticket(s: &str) -> Result<BlobTicket> {
-    let s = s.trim();
-
-    // Remove "sendme receive" prefix if present
-    let s = if s.starts_with("sendme receive ") {
-        s["sendme receive ".len()..].trim()
-    } else {
-        s
-    };
-
-    s.parse::<BlobTicket>()
-        .map_err(|e| anyhow::anyhow!("Failed to parse ticket: {}", e))
+    sendme_lib::links::parse_ticket(s)
 }
 
 /// Send request.
-#[allow(dead_code)]
 struct SendRequest {
     path: String,
-    transfer_id: String,
 }
 
 /// Receive request.
-#[allow(dead_code)]
 struct ReceiveRequest {
     ticket: BlobTicket,
     transfer_id: String,
 }
 
-/// Handle a send request.
-async fn handle_send_request(request: SendRequest, event_handler: EventHandler) -> Result<()> {
+/// Handle a send request by adding the path to the server shared by all sends.
+async fn handle_send_request(
+    request: SendRequest,
+    tasks: &TransferTasks,
+    event_handler: EventHandler,
+) -> Result<()> {
     let path = PathBuf::from(&request.path);
 
     if !path.exists() {
         return Err(anyhow::anyhow!("Path does not exist: {}", request.path));
     }
 
-    let args = SendArgs {
-        path,
-        ticket_type: AddrInfoOptions::RelayAndAddresses,
-        common: CommonConfig::default(),
-    };
-
+    let server = tasks.server(&event_handler).await?;
     let (progress_tx, mut progress_rx) = mpsc::channel(32);
     let event_handler_clone = event_handler.clone();
-    let request_path_clone = request.path.clone();
 
-    // Spawn progress forwarding task (runs indefinitely for connection events)
+    // Connection events come from the server, this only sees the import
     tokio::spawn(async move {
         while let Some(event) = progress_rx.recv().await {
             event_handler_clone.send_transfer_update(event);
         }
     });
 
-    // Run send_with_progress and send completion event
-    match sendme_lib::send_with_progress(args, progress_tx).await {
-        Ok(result) => {
-            event_handler.send_send_completed(result.ticket.to_string(), request_path_clone);
+    // Add the path and send completion event
+    match server.add_path_with_progress(path, progress_tx).await {
+        Ok(ticket) => {
+            event_handler.send_send_completed(ticket.to_string(), request.path);
         }
         Err(e) => {
             eprintln!("Send error: {}", e);
@@ -263,12 +303,15 @@ async fn handle_send_request(request: SendRequest, event_handler: EventHandler)
 /// Handle a receive request.
 async fn handle_receive_request(
     request: ReceiveRequest,
+    temp_dir: PathBuf,
     event_handler: EventHandler,
 ) -> Result<()> {
     let args = ReceiveArgs {
-        ticket: request.ticket,
-        common: CommonConfig::default(),
-        export_dir: None,
+        common: CommonConfig {
+            temp_dir: Some(temp_dir),
+            ..Default::default()
+        },
+        ..ReceiveArgs::new(request.ticket)
     };
 
     let (progress_tx, mut progress_rx) = mpsc::channel(32);
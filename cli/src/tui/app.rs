@@ -4,7 +4,7 @@ use crate::tui::file_search::FileSearchPopup;
 use sendme_lib::progress::{DownloadProgress, ProgressEvent};
 use sendme_lib::Hash;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Current tab in the application.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -73,6 +73,7 @@ pub enum TransferStatus {
     Serving,
     Connecting,
     Downloading,
+    Verifying,
     Exporting,
     Completed,
     Error(String),
@@ -88,6 +89,7 @@ impl TransferStatus {
                 | TransferStatus::Serving
                 | TransferStatus::Connecting
                 | TransferStatus::Downloading
+                | TransferStatus::Verifying
                 | TransferStatus::Exporting
         )
     }
@@ -107,6 +109,7 @@ impl std::fmt::Display for TransferStatus {
             TransferStatus::Serving => write!(f, "Serving (waiting for peer)"),
             TransferStatus::Connecting => write!(f, "Connecting..."),
             TransferStatus::Downloading => write!(f, "Downloading..."),
+            TransferStatus::Verifying => write!(f, "Verifying..."),
             TransferStatus::Exporting => write!(f, "Exporting files..."),
             TransferStatus::Completed => write!(f, "Completed"),
             TransferStatus::Error(msg) => write!(f, "Error: {}", msg),
@@ -144,6 +147,14 @@ pub struct Transfer {
     pub progress: u16,
     /// File names in the collection (for receive transfers).
     pub file_names: Vec<String>,
+    /// When the transfer started.
+    pub started: Instant,
+    /// When the current status was entered.
+    pub phase_started: Instant,
+    /// When the transfer finished, if it has.
+    pub finished: Option<Instant>,
+    /// Durations of the phases the transfer went through, in order.
+    pub phases: Vec<(TransferStatus, Duration)>,
 }
 
 impl Transfer {
@@ -166,11 +177,51 @@ impl Transfer {
                 .as_secs() as i64,
             progress: 0,
             file_names: Vec::new(),
+            started: Instant::now(),
+            phase_started: Instant::now(),
+            finished: None,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Time since the transfer started, or its total duration once finished.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.finished.unwrap_or(now).saturating_duration_since(self.started)
+    }
+
+    /// Time spent in the current status so far.
+    pub fn phase_elapsed(&self, now: Instant) -> Duration {
+        self.finished
+            .unwrap_or(now)
+            .saturating_duration_since(self.phase_started)
+    }
+
+    /// Change the status, recording how long the previous one lasted.
+    pub fn set_status(&mut self, status: TransferStatus) {
+        self.set_status_at(status, Instant::now());
+    }
+
+    /// Change the status at `now`, recording how long the previous one lasted.
+    pub fn set_status_at(&mut self, status: TransferStatus, now: Instant) {
+        if status == self.status {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.status, status);
+        let duration = now.saturating_duration_since(self.phase_started);
+        self.phases.push((previous, duration));
+        self.phase_started = now;
+        if self.status.is_finished() {
+            self.finished = Some(now);
         }
     }
 
     /// Update transfer progress based on progress event.
     pub fn update_progress(&mut self, event: &ProgressEvent) {
+        self.update_progress_at(event, Instant::now());
+    }
+
+    /// Update transfer progress based on an event that happened at `now`.
+    pub fn update_progress_at(&mut self, event: &ProgressEvent, now: Instant) {
         match event {
             ProgressEvent::Download(DownloadProgress::Metadata {
                 total_size,
@@ -180,7 +231,7 @@ impl Transfer {
                 self.total_bytes = *total_size;
                 self.total_files = *file_count;
                 self.file_names = names.clone();
-                self.status = TransferStatus::Downloading;
+                self.set_status_at(TransferStatus::Downloading, now);
             }
             ProgressEvent::Download(DownloadProgress::Downloading { offset, total }) => {
                 self.transferred_bytes = *offset;
@@ -191,12 +242,20 @@ impl Transfer {
                     0
                 };
             }
+            ProgressEvent::Download(DownloadProgress::Verifying { offset, total }) => {
+                self.set_status_at(TransferStatus::Verifying, now);
+                self.progress = if *total > 0 {
+                    (*offset as f64 / *total as f64 * 100.0) as u16
+                } else {
+                    0
+                };
+            }
             ProgressEvent::Download(DownloadProgress::Completed) => {
-                self.status = TransferStatus::Completed;
+                self.set_status_at(TransferStatus::Completed, now);
                 self.progress = 100;
             }
             ProgressEvent::Download(DownloadProgress::Connecting) => {
-                self.status = TransferStatus::Connecting;
+                self.set_status_at(TransferStatus::Connecting, now);
             }
             ProgressEvent::Import(_, progress) => match progress {
                 sendme_lib::progress::ImportProgress::Started { total_files } => {
@@ -205,14 +264,20 @@ impl Transfer {
                 sendme_lib::progress::ImportProgress::FileCompleted { .. } => {
                     self.transferred_files += 1;
                 }
+                sendme_lib::progress::ImportProgress::BatchProgress { completed, .. } => {
+                    self.transferred_files = *completed as u64;
+                }
                 sendme_lib::progress::ImportProgress::Completed { total_size } => {
                     self.total_bytes = *total_size;
-                    self.status = TransferStatus::Serving;
+                    self.set_status_at(TransferStatus::Serving, now);
+                }
+                sendme_lib::progress::ImportProgress::Cancelled => {
+                    self.set_status_at(TransferStatus::Cancelled, now);
                 }
                 _ => {}
             },
             ProgressEvent::Export(_, progress) => {
-                self.status = TransferStatus::Exporting;
+                self.set_status_at(TransferStatus::Exporting, now);
                 match progress {
                     sendme_lib::progress::ExportProgress::Started { total_files } => {
                         self.total_files = *total_files as u64;
@@ -221,7 +286,7 @@ impl Transfer {
                         self.transferred_files += 1;
                     }
                     sendme_lib::progress::ExportProgress::Completed => {
-                        self.status = TransferStatus::Completed;
+                        self.set_status_at(TransferStatus::Completed, now);
                     }
                     _ => {}
                 }
@@ -628,3 +693,55 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sendme_lib::progress::ExportProgress;
+
+    #[test]
+    fn test_phase_timings() {
+        let mut transfer = Transfer::new(TransferType::Receive, "from ticket".to_string());
+        let t0 = transfer.started;
+        transfer.phase_started = t0;
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        let connecting = ProgressEvent::Download(DownloadProgress::Connecting);
+        transfer.update_progress_at(&connecting, at(1));
+        let metadata = ProgressEvent::Download(DownloadProgress::Metadata {
+            total_size: 10,
+            file_count: 1,
+            names: vec!["a".to_string()],
+        });
+        transfer.update_progress_at(&metadata, at(4));
+        let downloading = ProgressEvent::Download(DownloadProgress::Downloading {
+            offset: 5,
+            total: 10,
+        });
+        transfer.update_progress_at(&downloading, at(6));
+        assert_eq!(transfer.phase_elapsed(at(9)), Duration::from_secs(5));
+        assert_eq!(transfer.elapsed(at(9)), Duration::from_secs(9));
+        let verifying = ProgressEvent::Download(DownloadProgress::Verifying {
+            offset: 0,
+            total: 10,
+        });
+        transfer.update_progress_at(&verifying, at(12));
+
+        let export = |progress| ProgressEvent::Export(String::new(), progress);
+        transfer.update_progress_at(&export(ExportProgress::Started { total_files: 1 }), at(14));
+        transfer.update_progress_at(&export(ExportProgress::Completed), at(15));
+
+        assert_eq!(
+            transfer.phases,
+            vec![
+                (TransferStatus::Initializing, Duration::from_secs(1)),
+                (TransferStatus::Connecting, Duration::from_secs(3)),
+                (TransferStatus::Downloading, Duration::from_secs(8)),
+                (TransferStatus::Verifying, Duration::from_secs(2)),
+                (TransferStatus::Exporting, Duration::from_secs(1)),
+            ]
+        );
+        // finished transfers stop counting
+        assert_eq!(transfer.elapsed(at(100)), Duration::from_secs(15));
+    }
+}
@@ -3,10 +3,12 @@
 pub mod app;
 pub mod event;
 pub mod file_search;
+pub mod tasks;
 mod ui;
 
 pub mod tabs;
 
 pub use app::{App, Transfer};
 pub use event::EventHandler;
+pub use tasks::TransferTasks;
 pub use ui::render_ui;
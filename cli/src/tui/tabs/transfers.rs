@@ -62,7 +62,15 @@ fn render_transfers_list(f: &mut Frame, app: &App, area: Rect) {
 
         f.render_widget(empty, chunks[1]);
     } else {
-        let header_cells = vec!["Type", "Path", "Status", "Progress", "Size", "Time"];
+        let header_cells = vec![
+            "Type",
+            "Path",
+            "Status",
+            "Progress",
+            "Size",
+            "Phase (total)",
+            "Time",
+        ];
         let header = Row::new(header_cells.iter().map(|h| {
             Cell::from(*h).style(
                 Style::default()
@@ -73,6 +81,7 @@ fn render_transfers_list(f: &mut Frame, app: &App, area: Rect) {
         .height(1)
         .bottom_margin(1);
 
+        let now = std::time::Instant::now();
         let rows: Vec<Row> = app
             .transfers
             .iter()
@@ -126,6 +135,11 @@ fn render_transfers_list(f: &mut Frame, app: &App, area: Rect) {
                     Cell::from(format!("{}%", transfer.progress))
                         .style(Style::default().fg(progress_color)),
                     Cell::from(format_bytes(transfer.total_bytes)),
+                    Cell::from(format!(
+                        "{} ({})",
+                        format_duration(transfer.phase_elapsed(now)),
+                        format_duration(transfer.elapsed(now))
+                    )),
                     Cell::from(format_time(transfer.created_at)),
                 ])
                 .style(row_style)
@@ -136,12 +150,13 @@ fn render_transfers_list(f: &mut Frame, app: &App, area: Rect) {
         let table = Table::new(
             rows,
             [
+                Constraint::Percentage(8),
+                Constraint::Percentage(22),
+                Constraint::Percentage(22),
                 Constraint::Percentage(10),
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
-                Constraint::Percentage(10),
-                Constraint::Percentage(15),
-                Constraint::Percentage(15),
+                Constraint::Percentage(12),
+                Constraint::Percentage(14),
+                Constraint::Percentage(12),
             ],
         )
         .header(header)
@@ -151,12 +166,13 @@ fn render_transfers_list(f: &mut Frame, app: &App, area: Rect) {
                 .border_style(Style::default().fg(Color::DarkGray)),
         )
         .widths(&[
+            Constraint::Percentage(8),
+            Constraint::Percentage(22),
+            Constraint::Percentage(22),
             Constraint::Percentage(10),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(10),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
+            Constraint::Percentage(12),
+            Constraint::Percentage(14),
+            Constraint::Percentage(12),
         ]);
 
         f.render_widget(table, chunks[1]);
@@ -369,6 +385,18 @@ fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// Format a duration as e.g. `42s`, `3m12s` or `1h05m`.
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
 /// Format timestamp to human readable time.
 fn format_time(timestamp: i64) -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -0,0 +1,238 @@
+//! Background transfers started from the TUI, and their cleanup on exit.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use sendme_lib::{AddrInfoOptions, CommonConfig, Server, ServerArgs};
+use tokio::{
+    sync::{mpsc, OnceCell},
+    task::JoinHandle,
+};
+
+use crate::tui::{
+    app::{App, TransferStatus, TransferType},
+    EventHandler,
+};
+
+/// The id of the temporary directory of the shared [`Server`].
+const SERVER_ID: &str = "server";
+
+/// The transfers running in the background, with the directories holding
+/// their data.
+///
+/// Each receive gets its own temporary directory, so everything it stored
+/// can be removed when the TUI exits, even if it was interrupted. Sends share
+/// one [`Server`], see [`TransferTasks::server`].
+pub struct TransferTasks {
+    base_dir: PathBuf,
+    tasks: Mutex<Vec<TransferTask>>,
+    server: OnceCell<Server>,
+}
+
+#[derive(Debug)]
+struct TransferTask {
+    temp_dir: PathBuf,
+    handle: JoinHandle<()>,
+}
+
+impl TransferTasks {
+    /// Track transfers whose temporary directories are created in `base_dir`.
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            tasks: Mutex::new(Vec::new()),
+            server: OnceCell::new(),
+        }
+    }
+
+    /// The server all sends are added to, so they don't bind an endpoint
+    /// each and share one node id.
+    ///
+    /// It is started by the first send, with its connection events forwarded
+    /// to `events`.
+    pub async fn server(&self, events: &EventHandler) -> anyhow::Result<&Server> {
+        self.server
+            .get_or_try_init(|| async {
+                let store_dir = self.temp_dir(SERVER_ID)?;
+                let (progress_tx, mut progress_rx) = mpsc::channel(32);
+                let events = events.clone();
+                tokio::spawn(async move {
+                    while let Some(event) = progress_rx.recv().await {
+                        events.send_transfer_update(event);
+                    }
+                });
+                let args = ServerArgs {
+                    store_dir: store_dir.clone(),
+                    ticket_type: AddrInfoOptions::RelayAndAddresses,
+                    common: CommonConfig {
+                        temp_dir: Some(store_dir),
+                        ..Default::default()
+                    },
+                    on_connect: None,
+                };
+                Server::spawn_with_progress(args, progress_tx).await
+            })
+            .await
+    }
+
+    /// The temporary directory of the server, see [`Self::server`].
+    pub fn server_dir(&self) -> PathBuf {
+        self.base_dir.join(format!("sendme-tui-{SERVER_ID}"))
+    }
+
+    /// The temporary directory for a transfer. It is created, so it can be
+    /// used as [`sendme_lib::CommonConfig::temp_dir`] right away.
+    pub fn temp_dir(&self, transfer_id: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.base_dir.join(format!("sendme-tui-{transfer_id}"));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Track a running transfer that stores its data in `temp_dir`.
+    pub fn track(&self, temp_dir: PathBuf, handle: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push(TransferTask { temp_dir, handle });
+    }
+
+    /// Stop all transfers and remove their temporary directories.
+    ///
+    /// Transfers in `app` that were still running are marked as cancelled,
+    /// and the returned summary tells what completed and what didn't.
+    pub async fn shutdown(&self, app: &mut App) -> ExitSummary {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in &tasks {
+            task.handle.abort();
+        }
+        let mut temp_dirs = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            // aborted tasks resolve to a cancelled error, which is expected here
+            let _ = task.handle.await;
+            temp_dirs.push(task.temp_dir);
+        }
+        // the store is closed before its directory is removed
+        if let Some(server) = self.server.get() {
+            if let Err(e) = server.shutdown().await {
+                tracing::warn!("failed to shut down the send server: {}", e);
+            }
+            temp_dirs.push(self.server_dir());
+        }
+        for dir in temp_dirs {
+            remove_temp_dir(&dir);
+        }
+
+        let mut summary = ExitSummary::default();
+        for transfer in &mut app.transfers {
+            let label = format!("{} {}", transfer.transfer_type.name(), transfer.path);
+            match &transfer.status {
+                TransferStatus::Completed => summary.completed.push(label),
+                TransferStatus::Error(_) => summary.failed.push(label),
+                TransferStatus::Cancelled => summary.cancelled.push(label),
+                TransferStatus::Serving if transfer.transfer_type == TransferType::Send => {
+                    summary.stopped.push(label);
+                    transfer.set_status(TransferStatus::Cancelled);
+                }
+                _ => {
+                    summary.cancelled.push(label);
+                    transfer.set_status(TransferStatus::Cancelled);
+                }
+            }
+        }
+        summary
+    }
+}
+
+fn remove_temp_dir(dir: &Path) {
+    match std::fs::remove_dir_all(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!("failed to remove {}: {}", dir.display(), e),
+    }
+}
+
+/// What happened to the transfers of a TUI session, see
+/// [`TransferTasks::shutdown`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExitSummary {
+    /// Transfers that completed.
+    pub completed: Vec<String>,
+    /// Sends that were serving their data until the TUI exited.
+    pub stopped: Vec<String>,
+    /// Transfers that were interrupted before they completed.
+    pub cancelled: Vec<String>,
+    /// Transfers that failed.
+    pub failed: Vec<String>,
+}
+
+impl std::fmt::Display for ExitSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} completed, {} stopped serving, {} cancelled, {} failed",
+            self.completed.len(),
+            self.stopped.len(),
+            self.cancelled.len(),
+            self.failed.len()
+        )?;
+        for label in &self.cancelled {
+            write!(f, "\n  cancelled: {label}")?;
+        }
+        for label in &self.failed {
+            write!(f, "\n  failed: {label}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+    use crate::tui::Transfer;
+
+    #[tokio::test]
+    async fn test_interrupt_cleans_up() -> anyhow::Result<()> {
+        let base = tempfile::tempdir()?;
+        let tasks = TransferTasks::new(base.path());
+        let mut app = App::new();
+
+        let mut done = Transfer::new(TransferType::Receive, "from ticket".to_string());
+        done.set_status(TransferStatus::Completed);
+        let mut serving = Transfer::new(TransferType::Send, "photos".to_string());
+        serving.set_status(TransferStatus::Serving);
+        let mut receiving = Transfer::new(TransferType::Receive, "from ticket".to_string());
+        receiving.set_status(TransferStatus::Downloading);
+        let receiving_id = receiving.id.clone();
+
+        // a receive that never finishes on its own, with data in its directory
+        let temp_dir = tasks.temp_dir(&receiving_id)?;
+        std::fs::write(temp_dir.join("partial"), "half a file")?;
+        let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel::<()>();
+        tasks.track(
+            temp_dir.clone(),
+            tokio::spawn(async move {
+                let _dropped_tx = dropped_tx;
+                std::future::pending::<()>().await;
+            }),
+        );
+        for transfer in [done, serving, receiving] {
+            app.add_transfer(transfer);
+        }
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(!app.running);
+        let summary = tasks.shutdown(&mut app).await;
+
+        // the task was dropped, and its data removed
+        assert!(dropped_rx.await.is_err());
+        assert!(!temp_dir.exists());
+        assert_eq!(summary.completed, vec!["Receive from ticket"]);
+        assert_eq!(summary.stopped, vec!["Send photos"]);
+        assert_eq!(summary.cancelled, vec!["Receive from ticket"]);
+        assert!(summary.failed.is_empty());
+        let receiving = app.transfers.iter().find(|t| t.id == receiving_id).unwrap();
+        assert_eq!(receiving.status, TransferStatus::Cancelled);
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! Extraction of received archives.
+//!
+//! A directory can be sent as a single `.tar` or `.zip` file. With
+//! [`crate::ReceiveArgs::auto_extract`], the receiver unpacks it into the
+//! export directory, so it gets the same tree as if the directory had been
+//! sent as is.
+
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::get_export_path;
+
+/// The archive formats that can be extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Tar,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// The format of a file, by the extension of its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (_, ext) = name.rsplit_once('.')?;
+        if ext.eq_ignore_ascii_case("tar") {
+            Some(Self::Tar)
+        } else if ext.eq_ignore_ascii_case("zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extract `archive` into `root`, returning the paths of the extracted files.
+///
+/// All entries are checked before anything is written, so an archive with
+/// an entry outside of `root` fails without extracting anything. Entries
+/// that are neither files nor directories, like symlinks, are skipped.
+pub fn extract(archive: &Path, kind: ArchiveKind, root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let open = || {
+        File::open(archive).with_context(|| format!("failed to open {}", archive.display()))
+    };
+    match kind {
+        ArchiveKind::Tar => {
+            for entry in tar::Archive::new(open()?).entries()? {
+                entry_path(root, &entry?.path()?)?;
+            }
+            extract_tar(open()?, root)
+        }
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(open()?)?;
+            for name in zip.file_names() {
+                entry_path(root, Path::new(name))?;
+            }
+            extract_zip(&mut zip, root)
+        }
+    }
+}
+
+fn extract_tar(file: File, root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(file).entries()? {
+        let mut entry = entry?;
+        let target = entry_path(root, &entry.path()?)?;
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if kind.is_file() {
+            write_entry(&mut entry, &target)?;
+            files.push(target);
+        } else {
+            tracing::warn!("⚠️ Skipping archive entry {}", target.display());
+        }
+    }
+    Ok(files)
+}
+
+fn extract_zip<R: Read + Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    root: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let target = entry_path(root, Path::new(entry.name()))?;
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.is_symlink() {
+            tracing::warn!("⚠️ Skipping archive entry {}", target.display());
+        } else {
+            write_entry(&mut entry, &target)?;
+            files.push(target);
+        }
+    }
+    Ok(files)
+}
+
+fn write_entry(reader: &mut impl Read, target: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = File::create(target)
+        .with_context(|| format!("failed to create {}", target.display()))?;
+    std::io::copy(reader, &mut file)?;
+    Ok(())
+}
+
+/// The path in `root` of an archive entry, failing for absolute paths and
+/// paths that would leave `root`.
+fn entry_path(root: &Path, entry: &Path) -> anyhow::Result<PathBuf> {
+    let relative: PathBuf = entry
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    let name = crate::canonicalized_path_to_string(&relative, true, false)
+        .with_context(|| format!("archive entry {} is outside of the root", entry.display()))?;
+    get_export_path(root, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    fn write_zip(path: &Path, files: &[(&str, &str)]) -> anyhow::Result<()> {
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default())?;
+            zip.write_all(data.as_bytes())?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_kind() {
+        assert_eq!(ArchiveKind::from_name("backup.tar"), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::from_name("photos.ZIP"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::from_name("notes.txt"), None);
+        assert_eq!(ArchiveKind::from_name("zip"), None);
+    }
+
+    #[test]
+    fn test_extract_tar() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = tempfile::tempdir()?;
+        let path = dir.path().join("tree.tar");
+        let mut tar = tar::Builder::new(File::create(&path)?);
+        for (name, data) in [("a.txt", "a"), ("sub/b.txt", "b")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, data.as_bytes())?;
+        }
+        tar.into_inner()?;
+
+        let files = extract(&path, ArchiveKind::Tar, root.path())?;
+        assert_eq!(files.len(), 2);
+        assert_eq!(std::fs::read_to_string(root.path().join("a.txt"))?, "a");
+        assert_eq!(std::fs::read_to_string(root.path().join("sub/b.txt"))?, "b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_slip_rejected() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root)?;
+        let path = dir.path().join("evil.zip");
+        write_zip(&path, &[("fine.txt", "fine"), ("../evil.txt", "evil")])?;
+
+        assert!(extract(&path, ArchiveKind::Zip, &root).is_err());
+        assert!(!dir.path().join("evil.txt").exists());
+        // nothing is extracted, not even the entries before the bad one
+        assert_eq!(std::fs::read_dir(&root)?.count(), 0);
+
+        write_zip(&path, &[("/etc/evil.txt", "evil")])?;
+        assert!(extract(&path, ArchiveKind::Zip, &root).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Collection diffs - receiving only what changed since an earlier share.
+//!
+//! When the same directory is shared again after it changed, a receiver that
+//! still has the earlier version only needs the files that were added or
+//! changed, and can delete the files that were removed, unless they were
+//! modified locally since.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use iroh_blobs::{format::collection::Collection, Hash};
+use serde::{Deserialize, Serialize};
+
+/// The difference between two collections, see [`collection_diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionDiff {
+    /// Files only in the new collection, with their hashes.
+    pub added: Vec<(String, Hash)>,
+    /// Files only in the old collection, with their old hashes.
+    pub removed: Vec<(String, Hash)>,
+    /// Files in both collections whose content changed, with their new hashes.
+    pub changed: Vec<(String, Hash)>,
+}
+
+impl CollectionDiff {
+    /// Whether the collections have the same files with the same content.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Names of the files that need to be fetched from the new collection.
+    pub fn to_fetch(&self) -> Vec<String> {
+        self.added
+            .iter()
+            .chain(self.changed.iter())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// The distinct blobs that need to be downloaded. Files with identical
+    /// content share a blob, so this can be smaller than [`Self::to_fetch`].
+    pub fn fetch_hashes(&self) -> HashSet<Hash> {
+        self.added
+            .iter()
+            .chain(self.changed.iter())
+            .map(|(_, hash)| *hash)
+            .collect()
+    }
+
+    /// Delete the removed files from a directory the old collection was
+    /// received into. Files that are already gone are skipped, and files whose
+    /// content no longer matches the old collection are kept.
+    pub fn remove_files(&self, root: &Path) -> anyhow::Result<RemovedFiles> {
+        let mut removed = RemovedFiles::default();
+        for (name, hash) in &self.removed {
+            let path = crate::get_export_path(root, name)?;
+            match crate::import::hash_file(&path) {
+                Ok(current) if current == *hash => {}
+                Ok(_) => {
+                    removed.kept.push(path);
+                    continue;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => anyhow::bail!("failed to read {}: {}", path.display(), e),
+            }
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed.deleted.push(path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => anyhow::bail!("failed to remove {}: {}", path.display(), e),
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// The outcome of [`CollectionDiff::remove_files`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RemovedFiles {
+    /// Paths that were deleted.
+    pub deleted: Vec<PathBuf>,
+    /// Paths that were modified locally, and therefore not deleted.
+    pub kept: Vec<PathBuf>,
+}
+
+/// Compare two collections by file name and content hash.
+///
+/// The entries in each list are sorted by name.
+pub fn collection_diff(old: &Collection, new: &Collection) -> CollectionDiff {
+    let old: BTreeMap<&String, &Hash> = old.iter().map(|(name, hash)| (name, hash)).collect();
+    let new: BTreeMap<&String, &Hash> = new.iter().map(|(name, hash)| (name, hash)).collect();
+
+    let mut diff = CollectionDiff::default();
+    for (name, hash) in &new {
+        match old.get(name) {
+            None => diff.added.push(((*name).clone(), **hash)),
+            Some(old_hash) if old_hash != hash => diff.changed.push(((*name).clone(), **hash)),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|(name, _)| !new.contains_key(*name))
+        .map(|(name, hash)| ((*name).clone(), **hash))
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(files: &[(&str, &str)]) -> Collection {
+        files
+            .iter()
+            .map(|(name, data)| (name.to_string(), Hash::new(data)))
+            .collect()
+    }
+
+    #[test]
+    fn test_collection_diff() {
+        let old = collection(&[("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")]);
+        let new = collection(&[
+            ("a.txt", "a"),
+            ("b.txt", "b2"),
+            ("d.txt", "d"),
+            ("e.txt", "d"),
+        ]);
+        let diff = collection_diff(&old, &new);
+        assert_eq!(
+            diff.added,
+            vec![
+                ("d.txt".to_string(), Hash::new("d")),
+                ("e.txt".to_string(), Hash::new("d")),
+            ]
+        );
+        assert_eq!(diff.changed, vec![("b.txt".to_string(), Hash::new("b2"))]);
+        assert_eq!(diff.removed, vec![("c.txt".to_string(), Hash::new("c"))]);
+
+        assert_eq!(diff.to_fetch(), vec!["d.txt", "e.txt", "b.txt"]);
+        let hashes = diff.fetch_hashes();
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.contains(&Hash::new("d")) && hashes.contains(&Hash::new("b2")));
+
+        assert!(collection_diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_remove_files() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("sub/old.txt"), "old")?;
+        std::fs::write(dir.path().join("edited.txt"), "edited locally")?;
+        std::fs::write(dir.path().join("kept.txt"), "kept")?;
+
+        let old = collection(&[
+            ("sub/old.txt", "old"),
+            ("gone.txt", "x"),
+            ("edited.txt", "edited"),
+            ("kept.txt", "kept"),
+        ]);
+        let new = collection(&[("kept.txt", "kept")]);
+        let removed = collection_diff(&old, &new).remove_files(dir.path())?;
+        assert_eq!(removed.deleted, vec![dir.path().join("sub/old.txt")]);
+        assert_eq!(removed.kept, vec![dir.path().join("edited.txt")]);
+        assert!(dir.path().join("edited.txt").exists());
+        assert!(dir.path().join("kept.txt").exists());
+        Ok(())
+    }
+}
@@ -1,24 +1,86 @@
 //! File export functionality.
 
-use iroh_blobs::{format::collection::Collection, store::fs::FsStore};
-use std::path::Path;
+use iroh_blobs::{api::Store, format::collection::Collection};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use n0_future::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::{get_export_path, progress::ProgressSenderTx};
+use crate::{
+    get_export_path, import::FileMetadata, progress::ProgressSenderTx, scheduler, ExportConflict,
+    NameRewrite, Priority,
+};
+
+/// The name of the manifest written with [`crate::ReceiveArgs::write_manifest`].
+///
+/// Each line holds the hex BLAKE3 hash of a file, two spaces and its name
+/// relative to the export directory, like the output of `b3sum`.
+pub const MANIFEST: &str = ".sendme-manifest.txt";
+
+/// Options for [`export`] and [`export_staged`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    /// Each file holds a worker from the shared [`scheduler`] pool at this
+    /// priority while it is copied.
+    pub priority: Priority,
+    /// Skip files that can't be written and return their names, instead of
+    /// aborting the export.
+    pub continue_on_error: bool,
+    /// Sync each file and its directory entry to disk before the file is
+    /// reported as completed. This is slower, but a file that was reported
+    /// survives a crash or power loss.
+    pub fsync: bool,
+    /// Give all files the permissions and modification times recorded in
+    /// [`crate::import::FILE_METADATA`]. Without it, only executable files get
+    /// back their permissions, on Unix.
+    pub apply_metadata: bool,
+    /// What to do with files that already exist in the export directory.
+    pub on_conflict: ExportConflict,
+}
+
+/// What [`export`] or [`export_staged`] did with the files of a collection.
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// The path each file was written to, by collection name. A file that
+    /// [`ExportOptions::on_conflict`] renamed is listed under its new path.
+    pub written: HashMap<String, PathBuf>,
+    /// Files that were left out because they already existed, see
+    /// [`ExportConflict::Skip`].
+    pub skipped: Vec<String>,
+    /// Files that could not be written, see [`ExportOptions::continue_on_error`].
+    pub failed: Vec<String>,
+}
 
 /// Export a collection to a directory.
 ///
-/// If `export_dir` is None, uses the current directory.
+/// If `export_dir` is None, uses the current directory. If `name_rewrite` is
+/// set, files are exported under the rewritten names.
+///
+/// Files of a [`crate::NonUtf8Policy::Lossy`] import are exported under their
+/// original names where the platform allows it, unless `name_rewrite` is set.
+///
+/// Each file is written under a temporary name and only renamed into place
+/// once complete.
 pub async fn export(
-    db: &FsStore,
+    db: &Store,
     collection: Collection,
     progress_tx: Option<ProgressSenderTx>,
     export_dir: Option<&Path>,
-) -> anyhow::Result<()> {
+    name_rewrite: Option<&NameRewrite>,
+    options: &ExportOptions,
+) -> anyhow::Result<ExportReport> {
+    let ExportOptions {
+        priority,
+        continue_on_error,
+        fsync,
+        apply_metadata,
+        on_conflict,
+    } = *options;
     // Use provided export_dir or fall back to current directory
     let root = export_dir
-        .map(std::path::PathBuf::from)
+        .map(PathBuf::from)
         .unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"));
 
     tracing::info!("📤 Exporting collection to: {:?}", root);
@@ -39,101 +101,603 @@ pub async fn export(
 
     tracing::info!("✅ Export directory writable: {:?}", root);
 
+    let originals = original_names(db, &collection).await?;
+    let recorded = file_metadata(db, &collection).await?;
+
+    // Resolve all targets up front, so an invalid rewrite fails before anything is written
+    let mut seen = HashSet::new();
+    let mut targets = Vec::with_capacity(collection.len());
+    for (name, hash) in collection.iter() {
+        if originals.is_some() && name == crate::import::ORIGINAL_NAMES {
+            continue;
+        }
+        if recorded.is_some() && name == crate::import::FILE_METADATA {
+            continue;
+        }
+        let export_name = match name_rewrite {
+            Some(rewrite) => rewrite.apply(name)?,
+            None => name.clone(),
+        };
+        anyhow::ensure!(
+            seen.insert(export_name.clone()),
+            "more than one file would be exported as {}",
+            export_name
+        );
+        let original = originals.as_ref().filter(|_| name_rewrite.is_none());
+        let target = match original.and_then(|originals| originals.get(name)) {
+            Some(relative) => root.join(relative),
+            None => get_export_path(&root, &export_name)?,
+        };
+        targets.push((name, *hash, target));
+    }
+    let mut report = ExportReport::default();
+    let mut taken = targets.iter().map(|(_, _, target)| target.clone()).collect();
+    targets.retain_mut(|(name, _, target)| {
+        match resolve_conflict(target, on_conflict, &mut taken) {
+            Some(resolved) => {
+                *target = resolved;
+                true
+            }
+            None => {
+                tracing::info!("⏭️ Skipping {}, {} already exists", name, target.display());
+                report.skipped.push(name.to_string());
+                false
+            }
+        }
+    });
+
     if let Some(ref tx) = progress_tx {
         let _ = tx
             .send(crate::progress::ProgressEvent::Export(
                 "".to_string(),
                 crate::progress::ExportProgress::Started {
-                    total_files: collection.len(),
+                    total_files: targets.len(),
+                },
+            ))
+            .await;
+    }
+
+    for (name, hash, target) in targets {
+        let _permit = scheduler::global().acquire(priority).await;
+        let metadata = recorded
+            .as_ref()
+            .and_then(|recorded| recorded.get(name))
+            .map(|metadata| restorable(*metadata, apply_metadata));
+        let path = target.clone();
+        match export_file(db, name, hash, target, metadata, fsync, &progress_tx).await {
+            Ok(()) => {
+                report.written.insert(name.clone(), path);
+            }
+            Err(e) if continue_on_error => {
+                tracing::warn!("⚠️ Skipping {}: {:#}", name, e);
+                report.failed.push(name.clone());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                "".to_string(),
+                crate::progress::ExportProgress::Completed,
+            ))
+            .await;
+    }
+
+    Ok(report)
+}
+
+/// Export a collection into `staging` first, and move the files to
+/// `export_dir` only once all of them match their hashes.
+///
+/// Files are moved with a rename where possible. If the export or the check
+/// fails, nothing is written to `export_dir` and the staged files are removed.
+pub async fn export_staged(
+    db: &Store,
+    collection: Collection,
+    progress_tx: Option<ProgressSenderTx>,
+    staging: &Path,
+    export_dir: &Path,
+    name_rewrite: Option<&NameRewrite>,
+    options: &ExportOptions,
+) -> anyhow::Result<ExportReport> {
+    // Resolve the names once, so a rewrite with state gives the same names for
+    // the export and the move
+    let mut export_names = HashMap::new();
+    for (name, _) in collection.iter() {
+        let export_name = match name_rewrite {
+            Some(rewrite) => rewrite.apply(name)?,
+            None => name.clone(),
+        };
+        export_names.insert(name.clone(), export_name);
+    }
+    let fixed = {
+        let names = export_names.clone();
+        NameRewrite::new(move |name| names.get(name).cloned().unwrap_or_else(|| name.into()))
+    };
+
+    let originals = match name_rewrite {
+        Some(_) => None,
+        None => original_names(db, &collection).await?,
+    };
+    let has_metadata = file_metadata(db, &collection).await?.is_some();
+
+    let staged = staging.join(format!(".sendme-staged-{}", uuid::Uuid::new_v4()));
+    tokio::fs::create_dir_all(&staged).await?;
+    let result = async {
+        let staged_options = ExportOptions {
+            on_conflict: ExportConflict::Overwrite,
+            ..*options
+        };
+        let failed = export(
+            db,
+            collection.clone(),
+            progress_tx,
+            Some(&staged),
+            Some(&fixed),
+            &staged_options,
+        )
+        .await?
+        .failed;
+        let names = (&export_names, originals.as_ref(), has_metadata);
+        move_verified(&collection, failed, &staged, export_dir, names, options).await
+    }
+    .await;
+    tokio::fs::remove_dir_all(&staged).await.ok();
+    result
+}
+
+/// Check the files exported to `staged` against the collection, then move
+/// them to `export_dir`, handling existing files as
+/// [`ExportOptions::on_conflict`] says. Nothing is moved if any file doesn't
+/// match. The `failed` files of the staged export are left out.
+///
+/// `names` holds the export name of each file, the original names of a
+/// lossy import, which take precedence, and whether the collection has
+/// [`crate::import::FILE_METADATA`], which is not exported.
+async fn move_verified(
+    collection: &Collection,
+    failed: Vec<String>,
+    staged: &Path,
+    export_dir: &Path,
+    names: (&HashMap<String, String>, Option<&HashMap<String, PathBuf>>, bool),
+    options: &ExportOptions,
+) -> anyhow::Result<ExportReport> {
+    let (export_names, originals, has_metadata) = names;
+    let ExportOptions {
+        on_conflict, fsync, ..
+    } = *options;
+    let mut moves = Vec::new();
+    for (name, hash) in collection.iter() {
+        if failed.contains(name) {
+            continue;
+        }
+        if originals.is_some() && name == crate::import::ORIGINAL_NAMES {
+            continue;
+        }
+        if has_metadata && name == crate::import::FILE_METADATA {
+            continue;
+        }
+        let export_name = export_names.get(name).unwrap_or(name);
+        let from = get_export_path(staged, export_name)?;
+        let path = from.clone();
+        let actual = tokio::task::spawn_blocking(move || crate::import::hash_file(&path)).await??;
+        anyhow::ensure!(actual == *hash, "{} does not match its hash after export", name);
+        let to = match originals.and_then(|originals| originals.get(name)) {
+            Some(relative) => export_dir.join(relative),
+            None => get_export_path(export_dir, export_name)?,
+        };
+        moves.push((name, from, to));
+    }
+    let mut report = ExportReport::default();
+    let mut taken = moves.iter().map(|(_, _, to)| to.clone()).collect();
+    for (name, from, to) in moves {
+        let Some(to) = resolve_conflict(&to, on_conflict, &mut taken) else {
+            tracing::info!("⏭️ Skipping {}, {} already exists", name, to.display());
+            report.skipped.push(name.clone());
+            continue;
+        };
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        persist(&from, &to, fsync).await?;
+        report.written.insert(name.clone(), to);
+    }
+    report.failed = failed;
+    Ok(report)
+}
+
+/// Write [`MANIFEST`] into `export_dir`, listing the files of `collection`
+/// that `report` says were written, under the names they were written as.
+///
+/// The hashes are the ones of the collection, which the data was verified
+/// against while it was downloaded. Skipped and failed files are left out.
+pub(crate) async fn write_manifest(
+    collection: &Collection,
+    export_dir: &Path,
+    report: &ExportReport,
+) -> anyhow::Result<()> {
+    let mut manifest = String::new();
+    for (name, hash) in collection.iter() {
+        let Some(path) = report.written.get(name) else {
+            continue;
+        };
+        let relative = path.strip_prefix(export_dir).unwrap_or(path);
+        let relative = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        manifest.push_str(&format!("{}  {}\n", hash.to_hex(), relative));
+    }
+    let path = export_dir.join(MANIFEST);
+    tokio::fs::write(&path, manifest)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {}", path.display(), e))
+}
+
+/// Where to export a file to `target`, or `None` to leave it out, if
+/// `on_conflict` says so because `target` already exists.
+///
+/// `taken` holds the targets of the export, so a renamed file never gets
+/// the name of another file of the collection. The new name is added to it.
+fn resolve_conflict(
+    target: &Path,
+    on_conflict: ExportConflict,
+    taken: &mut HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    if target.symlink_metadata().is_err() {
+        return Some(target.to_path_buf());
+    }
+    match on_conflict {
+        ExportConflict::Overwrite => Some(target.to_path_buf()),
+        ExportConflict::Skip => None,
+        ExportConflict::Rename => {
+            let renamed = (1..)
+                .map(|n| numbered(target, n))
+                .find(|path| path.symlink_metadata().is_err() && !taken.contains(path))?;
+            tracing::info!("📝 {} already exists, using {}", target.display(), renamed.display());
+            taken.insert(renamed.clone());
+            Some(renamed)
+        }
+    }
+}
+
+/// `path` with ` (n)` added to the file name, before the extension, e.g.
+/// `notes (2).txt`.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!(" ({n})"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// The original names recorded by a [`crate::NonUtf8Policy::Lossy`] import,
+/// as relative paths by collection name.
+///
+/// Returns `None` if the collection has no [`crate::import::ORIGINAL_NAMES`]
+/// file, or if that file is not a list of original names, in which case it is
+/// exported like any other file. Names that can't be restored on this
+/// platform are left out.
+async fn original_names(
+    db: &Store,
+    collection: &Collection,
+) -> anyhow::Result<Option<HashMap<String, PathBuf>>> {
+    let Some((_, hash)) = collection
+        .iter()
+        .find(|(name, _)| name == crate::import::ORIGINAL_NAMES)
+    else {
+        return Ok(None);
+    };
+    let data = db.get_bytes(*hash).await?;
+    let Ok(encoded) = serde_json::from_slice::<HashMap<String, String>>(&data) else {
+        return Ok(None);
+    };
+    let mut originals = HashMap::new();
+    for (name, bytes) in encoded {
+        let Ok(bytes) = hex::decode(&bytes) else {
+            return Ok(None);
+        };
+        if let Some(path) = original_path(&bytes) {
+            originals.insert(name, path);
+        }
+    }
+    Ok(Some(originals))
+}
+
+/// The metadata recorded by an import, by collection name.
+///
+/// Returns `None` if the collection has no [`crate::import::FILE_METADATA`]
+/// file, or if that file is not a map of metadata, in which case it is
+/// exported like any other file.
+async fn file_metadata(
+    db: &Store,
+    collection: &Collection,
+) -> anyhow::Result<Option<HashMap<String, FileMetadata>>> {
+    let Some((_, hash)) = collection
+        .iter()
+        .find(|(name, _)| name == crate::import::FILE_METADATA)
+    else {
+        return Ok(None);
+    };
+    let data = db.get_bytes(*hash).await?;
+    Ok(serde_json::from_slice(&data).ok())
+}
+
+/// What to restore of the `recorded` metadata of a file: the permissions of
+/// executables, or everything with `apply_metadata`.
+fn restorable(recorded: FileMetadata, apply_metadata: bool) -> FileMetadata {
+    if apply_metadata {
+        return recorded;
+    }
+    FileMetadata {
+        mode: recorded.mode.filter(|mode| mode & 0o111 != 0),
+        modified: None,
+    }
+}
+
+/// Give an exported file its recorded modification time and permissions.
+async fn restore_metadata(path: &Path, metadata: FileMetadata) -> std::io::Result<()> {
+    if let Some(nanos) = metadata.modified {
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_nanos(nanos);
+        set_modified(path, modified).await?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = metadata.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = std::fs::Permissions::from_mode(mode & 0o777);
+        tokio::fs::set_permissions(path, permissions).await?;
+    }
+    Ok(())
+}
+
+/// Set the modification time of the file at `path`.
+async fn set_modified(path: &Path, modified: std::time::SystemTime) -> std::io::Result<()> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        // futimens only needs the file to be ours, Windows needs write access
+        #[cfg(unix)]
+        let file = std::fs::File::open(path)?;
+        #[cfg(not(unix))]
+        let file = std::fs::File::options().write(true).open(path)?;
+        file.set_modified(modified)
+    })
+    .await?
+}
+
+/// A relative path from its bytes, if it is safe to export to.
+#[cfg(unix)]
+fn original_path(bytes: &[u8]) -> Option<PathBuf> {
+    use std::{os::unix::ffi::OsStrExt, path::Component};
+    let path = Path::new(std::ffi::OsStr::from_bytes(bytes));
+    let mut components = path.components().peekable();
+    components.peek()?;
+    components
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then(|| path.to_path_buf())
+}
+
+/// Names that are not valid UTF-8 can't be restored on this platform.
+#[cfg(not(unix))]
+fn original_path(_bytes: &[u8]) -> Option<PathBuf> {
+    None
+}
+
+/// Export a single blob to `target`, reporting progress under `name`.
+///
+/// The blob is written to a `.part` file next to `target`, which is renamed
+/// over `target` once complete, so readers never see a partial file. The
+/// `metadata` to restore, if any, is set afterwards.
+async fn export_file(
+    db: &Store,
+    name: &str,
+    hash: iroh_blobs::Hash,
+    target: PathBuf,
+    metadata: Option<FileMetadata>,
+    fsync: bool,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<()> {
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                name.to_string(),
+                crate::progress::ExportProgress::FileStarted {
+                    name: name.to_string(),
+                    size: 0,
                 },
             ))
             .await;
     }
 
-    for (_i, (name, hash)) in collection.iter().enumerate() {
-        let target = get_export_path(&root, name)?;
+    write_atomic(&mut db.reader(hash), &target, name, fsync, progress_tx).await?;
+    if let Some(metadata) = metadata {
+        restore_metadata(&target, metadata)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to restore the metadata of {}: {}", name, e))?;
+    }
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                name.to_string(),
+                crate::progress::ExportProgress::FileCompleted {
+                    name: name.to_string(),
+                },
+            ))
+            .await;
+    }
+    Ok(())
+}
 
-        // If file already exists, remove it to allow overwriting
-        if target.exists() {
-            std::fs::remove_file(&target).map_err(|e| {
-                anyhow::anyhow!(
-                    "failed to remove existing target {}: {}",
-                    target.display(),
-                    e
-                )
-            })?;
+/// Copy `reader` to `target` through a `.part` file in the same directory.
+///
+/// On failure the part file is removed and an existing `target` is left as is.
+/// If `fsync` is set, the file and the directory are synced to disk.
+async fn write_atomic<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    target: &Path,
+    name: &str,
+    fsync: bool,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<()> {
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid export target {}", target.display()))?;
+    let mut part_name = file_name.to_os_string();
+    part_name.push(".part");
+    let part = target.with_file_name(part_name);
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&part)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {}", part.display(), e))?;
+        copy_with_progress(reader, &mut file, name, progress_tx).await?;
+        if fsync {
+            file.sync_all().await?;
+        } else {
+            file.flush().await?;
         }
+        drop(file);
+        persist(&part, target, fsync).await
+    }
+    .await;
+    if result.is_err() {
+        tokio::fs::remove_file(&part).await.ok();
+    }
+    result.map_err(|e| anyhow::anyhow!("error exporting {}: {:#}", name, e))
+}
+
+/// Move a finished `tmp` file to `target`.
+///
+/// A rename is atomic, but fails if `tmp` is on another file system. Then the
+/// data is copied to a temporary file next to `target` and renamed. If
+/// `fsync` is set, the copy and the directory of `target` are synced.
+async fn persist(tmp: &Path, target: &Path, fsync: bool) -> anyhow::Result<()> {
+    if let Err(e) = tokio::fs::rename(tmp, target).await {
+        tracing::debug!("rename to {} failed ({}), copying instead", target.display(), e);
+        let staging = target.with_file_name(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        let copied = async {
+            tokio::fs::copy(tmp, &staging).await?;
+            // a restored modification time must survive the copy
+            set_modified(&staging, tokio::fs::metadata(tmp).await?.modified()?).await?;
+            if fsync {
+                tokio::fs::File::open(&staging).await?.sync_all().await?;
+            }
+            tokio::fs::rename(&staging, target).await
+        }
+        .await;
+        if let Err(e) = copied {
+            tokio::fs::remove_file(&staging).await.ok();
+            anyhow::bail!("failed to move export to {}: {}", target.display(), e);
+        }
+        tokio::fs::remove_file(tmp).await.ok();
+    }
+    if fsync {
+        sync_dir(target).await?;
+    }
+    Ok(())
+}
+
+/// Sync the directory that contains `path`, so a file renamed into it
+/// survives a crash.
+#[cfg(unix)]
+async fn sync_dir(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+    }
+    Ok(())
+}
+
+/// Directories can't be opened to sync them on this platform.
+#[cfg(not(unix))]
+async fn sync_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
 
+/// Copy `reader` into `writer`, reporting the offset after every chunk.
+async fn copy_with_progress<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+    name: &str,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<u64> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut offset = 0u64;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        offset += n as u64;
         if let Some(ref tx) = progress_tx {
             let _ = tx
                 .send(crate::progress::ProgressEvent::Export(
-                    name.clone(),
-                    crate::progress::ExportProgress::FileStarted {
-                        name: name.clone(),
-                        size: 0,
+                    name.to_string(),
+                    crate::progress::ExportProgress::FileProgress {
+                        name: name.to_string(),
+                        offset,
                     },
                 ))
                 .await;
         }
+    }
+    writer.flush().await?;
+    Ok(offset)
+}
 
-        let mut stream = db
-            .export_with_opts(iroh_blobs::api::blobs::ExportOptions {
-                hash: *hash,
-                target,
-                mode: iroh_blobs::api::blobs::ExportMode::Copy,
-            })
-            .stream()
-            .await;
+/// Stream a single-file collection into `writer`, e.g. stdout or a buffer.
+///
+/// Progress is reported by bytes written. Collections with more than one file
+/// need a directory, so they are rejected; use [`export`] for them.
+pub async fn export_to_writer<W: AsyncWrite + Unpin>(
+    db: &Store,
+    collection: Collection,
+    writer: &mut W,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<u64> {
+    anyhow::ensure!(
+        collection.len() == 1,
+        "can only write a single file, but the collection has {} files",
+        collection.len()
+    );
+    let (name, hash) = collection.iter().next().expect("checked above");
+    let name = name.clone();
 
-        while let Some(item) = stream.next().await {
-            match item {
-                iroh_blobs::api::blobs::ExportProgressItem::Size(size) => {
-                    if let Some(ref tx) = progress_tx {
-                        let _ = tx
-                            .send(crate::progress::ProgressEvent::Export(
-                                name.clone(),
-                                crate::progress::ExportProgress::FileProgress {
-                                    name: name.clone(),
-                                    offset: 0,
-                                },
-                            ))
-                            .await;
-                    }
-                    let _ = size;
-                }
-                iroh_blobs::api::blobs::ExportProgressItem::CopyProgress(offset) => {
-                    if let Some(ref tx) = progress_tx {
-                        let _ = tx
-                            .send(crate::progress::ProgressEvent::Export(
-                                name.clone(),
-                                crate::progress::ExportProgress::FileProgress {
-                                    name: name.clone(),
-                                    offset,
-                                },
-                            ))
-                            .await;
-                    }
-                }
-                iroh_blobs::api::blobs::ExportProgressItem::Done => {
-                    if let Some(ref tx) = progress_tx {
-                        let _ = tx
-                            .send(crate::progress::ProgressEvent::Export(
-                                name.clone(),
-                                crate::progress::ExportProgress::FileCompleted {
-                                    name: name.clone(),
-                                },
-                            ))
-                            .await;
-                    }
-                }
-                iroh_blobs::api::blobs::ExportProgressItem::Error(cause) => {
-                    anyhow::bail!("error exporting {}: {}", name, cause);
-                }
-            }
-        }
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                "".to_string(),
+                crate::progress::ExportProgress::Started { total_files: 1 },
+            ))
+            .await;
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                name.clone(),
+                crate::progress::ExportProgress::FileStarted {
+                    name: name.clone(),
+                    size: 0,
+                },
+            ))
+            .await;
     }
 
+    let offset = copy_with_progress(&mut db.reader(*hash), writer, &name, &progress_tx).await?;
+
     if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Export(
+                name.clone(),
+                crate::progress::ExportProgress::FileCompleted { name: name.clone() },
+            ))
+            .await;
         let _ = tx
             .send(crate::progress::ProgressEvent::Export(
                 "".to_string(),
@@ -142,5 +706,510 @@ pub async fn export(
             .await;
     }
 
-    Ok(())
+    Ok(offset)
+}
+
+/// The named pipe to stream a single-file collection into while it is
+/// downloaded, see [`crate::ReceiveArgs::fifo`].
+///
+/// Without an explicit `fifo`, the export path of the file is used if it is an
+/// existing named pipe.
+pub(crate) fn fifo_target(
+    fifo: Option<&Path>,
+    collection: &Collection,
+    export_dir: &Path,
+    name_rewrite: Option<&NameRewrite>,
+) -> anyhow::Result<Option<PathBuf>> {
+    if let Some(fifo) = fifo {
+        anyhow::ensure!(is_fifo(fifo), "{} is not a named pipe", fifo.display());
+        anyhow::ensure!(
+            collection.len() == 1,
+            "can only stream a single file into a pipe, but the collection has {} files",
+            collection.len()
+        );
+        return Ok(Some(fifo.to_path_buf()));
+    }
+    let Some((name, _)) = collection.iter().next().filter(|_| collection.len() == 1) else {
+        return Ok(None);
+    };
+    let name = match name_rewrite {
+        Some(rewrite) => rewrite.apply(name)?,
+        None => name.clone(),
+    };
+    let path = get_export_path(export_dir, &name)?;
+    Ok(is_fifo(&path).then_some(path))
+}
+
+/// Whether `export_dir` directly contains a named pipe, so the export path of
+/// a single file may be one, see [`fifo_target`].
+#[cfg(unix)]
+pub(crate) fn has_fifo(export_dir: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(entries) = std::fs::read_dir(export_dir) else {
+        return false;
+    };
+    entries
+        .flatten()
+        .any(|entry| entry.file_type().is_ok_and(|t| t.is_fifo()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn has_fifo(_export_dir: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|meta| meta.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Open the named pipe at `fifo` for writing. This waits for a reader.
+pub(crate) async fn open_fifo(fifo: &Path) -> anyhow::Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(fifo)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {}", fifo.display(), e))
+}
+
+/// Replace the error of a write to the pipe at `fifo` by a clearer one if the
+/// reader went away.
+pub(crate) fn pipe_error(e: anyhow::Error, fifo: &Path) -> anyhow::Error {
+    match e.downcast_ref::<std::io::Error>() {
+        Some(io) if io.kind() == std::io::ErrorKind::BrokenPipe => anyhow::anyhow!(
+            "the reader closed {} before the whole file was written",
+            fifo.display()
+        ),
+        _ => e,
+    }
+}
+
+/// Write the `size` bytes of blob `hash` into `pipe` as they arrive in the
+/// store, so a reader can start before the download is complete.
+///
+/// `verified` is how many bytes of the blob are in the store so far. Only
+/// those are read, in order.
+pub(crate) async fn stream_to_fifo<W: AsyncWrite + Unpin>(
+    db: &Store,
+    hash: iroh_blobs::Hash,
+    size: u64,
+    pipe: &mut W,
+    mut verified: tokio::sync::watch::Receiver<u64>,
+) -> anyhow::Result<u64> {
+    let mut reader = db.reader(hash);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut written = 0u64;
+    while written < size {
+        let available = *verified
+            .wait_for(|&offset| offset > written)
+            .await
+            .map_err(|_| anyhow::anyhow!("download stopped after {} bytes", written))?;
+        while written < available.min(size) {
+            let len = buf.len().min((available.min(size) - written) as usize);
+            reader.read_exact(&mut buf[..len]).await?;
+            pipe.write_all(&buf[..len]).await?;
+            written += len as u64;
+        }
+    }
+    pipe.flush().await?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use iroh_blobs::store::fs::FsStore;
+
+    use super::*;
+
+    async fn test_collection(db: &FsStore, files: &[(&str, &str)]) -> anyhow::Result<Collection> {
+        let mut entries = Vec::new();
+        for (name, data) in files {
+            let tag = db.add_bytes(data.as_bytes().to_vec()).await?;
+            entries.push((name.to_string(), tag.hash));
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    #[tokio::test]
+    async fn test_export_name_rewrite() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let export_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let files = [("photos/a.jpg", "a"), ("photos/b.jpg", "b"), ("notes.txt", "n")];
+        let collection = test_collection(&db, &files).await?;
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let rewrite = NameRewrite::new(move |name| {
+            if name.starts_with("photos/") && name.ends_with(".jpg") {
+                format!("img_{}.jpg", counter.fetch_add(1, Ordering::SeqCst))
+            } else {
+                name.to_string()
+            }
+        });
+        export(
+            &db,
+            collection,
+            None,
+            Some(export_dir.path()),
+            Some(&rewrite),
+            &ExportOptions::default(),
+        )
+        .await?;
+
+        let root = export_dir.path();
+        assert_eq!(std::fs::read_to_string(root.join("img_0.jpg"))?, "a");
+        assert_eq!(std::fs::read_to_string(root.join("img_1.jpg"))?, "b");
+        assert_eq!(std::fs::read_to_string(root.join("notes.txt"))?, "n");
+        assert!(!root.join("photos").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_unsafe_rewrite() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let export_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let collection = test_collection(&db, &[("a.txt", "a")]).await?;
+
+        let rewrite = NameRewrite::new(|name| format!("../{name}"));
+        let result = export(
+            &db,
+            collection,
+            None,
+            Some(export_dir.path()),
+            Some(&rewrite),
+            &ExportOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!export_dir.path().join("a.txt").exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_export_fsync() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let files = [("a.txt", "a"), ("dir/b.txt", "b")];
+        let collection = test_collection(&db, &files).await?;
+
+        for fsync in [true, false] {
+            let export_dir = tempfile::tempdir()?;
+            let root = export_dir.path();
+            let collection = collection.clone();
+            export(
+                &db,
+                collection,
+                None,
+                Some(root),
+                None,
+                &ExportOptions {
+                    fsync,
+                    ..Default::default()
+                },
+            )
+            .await?;
+            for (name, data) in files {
+                assert_eq!(std::fs::read_to_string(root.join(name))?, data);
+            }
+            assert_eq!(std::fs::read_dir(root.join("dir"))?.count(), 1);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_staged_export() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let staging = tempfile::tempdir()?;
+        let export_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let files = [("a.txt", "a"), ("dir/b.txt", "b")];
+        let collection = test_collection(&db, &files).await?;
+
+        // the staged copy of a file is changed before it is moved
+        let staged = staging.path().join("staged");
+        std::fs::create_dir(&staged)?;
+        export(
+            &db,
+            collection.clone(),
+            None,
+            Some(&staged),
+            None,
+            &ExportOptions::default(),
+        )
+        .await?;
+        std::fs::write(staged.join("dir/b.txt"), "changed")?;
+        let names = files
+            .iter()
+            .map(|(name, _)| (name.to_string(), name.to_string()))
+            .collect::<HashMap<_, _>>();
+        let names = (&names, None, false);
+        let to = export_dir.path();
+        let options = ExportOptions::default();
+        let result = move_verified(&collection, vec![], &staged, to, names, &options).await;
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_dir(export_dir.path())?.count(), 0);
+        std::fs::remove_dir_all(&staged)?;
+
+        let report = export_staged(
+            &db,
+            collection,
+            None,
+            staging.path(),
+            export_dir.path(),
+            None,
+            &ExportOptions::default(),
+        )
+        .await?;
+        assert!(report.failed.is_empty());
+        assert_eq!(std::fs::read_to_string(export_dir.path().join("a.txt"))?, "a");
+        assert_eq!(std::fs::read_to_string(export_dir.path().join("dir/b.txt"))?, "b");
+        assert_eq!(std::fs::read_dir(staging.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_to_writer() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let data = "streamed without touching the file system";
+        let collection = test_collection(&db, &[("single.txt", data)]).await?;
+
+        let mut out = Vec::new();
+        let written = export_to_writer(&db, collection, &mut out, None).await?;
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(out, data.as_bytes());
+
+        let collection = test_collection(&db, &[("a.txt", "a"), ("b.txt", "b")]).await?;
+        assert!(export_to_writer(&db, collection, &mut Vec::new(), None).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_empty_name() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let export_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let collection = test_collection(&db, &[("", "overwrites the root")]).await?;
+
+        let result = export(
+            &db,
+            collection,
+            None,
+            Some(export_dir.path()),
+            None,
+            &ExportOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(export_dir.path().is_dir());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_continue_on_error() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        // longer than any file system allows for a single name
+        let bad = "x".repeat(300);
+        let files = [("a.txt", "a"), (bad.as_str(), "bad"), ("b.txt", "b")];
+        let collection = test_collection(&db, &files).await?;
+
+        let export_dir = tempfile::tempdir()?;
+        let failed = export(
+            &db,
+            collection.clone(),
+            None,
+            Some(export_dir.path()),
+            None,
+            &ExportOptions {
+                continue_on_error: true,
+                ..Default::default()
+            },
+        )
+        .await?
+        .failed;
+        assert_eq!(failed, vec![bad]);
+        assert_eq!(std::fs::read_to_string(export_dir.path().join("a.txt"))?, "a");
+        assert_eq!(std::fs::read_to_string(export_dir.path().join("b.txt"))?, "b");
+
+        let export_dir = tempfile::tempdir()?;
+        let result = export(
+            &db,
+            collection,
+            None,
+            Some(export_dir.path()),
+            None,
+            &ExportOptions::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    /// A reader that yields some data and then fails, like a dropped connection.
+    struct FailingReader(Option<&'static [u8]>);
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(match self.0.take() {
+                Some(data) => {
+                    buf.put_slice(data);
+                    Ok(())
+                }
+                None => Err(std::io::Error::other("connection lost")),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_is_atomic() -> anyhow::Result<()> {
+        let export_dir = tempfile::tempdir()?;
+        let target = export_dir.path().join("nested/file.txt");
+
+        let mut reader = FailingReader(Some(b"partial".as_slice()));
+        let result = write_atomic(&mut reader, &target, "nested/file.txt", false, &None).await;
+        assert!(result.is_err());
+        assert!(!target.exists());
+        assert!(!export_dir.path().join("nested/file.txt.part").exists());
+
+        // a failed export leaves the previous file in place
+        std::fs::write(&target, "previous")?;
+        let mut reader = FailingReader(Some(b"partial".as_slice()));
+        let result = write_atomic(&mut reader, &target, "nested/file.txt", false, &None).await;
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&target)?, "previous");
+
+        let mut reader: &[u8] = b"complete";
+        write_atomic(&mut reader, &target, "nested/file.txt", false, &None).await?;
+        assert_eq!(std::fs::read_to_string(&target)?, "complete");
+        assert_eq!(std::fs::read_dir(target.parent().unwrap())?.count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_conflicts() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let files = [("notes.txt", "new"), ("notes (1).txt", "one"), ("README", "readme")];
+        let collection = test_collection(&db, &files).await?;
+
+        // returns the files in the export directory and their content
+        let export_to = |on_conflict, staged: bool| {
+            let (db, collection) = (&db, collection.clone());
+            async move {
+                let export_dir = tempfile::tempdir()?;
+                std::fs::write(export_dir.path().join("notes.txt"), "old")?;
+                std::fs::write(export_dir.path().join("README"), "mine")?;
+                let options = ExportOptions {
+                    on_conflict,
+                    ..Default::default()
+                };
+                if staged {
+                    let staging = tempfile::tempdir()?;
+                    let (from, to) = (staging.path(), export_dir.path());
+                    export_staged(db, collection, None, from, to, None, &options).await?;
+                } else {
+                    let to = Some(export_dir.path());
+                    export(db, collection, None, to, None, &options).await?;
+                }
+                let mut found = Vec::new();
+                for entry in std::fs::read_dir(export_dir.path())? {
+                    let path = entry?.path();
+                    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    found.push((name, std::fs::read_to_string(&path)?));
+                }
+                found.sort();
+                anyhow::Ok(found)
+            }
+        };
+        let found = |files: &[(&str, &str)]| {
+            files
+                .iter()
+                .map(|(name, data)| (name.to_string(), data.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        for staged in [false, true] {
+            assert_eq!(
+                export_to(ExportConflict::Overwrite, staged).await?,
+                found(&[("README", "readme"), ("notes (1).txt", "one"), ("notes.txt", "new")])
+            );
+            assert_eq!(
+                export_to(ExportConflict::Skip, staged).await?,
+                found(&[("README", "mine"), ("notes (1).txt", "one"), ("notes.txt", "old")])
+            );
+            // `notes (1).txt` is taken by a file of the collection
+            assert_eq!(
+                export_to(ExportConflict::Rename, staged).await?,
+                found(&[
+                    ("README", "mine"),
+                    ("README (1)", "readme"),
+                    ("notes (1).txt", "one"),
+                    ("notes (2).txt", "new"),
+                    ("notes.txt", "old"),
+                ])
+            );
+        }
+        assert_eq!(numbered(Path::new("dir/.env"), 3), Path::new("dir/.env (3)"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_manifest_after_conflicts() -> anyhow::Result<()> {
+        let store_dir = tempfile::tempdir()?;
+        let db = FsStore::load(store_dir.path()).await?;
+        let files = [("notes.txt", "new"), ("dir/a.txt", "a")];
+        let collection = test_collection(&db, &files).await?;
+        let line = |data: &str, name: &str| {
+            format!("{}  {}\n", blake3::hash(data.as_bytes()).to_hex(), name)
+        };
+
+        for staged in [false, true] {
+            for on_conflict in [ExportConflict::Skip, ExportConflict::Rename] {
+                let export_dir = tempfile::tempdir()?;
+                let to = export_dir.path();
+                std::fs::write(to.join("notes.txt"), "old")?;
+                let options = ExportOptions {
+                    on_conflict,
+                    ..Default::default()
+                };
+                let report = if staged {
+                    let staging = tempfile::tempdir()?;
+                    let from = staging.path();
+                    export_staged(&db, collection.clone(), None, from, to, None, &options).await?
+                } else {
+                    export(&db, collection.clone(), None, Some(to), None, &options).await?
+                };
+                write_manifest(&collection, to, &report).await?;
+
+                let manifest = std::fs::read_to_string(to.join(MANIFEST))?;
+                let expected = match on_conflict {
+                    // the old notes.txt is still there, so it is not listed
+                    ExportConflict::Skip => line("a", "dir/a.txt"),
+                    _ => line("new", "notes (1).txt") + &line("a", "dir/a.txt"),
+                };
+                assert_eq!(manifest, expected);
+            }
+        }
+        Ok(())
+    }
 }
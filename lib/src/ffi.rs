@@ -0,0 +1,695 @@
+//! A C API, for frontends that are not written in Rust, e.g. Swift or Kotlin
+//! bindings generated with cbindgen.
+//!
+//! Link it from a `staticlib` or `cdylib` crate that depends on sendme-lib
+//! with the `ffi` feature. The calls block until they are done, and report
+//! progress to a callback on the calling thread. Strings passed to the
+//! callback are only valid until it returns.
+#![allow(non_camel_case_types)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_void, CStr, CString},
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+use crate::{progress::*, AddrInfoOptions, CommonConfig, ReceiveArgs, SendArgs, SendResult};
+
+/// Runs the transfers, and keeps serving sends after [`sendme_send`] returns.
+static RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the runtime")
+});
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// What a [`sendme_progress_event_t`] is about.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum sendme_event_category_t {
+    /// [`ProgressEvent::Import`].
+    SENDME_EVENT_IMPORT,
+    /// [`ProgressEvent::Export`].
+    SENDME_EVENT_EXPORT,
+    /// [`ProgressEvent::Download`].
+    SENDME_EVENT_DOWNLOAD,
+    /// [`ProgressEvent::Connection`].
+    SENDME_EVENT_CONNECTION,
+    /// [`ProgressEvent::SendReady`].
+    SENDME_EVENT_SEND_READY,
+}
+
+/// A [`ProgressEvent`] as a C struct.
+///
+/// Fields that don't apply to an event are zero, or null for strings.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct sendme_progress_event_t {
+    /// What the event is about.
+    pub category: sendme_event_category_t,
+    /// The variant in snake case, e.g. `file_progress`.
+    pub kind: *const c_char,
+    /// A file name, ticket, endpoint id, hash or reason, depending on the
+    /// event. The names of a download's metadata are separated by newlines.
+    pub text: *const c_char,
+    /// A byte offset, or the number of items done.
+    pub offset: u64,
+    /// A size in bytes, a rate in bytes per second, or the number of items to
+    /// do.
+    pub total: u64,
+    /// A number of files, or a reconnect attempt.
+    pub count: u64,
+    /// The connection of a provider event.
+    pub connection_id: u64,
+    /// The request of a provider event.
+    pub request_id: u64,
+    /// Whether the endpoint is online or reachable.
+    pub flag: bool,
+}
+
+/// Called with each progress event and the `user_data` of the call.
+pub type sendme_progress_cb =
+    Option<unsafe extern "C" fn(event: *const sendme_progress_event_t, user_data: *mut c_void)>;
+
+/// A [`sendme_progress_event_t`] together with the strings it points to.
+#[derive(Debug)]
+pub struct FfiProgressEvent {
+    category: sendme_event_category_t,
+    kind: CString,
+    text: Option<CString>,
+    offset: u64,
+    total: u64,
+    count: u64,
+    connection_id: u64,
+    request_id: u64,
+    flag: bool,
+}
+
+impl FfiProgressEvent {
+    fn with(category: sendme_event_category_t, kind: &str) -> Self {
+        Self {
+            category,
+            kind: c_string(kind),
+            text: None,
+            offset: 0,
+            total: 0,
+            count: 0,
+            connection_id: 0,
+            request_id: 0,
+            flag: false,
+        }
+    }
+
+    fn text(mut self, text: &str) -> Self {
+        self.text = Some(c_string(text));
+        self
+    }
+
+    /// Convert `event`.
+    pub fn new(event: &ProgressEvent) -> Self {
+        use sendme_event_category_t::*;
+
+        match event {
+            ProgressEvent::Import(name, progress) => {
+                let event = Self::with(SENDME_EVENT_IMPORT, "");
+                let event = if name.is_empty() { event } else { event.text(name) };
+                match progress {
+                    ImportProgress::Started { total_files } => Self {
+                        kind: c_string("started"),
+                        count: *total_files as u64,
+                        ..event
+                    },
+                    ImportProgress::FileStarted { name, size } => Self {
+                        kind: c_string("file_started"),
+                        total: *size,
+                        ..event.text(name)
+                    },
+                    ImportProgress::FileProgress { name, offset } => Self {
+                        kind: c_string("file_progress"),
+                        offset: *offset,
+                        ..event.text(name)
+                    },
+                    ImportProgress::FileCompleted { name } => Self {
+                        kind: c_string("file_completed"),
+                        ..event.text(name)
+                    },
+                    ImportProgress::FileChanged { name } => Self {
+                        kind: c_string("file_changed"),
+                        ..event.text(name)
+                    },
+                    ImportProgress::FileSkipped { name } => Self {
+                        kind: c_string("file_skipped"),
+                        ..event.text(name)
+                    },
+                    ImportProgress::BatchProgress { completed, total } => Self {
+                        kind: c_string("batch_progress"),
+                        offset: *completed as u64,
+                        total: *total as u64,
+                        ..event
+                    },
+                    ImportProgress::Completed { total_size } => Self {
+                        kind: c_string("completed"),
+                        total: *total_size,
+                        ..event
+                    },
+                    ImportProgress::Cancelled => Self {
+                        kind: c_string("cancelled"),
+                        ..event
+                    },
+                }
+            }
+            ProgressEvent::Export(name, progress) => {
+                let event = Self::with(SENDME_EVENT_EXPORT, "");
+                let event = if name.is_empty() { event } else { event.text(name) };
+                match progress {
+                    ExportProgress::Started { total_files } => Self {
+                        kind: c_string("started"),
+                        count: *total_files as u64,
+                        ..event
+                    },
+                    ExportProgress::FileStarted { name, size } => Self {
+                        kind: c_string("file_started"),
+                        total: *size,
+                        ..event.text(name)
+                    },
+                    ExportProgress::FileProgress { name, offset } => Self {
+                        kind: c_string("file_progress"),
+                        offset: *offset,
+                        ..event.text(name)
+                    },
+                    ExportProgress::FileCompleted { name } => Self {
+                        kind: c_string("file_completed"),
+                        ..event.text(name)
+                    },
+                    ExportProgress::Completed => Self {
+                        kind: c_string("completed"),
+                        ..event
+                    },
+                }
+            }
+            ProgressEvent::Download(progress) => {
+                let event = |kind| Self::with(SENDME_EVENT_DOWNLOAD, kind);
+                match progress {
+                    DownloadProgress::EndpointReady { online } => Self {
+                        flag: *online,
+                        ..event("endpoint_ready")
+                    },
+                    DownloadProgress::Throttled { bytes_per_sec } => Self {
+                        total: *bytes_per_sec,
+                        ..event("throttled")
+                    },
+                    DownloadProgress::Connecting => event("connecting"),
+                    DownloadProgress::GettingSizes => event("getting_sizes"),
+                    DownloadProgress::Metadata {
+                        total_size,
+                        file_count,
+                        names,
+                    } => Self {
+                        total: *total_size,
+                        count: *file_count,
+                        ..event("metadata").text(&names.join("\n"))
+                    },
+                    DownloadProgress::Downloading { offset, total } => Self {
+                        offset: *offset,
+                        total: *total,
+                        ..event("downloading")
+                    },
+                    DownloadProgress::Verifying { offset, total } => Self {
+                        offset: *offset,
+                        total: *total,
+                        ..event("verifying")
+                    },
+                    DownloadProgress::Completed => event("completed"),
+                    DownloadProgress::Restarted { reason } => event("restarted").text(reason),
+                    DownloadProgress::Stalled { attempt, reason } => Self {
+                        count: u64::from(*attempt),
+                        ..event("stalled").text(reason)
+                    },
+                    DownloadProgress::Resumed { attempt } => Self {
+                        count: u64::from(*attempt),
+                        ..event("resumed")
+                    },
+                }
+            }
+            ProgressEvent::Connection(status) => {
+                let event = |kind, connection_id| Self {
+                    connection_id,
+                    ..Self::with(SENDME_EVENT_CONNECTION, kind)
+                };
+                match status {
+                    ConnectionStatus::EndpointReady { online } => Self {
+                        flag: *online,
+                        ..event("endpoint_ready", 0)
+                    },
+                    ConnectionStatus::ServerReady { reachable } => Self {
+                        flag: *reachable,
+                        ..event("server_ready", 0)
+                    },
+                    ConnectionStatus::ClientConnected {
+                        endpoint_id,
+                        connection_id,
+                    } => event("client_connected", *connection_id).text(endpoint_id),
+                    ConnectionStatus::ConnectionClosed { connection_id } => {
+                        event("connection_closed", *connection_id)
+                    }
+                    ConnectionStatus::RequestStarted {
+                        connection_id,
+                        request_id,
+                        hash,
+                        size,
+                    } => Self {
+                        request_id: *request_id,
+                        total: *size,
+                        ..event("request_started", *connection_id).text(&hash.to_hex())
+                    },
+                    ConnectionStatus::RequestProgress {
+                        connection_id,
+                        request_id,
+                        offset,
+                    } => Self {
+                        request_id: *request_id,
+                        offset: *offset,
+                        ..event("request_progress", *connection_id)
+                    },
+                    ConnectionStatus::RequestCompleted {
+                        connection_id,
+                        request_id,
+                    } => Self {
+                        request_id: *request_id,
+                        ..event("request_completed", *connection_id)
+                    },
+                    ConnectionStatus::RequestRejected {
+                        connection_id,
+                        request_id,
+                        hash,
+                    } => Self {
+                        request_id: *request_id,
+                        ..event("request_rejected", *connection_id).text(&hash.to_hex())
+                    },
+                }
+            }
+            ProgressEvent::SendReady {
+                ticket,
+                total_size,
+                ..
+            } => Self {
+                total: *total_size,
+                ..Self::with(SENDME_EVENT_SEND_READY, "send_ready").text(&ticket.to_string())
+            },
+        }
+    }
+
+    /// The C struct, valid as long as `self` is.
+    pub fn as_c(&self) -> sendme_progress_event_t {
+        sendme_progress_event_t {
+            category: self.category,
+            kind: self.kind.as_ptr(),
+            text: self.text.as_ref().map_or(std::ptr::null(), |text| text.as_ptr()),
+            offset: self.offset,
+            total: self.total,
+            count: self.count,
+            connection_id: self.connection_id,
+            request_id: self.request_id,
+            flag: self.flag,
+        }
+    }
+}
+
+/// Receive `ticket` into the directory `export_dir`, keeping the partial
+/// data in `temp_dir`.
+///
+/// Returns 0 on success, or -1 on failure, see [`sendme_last_error`].
+///
+/// # Safety
+///
+/// `ticket`, `export_dir` and `temp_dir` must be valid NUL terminated
+/// strings. `callback` may be null, and is called with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn sendme_receive(
+    ticket: *const c_char,
+    export_dir: *const c_char,
+    temp_dir: *const c_char,
+    callback: sendme_progress_cb,
+    user_data: *mut c_void,
+) -> i32 {
+    let result = receive_args(ticket, export_dir, temp_dir).and_then(|args| {
+        run_with_progress(callback, user_data, |tx| crate::receive_with_progress(args, tx))
+    });
+    match result {
+        Ok(_) => 0,
+        Err(e) => set_error(e),
+    }
+}
+
+/// Import `path` and serve it until the process exits, with the data in
+/// `temp_dir`.
+///
+/// Returns the ticket, to be freed with [`sendme_string_free`], or null on
+/// failure, see [`sendme_last_error`]. Events are reported until the ticket
+/// is returned.
+///
+/// # Safety
+///
+/// `path` and `temp_dir` must be valid NUL terminated strings. `callback`
+/// may be null, and is called with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn sendme_send(
+    path: *const c_char,
+    temp_dir: *const c_char,
+    callback: sendme_progress_cb,
+    user_data: *mut c_void,
+) -> *mut c_char {
+    let result = send_args(path, temp_dir).and_then(|args| {
+        run_with_progress(callback, user_data, |tx| crate::send_with_progress(args, tx))
+    });
+    match result {
+        Ok(SendResult { ticket, .. }) => c_string(&ticket.to_string()).into_raw(),
+        Err(e) => {
+            set_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// The message of the last error on this thread, or null. It is valid until
+/// the next call on this thread.
+#[no_mangle]
+pub extern "C" fn sendme_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Free a string returned by this API.
+///
+/// # Safety
+///
+/// `s` must be null, or a string returned by this API that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn sendme_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// # Safety
+///
+/// See [`sendme_receive`].
+unsafe fn receive_args(
+    ticket: *const c_char,
+    export_dir: *const c_char,
+    temp_dir: *const c_char,
+) -> anyhow::Result<ReceiveArgs> {
+    let ticket = crate::links::parse_ticket(str_arg(ticket)?)?;
+    Ok(ReceiveArgs {
+        common: CommonConfig {
+            temp_dir: Some(PathBuf::from(str_arg(temp_dir)?)),
+            ..Default::default()
+        },
+        export_dir: Some(PathBuf::from(str_arg(export_dir)?)),
+        ..ReceiveArgs::new(ticket)
+    })
+}
+
+/// # Safety
+///
+/// See [`sendme_send`].
+unsafe fn send_args(path: *const c_char, temp_dir: *const c_char) -> anyhow::Result<SendArgs> {
+    let path = PathBuf::from(str_arg(path)?);
+    Ok(SendArgs {
+        ticket_type: AddrInfoOptions::RelayAndAddresses,
+        common: CommonConfig {
+            temp_dir: Some(PathBuf::from(str_arg(temp_dir)?)),
+            ..Default::default()
+        },
+        ..SendArgs::new(path)
+    })
+}
+
+/// Run the transfer started by `start` to completion, passing its events to
+/// `callback` on this thread.
+fn run_with_progress<T, F>(
+    callback: sendme_progress_cb,
+    user_data: *mut c_void,
+    start: impl FnOnce(ProgressSenderTx) -> F,
+) -> anyhow::Result<T>
+where
+    F: std::future::Future<Output = anyhow::Result<T>>,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+    let report = |event: &ProgressEvent| {
+        if let Some(callback) = callback {
+            let event = FfiProgressEvent::new(event);
+            // SAFETY: the caller passed a valid callback for `user_data`
+            unsafe { callback(&event.as_c(), user_data) };
+        }
+    };
+    RUNTIME.block_on(async {
+        let transfer = start(tx);
+        tokio::pin!(transfer);
+        let result = loop {
+            tokio::select! {
+                result = &mut transfer => break result,
+                Some(event) = rx.recv() => report(&event),
+            }
+        };
+        while let Ok(event) = rx.try_recv() {
+            report(&event);
+        }
+        result
+    })
+}
+
+/// # Safety
+///
+/// `s` must be a valid NUL terminated string, or null.
+unsafe fn str_arg<'a>(s: *const c_char) -> anyhow::Result<&'a str> {
+    anyhow::ensure!(!s.is_null(), "unexpected null string");
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).expect("NUL bytes were removed")
+}
+
+fn set_error(e: anyhow::Error) -> i32 {
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(c_string(&format!("{e:#}"))));
+    -1
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::{EndpointAddr, SecretKey};
+    use iroh_blobs::{ticket::BlobTicket, BlobFormat, Hash};
+
+    use super::{sendme_event_category_t::*, *};
+
+    fn text(event: &sendme_progress_event_t) -> Option<String> {
+        if event.text.is_null() {
+            return None;
+        }
+        let text = unsafe { CStr::from_ptr(event.text) };
+        Some(text.to_str().unwrap().to_string())
+    }
+
+    fn kind(event: &sendme_progress_event_t) -> &str {
+        unsafe { CStr::from_ptr(event.kind) }.to_str().unwrap()
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let hash = Hash::new("ffi");
+        let addr = EndpointAddr::new(SecretKey::generate(&mut rand::rng()).public());
+        let ticket = BlobTicket::new(addr, hash, BlobFormat::HashSeq);
+        let events = [
+            ProgressEvent::Import("".into(), ImportProgress::Started { total_files: 3 }),
+            ProgressEvent::Import(
+                "a.txt".into(),
+                ImportProgress::FileStarted {
+                    name: "a.txt".into(),
+                    size: 10,
+                },
+            ),
+            ProgressEvent::Import(
+                "a.txt".into(),
+                ImportProgress::FileProgress {
+                    name: "a.txt".into(),
+                    offset: 4,
+                },
+            ),
+            ProgressEvent::Import(
+                "a.txt".into(),
+                ImportProgress::FileCompleted {
+                    name: "a.txt".into(),
+                },
+            ),
+            ProgressEvent::Import(
+                "".into(),
+                ImportProgress::BatchProgress {
+                    completed: 2,
+                    total: 5,
+                },
+            ),
+            ProgressEvent::Import("".into(), ImportProgress::Completed { total_size: 10 }),
+            ProgressEvent::Import("".into(), ImportProgress::Cancelled),
+            ProgressEvent::Export("".into(), ExportProgress::Started { total_files: 1 }),
+            ProgressEvent::Export(
+                "b.txt".into(),
+                ExportProgress::FileStarted {
+                    name: "b.txt".into(),
+                    size: 7,
+                },
+            ),
+            ProgressEvent::Export(
+                "b.txt".into(),
+                ExportProgress::FileProgress {
+                    name: "b.txt".into(),
+                    offset: 6,
+                },
+            ),
+            ProgressEvent::Export(
+                "b.txt".into(),
+                ExportProgress::FileCompleted {
+                    name: "b.txt".into(),
+                },
+            ),
+            ProgressEvent::Export("".into(), ExportProgress::Completed),
+            ProgressEvent::Download(DownloadProgress::EndpointReady { online: true }),
+            ProgressEvent::Download(DownloadProgress::Throttled { bytes_per_sec: 50 }),
+            ProgressEvent::Download(DownloadProgress::Connecting),
+            ProgressEvent::Download(DownloadProgress::GettingSizes),
+            ProgressEvent::Download(DownloadProgress::Metadata {
+                total_size: 20,
+                file_count: 2,
+                names: vec!["a".into(), "b".into()],
+            }),
+            ProgressEvent::Download(DownloadProgress::Downloading {
+                offset: 8,
+                total: 20,
+            }),
+            ProgressEvent::Download(DownloadProgress::Verifying {
+                offset: 12,
+                total: 20,
+            }),
+            ProgressEvent::Download(DownloadProgress::Completed),
+            ProgressEvent::Download(DownloadProgress::Restarted {
+                reason: "corrupt".into(),
+            }),
+            ProgressEvent::Download(DownloadProgress::Stalled {
+                attempt: 2,
+                reason: "timeout".into(),
+            }),
+            ProgressEvent::Download(DownloadProgress::Resumed { attempt: 2 }),
+            ProgressEvent::Connection(ConnectionStatus::EndpointReady { online: false }),
+            ProgressEvent::Connection(ConnectionStatus::ServerReady { reachable: true }),
+            ProgressEvent::Connection(ConnectionStatus::ClientConnected {
+                endpoint_id: "peer".into(),
+                connection_id: 1,
+            }),
+            ProgressEvent::Connection(ConnectionStatus::ConnectionClosed { connection_id: 1 }),
+            ProgressEvent::Connection(ConnectionStatus::RequestStarted {
+                connection_id: 1,
+                request_id: 2,
+                hash,
+                size: 30,
+            }),
+            ProgressEvent::Connection(ConnectionStatus::RequestProgress {
+                connection_id: 1,
+                request_id: 2,
+                offset: 12,
+            }),
+            ProgressEvent::Connection(ConnectionStatus::RequestCompleted {
+                connection_id: 1,
+                request_id: 2,
+            }),
+            ProgressEvent::Connection(ConnectionStatus::RequestRejected {
+                connection_id: 1,
+                request_id: 3,
+                hash,
+            }),
+            ProgressEvent::SendReady {
+                ticket: ticket.clone(),
+                hash,
+                total_size: 40,
+            },
+        ];
+        let events: Vec<_> = events.iter().map(FfiProgressEvent::new).collect();
+        let hex = hash.to_hex().to_string();
+        let ticket = ticket.to_string();
+        let c: Vec<_> = events.iter().map(FfiProgressEvent::as_c).collect();
+
+        // category, kind, text, [offset, total, count, connection_id, request_id], flag
+        let expected = [
+            (SENDME_EVENT_IMPORT, "started", None, [0, 0, 3, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "file_started", Some("a.txt"), [0, 10, 0, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "file_progress", Some("a.txt"), [4, 0, 0, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "file_completed", Some("a.txt"), [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "batch_progress", None, [2, 5, 0, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "completed", None, [0, 10, 0, 0, 0], false),
+            (SENDME_EVENT_IMPORT, "cancelled", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_EXPORT, "started", None, [0, 0, 1, 0, 0], false),
+            (SENDME_EVENT_EXPORT, "file_started", Some("b.txt"), [0, 7, 0, 0, 0], false),
+            (SENDME_EVENT_EXPORT, "file_progress", Some("b.txt"), [6, 0, 0, 0, 0], false),
+            (SENDME_EVENT_EXPORT, "file_completed", Some("b.txt"), [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_EXPORT, "completed", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "endpoint_ready", None, [0, 0, 0, 0, 0], true),
+            (SENDME_EVENT_DOWNLOAD, "throttled", None, [0, 50, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "connecting", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "getting_sizes", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "metadata", Some("a\nb"), [0, 20, 2, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "downloading", None, [8, 20, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "verifying", None, [12, 20, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "completed", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "restarted", Some("corrupt"), [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "stalled", Some("timeout"), [0, 0, 2, 0, 0], false),
+            (SENDME_EVENT_DOWNLOAD, "resumed", None, [0, 0, 2, 0, 0], false),
+            (SENDME_EVENT_CONNECTION, "endpoint_ready", None, [0, 0, 0, 0, 0], false),
+            (SENDME_EVENT_CONNECTION, "server_ready", None, [0, 0, 0, 0, 0], true),
+            (SENDME_EVENT_CONNECTION, "client_connected", Some("peer"), [0, 0, 0, 1, 0], false),
+            (SENDME_EVENT_CONNECTION, "connection_closed", None, [0, 0, 0, 1, 0], false),
+            (SENDME_EVENT_CONNECTION, "request_started", Some(&*hex), [0, 30, 0, 1, 2], false),
+            (SENDME_EVENT_CONNECTION, "request_progress", None, [12, 0, 0, 1, 2], false),
+            (SENDME_EVENT_CONNECTION, "request_completed", None, [0, 0, 0, 1, 2], false),
+            (SENDME_EVENT_CONNECTION, "request_rejected", Some(&*hex), [0, 0, 0, 1, 3], false),
+            (SENDME_EVENT_SEND_READY, "send_ready", Some(&*ticket), [0, 40, 0, 0, 0], false),
+        ];
+        assert_eq!(c.len(), expected.len());
+        for (event, want) in c.iter().zip(expected) {
+            let (category, want_kind, want_text, numbers, flag) = want;
+            assert_eq!(event.category, category, "{want_kind}");
+            assert_eq!(kind(event), want_kind);
+            assert_eq!(text(event).as_deref(), want_text, "{want_kind}");
+            let got = [
+                event.offset,
+                event.total,
+                event.count,
+                event.connection_id,
+                event.request_id,
+            ];
+            assert_eq!(got, numbers, "{want_kind}");
+            assert_eq!(event.flag, flag, "{want_kind}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let ticket = c_string("not a ticket");
+        let dir = c_string(".");
+        let code = unsafe {
+            sendme_receive(ticket.as_ptr(), dir.as_ptr(), dir.as_ptr(), None, std::ptr::null_mut())
+        };
+        assert_eq!(code, -1);
+        let error = unsafe { CStr::from_ptr(sendme_last_error()) };
+        assert!(error.to_str().unwrap().contains("ticket"));
+        unsafe { sendme_string_free(std::ptr::null_mut()) };
+    }
+}
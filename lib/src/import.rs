@@ -1,172 +1,956 @@
 //! File import functionality.
 
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use anyhow::Context;
 use futures_buffered::BufferedStreamExt;
-use iroh_blobs::{format::collection::Collection, store::fs::FsStore, BlobFormat};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use iroh_blobs::{
+    api::{blobs::AddProgress, Store, TempTag},
+    format::collection::Collection,
+    BlobFormat, HashAndFormat,
+};
 
 use n0_future::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    progress::ProgressSenderTx,
+    scheduler::{self, WorkerPool},
+    validate_path_component, NonUtf8Policy, Priority,
+};
+
+/// The default for [`crate::CommonConfig::import_batch_threshold`].
+pub const DEFAULT_BATCH_THRESHOLD: usize = 1000;
+
+/// Files of this size or larger report their own progress, even when the
+/// import reports progress in batches.
+pub const LARGE_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The number of [`ImportProgress::BatchProgress`] events an import sends,
+/// at most.
+///
+/// [`ImportProgress::BatchProgress`]: crate::progress::ImportProgress::BatchProgress
+const BATCHES: usize = 100;
+
+/// Which files of a directory go into an import.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportFilter {
+    /// Leave out what the `.gitignore` files in the directory ignore, and
+    /// `.git` directories, see [`crate::SendArgs::respect_gitignore`].
+    pub gitignore: bool,
+    /// Leave out files whose names match one of these glob patterns, see
+    /// [`crate::SendArgs::exclude`].
+    pub exclude: Vec<String>,
+    /// Import the targets of symlinks instead of leaving them out, see
+    /// [`crate::SendArgs::follow_symlinks`].
+    pub follow_symlinks: bool,
+}
+
+impl ImportFilter {
+    /// Compile the patterns, once for a whole import.
+    fn compile(&self) -> anyhow::Result<Filter> {
+        let mut exclude = GlobSetBuilder::new();
+        for pattern in &self.exclude {
+            let glob = Glob::new(pattern)
+                .with_context(|| format!("invalid exclude pattern {pattern:?}"))?;
+            exclude.add(glob);
+        }
+        Ok(Filter {
+            gitignore: self.gitignore,
+            exclude: exclude.build()?,
+            follow_symlinks: self.follow_symlinks,
+        })
+    }
+}
+
+/// An [`ImportFilter`] that is ready to use.
+struct Filter {
+    gitignore: bool,
+    exclude: GlobSet,
+    follow_symlinks: bool,
+}
+
+/// A function applied to the content of each file before it is imported, see
+/// [`import`].
+///
+/// It is called with the name of the file in the collection and its content,
+/// and returns the bytes to import instead, or `None` to leave the file out.
+pub type ImportTransform = Box<dyn Fn(&str, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Options for [`import`].
+pub struct ImportOptions {
+    /// Each file holds a worker from the shared [`scheduler`] pool at this
+    /// priority while it is imported, so concurrent transfers with a higher
+    /// priority go first.
+    pub priority: Priority,
+    /// Normalize the names in the collection to Unicode NFC, see
+    /// [`crate::canonicalized_path_to_string`].
+    pub normalize_names: bool,
+    /// If set, the bytes it returns are imported and hashed instead of the
+    /// file content, e.g. to strip metadata from images. This reads each file
+    /// into memory, and holds both the original and the transformed content
+    /// while the file is imported, so it is not suited for large files.
+    pub transform: Option<ImportTransform>,
+    /// If there are more files, small files don't report their own progress,
+    /// see [`crate::CommonConfig::import_batch_threshold`].
+    pub batch_threshold: usize,
+    /// Import files that change while they are imported once more. They are
+    /// reported as [`ImportProgress::FileChanged`] either way.
+    ///
+    /// [`ImportProgress::FileChanged`]: crate::progress::ImportProgress::FileChanged
+    pub reimport_changed: bool,
+    /// What to do with files whose names are not valid UTF-8.
+    pub non_utf8_names: NonUtf8Policy,
+    /// Which files of a directory are imported.
+    pub filter: ImportFilter,
+    /// Add the permissions and modification times of all files to
+    /// [`FILE_METADATA`]. Without it, only the permissions of executable files
+    /// are added, on Unix.
+    pub record_metadata: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::from_common(&crate::CommonConfig::default())
+    }
+}
 
-use crate::{progress::ProgressSenderTx, validate_path_component};
+impl ImportOptions {
+    /// The options that `common` sets, and the defaults for all others.
+    pub fn from_common(common: &crate::CommonConfig) -> Self {
+        Self {
+            priority: common.priority,
+            normalize_names: common.normalize_names,
+            transform: None,
+            batch_threshold: common.import_batch_threshold,
+            reimport_changed: false,
+            non_utf8_names: NonUtf8Policy::default(),
+            filter: ImportFilter::default(),
+            record_metadata: false,
+        }
+    }
+}
 
-/// Import a file or directory into the database.
+/// Import files or directories into the database.
 ///
 /// The returned tag always refers to a collection. If the input is a file, this
 /// is a collection with a single blob, named like the file.
 ///
 /// If the input is a directory, the collection contains all the files in the
 /// directory.
+///
+/// With more than one path, all of them go into the same collection, see
+/// [`list_paths`] for how their names are chosen.
+///
+/// On Unix, the permissions of executable files are added to the collection
+/// as [`FILE_METADATA`], so export can restore them.
 pub async fn import(
-    path: std::path::PathBuf,
-    db: &FsStore,
+    paths: Vec<PathBuf>,
+    db: &Store,
+    options: &ImportOptions,
     progress_tx: Option<ProgressSenderTx>,
 ) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
-    import_internal(path, db, progress_tx).await
+    let pool = scheduler::global();
+    let check = ChangeCheck::new(options.reimport_changed);
+    import_internal(&paths, db, pool, options, check, progress_tx).await
 }
 
 async fn import_internal(
-    path: std::path::PathBuf,
-    db: &FsStore,
+    paths: &[PathBuf],
+    db: &Store,
+    pool: &WorkerPool,
+    options: &ImportOptions,
+    check: ChangeCheck,
     progress_tx: Option<ProgressSenderTx>,
 ) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    let ImportOptions {
+        priority,
+        normalize_names,
+        ref transform,
+        batch_threshold,
+        non_utf8_names,
+        ref filter,
+        record_metadata,
+        ..
+    } = *options;
     let parallelism = num_cpus::get();
-    let path = path.canonicalize()?;
+    let Listing {
+        files: data_sources,
+        originals,
+        skipped,
+    } = list_paths(paths, normalize_names, non_utf8_names, filter)?;
+    let mut recorded = file_metadata(&data_sources, record_metadata)?;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                "".to_string(),
+                crate::progress::ImportProgress::Started {
+                    total_files: data_sources.len(),
+                },
+            ))
+            .await;
+        for name in skipped {
+            let _ = tx
+                .send(crate::progress::ProgressEvent::Import(
+                    name.clone(),
+                    crate::progress::ImportProgress::FileSkipped { name },
+                ))
+                .await;
+        }
+    }
+
+    // import all the files, using num_cpus workers, return names and temp tags
+    let batch = BatchProgress::new(&progress_tx, data_sources.len(), batch_threshold);
+    let mut names_and_tags = n0_future::stream::iter(data_sources)
+        .map(|(name, path, size)| {
+            let db = db.clone();
+            let changes_tx = progress_tx.clone();
+            let progress_tx = batch.file_progress(&progress_tx, Some(size));
+            let batch = &batch;
+            async move {
+                let _permit = pool.acquire(priority).await;
+                let result = match transform {
+                    None => import_checked(name, path, db, check, progress_tx, changes_tx)
+                        .await
+                        .map(Some),
+                    Some(transform) => {
+                        let data = tokio::fs::read(&path)
+                            .await
+                            .with_context(|| format!("failed to read {}", path.display()))?;
+                        match transform(&name, &data) {
+                            Some(data) => import_bytes(name, data, db, progress_tx).await.map(Some),
+                            None => Ok(None),
+                        }
+                    }
+                };
+                batch.file_done().await;
+                result
+            }
+        })
+        .buffered_unordered(parallelism)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .filter_map(Result::transpose)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if !originals.is_empty() {
+        anyhow::ensure!(
+            names_and_tags.iter().all(|(name, _, _)| name != ORIGINAL_NAMES),
+            "can not record the original names, the data already contains {}",
+            ORIGINAL_NAMES
+        );
+        let originals = originals
+            .iter()
+            .map(|(name, bytes)| (name, hex::encode(bytes)))
+            .collect::<BTreeMap<_, _>>();
+        let data = serde_json::to_vec(&originals)?;
+        let name = ORIGINAL_NAMES.to_string();
+        names_and_tags.push(import_bytes(name, data, db.clone(), None).await?);
+    }
+
+    // a transform can leave files out
+    let imported = names_and_tags
+        .iter()
+        .map(|(name, _, _)| name.as_str())
+        .collect::<std::collections::HashSet<_>>();
+    recorded.retain(|name, _| imported.contains(name.as_str()));
+    if !recorded.is_empty() {
+        anyhow::ensure!(
+            !imported.contains(FILE_METADATA),
+            "can not record the file metadata, the data already contains {}",
+            FILE_METADATA
+        );
+        let data = serde_json::to_vec(&recorded)?;
+        let name = FILE_METADATA.to_string();
+        names_and_tags.push(import_bytes(name, data, db.clone(), None).await?);
+    }
+
+    store_collection(names_and_tags, db, progress_tx).await
+}
+
+/// The name of the file that records the permissions and modification times
+/// of files.
+///
+/// It holds a JSON object that maps collection names to [`FileMetadata`].
+/// By default only the permissions of files with an execute bit are recorded,
+/// so a collection without executables gets no such file.
+pub const FILE_METADATA: &str = ".sendme-meta.json";
+
+/// What [`FILE_METADATA`] records about a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// The Unix permission bits, e.g. `0o755`. Not recorded on other
+    /// platforms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// The modification time, in nanoseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+/// The [`FileMetadata`] to record of each file, by name.
+///
+/// Without `all`, only the permissions of files with an execute bit.
+fn file_metadata(
+    files: &[(String, PathBuf, u64)],
+    all: bool,
+) -> anyhow::Result<BTreeMap<String, FileMetadata>> {
+    let mut recorded = BTreeMap::new();
+    for (name, path, _) in files {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & 0o777)
+        };
+        #[cfg(not(unix))]
+        let mode: Option<u32> = None;
+        let entry = FileMetadata {
+            mode: mode.filter(|mode| all || mode & 0o111 != 0),
+            modified: match all {
+                true => Some(modified_nanos(&metadata)?),
+                false => None,
+            },
+        };
+        if entry != FileMetadata::default() {
+            recorded.insert(name.clone(), entry);
+        }
+    }
+    Ok(recorded)
+}
+
+/// The modification time of a file in nanoseconds since the Unix epoch.
+fn modified_nanos(metadata: &std::fs::Metadata) -> anyhow::Result<u64> {
+    let modified = metadata.modified()?;
+    Ok(modified.duration_since(std::time::UNIX_EPOCH)?.as_nanos() as u64)
+}
+
+/// The name of the file that records the original names of a
+/// [`NonUtf8Policy::Lossy`] import.
+///
+/// It holds a JSON object that maps collection names to the hex encoded
+/// bytes of the original path.
+pub const ORIGINAL_NAMES: &str = ".sendme-original-names.json";
+
+/// The files found by [`list_files`].
+#[derive(Debug, Default)]
+struct Listing {
+    /// The files as (name, path, size) tuples.
+    files: Vec<(String, PathBuf, u64)>,
+    /// The bytes of the original relative path of files whose names were
+    /// made valid UTF-8, by name.
+    originals: BTreeMap<String, Vec<u8>>,
+    /// Files that were left out, with the invalid bytes of their names
+    /// replaced.
+    skipped: Vec<String>,
+}
+
+/// List the files in a file or directory, named relative to the parent of
+/// `path`. What `filter` leaves out is skipped.
+///
+/// Symlinks are skipped too, unless `filter` follows them. Then they are
+/// named like the link, and a link to a directory above it is skipped with a
+/// warning instead of being walked forever, and so is a broken link.
+fn list_files(
+    path: &Path,
+    normalize_names: bool,
+    non_utf8_names: NonUtf8Policy,
+    filter: &Filter,
+) -> anyhow::Result<Listing> {
     anyhow::ensure!(path.exists(), "path {} does not exist", path.display());
     let root = path.parent().context("get parent")?;
 
     // walkdir also works for files, so we don't need to special case them
-    let files = walkdir::WalkDir::new(path.clone()).into_iter();
+    let mut listing = Listing::default();
+    // the .gitignore files of the directories above the current entry, by depth
+    let mut gitignores: Vec<(usize, Gitignore)> = Vec::new();
+    let mut ignored = 0;
+    let mut walk = walkdir::WalkDir::new(path)
+        .follow_links(filter.follow_symlinks)
+        .into_iter();
+    while let Some(entry) = walk.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // walkdir compares the canonical path of each linked directory
+            // with those of the directories above it
+            Err(e) if e.loop_ancestor().is_some() => {
+                let link = e.path().unwrap_or(path).display();
+                tracing::warn!("⚠️ Skipping {}, it links to a directory above it", link);
+                continue;
+            }
+            Err(e) if filter.follow_symlinks && is_broken_link(&e) => {
+                let link = e.path().unwrap_or(path).display();
+                tracing::warn!("⚠️ Skipping {}, it links to nothing", link);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if filter.gitignore {
+            let (depth, is_dir) = (entry.depth(), entry.file_type().is_dir());
+            gitignores.retain(|(parent, _)| *parent < depth);
+            let git_dir = is_dir && entry.file_name() == ".git";
+            if depth > 0 && (git_dir || is_ignored(&gitignores, entry.path(), is_dir)) {
+                tracing::debug!("ignoring {}", entry.path().display());
+                if is_dir {
+                    walk.skip_current_dir();
+                }
+                ignored += 1;
+                continue;
+            }
+            if is_dir {
+                if let Some(gitignore) = load_gitignore(entry.path()) {
+                    gitignores.push((depth, gitignore));
+                }
+            }
+        }
+        if !entry.file_type().is_file() {
+            // Skip symlinks that are not followed. Directories are handled by
+            // WalkDir.
+            continue;
+        }
+        let size = entry.metadata()?.len();
+        let path = entry.into_path();
+        let relative = path.strip_prefix(root)?;
+        let (name, original) =
+            if relative.to_str().is_some() || non_utf8_names == NonUtf8Policy::Error {
+                let name = crate::canonicalized_path_to_string(relative, true, normalize_names)?;
+                (name, None)
+            } else {
+                let lossy = relative.to_string_lossy().into_owned();
+                if non_utf8_names == NonUtf8Policy::Skip {
+                    tracing::warn!("⚠️ Skipping {}, its name is not valid UTF-8", lossy);
+                    listing.skipped.push(lossy);
+                    continue;
+                }
+                let name = crate::canonicalized_path_to_string(&lossy, true, normalize_names)?;
+                (name, Some(path_bytes(relative)))
+            };
+        if filter.exclude.is_match(&name) {
+            tracing::debug!("excluding {}", name);
+            ignored += 1;
+            continue;
+        }
+        if let Some(original) = original {
+            listing.originals.insert(name.clone(), original);
+        }
+        listing.files.push((name, path, size));
+    }
 
-    // flatten the directory structure into a list of (name, path) pairs.
-    // ignore symlinks.
-    let data_sources: Vec<(String, std::path::PathBuf)> = files
-        .map(|entry| {
-            let entry = entry?;
-            if !entry.file_type().is_file() {
-                // Skip symlinks. Directories are handled by WalkDir.
-                return Ok(None);
+    if !listing.originals.is_empty() {
+        // different names can become the same when invalid bytes are replaced
+        let mut names = std::collections::BTreeSet::new();
+        for (name, _, _) in &listing.files {
+            anyhow::ensure!(names.insert(name), "more than one file is named {}", name);
+        }
+    }
+    anyhow::ensure!(
+        ignored == 0 || !listing.files.is_empty() || !listing.skipped.is_empty(),
+        "nothing to send, all files in {} are ignored or excluded",
+        path.display()
+    );
+    Ok(listing)
+}
+
+/// Whether walking failed because of a symlink whose target does not exist.
+fn is_broken_link(e: &walkdir::Error) -> bool {
+    let not_found = e
+        .io_error()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound);
+    not_found && e.path().is_some_and(Path::is_symlink)
+}
+
+/// Read the `.gitignore` file of `dir`, if it has one.
+///
+/// Patterns that can't be parsed are left out with a warning, like git does.
+fn load_gitignore(dir: &Path) -> Option<Gitignore> {
+    let file = dir.join(".gitignore");
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(e) = builder.add(&file) {
+        tracing::warn!("⚠️ Problem with {}: {}", file.display(), e);
+    }
+    match builder.build() {
+        Ok(gitignore) => Some(gitignore),
+        Err(e) => {
+            tracing::warn!("⚠️ Not using {}: {}", file.display(), e);
+            None
+        }
+    }
+}
+
+/// Whether the innermost `.gitignore` with a matching pattern ignores `path`.
+///
+/// A negated pattern (`!keep.log`) in a deeper directory overrides its
+/// parents, but nothing below an ignored directory is looked at.
+fn is_ignored(gitignores: &[(usize, Gitignore)], path: &Path, is_dir: bool) -> bool {
+    gitignores
+        .iter()
+        .rev()
+        .map(|(_, gitignore)| gitignore.matched(path, is_dir))
+        .find(|matched| !matched.is_none())
+        .is_some_and(|matched| matched.is_ignore())
+}
+
+/// List the files of several paths, as one collection.
+///
+/// Each path is named relative to its own parent, like [`list_files`] does.
+/// If two paths have the same name, the files of the later one get the
+/// position of the path as a prefix, e.g. `2-notes.txt` or `2-photos/a.jpg`.
+fn list_paths(
+    paths: &[PathBuf],
+    normalize_names: bool,
+    non_utf8_names: NonUtf8Policy,
+    filter: &ImportFilter,
+) -> anyhow::Result<Listing> {
+    anyhow::ensure!(!paths.is_empty(), "no paths to import");
+    let filter = filter.compile()?;
+    let mut listing = Listing::default();
+    let mut top_names = std::collections::BTreeSet::new();
+    for (i, path) in paths.iter().enumerate() {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("path {} does not exist", path.display()))?;
+        let mut part = list_files(&path, normalize_names, non_utf8_names, &filter)?;
+        let name = path.file_name().context("get file name")?.to_string_lossy();
+        let name = crate::canonicalized_path_to_string(&*name, true, normalize_names)?;
+        let mut prefix = String::new();
+        let mut n = i + 1;
+        while !top_names.insert(format!("{prefix}{name}")) {
+            prefix = format!("{n}-");
+            n += 1;
+        }
+        if !prefix.is_empty() {
+            tracing::info!("{name} is already in the collection, adding it as {prefix}{name}");
+            for (file, _, _) in &mut part.files {
+                file.insert_str(0, &prefix);
+            }
+            part.originals = std::mem::take(&mut part.originals)
+                .into_iter()
+                .map(|(file, bytes)| {
+                    let bytes = prefix.as_bytes().iter().copied().chain(bytes).collect();
+                    (format!("{prefix}{file}"), bytes)
+                })
+                .collect();
+            for file in &mut part.skipped {
+                file.insert_str(0, &prefix);
             }
-            let path = entry.into_path();
-            let relative = path.strip_prefix(root)?;
-            let name = crate::canonicalized_path_to_string(relative, true)?;
-            anyhow::Ok(Some((name, path)))
+        }
+        listing.files.append(&mut part.files);
+        listing.originals.append(&mut part.originals);
+        listing.skipped.append(&mut part.skipped);
+    }
+    Ok(listing)
+}
+
+/// The bytes of a path, to restore a name that is not valid UTF-8.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// The bytes of a path. Names that are not valid UTF-8 can't be restored on
+/// this platform, so this is the lossy name.
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Read the files of imported `paths` again, and check that they have the
+/// same content as in `collection`, see [`crate::SendArgs::double_read_verify`].
+///
+/// Fails with the names of the files that read differently.
+pub(crate) async fn verify_reads(
+    paths: &[PathBuf],
+    collection: &Collection,
+    options: &ImportOptions,
+) -> anyhow::Result<()> {
+    let ImportOptions {
+        normalize_names,
+        non_utf8_names,
+        ref filter,
+        ..
+    } = *options;
+    let listing = list_paths(paths, normalize_names, non_utf8_names, filter)?;
+    check_reads(listing.files, collection, options.priority, hash_file).await
+}
+
+/// Compare the hash `rehash` gets for each file to its hash in `collection`.
+async fn check_reads(
+    files: Vec<(String, PathBuf, u64)>,
+    collection: &Collection,
+    priority: Priority,
+    rehash: fn(&Path) -> std::io::Result<iroh_blobs::Hash>,
+) -> anyhow::Result<()> {
+    let pool = scheduler::global();
+    let expected = collection.iter().cloned().collect::<HashMap<_, _>>();
+    let files = files
+        .into_iter()
+        .filter_map(|(name, path, _)| expected.get(&name).map(|hash| (name, path, *hash)));
+    let mut mismatched = n0_future::stream::iter(files)
+        .map(|(name, path, hash)| async move {
+            let _permit = pool.acquire(priority).await;
+            let read = tokio::task::spawn_blocking(move || rehash(&path)).await??;
+            anyhow::Ok((read != hash).then_some(name))
         })
+        .buffered_unordered(num_cpus::get())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
         .filter_map(Result::transpose)
         .collect::<anyhow::Result<Vec<_>>>()?;
+    mismatched.sort();
+    anyhow::ensure!(
+        mismatched.is_empty(),
+        "reading these files again gave different content, the disk may be failing: {}",
+        mismatched.join(", ")
+    );
+    Ok(())
+}
+
+/// The blake3 hash of a file, which is its hash as a blob.
+pub(crate) fn hash_file(path: &Path) -> std::io::Result<iroh_blobs::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(std::fs::File::open(path)?)?;
+    Ok(iroh_blobs::Hash::from_bytes(*hasher.finalize().as_bytes()))
+}
+
+/// The file in a persistent store that describes the last
+/// [`import_indexed`] into it.
+const INDEX_FILE: &str = "sendme-index.json";
+
+/// The tag that keeps the data of the last [`import_indexed`] in the store.
+const INDEX_TAG: &str = "sendme-index";
+
+/// What was imported into a persistent store, see [`import_indexed`].
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportIndex {
+    /// The imported path.
+    source: PathBuf,
+    normalize_names: bool,
+    /// Whether [`FILE_METADATA`] records all files.
+    #[serde(default)]
+    record_metadata: bool,
+    /// The imported files as (name, size, modification time in nanoseconds).
+    files: Vec<(String, u64, u64)>,
+    hash: iroh_blobs::Hash,
+    size: u64,
+}
+
+/// Like [`import`], but skips importing if `path` didn't change since the
+/// last import into the persistent store in `store_dir`.
+///
+/// The path is considered unchanged if it has the same files with the same
+/// sizes and modification times. Files are referenced in place where
+/// possible, so they must not be modified while they are served.
+///
+/// A transform can't be used, as the index doesn't record it.
+pub(crate) async fn import_indexed(
+    path: PathBuf,
+    db: &Store,
+    store_dir: &Path,
+    options: &ImportOptions,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    anyhow::ensure!(
+        options.transform.is_none(),
+        "a persistent store can not be used with a transform"
+    );
+    let ImportOptions {
+        normalize_names,
+        non_utf8_names,
+        record_metadata,
+        ..
+    } = *options;
+    let path = path.canonicalize()?;
+    let filter = options.filter.compile()?;
+    let files = list_files(&path, normalize_names, non_utf8_names, &filter)?
+        .files
+        .into_iter()
+        .map(|(name, path, size)| {
+            let nanos = modified_nanos(&std::fs::metadata(&path)?)?;
+            anyhow::Ok((name, size, nanos))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let index_path = store_dir.join(INDEX_FILE);
+    let index = std::fs::read(&index_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ImportIndex>(&data).ok());
+    if let Some(index) = index {
+        let unchanged = index.source == path
+            && index.normalize_names == normalize_names
+            && index.record_metadata == record_metadata
+            && index.files == files;
+        if unchanged
+            && db
+                .remote()
+                .local(HashAndFormat::hash_seq(index.hash))
+                .await?
+                .is_complete()
+        {
+            tracing::info!("♻️ {} is unchanged, reusing its index", path.display());
+            let collection = Collection::load(index.hash, db).await?;
+            if let Some(ref tx) = progress_tx {
+                let _ = tx
+                    .send(crate::progress::ProgressEvent::Import(
+                        "".to_string(),
+                        crate::progress::ImportProgress::Completed {
+                            total_size: index.size,
+                        },
+                    ))
+                    .await;
+            }
+            return Ok((index.hash, index.size, collection));
+        }
+    }
+
+    let pool = scheduler::global();
+    let check = ChangeCheck::new(options.reimport_changed);
+    let paths = std::slice::from_ref(&path);
+    let (hash, size, collection) =
+        import_internal(paths, db, pool, options, check, progress_tx).await?;
+    db.tags().set(INDEX_TAG, HashAndFormat::hash_seq(hash)).await?;
+    let index = ImportIndex {
+        source: path,
+        normalize_names,
+        record_metadata,
+        files,
+        hash,
+        size,
+    };
+    std::fs::write(&index_path, serde_json::to_vec(&index)?)?;
+    Ok((hash, size, collection))
+}
+
+/// A single file to add to a collection with [`import_many`].
+#[derive(Debug, Clone)]
+pub enum ImportEntry {
+    /// A file on the local file system.
+    Path {
+        /// The name of the file in the collection.
+        name: String,
+        /// The path to read the file from.
+        path: std::path::PathBuf,
+    },
+    /// File content that is already in memory.
+    Bytes {
+        /// The name of the file in the collection.
+        name: String,
+        /// The file content.
+        data: Vec<u8>,
+    },
+    /// A file that can only be opened, not addressed by a path, e.g. a content
+    /// URI. It is streamed into the store without reading it into memory.
+    Open {
+        /// The name of the file in the collection.
+        name: String,
+        /// Opens the file for reading once it is imported.
+        open: OpenFile,
+    },
+    /// A file downloaded over HTTP(S), streamed into the store without
+    /// saving it to disk first.
+    #[cfg(feature = "url")]
+    Url {
+        /// The name of the file in the collection.
+        name: String,
+        /// The URL to download the file from.
+        url: String,
+    },
+}
+
+impl ImportEntry {
+    /// The name of the entry in the collection.
+    pub fn name(&self) -> &str {
+        match self {
+            ImportEntry::Path { name, .. }
+            | ImportEntry::Bytes { name, .. }
+            | ImportEntry::Open { name, .. } => name,
+            #[cfg(feature = "url")]
+            ImportEntry::Url { name, .. } => name,
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            ImportEntry::Path { name, .. }
+            | ImportEntry::Bytes { name, .. }
+            | ImportEntry::Open { name, .. } => name,
+            #[cfg(feature = "url")]
+            ImportEntry::Url { name, .. } => name,
+        }
+    }
+
+    /// The size of the entry, if it is known before importing it.
+    fn size_hint(&self) -> Option<u64> {
+        match self {
+            ImportEntry::Path { path, .. } => std::fs::metadata(path).ok().map(|m| m.len()),
+            ImportEntry::Bytes { data, .. } => Some(data.len() as u64),
+            ImportEntry::Open { .. } => None,
+            #[cfg(feature = "url")]
+            ImportEntry::Url { .. } => None,
+        }
+    }
+}
+
+/// Opens the file of an [`ImportEntry::Open`].
+///
+/// Files are only opened while they are imported, so picking many files
+/// doesn't keep all of them open at once.
+#[derive(Clone)]
+pub struct OpenFile(std::sync::Arc<dyn Fn() -> std::io::Result<std::fs::File> + Send + Sync>);
+
+impl OpenFile {
+    /// Wrap a function that opens the file for reading.
+    pub fn new(open: impl Fn() -> std::io::Result<std::fs::File> + Send + Sync + 'static) -> Self {
+        Self(std::sync::Arc::new(open))
+    }
+}
+
+impl std::fmt::Debug for OpenFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OpenFile(..)")
+    }
+}
+
+/// Progress reporting for imports of many files.
+///
+/// Instead of per file events, small files are only counted, and the count is
+/// reported as [`ImportProgress::BatchProgress`] at most [`BATCHES`] times.
+///
+/// [`ImportProgress::BatchProgress`]: crate::progress::ImportProgress::BatchProgress
+struct BatchProgress {
+    tx: Option<ProgressSenderTx>,
+    total: usize,
+    every: usize,
+    completed: AtomicUsize,
+}
+
+impl BatchProgress {
+    /// Batch the progress of `total` files if there are more than `threshold`.
+    fn new(progress_tx: &Option<ProgressSenderTx>, total: usize, threshold: usize) -> Self {
+        Self {
+            tx: progress_tx.clone().filter(|_| total > threshold),
+            total,
+            every: total.div_ceil(BATCHES).max(1),
+            completed: AtomicUsize::new(0),
+        }
+    }
+
+    /// The progress sender for a single file. Small files of a batched
+    /// import don't get one. Files of unknown size, like URL and stdin
+    /// sources, do, since they may be large.
+    fn file_progress(
+        &self,
+        progress_tx: &Option<ProgressSenderTx>,
+        size: Option<u64>,
+    ) -> Option<ProgressSenderTx> {
+        let small = size.is_some_and(|size| size < LARGE_FILE_SIZE);
+        if self.tx.is_some() && small {
+            None
+        } else {
+            progress_tx.clone()
+        }
+    }
+
+    /// Count a finished file, reporting the count every so often.
+    async fn file_done(&self) {
+        let Some(ref tx) = self.tx else {
+            return;
+        };
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        if completed % self.every == 0 || completed == self.total {
+            let _ = tx
+                .send(crate::progress::ProgressEvent::Import(
+                    "".to_string(),
+                    crate::progress::ImportProgress::BatchProgress {
+                        completed,
+                        total: self.total,
+                    },
+                ))
+                .await;
+        }
+    }
+}
+
+/// Import several files into a single collection.
+///
+/// Unlike [`import`], the entries don't have to share a parent directory, so
+/// this can be used for files selected together in a file picker.
+///
+/// If `root` is set, every entry is put in a folder with this name, so the
+/// receiver gets a single directory like for a sent directory.
+pub async fn import_many(
+    mut entries: Vec<ImportEntry>,
+    root: Option<String>,
+    db: &Store,
+    priority: Priority,
+    batch_threshold: usize,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    let pool = scheduler::global();
+    anyhow::ensure!(!entries.is_empty(), "nothing to import");
+    if let Some(root) = root {
+        validate_path_component(&root)?;
+        for entry in &mut entries {
+            let name = entry.name_mut();
+            *name = format!("{root}/{name}");
+        }
+    }
+    let mut names = std::collections::BTreeSet::new();
+    for entry in &entries {
+        for part in entry.name().split('/') {
+            validate_path_component(part)?;
+        }
+        anyhow::ensure!(names.insert(entry.name()), "duplicate name {}", entry.name());
+    }
 
     if let Some(ref tx) = progress_tx {
         let _ = tx
             .send(crate::progress::ProgressEvent::Import(
                 "".to_string(),
                 crate::progress::ImportProgress::Started {
-                    total_files: data_sources.len(),
+                    total_files: entries.len(),
                 },
             ))
             .await;
     }
 
-    // import all the files, using num_cpus workers, return names and temp tags
-    let mut names_and_tags = n0_future::stream::iter(data_sources)
-        .map(|(name, path)| {
+    let parallelism = num_cpus::get();
+    let batch = BatchProgress::new(&progress_tx, entries.len(), batch_threshold);
+    let names_and_tags = n0_future::stream::iter(entries)
+        .map(|entry| {
             let db = db.clone();
-            let progress_tx = progress_tx.clone();
+            let progress_tx = batch.file_progress(&progress_tx, entry.size_hint());
+            let batch = &batch;
             async move {
-                if let Some(ref tx) = progress_tx {
-                    let _ = tx
-                        .send(crate::progress::ProgressEvent::Import(
-                            name.clone(),
-                            crate::progress::ImportProgress::FileStarted {
-                                name: name.clone(),
-                                size: 0,
-                            },
-                        ))
-                        .await;
-                }
-
-                let import = db.add_path_with_opts(iroh_blobs::api::blobs::AddPathOptions {
-                    path,
-                    mode: iroh_blobs::api::blobs::ImportMode::TryReference,
-                    format: BlobFormat::Raw,
-                });
-                let mut stream = import.stream().await;
-                let mut item_size = 0u64;
-                let temp_tag = loop {
-                    let item = stream
-                        .next()
-                        .await
-                        .context("import stream ended without a tag")?;
-                    match item {
-                        iroh_blobs::api::blobs::AddProgressItem::Size(size) => {
-                            item_size = size;
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx
-                                    .send(crate::progress::ProgressEvent::Import(
-                                        name.clone(),
-                                        crate::progress::ImportProgress::FileProgress {
-                                            name: name.clone(),
-                                            offset: 0,
-                                        },
-                                    ))
-                                    .await;
-                            }
-                        }
-                        iroh_blobs::api::blobs::AddProgressItem::CopyProgress(offset) => {
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx
-                                    .send(crate::progress::ProgressEvent::Import(
-                                        name.clone(),
-                                        crate::progress::ImportProgress::FileProgress {
-                                            name: name.clone(),
-                                            offset,
-                                        },
-                                    ))
-                                    .await;
-                            }
-                        }
-                        iroh_blobs::api::blobs::AddProgressItem::CopyDone => {
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx
-                                    .send(crate::progress::ProgressEvent::Import(
-                                        name.clone(),
-                                        crate::progress::ImportProgress::FileProgress {
-                                            name: name.clone(),
-                                            offset: 0,
-                                        },
-                                    ))
-                                    .await;
-                            }
-                        }
-                        iroh_blobs::api::blobs::AddProgressItem::OutboardProgress(offset) => {
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx
-                                    .send(crate::progress::ProgressEvent::Import(
-                                        name.clone(),
-                                        crate::progress::ImportProgress::FileProgress {
-                                            name: name.clone(),
-                                            offset,
-                                        },
-                                    ))
-                                    .await;
-                            }
-                        }
-                        iroh_blobs::api::blobs::AddProgressItem::Error(cause) => {
-                            anyhow::bail!("error importing {}: {}", name, cause);
-                        }
-                        iroh_blobs::api::blobs::AddProgressItem::Done(tt) => {
-                            if let Some(ref tx) = progress_tx {
-                                let _ = tx
-                                    .send(crate::progress::ProgressEvent::Import(
-                                        name.clone(),
-                                        crate::progress::ImportProgress::FileCompleted {
-                                            name: name.clone(),
-                                        },
-                                    ))
-                                    .await;
-                            }
-                            break tt;
-                        }
+                let _permit = pool.acquire(priority).await;
+                let result = match entry {
+                    ImportEntry::Path { name, path } => {
+                        import_file(name, path, db, progress_tx).await
+                    }
+                    ImportEntry::Bytes { name, data } => {
+                        import_bytes(name, data, db, progress_tx).await
                     }
+                    ImportEntry::Open { name, open } => {
+                        import_open(name, open, db, progress_tx).await
+                    }
+                    #[cfg(feature = "url")]
+                    ImportEntry::Url { name, url } => import_url(name, url, db, progress_tx).await,
                 };
-                anyhow::Ok((name, temp_tag, item_size))
+                batch.file_done().await;
+                result
             }
         })
         .buffered_unordered(parallelism)
@@ -175,6 +959,319 @@ async fn import_internal(
         .into_iter()
         .collect::<anyhow::Result<Vec<_>>>()?;
 
+    store_collection(names_and_tags, db, progress_tx).await
+}
+
+/// Import a single file from the file system, reporting progress.
+async fn import_file(
+    name: String,
+    path: std::path::PathBuf,
+    db: Store,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(String, TempTag, u64)> {
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                name.clone(),
+                crate::progress::ImportProgress::FileStarted {
+                    name: name.clone(),
+                    size: 0,
+                },
+            ))
+            .await;
+    }
+
+    let import = db.add_path_with_opts(iroh_blobs::api::blobs::AddPathOptions {
+        path,
+        mode: iroh_blobs::api::blobs::ImportMode::TryReference,
+        format: BlobFormat::Raw,
+    });
+    let (temp_tag, item_size) = drive_add(&name, import, &progress_tx).await?;
+    Ok((name, temp_tag, item_size))
+}
+
+/// The size and modification time of a file, to tell if it changed.
+type FileStamp = (u64, Option<std::time::SystemTime>);
+
+fn file_stamp(path: &Path) -> std::io::Result<FileStamp> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+/// How an import checks for files that change while they are imported.
+#[derive(Debug, Clone, Copy)]
+struct ChangeCheck {
+    /// Import a changed file once more.
+    reimport: bool,
+    stamp: fn(&Path) -> std::io::Result<FileStamp>,
+}
+
+impl ChangeCheck {
+    fn new(reimport: bool) -> Self {
+        Self {
+            reimport,
+            stamp: file_stamp,
+        }
+    }
+}
+
+/// Import a file like [`import_file`], and check that its size and
+/// modification time didn't change meanwhile.
+///
+/// Changes are reported to `changes_tx`, which unlike `progress_tx` is also
+/// set for the small files of a batched import.
+async fn import_checked(
+    name: String,
+    path: PathBuf,
+    db: Store,
+    check: ChangeCheck,
+    progress_tx: Option<ProgressSenderTx>,
+    changes_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(String, TempTag, u64)> {
+    let mut attempts = if check.reimport { 2 } else { 1 };
+    loop {
+        let before = (check.stamp)(&path)?;
+        let imported =
+            import_file(name.clone(), path.clone(), db.clone(), progress_tx.clone()).await?;
+        let after = (check.stamp)(&path)?;
+        if after == before && imported.2 == before.0 {
+            return Ok(imported);
+        }
+        tracing::warn!("⚠️ {} changed while it was imported", name);
+        if let Some(ref tx) = changes_tx {
+            let _ = tx
+                .send(crate::progress::ProgressEvent::Import(
+                    name.clone(),
+                    crate::progress::ImportProgress::FileChanged { name: name.clone() },
+                ))
+                .await;
+        }
+        attempts -= 1;
+        if attempts == 0 {
+            return Ok(imported);
+        }
+    }
+}
+
+/// Drive an add operation to completion, forwarding its progress.
+async fn drive_add(
+    name: &str,
+    add: AddProgress<'_>,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<(TempTag, u64)> {
+    let mut stream = add.stream().await;
+    let mut item_size = 0u64;
+    let temp_tag = loop {
+        let item = stream.next().await.context("import stream ended without a tag")?;
+        match item {
+            iroh_blobs::api::blobs::AddProgressItem::Size(size) => {
+                item_size = size;
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(crate::progress::ProgressEvent::Import(
+                            name.to_string(),
+                            crate::progress::ImportProgress::FileProgress {
+                                name: name.to_string(),
+                                offset: 0,
+                            },
+                        ))
+                        .await;
+                }
+            }
+            iroh_blobs::api::blobs::AddProgressItem::CopyProgress(offset) => {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(crate::progress::ProgressEvent::Import(
+                            name.to_string(),
+                            crate::progress::ImportProgress::FileProgress {
+                                name: name.to_string(),
+                                offset,
+                            },
+                        ))
+                        .await;
+                }
+            }
+            iroh_blobs::api::blobs::AddProgressItem::CopyDone => {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(crate::progress::ProgressEvent::Import(
+                            name.to_string(),
+                            crate::progress::ImportProgress::FileProgress {
+                                name: name.to_string(),
+                                offset: 0,
+                            },
+                        ))
+                        .await;
+                }
+            }
+            iroh_blobs::api::blobs::AddProgressItem::OutboardProgress(offset) => {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(crate::progress::ProgressEvent::Import(
+                            name.to_string(),
+                            crate::progress::ImportProgress::FileProgress {
+                                name: name.to_string(),
+                                offset,
+                            },
+                        ))
+                        .await;
+                }
+            }
+            iroh_blobs::api::blobs::AddProgressItem::Error(cause) => {
+                anyhow::bail!("error importing {}: {}", name, cause);
+            }
+            iroh_blobs::api::blobs::AddProgressItem::Done(tt) => {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(crate::progress::ProgressEvent::Import(
+                            name.to_string(),
+                            crate::progress::ImportProgress::FileCompleted {
+                                name: name.to_string(),
+                            },
+                        ))
+                        .await;
+                }
+                break tt;
+            }
+        }
+    };
+    Ok((temp_tag, item_size))
+}
+
+/// Import a single in-memory file, reporting progress.
+async fn import_bytes(
+    name: String,
+    data: Vec<u8>,
+    db: Store,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(String, TempTag, u64)> {
+    let size = data.len() as u64;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                name.clone(),
+                crate::progress::ImportProgress::FileStarted {
+                    name: name.clone(),
+                    size,
+                },
+            ))
+            .await;
+    }
+
+    let temp_tag = db.add_bytes(data).temp_tag().await?;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                name.clone(),
+                crate::progress::ImportProgress::FileCompleted { name: name.clone() },
+            ))
+            .await;
+    }
+    Ok((name, temp_tag, size))
+}
+
+/// Stream an opened file into the store, reporting progress.
+async fn import_open(
+    name: String,
+    open: OpenFile,
+    db: Store,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(String, TempTag, u64)> {
+    use tokio::io::AsyncReadExt;
+
+    let file = tokio::task::spawn_blocking(move || (open.0)())
+        .await?
+        .with_context(|| format!("failed to open {}", name))?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                name.clone(),
+                crate::progress::ImportProgress::FileStarted {
+                    name: name.clone(),
+                    size,
+                },
+            ))
+            .await;
+    }
+
+    // The state is `None` after a read error, which ends the stream.
+    let file = Some(tokio::fs::File::from_std(file));
+    let stream = n0_future::stream::unfold(file, |file| async move {
+        let mut file = file?;
+        let mut buf = vec![0u8; 64 * 1024];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(bytes::Bytes::from(buf)), Some(file)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    let add = db.add_stream(stream).await;
+    let (temp_tag, item_size) = drive_add(&name, add, &progress_tx).await?;
+    Ok((name, temp_tag, item_size))
+}
+
+/// Derive a file name from a URL: its last path segment, or else its host.
+#[cfg(feature = "url")]
+pub fn name_from_url(url: &str) -> anyhow::Result<String> {
+    let url = reqwest::Url::parse(url)?;
+    let segment = url
+        .path_segments()
+        .and_then(|segments| segments.filter(|s| !s.is_empty()).last());
+    segment
+        .or(url.host_str())
+        .map(str::to_string)
+        .with_context(|| format!("can not derive a name from {}", url))
+}
+
+/// Stream an HTTP(S) download into the store, reporting progress.
+#[cfg(feature = "url")]
+async fn import_url(
+    name: String,
+    url: String,
+    db: Store,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(String, TempTag, u64)> {
+    let url = reqwest::Url::parse(&url)?;
+    anyhow::ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "unsupported URL scheme {}",
+        url.scheme()
+    );
+
+    // The default client follows redirects.
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let size = response.content_length().unwrap_or(0);
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(crate::progress::ProgressEvent::Import(
+                name.clone(),
+                crate::progress::ImportProgress::FileStarted {
+                    name: name.clone(),
+                    size,
+                },
+            ))
+            .await;
+    }
+
+    let stream = response
+        .bytes_stream()
+        .map(|chunk| chunk.map_err(std::io::Error::other));
+    let add = db.add_stream(stream).await;
+    let (temp_tag, item_size) = drive_add(&name, add, &progress_tx).await?;
+    Ok((name, temp_tag, item_size))
+}
+
+/// Store the imported files as a collection and report completion.
+async fn store_collection(
+    mut names_and_tags: Vec<(String, TempTag, u64)>,
+    db: &Store,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
     names_and_tags.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
 
     // total size of all files
@@ -205,6 +1302,94 @@ async fn import_internal(
     Ok((hash, size, collection))
 }
 
+/// The name of the note added by [`crate::SendArgs::attach_note`].
+pub const NOTE_NAME: &str = "SENDME_NOTE.txt";
+
+/// Add `note` to an imported collection as a top level [`NOTE_NAME`] file.
+///
+/// Fails if the collection already has a file with that name, so a note can
+/// never replace real data.
+pub(crate) async fn attach_note(
+    db: &Store,
+    collection: Collection,
+    size: u64,
+    note: String,
+) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    validate_path_component(NOTE_NAME)?;
+    anyhow::ensure!(
+        collection.iter().all(|(name, _)| name != NOTE_NAME),
+        "can not attach a note, the data already contains {}",
+        NOTE_NAME
+    );
+    let (name, tag, note_size) =
+        import_bytes(NOTE_NAME.to_string(), note.into_bytes(), db.clone(), None).await?;
+
+    let mut entries = collection.iter().cloned().collect::<Vec<_>>();
+    entries.push((name, tag.hash()));
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let collection = entries.into_iter().collect::<Collection>();
+    let collection_tag = collection.clone().store(db).await?;
+    drop(tag);
+
+    Ok((collection_tag.hash(), size + note_size, collection))
+}
+
+/// Store a single-file collection again under the bare file name, e.g.
+/// `dir/file.ext` as `file.ext`. What [`FILE_METADATA`] and
+/// [`ORIGINAL_NAMES`] record about the file moves to the new name.
+///
+/// Other collections are returned unchanged.
+pub(crate) async fn unwrap_single_file(
+    db: &Store,
+    hash: iroh_blobs::Hash,
+    size: u64,
+    collection: Collection,
+) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    let internal = [FILE_METADATA, ORIGINAL_NAMES];
+    let mut files = collection
+        .iter()
+        .filter(|(name, _)| !internal.contains(&name.as_str()));
+    let file = match (files.next(), files.next()) {
+        (Some((name, hash)), None) => name
+            .rsplit_once('/')
+            .map(|(_, file)| (name.clone(), file.to_string(), *hash)),
+        _ => None,
+    };
+    let Some((name, file, file_hash)) = file else {
+        return Ok((hash, size, collection));
+    };
+    let mut entries = vec![(file.clone(), file_hash)];
+    let (mut size, mut tags) = (size, Vec::new());
+    for internal in internal {
+        let Some((_, hash)) = collection.iter().find(|(name, _)| name == internal) else {
+            continue;
+        };
+        // both files map collection names to what they record
+        let data = db.get_bytes(*hash).await?;
+        let mut recorded: BTreeMap<String, serde_json::Value> = serde_json::from_slice(&data)?;
+        size -= data.len() as u64;
+        if let Some(mut entry) = recorded.remove(&name) {
+            if internal == ORIGINAL_NAMES {
+                // the original name is a path too, keep only its file name
+                let bytes = entry.as_str().and_then(|hex| hex::decode(hex).ok());
+                let bytes = bytes.context("invalid original name")?;
+                let start = bytes.iter().rposition(|b| *b == b'/').map_or(0, |i| i + 1);
+                entry = hex::encode(&bytes[start..]).into();
+            }
+            let data = serde_json::to_vec(&BTreeMap::from([(file.clone(), entry)]))?;
+            let (name, tag, data_size) =
+                import_bytes(internal.to_string(), data, db.clone(), None).await?;
+            entries.push((name, tag.hash()));
+            size += data_size;
+            tags.push(tag);
+        }
+    }
+    let collection = entries.into_iter().collect::<Collection>();
+    let collection_tag = collection.clone().store(db).await?;
+    drop(tags);
+    Ok((collection_tag.hash(), size, collection))
+}
+
 /// Get the export path for a given name relative to a root directory.
 pub fn get_export_path(root: &std::path::Path, name: &str) -> anyhow::Result<std::path::PathBuf> {
     let parts = name.split('/');
@@ -232,9 +1417,12 @@ pub fn get_export_path(root: &std::path::Path, name: &str) -> anyhow::Result<std
 pub async fn import_from_bytes(
     name: String,
     data: Vec<u8>,
-    db: &FsStore,
+    db: &Store,
     progress_tx: Option<ProgressSenderTx>,
 ) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+    for part in name.split('/') {
+        validate_path_component(part)?;
+    }
     let size = data.len() as u64;
 
     if let Some(ref tx) = progress_tx {
@@ -278,3 +1466,893 @@ pub async fn import_from_bytes(
 
     Ok((hash, size, collection))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    #[cfg(feature = "url")]
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use iroh_blobs::store::fs::FsStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_high_priority_import_first() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let low = source.path().join("low.bin");
+        let high = source.path().join("high.bin");
+        std::fs::write(&low, vec![1u8; 1 << 20])?;
+        std::fs::write(&high, b"small and urgent")?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+
+        // a single worker that is busy until both imports are queued
+        let pool = WorkerPool::new(1);
+        let busy = pool.acquire(Priority::Normal).await;
+        let finished = Mutex::new(Vec::new());
+
+        let run = |path, priority| {
+            let (db, pool, finished) = (&db, &pool, &finished);
+            async move {
+                import_internal(
+                    &[path],
+                    db,
+                    pool,
+                    &ImportOptions {
+                        priority,
+                        batch_threshold: 0,
+                        ..Default::default()
+                    },
+                    ChangeCheck::new(false),
+                    None,
+                )
+                .await?;
+                finished.lock().unwrap().push(priority);
+                anyhow::Ok(())
+            }
+        };
+        let release = async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(busy);
+        };
+        let (low, high, ()) =
+            tokio::join!(run(low, Priority::Low), run(high, Priority::High), release);
+        low?;
+        high?;
+
+        assert_eq!(*finished.lock().unwrap(), [Priority::High, Priority::Low]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_many_with_root() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let entries = || {
+            vec![
+                ImportEntry::Bytes {
+                    name: "IMG_0001.jpg".to_string(),
+                    data: b"first photo".to_vec(),
+                },
+                ImportEntry::Bytes {
+                    name: "notes/trip.txt".to_string(),
+                    data: b"trip notes".to_vec(),
+                },
+            ]
+        };
+        let root = Some("Holiday".to_string());
+        let (_, _, collection) =
+            import_many(entries(), root, &db, Priority::Normal, 0, None).await?;
+        let names: Vec<_> = collection.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Holiday/IMG_0001.jpg", "Holiday/notes/trip.txt"]);
+
+        for root in ["", "a/b"] {
+            let root = Some(root.to_string());
+            assert!(import_many(entries(), root, &db, Priority::Normal, 0, None)
+                .await
+                .is_err());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_many_opened() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let data = vec![7u8; 200 * 1024];
+        let path = dir.path().join("picked.bin");
+        std::fs::write(&path, &data)?;
+        let entry = ImportEntry::Open {
+            name: "picked.bin".to_string(),
+            open: OpenFile::new(move || std::fs::File::open(&path)),
+        };
+        let (_, size, collection) =
+            import_many(vec![entry], None, &db, Priority::Normal, 0, None).await?;
+        assert_eq!(size, data.len() as u64);
+        let (_, hash) = collection.iter().next().unwrap();
+        assert_eq!(*hash, iroh_blobs::Hash::new(&data));
+
+        let missing = ImportEntry::Open {
+            name: "missing.bin".to_string(),
+            open: OpenFile::new(|| std::fs::File::open("/nonexistent/missing.bin")),
+        };
+        let err = import_many(vec![missing], None, &db, Priority::Normal, 0, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing.bin"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_empty_name() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let entry = ImportEntry::Bytes {
+            name: String::new(),
+            data: b"nameless".to_vec(),
+        };
+        assert!(import_many(vec![entry], None, &db, Priority::Normal, 0, None).await.is_err());
+        assert!(import_from_bytes(String::new(), b"nameless".to_vec(), &db, None)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_normalizes_names() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        // "café" as written by macOS (decomposed) and by most other systems
+        let (nfd, nfc) = ("cafe\u{301}", "caf\u{e9}");
+        let names = |normalize| {
+            let db = db.clone();
+            async move {
+                let source = tempfile::tempdir()?;
+                let root = source.path().join(nfd);
+                std::fs::create_dir(&root)?;
+                std::fs::write(root.join(format!("{nfd}.txt")), "menu")?;
+                let pool = WorkerPool::new(1);
+                let (_, _, collection) = import_internal(
+                    &[root],
+                    &db,
+                    &pool,
+                    &ImportOptions {
+                        normalize_names: normalize,
+                        ..Default::default()
+                    },
+                    ChangeCheck::new(false),
+                    None,
+                )
+                .await?;
+                anyhow::Ok(collection.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>())
+            }
+        };
+        assert_eq!(names(true).await?, vec![format!("{nfc}/{nfc}.txt")]);
+        assert_eq!(names(false).await?, vec![format!("{nfd}/{nfd}.txt")]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_transform() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("docs");
+        std::fs::create_dir(&root)?;
+        std::fs::write(root.join("note.txt"), "quiet words")?;
+        std::fs::write(root.join("secret.txt"), "left out")?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+
+        let transform: ImportTransform = Box::new(|name, data| {
+            (name != "docs/secret.txt").then(|| data.to_ascii_uppercase())
+        });
+        let (_, size, collection) = import(
+            vec![root],
+            &db,
+            &ImportOptions {
+                transform: Some(transform),
+                batch_threshold: 0,
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+        let files = collection.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![("docs/note.txt".to_string(), iroh_blobs::Hash::new("QUIET WORDS"))]
+        );
+        assert_eq!(size, "QUIET WORDS".len() as u64);
+
+        let target = tempfile::tempdir()?;
+        crate::export::export(
+            &db,
+            collection,
+            None,
+            Some(target.path()),
+            None,
+            &crate::export::ExportOptions::default(),
+        )
+        .await?;
+        assert_eq!(std::fs::read_to_string(target.path().join("docs/note.txt"))?, "QUIET WORDS");
+        assert!(!target.path().join("docs/secret.txt").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batched_import_progress() -> anyhow::Result<()> {
+        use crate::progress::ImportProgress;
+
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("tiny");
+        std::fs::create_dir(&root)?;
+        let files = 500;
+        for i in 0..files {
+            std::fs::write(root.join(format!("{i}.txt")), format!("file {i}"))?;
+        }
+        let total_size = (0..files).map(|i| format!("file {i}").len() as u64).sum::<u64>();
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let events = tokio::spawn(async move {
+            let mut events = Vec::new();
+            while let Some(crate::progress::ProgressEvent::Import(_, progress)) = rx.recv().await {
+                events.push(progress);
+            }
+            events
+        });
+        let pool = WorkerPool::new(4);
+        let (_, size, collection) = import_internal(
+            &[root],
+            &db,
+            &pool,
+            &ImportOptions {
+                batch_threshold: 100,
+                ..Default::default()
+            },
+            ChangeCheck::new(false),
+            Some(tx),
+        )
+        .await?;
+        let events = events.await?;
+        assert_eq!(size, total_size);
+        assert_eq!(collection.len(), files);
+
+        // started, at most one event per batch, and completed
+        assert!(events.len() <= BATCHES + 2, "{} events", events.len());
+        assert!(!events.iter().any(|e| matches!(e, ImportProgress::FileCompleted { .. })));
+        let batches = events
+            .iter()
+            .filter_map(|e| match e {
+                ImportProgress::BatchProgress { completed, total } => Some((*completed, *total)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(batches.last(), Some(&(files, files)));
+        assert!(matches!(
+            events.last(),
+            Some(ImportProgress::Completed { total_size: size }) if *size == total_size
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_file_progress() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let tx = Some(tx);
+        let batched = BatchProgress::new(&tx, 10, 5);
+        assert!(batched.file_progress(&tx, Some(1)).is_none());
+        assert!(batched.file_progress(&tx, Some(LARGE_FILE_SIZE)).is_some());
+        assert!(batched.file_progress(&tx, None).is_some());
+
+        let unbatched = BatchProgress::new(&tx, 10, 10);
+        assert!(unbatched.file_progress(&tx, Some(1)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_changed_file_reported() -> anyhow::Result<()> {
+        use std::sync::atomic::AtomicU64;
+
+        use crate::progress::{ImportProgress, ProgressEvent};
+
+        // a file that grows between every two looks at it
+        fn growing(path: &Path) -> std::io::Result<FileStamp> {
+            static CALLS: AtomicU64 = AtomicU64::new(0);
+            let (size, modified) = file_stamp(path)?;
+            Ok((size + CALLS.fetch_add(1, Ordering::Relaxed), modified))
+        }
+
+        let source = tempfile::tempdir()?;
+        let path = source.path().join("live.log");
+        std::fs::write(&path, "still being written")?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let pool = WorkerPool::new(1);
+
+        for reimport in [false, true] {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+            let check = ChangeCheck {
+                reimport,
+                stamp: growing,
+            };
+            let (_, _, collection) = import_internal(
+                std::slice::from_ref(&path),
+                &db,
+                &pool,
+                &ImportOptions::default(),
+                check,
+                Some(tx),
+            )
+            .await?;
+            assert_eq!(collection.len(), 1);
+
+            let (mut started, mut changed) = (0, 0);
+            while let Ok(ProgressEvent::Import(_, progress)) = rx.try_recv() {
+                match progress {
+                    ImportProgress::FileStarted { .. } => started += 1,
+                    ImportProgress::FileChanged { name } => {
+                        assert_eq!(name, "live.log");
+                        changed += 1;
+                    }
+                    _ => {}
+                }
+            }
+            let attempts = if reimport { 2 } else { 1 };
+            assert_eq!((started, changed), (attempts, attempts));
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_names() -> anyhow::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        use crate::progress::{ImportProgress, ProgressEvent};
+
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("dir");
+        std::fs::create_dir(&root)?;
+        std::fs::write(root.join("ok.txt"), "ok")?;
+        let bad = std::ffi::OsStr::from_bytes(b"bad\xff.txt");
+        if std::fs::write(root.join(bad), "bad").is_err() {
+            // some file systems only allow valid UTF-8 names
+            return Ok(());
+        }
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let pool = WorkerPool::new(1);
+        let import = |non_utf8_names, tx| {
+            let (root, db, pool) = (&root, &db, &pool);
+            async move {
+                let options = ImportOptions {
+                    non_utf8_names,
+                    ..Default::default()
+                };
+                let paths = std::slice::from_ref(root);
+                import_internal(paths, db, pool, &options, ChangeCheck::new(false), tx).await
+            }
+        };
+
+        assert!(import(NonUtf8Policy::Error, None).await.is_err());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+        let (_, _, collection) = import(NonUtf8Policy::Skip, Some(tx)).await?;
+        let names: Vec<_> = collection.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["dir/ok.txt"]);
+        let mut skipped = Vec::new();
+        while let Ok(ProgressEvent::Import(_, progress)) = rx.try_recv() {
+            if let ImportProgress::FileSkipped { name } = progress {
+                skipped.push(name);
+            }
+        }
+        assert_eq!(skipped, ["dir/bad\u{FFFD}.txt"]);
+
+        let (_, _, collection) = import(NonUtf8Policy::Lossy, None).await?;
+        let names: Vec<_> = collection.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"dir/bad\u{FFFD}.txt"));
+        assert!(names.contains(&ORIGINAL_NAMES));
+
+        let target = tempfile::tempdir()?;
+        crate::export::export(
+            &db,
+            collection,
+            None,
+            Some(target.path()),
+            None,
+            &crate::export::ExportOptions::default(),
+        )
+        .await?;
+        let exported = target.path().join("dir");
+        assert_eq!(std::fs::read_to_string(exported.join(bad))?, "bad");
+        assert_eq!(std::fs::read_to_string(exported.join("ok.txt"))?, "ok");
+        assert!(!target.path().join(ORIGINAL_NAMES).exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unwrap_single_file_original_names() -> anyhow::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("dir");
+        std::fs::create_dir(&root)?;
+        let bad = std::ffi::OsStr::from_bytes(b"bad\xff.txt");
+        if std::fs::write(root.join(bad), "bad").is_err() {
+            // some file systems only allow valid UTF-8 names
+            return Ok(());
+        }
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let options = ImportOptions {
+            non_utf8_names: NonUtf8Policy::Lossy,
+            ..Default::default()
+        };
+        let (hash, size, collection) = import(vec![root], &db, &options, None).await?;
+        let (_, _, collection) = unwrap_single_file(&db, hash, size, collection).await?;
+        let names: Vec<_> = collection.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["bad\u{FFFD}.txt", ORIGINAL_NAMES]);
+
+        let target = tempfile::tempdir()?;
+        crate::export::export(
+            &db,
+            collection,
+            None,
+            Some(target.path()),
+            None,
+            &crate::export::ExportOptions::default(),
+        )
+        .await?;
+        assert_eq!(std::fs::read_to_string(target.path().join(bad))?, "bad");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_modes_round_trip() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("tools");
+        std::fs::create_dir(&root)?;
+        std::fs::write(root.join("run.sh"), "#!/bin/sh\n")?;
+        std::fs::set_permissions(root.join("run.sh"), std::fs::Permissions::from_mode(0o750))?;
+        std::fs::write(root.join("README"), "readme")?;
+        std::fs::set_permissions(root.join("README"), std::fs::Permissions::from_mode(0o644))?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let (_, _, collection) = import_metadata(&root, &db, false).await?;
+        let recorded = recorded_metadata(&db, &collection).await?;
+        let run = FileMetadata {
+            mode: Some(0o750),
+            modified: None,
+        };
+        assert_eq!(recorded, BTreeMap::from([("tools/run.sh".to_string(), run)]));
+
+        let mode = |path: PathBuf| {
+            anyhow::Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+        let target = tempfile::tempdir()?;
+        crate::export::export(
+            &db,
+            collection.clone(),
+            None,
+            Some(target.path()),
+            None,
+            &crate::export::ExportOptions::default(),
+        )
+        .await?;
+        assert_eq!(mode(target.path().join("tools/run.sh"))?, 0o750);
+        assert!(!target.path().join(FILE_METADATA).exists());
+
+        let staged = tempfile::tempdir()?;
+        let target = tempfile::tempdir()?;
+        crate::export::export_staged(
+            &db,
+            collection,
+            None,
+            staged.path(),
+            target.path(),
+            None,
+            &crate::export::ExportOptions::default(),
+        )
+        .await?;
+        assert_eq!(mode(target.path().join("tools/run.sh"))?, 0o750);
+        assert_eq!(std::fs::read_to_string(target.path().join("tools/README"))?, "readme");
+        assert!(!target.path().join(FILE_METADATA).exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sender_metadata_round_trip() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("backup");
+        std::fs::create_dir(&root)?;
+        let old = std::time::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (name, mode) in [("notes.txt", 0o640), ("run.sh", 0o755)] {
+            std::fs::write(root.join(name), name)?;
+            let file = std::fs::File::options().write(true).open(root.join(name))?;
+            file.set_modified(old + Duration::from_millis(mode))?;
+            file.set_permissions(std::fs::Permissions::from_mode(mode as u32))?;
+        }
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let (_, _, collection) = import_metadata(&root, &db, true).await?;
+        let recorded = recorded_metadata(&db, &collection).await?;
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded["backup/notes.txt"].mode, Some(0o640));
+
+        // returns the modification time and mode of each exported file
+        let exported = |target: &Path| {
+            ["notes.txt", "run.sh"].map(|name| {
+                let metadata = std::fs::metadata(target.join("backup").join(name)).unwrap();
+                (metadata.modified().unwrap(), metadata.permissions().mode() & 0o777)
+            })
+        };
+        let expected = [
+            (old + Duration::from_millis(0o640), 0o640),
+            (old + Duration::from_millis(0o755), 0o755),
+        ];
+        for apply in [true, false] {
+            let target = tempfile::tempdir()?;
+            crate::export::export(
+                &db,
+                collection.clone(),
+                None,
+                Some(target.path()),
+                None,
+                &crate::export::ExportOptions {
+                    apply_metadata: apply,
+                    ..Default::default()
+                },
+            )
+            .await?;
+            let [notes, run] = exported(target.path());
+            if apply {
+                assert_eq!([notes, run], expected);
+            } else {
+                // only the executable bits are restored
+                assert_ne!(notes.0, expected[0].0);
+                assert_ne!(run.0, expected[1].0);
+                assert_eq!(run.1, 0o755);
+            }
+        }
+
+        let staged = tempfile::tempdir()?;
+        let target = tempfile::tempdir()?;
+        crate::export::export_staged(
+            &db,
+            collection,
+            None,
+            staged.path(),
+            target.path(),
+            None,
+            &crate::export::ExportOptions {
+                apply_metadata: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert_eq!(exported(target.path()), expected);
+        Ok(())
+    }
+
+    async fn import_metadata(
+        root: &Path,
+        db: &Store,
+        record_metadata: bool,
+    ) -> anyhow::Result<(iroh_blobs::Hash, u64, Collection)> {
+        import_internal(
+            &[root.to_path_buf()],
+            db,
+            &WorkerPool::new(1),
+            &ImportOptions {
+                record_metadata,
+                ..Default::default()
+            },
+            ChangeCheck::new(false),
+            None,
+        )
+        .await
+    }
+
+    /// The [`FILE_METADATA`] of `collection`.
+    async fn recorded_metadata(
+        db: &Store,
+        collection: &Collection,
+    ) -> anyhow::Result<BTreeMap<String, FileMetadata>> {
+        let (_, hash) = collection
+            .iter()
+            .find(|(name, _)| name == FILE_METADATA)
+            .context("metadata is recorded")?;
+        Ok(serde_json::from_slice(&db.get_bytes(*hash).await?)?)
+    }
+
+    #[tokio::test]
+    async fn test_import_indexed_reuses_index() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("dataset");
+        std::fs::create_dir(&root)?;
+        std::fs::write(root.join("a.bin"), vec![1u8; 64 * 1024])?;
+        std::fs::write(root.join("b.bin"), vec![2u8; 64 * 1024])?;
+        let store_dir = tempfile::tempdir()?;
+
+        // returns the result, and whether the files were imported
+        let serve = |root: PathBuf| {
+            let store_dir = store_dir.path().to_path_buf();
+            async move {
+                let db = FsStore::load(&store_dir).await?;
+                let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+                let imported = tokio::spawn(async move {
+                    let mut imported = false;
+                    while let Some(event) = rx.recv().await {
+                        imported |= matches!(
+                            event,
+                            crate::progress::ProgressEvent::Import(
+                                _,
+                                crate::progress::ImportProgress::Started { .. }
+                            )
+                        );
+                    }
+                    imported
+                });
+                let result =
+                    import_indexed(root, &db, &store_dir, &ImportOptions::default(), Some(tx))
+                        .await?;
+                db.shutdown().await?;
+                anyhow::Ok((result, imported.await?))
+            }
+        };
+
+        let ((hash, size, _), imported) = serve(root.clone()).await?;
+        assert!(imported);
+        let ((hash2, size2, collection), imported) = serve(root.clone()).await?;
+        assert!(!imported, "unchanged data was imported again");
+        assert_eq!((hash2, size2), (hash, size));
+        assert_eq!(collection.len(), 2);
+
+        // a change is picked up
+        std::fs::write(root.join("c.bin"), "new")?;
+        let ((hash3, _, _), imported) = serve(root).await?;
+        assert!(imported);
+        assert_ne!(hash3, hash);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_reads() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("data");
+        std::fs::create_dir(&root)?;
+        std::fs::write(root.join("a.txt"), "steady")?;
+        std::fs::write(root.join("b.txt"), "flaky")?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let paths = vec![root.clone()];
+        let options = ImportOptions {
+            batch_threshold: 0,
+            ..Default::default()
+        };
+        let (_, _, collection) = import(paths.clone(), &db, &options, None).await?;
+        verify_reads(&paths, &collection, &options).await?;
+
+        // a second read of b.txt that returns other bytes
+        let flaky = |path: &Path| {
+            if path.ends_with("b.txt") {
+                Ok(iroh_blobs::Hash::new("flakY"))
+            } else {
+                hash_file(path)
+            }
+        };
+        let filter = options.filter.compile()?;
+        let files = list_files(&root.canonicalize()?, true, NonUtf8Policy::Error, &filter)?.files;
+        let err = check_reads(files, &collection, Priority::Normal, flaky)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with(": data/b.txt"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_gitignore_filter() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("data");
+        for dir in ["target", "src", ".git"] {
+            std::fs::create_dir_all(root.join(dir))?;
+        }
+        let files = [
+            (".gitignore", "target/\n*.log\n!keep.log\n"),
+            ("a.log", "noise"),
+            ("keep.log", "kept"),
+            ("target/out.bin", "build output"),
+            (".git/HEAD", "ref: refs/heads/main"),
+            ("src/.gitignore", "generated.rs\n!debug.log\n"),
+            ("src/main.rs", "fn main() {}"),
+            ("src/generated.rs", "// generated"),
+            ("src/debug.log", "kept by src/.gitignore"),
+        ];
+        for (name, content) in files {
+            std::fs::write(root.join(name), content)?;
+        }
+        let gitignore = ImportFilter {
+            gitignore: true,
+            ..Default::default()
+        };
+        assert_eq!(list_names(&root, &ImportFilter::default())?.len(), files.len());
+        assert_eq!(
+            list_names(&root, &gitignore)?,
+            vec![
+                "data/.gitignore",
+                "data/keep.log",
+                "data/src/.gitignore",
+                "data/src/debug.log",
+                "data/src/main.rs",
+            ]
+        );
+
+        // a directory with nothing left to send is an error, an empty one is not
+        let ignored = source.path().join("ignored");
+        std::fs::create_dir(&ignored)?;
+        std::fs::write(ignored.join(".gitignore"), "*\n")?;
+        let err = list_names(&ignored, &gitignore).unwrap_err();
+        assert!(err.to_string().contains("are ignored or excluded"), "{err}");
+        let empty = source.path().join("empty");
+        std::fs::create_dir(&empty)?;
+        assert!(list_names(&empty, &gitignore)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_filter() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let root = source.path().join("data");
+        for dir in ["cache", "sub/cache"] {
+            std::fs::create_dir_all(root.join(dir))?;
+        }
+        for name in [
+            "a.tmp",
+            "b.txt",
+            "cache/x.bin",
+            "sub/cache/y.bin",
+            "sub/notes.tmp.txt",
+            "sub/cachefile",
+        ] {
+            std::fs::write(root.join(name), name)?;
+        }
+        // patterns match the names in the collection, which start with `data/`
+        let filter = ImportFilter {
+            exclude: vec!["*.tmp".to_string(), "**/cache/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            list_names(&root, &filter)?,
+            vec!["data/b.txt", "data/sub/cachefile", "data/sub/notes.tmp.txt"]
+        );
+        let filter = ImportFilter {
+            exclude: vec!["data/sub/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            list_names(&root, &filter)?,
+            vec!["data/a.tmp", "data/b.txt", "data/cache/x.bin"]
+        );
+
+        let filter = ImportFilter {
+            exclude: vec!["[unclosed".to_string()],
+            ..Default::default()
+        };
+        let err = list_names(&root, &filter).unwrap_err();
+        assert!(err.to_string().contains("invalid exclude pattern"), "{err}");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() -> anyhow::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let source = tempfile::tempdir()?;
+        let outside = source.path().join("outside");
+        std::fs::create_dir_all(outside.join("photos"))?;
+        std::fs::write(outside.join("notes.txt"), "notes")?;
+        std::fs::write(outside.join("photos/a.jpg"), "a")?;
+        let root = source.path().join("data");
+        std::fs::create_dir_all(root.join("sub"))?;
+        std::fs::write(root.join("own.txt"), "own")?;
+        symlink(outside.join("notes.txt"), root.join("notes.txt"))?;
+        symlink(outside.join("photos"), root.join("photos"))?;
+        symlink(&root, root.join("sub/loop"))?;
+        symlink(source.path().join("missing"), root.join("broken"))?;
+
+        assert_eq!(list_names(&root, &ImportFilter::default())?, vec!["data/own.txt"]);
+        let filter = ImportFilter {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            list_names(&root, &filter)?,
+            vec!["data/notes.txt", "data/own.txt", "data/photos/a.jpg"]
+        );
+        // the files are read through the link
+        let filter = filter.compile()?;
+        let listing = list_files(&root.canonicalize()?, true, NonUtf8Policy::Error, &filter)?;
+        let (_, path, size) = listing
+            .files
+            .iter()
+            .find(|(name, _, _)| name == "data/notes.txt")
+            .context("notes.txt is listed")?;
+        assert_eq!((std::fs::read_to_string(path)?.as_str(), *size), ("notes", 5));
+        Ok(())
+    }
+
+    /// The sorted names of the files in `path` that `filter` lets through.
+    fn list_names(path: &Path, filter: &ImportFilter) -> anyhow::Result<Vec<String>> {
+        let filter = filter.compile()?;
+        let listing = list_files(&path.canonicalize()?, true, NonUtf8Policy::Error, &filter)?;
+        let mut names = listing.files.into_iter().map(|(name, _, _)| name).collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Serve `body` once over plain HTTP, returning the server address.
+    #[cfg(feature = "url")]
+    async fn serve_once(body: Vec<u8>) -> anyhow::Result<std::net::SocketAddr> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await?;
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            socket.write_all(header.as_bytes()).await?;
+            socket.write_all(&body).await?;
+            anyhow::Ok(())
+        });
+        Ok(addr)
+    }
+
+    #[cfg(feature = "url")]
+    #[tokio::test]
+    async fn test_import_url() -> anyhow::Result<()> {
+        let data = b"known bytes served over http".to_vec();
+        let addr = serve_once(data.clone()).await?;
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+
+        let url = format!("http://{addr}/files/blob.bin");
+        let name = name_from_url(&url)?;
+        assert_eq!(name, "blob.bin");
+        let entries = vec![ImportEntry::Url { name, url }];
+        let (_, size, collection) =
+            import_many(entries, None, &db, Priority::Normal, 0, None).await?;
+
+        assert_eq!(size, data.len() as u64);
+        let (name, hash) = collection.iter().next().unwrap();
+        assert_eq!(name, "blob.bin");
+        assert_eq!(*hash, iroh_blobs::Hash::new(&data));
+        Ok(())
+    }
+
+    #[cfg(feature = "url")]
+    #[tokio::test]
+    async fn test_import_url_rejects_scheme() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = FsStore::load(dir.path()).await?;
+        let entry = ImportEntry::Url {
+            name: "passwd".to_string(),
+            url: "file:///etc/passwd".to_string(),
+        };
+        assert!(import_many(vec![entry], None, &db, Priority::Normal, 0, None).await.is_err());
+        Ok(())
+    }
+}
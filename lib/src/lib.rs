@@ -5,11 +5,24 @@
 
 use anyhow::Context;
 
+pub mod archive;
+pub mod diff;
 pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod import;
+pub mod links;
+pub mod metrics;
+pub mod plan;
 pub mod progress;
+pub mod qr;
 pub mod receive;
+pub mod scheduler;
 pub mod send;
+pub mod server;
+#[cfg(feature = "url")]
+pub mod shortlink;
+pub mod throttle;
 pub mod types;
 
 pub use progress::*;
@@ -18,32 +31,222 @@ pub use types::*;
 // Re-export commonly used types from dependencies
 pub use iroh::{RelayUrl, SecretKey};
 pub use iroh_blobs::{ticket::BlobTicket, BlobFormat, Hash};
+pub use tokio_util::sync::CancellationToken;
 
 // Public API
-pub use import::{get_export_path, import_from_bytes};
-pub use receive::{receive, receive_with_progress};
-pub use send::{send, send_with_progress};
+pub use diff::{collection_diff, CollectionDiff, RemovedFiles};
+pub use import::{
+    get_export_path, import_from_bytes, import_many, ImportEntry, ImportFilter, ImportTransform,
+    OpenFile,
+};
+#[cfg(feature = "url")]
+pub use import::name_from_url;
+pub use plan::{fetch_plan, test_connect, ConnectPath, ConnectReport, FileCheck, ReceivePlan};
+pub use receive::{receive, receive_many, receive_with_progress, ReceiveError};
+pub use send::{
+    send, send_bytes, send_bytes_with_progress, send_many, send_many_with_progress, send_serve_dir,
+    send_serve_dir_with_progress, send_with_progress, ConnectionSnapshot, SendHandle,
+};
+pub use server::{ServedPath, Server};
 
 /// Get or create a secret key for the iroh endpoint.
 ///
-/// If the `IROH_SECRET` environment variable is set, it will be parsed as a secret key.
-/// Otherwise, a new random secret key will be generated.
+/// The key is taken from the first of these that is set:
+///
+/// - `IROH_SECRET_FILE`: path to a file containing the key, as hex or as 32 raw bytes.
+/// - `IROH_SECRET`: the key as hex.
+///
+/// Otherwise, a new random secret key will be generated. Prefer the file on shared
+/// machines, since environment variables show up in process listings.
 pub fn get_or_create_secret(verbose: bool) -> anyhow::Result<SecretKey> {
-    match std::env::var("IROH_SECRET") {
-        Ok(secret) => {
-            let bytes = hex::decode(secret).context("invalid hex in secret")?;
-            let bytes: [u8; 32] = bytes
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("secret key must be 32 bytes"))?;
-            Ok(SecretKey::from_bytes(&bytes))
+    let file = std::env::var_os("IROH_SECRET_FILE").map(std::path::PathBuf::from);
+    let hex = std::env::var("IROH_SECRET").ok();
+    load_secret(file.as_deref(), hex.as_deref(), verbose)
+}
+
+/// Derive a secret key from `seed`, so the same seed always gives the same
+/// endpoint id, e.g. for tests or reproducible deployments.
+///
+/// Anyone who knows the seed can act as the endpoint, so it must be kept as
+/// secret as the key itself.
+pub fn secret_from_seed(seed: &[u8]) -> SecretKey {
+    SecretKey::from_bytes(&blake3::derive_key("sendme 2025 endpoint secret key", seed))
+}
+
+fn load_secret(
+    file: Option<&std::path::Path>,
+    hex: Option<&str>,
+    verbose: bool,
+) -> anyhow::Result<SecretKey> {
+    if let Some(path) = file {
+        return read_secret_file(path);
+    }
+    if let Some(secret) = hex {
+        return parse_hex_secret(secret);
+    }
+    let key = SecretKey::generate(&mut rand::rng());
+    if verbose {
+        let key = hex::encode(key.to_bytes());
+        eprintln!("using secret key {key}");
+    }
+    Ok(key)
+}
+
+fn parse_hex_secret(secret: &str) -> anyhow::Result<SecretKey> {
+    let bytes = hex::decode(secret).context("invalid hex in secret")?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("secret key must be 32 bytes"))?;
+    Ok(SecretKey::from_bytes(&bytes))
+}
+
+fn read_secret_file(path: &std::path::Path) -> anyhow::Result<SecretKey> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("failed to read secret file {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o004 != 0 {
+            tracing::warn!(
+                "secret file {} is world-readable, consider chmod 600",
+                path.display()
+            );
+        }
+    }
+    if let Ok(bytes) = <[u8; 32]>::try_from(data.as_slice()) {
+        return Ok(SecretKey::from_bytes(&bytes));
+    }
+    let text = std::str::from_utf8(&data)
+        .ok()
+        .context("secret file must contain 32 raw bytes or 64 hex characters")?;
+    parse_hex_secret(text.trim())
+        .with_context(|| format!("invalid secret in {}", path.display()))
+}
+
+/// Bind the endpoint created by `builder` to the addresses in `common`.
+///
+/// The builder function is called again to fall back to an ephemeral port if
+/// `common.bind_port` is taken.
+pub(crate) async fn bind_with_config(
+    common: &CommonConfig,
+    builder: impl Fn() -> iroh::endpoint::Builder,
+) -> anyhow::Result<iroh::Endpoint> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+    let with_addrs = |port: Option<u16>| {
+        let mut builder = builder();
+        let v4 = common
+            .magic_ipv4_addr
+            .or(port.map(|port| SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)));
+        let v6 = common
+            .magic_ipv6_addr
+            .or(port.map(|port| SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0)));
+        if let Some(addr) = v4 {
+            builder = builder.bind_addr_v4(addr);
+        }
+        if let Some(addr) = v6 {
+            builder = builder.bind_addr_v6(addr);
         }
-        Err(_) => {
-            let key = SecretKey::generate(&mut rand::rng());
-            if verbose {
-                let key = hex::encode(key.to_bytes());
-                eprintln!("using secret key {key}");
+        builder
+    };
+
+    let Some(port) = common.bind_port else {
+        return Ok(with_addrs(None).bind().await?);
+    };
+    match with_addrs(Some(port)).bind().await {
+        Ok(endpoint) => Ok(endpoint),
+        Err(e) => {
+            tracing::warn!("⚠️ Could not bind port {}, using an ephemeral port: {}", port, e);
+            Ok(with_addrs(None).bind().await?)
+        }
+    }
+}
+
+/// How long [`prewarm`] waits for the endpoint to be online.
+pub const PREWARM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Wait until `endpoint` can be reached by its peers, so that relay and DNS
+/// setup isn't counted as part of a transfer.
+///
+/// With relays, this waits for a connection to the home relay, otherwise for
+/// a direct address. Returns false if that took longer than
+/// [`PREWARM_TIMEOUT`], the endpoint is still usable then.
+pub(crate) async fn prewarm(endpoint: &iroh::Endpoint, common: &CommonConfig) -> bool {
+    use iroh::Watcher;
+    use n0_future::StreamExt;
+
+    let ready = async {
+        if !matches!(common.relay, RelayModeOption::Disabled) {
+            endpoint.online().await;
+        }
+        let mut addrs = endpoint.watch_addr().stream();
+        while let Some(addr) = addrs.next().await {
+            if !addr.addrs.is_empty() {
+                return true;
             }
-            Ok(key)
+        }
+        false
+    };
+    tokio::time::timeout(PREWARM_TIMEOUT, ready)
+        .await
+        .unwrap_or(false)
+}
+
+/// Remove the temporary directories of sends and receives in the directory
+/// that transfers with `common` use, e.g. the ones a crash left behind.
+/// Returns the removed directories, a directory that can't be removed is
+/// logged and skipped.
+///
+/// Only directories named with [`CommonConfig::temp_prefix`] are removed, and
+/// they must not be in use by a running transfer.
+pub fn remove_temp_dirs(common: &CommonConfig) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let base_dir = match &common.temp_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::current_dir()?,
+    };
+    let prefixes = [common.temp_name("send", ""), common.temp_name("recv", "")];
+    let mut removed = Vec::new();
+    for entry in std::fs::read_dir(&base_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !entry.file_type()?.is_dir() || !prefixes.iter().any(|p| name.starts_with(p)) {
+            continue;
+        }
+        let path = entry.path();
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed.push(path),
+            Err(e) => tracing::warn!("failed to remove {}: {}", path.display(), e),
+        }
+    }
+    Ok(removed)
+}
+
+/// The store of a transfer, as selected by [`StoreBackend`].
+pub(crate) enum BlobStore {
+    Fs(iroh_blobs::store::fs::FsStore),
+    Mem(iroh_blobs::store::mem::MemStore),
+}
+
+impl BlobStore {
+    /// Load the store in `dir`, or create an in-memory store if there is no
+    /// directory.
+    pub(crate) async fn load(dir: Option<&std::path::Path>) -> anyhow::Result<Self> {
+        Ok(match dir {
+            Some(dir) => Self::Fs(iroh_blobs::store::fs::FsStore::load(dir).await?),
+            None => Self::Mem(iroh_blobs::store::mem::MemStore::new()),
+        })
+    }
+}
+
+impl std::ops::Deref for BlobStore {
+    type Target = iroh_blobs::api::Store;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Fs(store) => store.as_ref(),
+            Self::Mem(store) => store.as_ref(),
         }
     }
 }
@@ -55,10 +258,17 @@ pub fn get_or_create_secret(verbose: bool) -> anyhow::Result<SecretKey> {
 ///
 /// This function will also fail if the path is non-canonical, i.e. contains `..` or `.`,
 /// or if the path components contain any path separators.
+///
+/// If `normalize` is true, the result is normalized to Unicode NFC. macOS often
+/// reports names in decomposed form, so without this the same tree would get
+/// different names there than on other platforms. Exported names need no
+/// conversion back, since macOS file systems accept either form.
 pub fn canonicalized_path_to_string(
     path: impl AsRef<std::path::Path>,
     must_be_relative: bool,
+    normalize: bool,
 ) -> anyhow::Result<String> {
+    use unicode_normalization::UnicodeNormalization;
     use std::path::Component;
 
     let mut path_str = String::new();
@@ -89,8 +299,12 @@ pub fn canonicalized_path_to_string(
             _ => Some(Err(anyhow::anyhow!("invalid path component {:?}", c))),
         })
         .collect::<anyhow::Result<Vec<_>>>()?;
+    anyhow::ensure!(!parts.is_empty(), "path has no file name components");
     let parts = parts.join("/");
     path_str.push_str(&parts);
+    if normalize {
+        path_str = path_str.nfc().collect();
+    }
     Ok(path_str)
 }
 
@@ -98,9 +312,87 @@ pub fn canonicalized_path_to_string(
 ///
 /// Ensures the component does not contain path separators.
 pub fn validate_path_component(component: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(!component.is_empty(), "path components must not be empty");
     anyhow::ensure!(
         !component.contains('/'),
         "path components must not contain the path separator /"
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_from_file() -> anyhow::Result<()> {
+        let key = SecretKey::generate(&mut rand::rng());
+        let dir = tempfile::tempdir()?;
+
+        let hex_file = dir.path().join("secret.hex");
+        std::fs::write(&hex_file, format!("{}\n", hex::encode(key.to_bytes())))?;
+        let raw_file = dir.path().join("secret.bin");
+        std::fs::write(&raw_file, key.to_bytes())?;
+
+        // the file takes precedence over the env var
+        let other = hex::encode(SecretKey::generate(&mut rand::rng()).to_bytes());
+        let loaded = load_secret(Some(&hex_file), Some(&other), false)?;
+        assert_eq!(loaded.public(), key.public());
+        let loaded = load_secret(Some(&raw_file), None, false)?;
+        assert_eq!(loaded.public(), key.public());
+
+        std::fs::write(&hex_file, "abcd")?;
+        assert!(load_secret(Some(&hex_file), None, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_from_env() -> anyhow::Result<()> {
+        let key = SecretKey::generate(&mut rand::rng());
+        let loaded = load_secret(None, Some(&hex::encode(key.to_bytes())), false)?;
+        assert_eq!(loaded.public(), key.public());
+        assert!(load_secret(None, Some("not hex"), false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_generated() -> anyhow::Result<()> {
+        let a = load_secret(None, None, false)?;
+        let b = load_secret(None, None, false)?;
+        assert_ne!(a.public(), b.public());
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_from_seed() {
+        let seed = b"integration test";
+        let key = secret_from_seed(seed);
+        assert_eq!(key.to_bytes(), secret_from_seed(seed).to_bytes());
+        assert_eq!(key.public(), secret_from_seed(seed).public());
+        assert_ne!(key.public(), secret_from_seed(b"another test").public());
+        assert_ne!(key.public(), secret_from_seed(b"").public());
+    }
+
+    #[test]
+    fn test_empty_names_rejected() {
+        assert!(canonicalized_path_to_string("", true, true).is_err());
+        assert!(canonicalized_path_to_string("/", false, true).is_err());
+        assert_eq!(canonicalized_path_to_string("a/b", true, true).unwrap(), "a/b");
+
+        assert!(validate_path_component("").is_err());
+        let root = std::path::Path::new("/tmp/export");
+        assert!(get_export_path(root, "").is_err());
+        assert!(get_export_path(root, "dir//file").is_err());
+        assert!(get_export_path(root, "dir/").is_err());
+    }
+
+    #[test]
+    fn test_names_normalized_to_nfc() -> anyhow::Result<()> {
+        let nfd = "Cafe\u{301}/Men\u{303}u.txt";
+        let nfc = "Caf\u{e9}/Me\u{f1}u.txt";
+        assert_eq!(canonicalized_path_to_string(nfd, true, true)?, nfc);
+        assert_eq!(canonicalized_path_to_string(nfc, true, true)?, nfc);
+        assert_eq!(canonicalized_path_to_string(nfd, true, false)?, nfd);
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! Share strings for tickets.
+//!
+//! All frontends use these, so a ticket shared from one of them as a command,
+//! a deep link or a web link can be pasted into any other.
+
+use iroh_blobs::ticket::BlobTicket;
+
+use crate::types::{apply_options, AddrInfoOptions};
+
+/// The CLI command prefix for receiving a ticket.
+const RECEIVE_COMMAND: &str = "sendme receive ";
+
+/// The prefix of deep links that open the app.
+const DEEP_LINK: &str = "sendme://receive/";
+
+/// The query parameter that carries the ticket in web links.
+const WEB_PARAM: &str = "ticket=";
+
+/// The key derivation context for [`verification_code`].
+const VERIFICATION_CONTEXT: &str = "sendme 2025 ticket verification code";
+
+/// The CLI command to receive a ticket, e.g. `sendme receive blob...`.
+pub fn receive_command(ticket: &BlobTicket) -> String {
+    format!("{RECEIVE_COMMAND}{ticket}")
+}
+
+/// A link that opens the app to receive a ticket, e.g. `sendme://receive/blob...`.
+pub fn deep_link(ticket: &BlobTicket) -> String {
+    format!("{DEEP_LINK}{ticket}")
+}
+
+/// A link to a web page at `base` that receives a ticket, passed as the
+/// URL-encoded `ticket` query parameter.
+pub fn web_link(base: &str, ticket: &BlobTicket) -> String {
+    let separator = if base.contains('?') { '&' } else { '?' };
+    let ticket = percent_encode(&ticket.to_string());
+    format!("{base}{separator}{WEB_PARAM}{ticket}")
+}
+
+/// Parse a ticket from any of the share strings above, or a bare ticket.
+pub fn parse_ticket(s: &str) -> anyhow::Result<BlobTicket> {
+    let s = s.trim();
+    let ticket = if let Some(rest) = s.strip_prefix(RECEIVE_COMMAND) {
+        rest.trim().to_string()
+    } else if let Some(rest) = s.strip_prefix(DEEP_LINK) {
+        rest.trim_end_matches('/').to_string()
+    } else if let Some((_, query)) = s.split_once('?') {
+        let value = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(WEB_PARAM))
+            .ok_or_else(|| anyhow::anyhow!("link has no ticket parameter"))?;
+        percent_decode(value)?
+    } else {
+        s.to_string()
+    };
+    ticket
+        .parse::<BlobTicket>()
+        .map_err(|e| anyhow::anyhow!("Failed to parse ticket: {}", e))
+}
+
+/// A copy of `ticket` with only the address information allowed by `opts`,
+/// e.g. to share a relay-only ticket without the sender's IP addresses.
+///
+/// This can only remove information: converting a relay-only ticket to
+/// [`AddrInfoOptions::RelayAndAddresses`] yields the same ticket.
+pub fn convert_ticket(ticket: &BlobTicket, opts: AddrInfoOptions) -> BlobTicket {
+    let mut addr = ticket.addr().clone();
+    apply_options(&mut addr, opts);
+    BlobTicket::new(addr, ticket.hash(), ticket.format())
+}
+
+/// A short code derived from the sender's node id, e.g. `482-193`.
+///
+/// The sender shows it next to the ticket, and the receiver compares it over a
+/// channel they trust, e.g. read out on a call. A ticket that was swapped on
+/// the way points to a different node, which has a different code.
+pub fn verification_code(ticket: &BlobTicket) -> String {
+    let key = blake3::derive_key(VERIFICATION_CONTEXT, ticket.addr().id.as_bytes());
+    let n = u32::from_le_bytes([key[0], key[1], key[2], key[3]]) % 1_000_000;
+    format!("{:03}-{:03}", n / 1000, n % 1000)
+}
+
+/// Check that `code` is the [`verification_code`] of `ticket`. Spaces and
+/// dashes in `code` are ignored.
+pub fn check_verification_code(ticket: &BlobTicket, code: &str) -> anyhow::Result<()> {
+    let digits = |s: &str| s.chars().filter(|c| !matches!(c, ' ' | '-')).collect::<String>();
+    anyhow::ensure!(
+        digits(code) == digits(&verification_code(ticket)),
+        "verification code {} does not match the ticket, it may have been tampered with",
+        code.trim()
+    );
+    Ok(())
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> anyhow::Result<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [
+                bytes.next().unwrap_or_default(),
+                bytes.next().unwrap_or_default(),
+            ];
+            let hex = std::str::from_utf8(&hex)?;
+            out.push(u8::from_str_radix(hex, 16)?);
+        } else {
+            out.push(byte);
+        }
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::{EndpointAddr, RelayUrl, SecretKey, TransportAddr};
+    use iroh_blobs::{BlobFormat, Hash};
+
+    use super::*;
+
+    fn ticket() -> BlobTicket {
+        let addr = EndpointAddr {
+            id: SecretKey::generate(&mut rand::rng()).public(),
+            addrs: [
+                TransportAddr::Ip("192.168.1.2:1234".parse().unwrap()),
+                TransportAddr::Relay("https://relay.example.com".parse().unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        BlobTicket::new(addr, Hash::new("links"), BlobFormat::HashSeq)
+    }
+
+    #[test]
+    fn test_links_round_trip() -> anyhow::Result<()> {
+        let ticket = ticket();
+        let command = receive_command(&ticket);
+        assert!(command.starts_with("sendme receive blob"));
+        assert_eq!(parse_ticket(&command)?, ticket);
+
+        let link = deep_link(&ticket);
+        assert!(link.starts_with("sendme://receive/"));
+        assert_eq!(parse_ticket(&link)?, ticket);
+
+        let web = web_link("https://example.com/receive?lang=en", &ticket);
+        assert!(web.starts_with("https://example.com/receive?lang=en&ticket="));
+        assert_eq!(parse_ticket(&web)?, ticket);
+
+        assert_eq!(parse_ticket(&format!(" {ticket}\n"))?, ticket);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_ticket() {
+        let ticket = ticket();
+        let relay = convert_ticket(&ticket, AddrInfoOptions::Relay);
+        assert_eq!(relay.addr().id, ticket.addr().id);
+        assert_eq!(relay.hash(), ticket.hash());
+        assert_eq!(relay.format(), ticket.format());
+        assert_eq!(relay.addr().ip_addrs().count(), 0);
+        let url: RelayUrl = "https://relay.example.com".parse().unwrap();
+        assert_eq!(relay.addr().relay_urls().collect::<Vec<_>>(), vec![&url]);
+
+        let id = convert_ticket(&ticket, AddrInfoOptions::Id);
+        assert_eq!(id.addr().id, ticket.addr().id);
+        assert!(id.addr().addrs.is_empty());
+
+        // information that was removed can't be added back
+        assert_eq!(convert_ticket(&id, AddrInfoOptions::RelayAndAddresses), id);
+    }
+
+    #[test]
+    fn test_verification_code() -> anyhow::Result<()> {
+        let ticket = ticket();
+        let code = verification_code(&ticket);
+        assert_eq!(code.len(), 7);
+        assert_eq!(code, verification_code(&ticket));
+        check_verification_code(&ticket, &code)?;
+        check_verification_code(&ticket, &code.replace('-', " "))?;
+
+        // same node, other content: same code
+        let other = BlobTicket::new(ticket.addr().clone(), Hash::new("other"), BlobFormat::Raw);
+        assert_eq!(verification_code(&other), code);
+
+        // a ticket for another node
+        let swapped = self::ticket();
+        assert_ne!(verification_code(&swapped), code);
+        assert!(check_verification_code(&swapped, &code).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_encoding() -> anyhow::Result<()> {
+        assert_eq!(percent_encode("a b/c?d"), "a%20b%2Fc%3Fd");
+        assert_eq!(percent_decode("a%20b%2Fc%3Fd")?, "a b/c?d");
+        assert!(percent_decode("%zz").is_err());
+        Ok(())
+    }
+}
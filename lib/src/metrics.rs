@@ -0,0 +1,131 @@
+//! Transfer metrics.
+//!
+//! With the `metrics` feature enabled, transfers are reported through the
+//! [`metrics`](https://docs.rs/metrics) facade, so any installed recorder
+//! (e.g. a Prometheus exporter) can collect them. Without the feature, all
+//! functions in this module are no-ops.
+
+use std::time::{Duration, Instant};
+
+/// Gauge of transfers in progress, labeled by `direction`.
+pub const ACTIVE_TRANSFERS: &str = "sendme_active_transfers";
+/// Counter of finished transfers, labeled by `direction`.
+pub const TRANSFERS_TOTAL: &str = "sendme_transfers_total";
+/// Histogram of transfer durations in seconds, labeled by `direction`.
+pub const TRANSFER_DURATION: &str = "sendme_transfer_duration_seconds";
+/// Histogram of import durations in seconds.
+pub const IMPORT_DURATION: &str = "sendme_import_duration_seconds";
+/// Counter of payload bytes served to receivers.
+pub const BYTES_SENT: &str = "sendme_bytes_sent_total";
+/// Counter of payload bytes downloaded from senders.
+pub const BYTES_RECEIVED: &str = "sendme_bytes_received_total";
+
+/// Direction of a transfer, used as a metrics label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Send,
+    Receive,
+}
+
+impl Direction {
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    fn label(self) -> &'static str {
+        match self {
+            Direction::Send => "send",
+            Direction::Receive => "receive",
+        }
+    }
+}
+
+/// Tracks a transfer in the active transfers gauge until dropped.
+pub(crate) struct TransferGuard {
+    direction: Direction,
+    started: Instant,
+}
+
+impl TransferGuard {
+    pub(crate) fn new(direction: Direction) -> Self {
+        #[cfg(feature = "metrics")]
+        ::metrics::gauge!(ACTIVE_TRANSFERS, "direction" => direction.label()).increment(1.0);
+        Self {
+            direction,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        #[cfg(feature = "metrics")]
+        {
+            let direction = self.direction.label();
+            ::metrics::gauge!(ACTIVE_TRANSFERS, "direction" => direction).decrement(1.0);
+            ::metrics::counter!(TRANSFERS_TOTAL, "direction" => direction).increment(1);
+            ::metrics::histogram!(TRANSFER_DURATION, "direction" => direction)
+                .record(elapsed.as_secs_f64());
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = (self.direction, elapsed);
+    }
+}
+
+/// Record the time it took to import data for sending.
+pub(crate) fn import_finished(duration: Duration) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!(IMPORT_DURATION).record(duration.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = duration;
+}
+
+/// Record payload bytes served to a receiver.
+pub(crate) fn bytes_sent(bytes: u64) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(BYTES_SENT).increment(bytes);
+    #[cfg(not(feature = "metrics"))]
+    let _ = bytes;
+}
+
+/// Record payload bytes downloaded from a sender.
+pub(crate) fn bytes_received(bytes: u64) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(BYTES_RECEIVED).increment(bytes);
+    #[cfg(not(feature = "metrics"))]
+    let _ = bytes;
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::*;
+    use crate::types::LoopbackTransfer;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_counters_after_transfer() -> anyhow::Result<()> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // only for this thread, which runs all tasks of the test, so other
+        // tests don't record into it
+        let _recorder = ::metrics::set_default_local_recorder(&recorder);
+
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("hello.txt", "hello metrics").await?;
+        crate::receive(t.receive_args(sent.ticket)).await?;
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name: &str| {
+            snapshot
+                .iter()
+                .filter(|(key, ..)| key.key().name() == name)
+                .map(|(.., value)| match value {
+                    DebugValue::Counter(value) => *value,
+                    _ => 0,
+                })
+                .sum::<u64>()
+        };
+        assert!(counter(BYTES_RECEIVED) >= 13);
+        assert!(counter(TRANSFERS_TOTAL) >= 1);
+        Ok(())
+    }
+}
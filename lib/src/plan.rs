@@ -0,0 +1,452 @@
+//! Receive plans - reviewing a collection before downloading it.
+//!
+//! A plan contains the names, hashes and sizes of all files in a collection,
+//! fetched without downloading any of the file content. It can be saved as
+//! JSON, reviewed or edited, and later executed with [`ReceivePlan::receive_args`].
+//!
+//! [`test_connect`] goes one step less far: it only checks that the sender can
+//! be reached, and how. [`ReceivePlan::verify`] checks files obtained some other
+//! way against a plan.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path, time::Duration};
+
+use iroh::{endpoint::ConnectionType, EndpointId, Watcher};
+use iroh_blobs::{
+    format::collection::Collection, get::request::get_hash_seq_and_sizes, store::mem::MemStore,
+    ticket::BlobTicket, Hash,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{receive, restrict_relays, CommonConfig, ReceiveArgs};
+
+/// A single file in a [`ReceivePlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    /// Name of the file in the collection.
+    pub name: String,
+    /// Hash of the file content.
+    pub hash: Hash,
+    /// Size of the file in bytes.
+    pub size: u64,
+}
+
+/// A directory in [`ReceivePlan::tree`].
+#[derive(Default)]
+struct TreeDir<'a> {
+    dirs: BTreeMap<&'a str, TreeDir<'a>>,
+    files: BTreeMap<&'a str, u64>,
+}
+
+impl TreeDir<'_> {
+    fn write(&self, out: &mut String, depth: usize, format_size: &impl Fn(u64) -> String) {
+        let indent = "  ".repeat(depth);
+        for (name, dir) in &self.dirs {
+            let _ = writeln!(out, "{indent}{name}/");
+            dir.write(out, depth + 1, format_size);
+        }
+        for (name, size) in &self.files {
+            let _ = writeln!(out, "{indent}{name} ({})", format_size(*size));
+        }
+    }
+}
+
+/// Metadata of a collection, fetched without downloading the payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivePlan {
+    /// The ticket the plan was created from.
+    pub ticket: String,
+    /// Hash of the collection.
+    pub hash: Hash,
+    /// Files to receive.
+    pub files: Vec<PlanEntry>,
+    /// Total size of all files in the plan.
+    pub total_size: u64,
+}
+
+impl ReceivePlan {
+    /// Load a plan from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("failed to read plan {}: {}", path.display(), e))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Save the plan as a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Keep only the files with the given names.
+    ///
+    /// Fails if any of the names is not part of the plan.
+    pub fn retain(&mut self, names: &[String]) -> anyhow::Result<()> {
+        for name in names {
+            anyhow::ensure!(
+                self.files.iter().any(|entry| &entry.name == name),
+                "{} is not part of the plan",
+                name
+            );
+        }
+        self.files.retain(|entry| names.contains(&entry.name));
+        self.total_size = self.files.iter().map(|entry| entry.size).sum();
+        Ok(())
+    }
+
+    /// The files in this plan as a collection, e.g. to compare it with
+    /// [`crate::collection_diff`].
+    pub fn collection(&self) -> Collection {
+        self.files
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.hash))
+            .collect()
+    }
+
+    /// An indented tree of the files in this plan, grouped by directory, with
+    /// the size of each file and a total line at the end.
+    ///
+    /// Subdirectories are listed before the files of a directory, both sorted
+    /// by name. Sizes are shown with `format_size`.
+    pub fn tree(&self, format_size: impl Fn(u64) -> String) -> String {
+        let mut root = TreeDir::default();
+        for entry in &self.files {
+            let mut parts: Vec<&str> = entry.name.split('/').collect();
+            let file = parts.pop().unwrap_or_default();
+            let dir = parts
+                .into_iter()
+                .fold(&mut root, |dir, part| dir.dirs.entry(part).or_default());
+            dir.files.insert(file, entry.size);
+        }
+        let mut out = String::new();
+        root.write(&mut out, 0, &format_size);
+        let _ = writeln!(out, "{} files, {}", self.files.len(), format_size(self.total_size));
+        out
+    }
+
+    /// Check local files against the hashes in this plan.
+    ///
+    /// `path` is the file itself for a single-file plan, or otherwise the
+    /// directory the collection was received into.
+    pub fn verify(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<FileCheck>> {
+        let path = path.as_ref();
+        let single = self.files.len() == 1 && !path.is_dir();
+        self.files
+            .iter()
+            .map(|entry| {
+                let file = if single {
+                    path.to_path_buf()
+                } else {
+                    crate::get_export_path(path, &entry.name)?
+                };
+                let actual = if file.is_file() {
+                    Some(hash_file(&file)?)
+                } else {
+                    None
+                };
+                Ok(FileCheck {
+                    name: entry.name.clone(),
+                    expected: entry.hash,
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// Build the arguments to receive exactly the files in this plan.
+    pub fn receive_args(
+        &self,
+        common: CommonConfig,
+        export_dir: Option<std::path::PathBuf>,
+    ) -> anyhow::Result<ReceiveArgs> {
+        let ticket: BlobTicket = self.ticket.parse()?;
+        anyhow::ensure!(ticket.hash() == self.hash, "plan hash does not match its ticket");
+        Ok(ReceiveArgs {
+            common,
+            export_dir,
+            only_files: Some(self.files.iter().map(|entry| entry.name.clone()).collect()),
+            ..ReceiveArgs::new(ticket)
+        })
+    }
+}
+
+/// The result of checking one local file, see [`ReceivePlan::verify`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCheck {
+    /// Name of the file in the collection.
+    pub name: String,
+    /// Hash of the file in the collection.
+    pub expected: Hash,
+    /// Hash of the local file, or None if it does not exist.
+    pub actual: Option<Hash>,
+}
+
+impl FileCheck {
+    /// Whether the local file matches the collection.
+    pub fn matches(&self) -> bool {
+        self.actual == Some(self.expected)
+    }
+}
+
+/// Hash a local file the same way as blobs are hashed, without reading it
+/// into memory at once.
+fn hash_file(path: &Path) -> anyhow::Result<Hash> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(Hash::from_bytes(*hasher.finalize().as_bytes()))
+}
+
+/// Connect to the sender and fetch the collection metadata and file sizes,
+/// without downloading any file content.
+pub async fn fetch_plan(args: ReceiveArgs) -> anyhow::Result<ReceivePlan> {
+    let ticket = args.ticket;
+    let hash = ticket.hash();
+    let mut addr = ticket.addr().clone();
+    if let Some(ref allowed) = args.allowed_relays {
+        restrict_relays(&mut addr, allowed)?;
+    }
+    receive::select_relay(&mut addr, args.relay_selection).await;
+    let endpoint = receive::bind_endpoint(&ticket, &args.common).await?;
+    // the connection and endpoint are closed whether this fails or not
+    let result = async {
+        let connection = endpoint.connect(addr, iroh_blobs::protocol::ALPN).await?;
+        let result = async {
+            let (hash_seq, sizes) =
+                get_hash_seq_and_sizes(&connection, &hash, 1024 * 1024 * 32, None)
+                    .await
+                    .map_err(receive::show_get_error)?;
+
+            // The metadata is small, so keep it in memory instead of a temp directory.
+            let store = MemStore::default();
+            let collection =
+                receive::download_metadata(store.as_ref(), &connection, hash, &hash_seq).await?;
+            anyhow::Ok((hash_seq, sizes, collection))
+        }
+        .await;
+        connection.close(0u32.into(), b"done");
+        result
+    }
+    .await;
+    endpoint.close().await;
+    let (hash_seq, sizes, collection) = result?;
+
+    let files = collection
+        .iter()
+        .map(|(name, file_hash)| {
+            let size = hash_seq
+                .iter()
+                .position(|h| h == *file_hash)
+                .and_then(|idx| sizes.get(idx).copied())
+                .unwrap_or_default();
+            PlanEntry {
+                name: name.clone(),
+                hash: *file_hash,
+                size,
+            }
+        })
+        .collect::<Vec<_>>();
+    let total_size = files.iter().map(|entry| entry.size).sum();
+
+    Ok(ReceivePlan {
+        ticket: ticket.to_string(),
+        hash,
+        files,
+        total_size,
+    })
+}
+
+/// How the connection to the sender was routed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectPath {
+    /// A direct UDP path.
+    Direct,
+    /// Through a relay server.
+    Relay,
+    /// Both, while holepunching is in progress.
+    Mixed,
+    /// Not known.
+    Unknown,
+}
+
+/// The result of [`test_connect`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectReport {
+    /// The endpoint that was reached.
+    pub sender: EndpointId,
+    /// How the connection was routed after the handshake.
+    pub path: ConnectPath,
+    /// Round trip time of the connection.
+    pub rtt: Duration,
+    /// Number of blobs in the collection, including its metadata.
+    pub blobs: usize,
+    /// Total size of all blobs in the collection.
+    pub total_size: u64,
+}
+
+/// Check that the sender of a ticket can be reached, without downloading
+/// anything but the blob sizes.
+///
+/// Fails if the sender can not be reached or does not serve the ticket's hash.
+pub async fn test_connect(args: ReceiveArgs) -> anyhow::Result<ConnectReport> {
+    let ticket = args.ticket;
+    let hash = ticket.hash();
+    let mut addr = ticket.addr().clone();
+    if let Some(ref allowed) = args.allowed_relays {
+        restrict_relays(&mut addr, allowed)?;
+    }
+    receive::select_relay(&mut addr, args.relay_selection).await;
+    let sender = addr.id;
+    let endpoint = receive::bind_endpoint(&ticket, &args.common).await?;
+    // the connection and endpoint are closed whether this fails or not
+    let result = async {
+        let connection = endpoint.connect(addr, iroh_blobs::protocol::ALPN).await?;
+        let sizes = get_hash_seq_and_sizes(&connection, &hash, 1024 * 1024 * 32, None)
+            .await
+            .map_err(receive::show_get_error);
+        let path = match endpoint.conn_type(sender).map(|mut watcher| watcher.get()) {
+            Some(ConnectionType::Direct(_)) => ConnectPath::Direct,
+            Some(ConnectionType::Relay(_)) => ConnectPath::Relay,
+            Some(ConnectionType::Mixed(..)) => ConnectPath::Mixed,
+            Some(ConnectionType::None) | None => ConnectPath::Unknown,
+        };
+        let rtt = connection.rtt();
+        connection.close(0u32.into(), b"done");
+        anyhow::Ok((sizes?, path, rtt))
+    }
+    .await;
+    endpoint.close().await;
+    let ((hash_seq, sizes), path, rtt) = result?;
+
+    Ok(ConnectReport {
+        sender,
+        path,
+        rtt,
+        blobs: hash_seq.len(),
+        total_size: sizes.iter().sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64) -> PlanEntry {
+        PlanEntry {
+            name: name.to_string(),
+            hash: Hash::new(name),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_plan_retain() {
+        let mut plan = ReceivePlan {
+            ticket: String::new(),
+            hash: Hash::new("collection"),
+            files: vec![entry("dir/a", 10), entry("dir/b", 20), entry("dir/c", 30)],
+            total_size: 60,
+        };
+        plan.retain(&["dir/a".to_string(), "dir/c".to_string()]).unwrap();
+        assert_eq!(plan.files, vec![entry("dir/a", 10), entry("dir/c", 30)]);
+        assert_eq!(plan.total_size, 40);
+
+        assert!(plan.retain(&["dir/b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_plan_tree() {
+        let plan = ReceivePlan {
+            ticket: String::new(),
+            hash: Hash::new("collection"),
+            files: vec![
+                entry("photos/2024/summer/beach.jpg", 300),
+                entry("photos/2024/a.jpg", 100),
+                entry("photos/cover.jpg", 50),
+                entry("notes.txt", 5),
+                entry("photos/2023/b.jpg", 200),
+            ],
+            total_size: 655,
+        };
+        let expected = "\
+photos/
+  2023/
+    b.jpg (200 B)
+  2024/
+    summer/
+      beach.jpg (300 B)
+    a.jpg (100 B)
+  cover.jpg (50 B)
+notes.txt (5 B)
+5 files, 655 B
+";
+        assert_eq!(plan.tree(|size| format!("{size} B")), expected);
+    }
+
+    #[test]
+    fn test_plan_save_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json");
+        let plan = ReceivePlan {
+            ticket: "ticket".to_string(),
+            hash: Hash::new("collection"),
+            files: vec![entry("a.txt", 3)],
+            total_size: 3,
+        };
+        plan.save(&path).unwrap();
+        assert_eq!(ReceivePlan::load(&path).unwrap(), plan);
+    }
+
+    #[tokio::test]
+    async fn test_connect_loopback() -> anyhow::Result<()> {
+        let t = crate::types::LoopbackTransfer::new()?;
+        let sent = t.send("hello.txt", "hello").await?;
+
+        let report = test_connect(t.receive_args(sent.ticket.clone())).await?;
+        assert_eq!(report.sender, sent.ticket.addr().id);
+        assert_eq!(report.path, ConnectPath::Direct);
+        assert!(report.total_size >= 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_verify() -> anyhow::Result<()> {
+        let plan_for = |files: &[(&str, &str)]| ReceivePlan {
+            ticket: String::new(),
+            hash: Hash::new("collection"),
+            files: files
+                .iter()
+                .map(|(name, data)| PlanEntry {
+                    name: name.to_string(),
+                    hash: Hash::new(data),
+                    size: data.len() as u64,
+                })
+                .collect(),
+            total_size: 0,
+        };
+        let dir = tempfile::tempdir()?;
+
+        // a single file is checked directly
+        let file = dir.path().join("single.txt");
+        std::fs::write(&file, "known content")?;
+        let checks = plan_for(&[("single.txt", "known content")]).verify(&file)?;
+        assert!(checks.iter().all(FileCheck::matches));
+        let checks = plan_for(&[("single.txt", "other content")]).verify(&file)?;
+        assert!(!checks[0].matches());
+        assert_eq!(checks[0].actual, Some(Hash::new("known content")));
+
+        // a directory is checked file by file
+        std::fs::create_dir(dir.path().join("data"))?;
+        std::fs::write(dir.path().join("data/a.txt"), "a")?;
+        std::fs::write(dir.path().join("data/b.txt"), "tampered")?;
+        let plan = plan_for(&[("data/a.txt", "a"), ("data/b.txt", "b"), ("data/c.txt", "c")]);
+        let checks = plan.verify(dir.path())?;
+        let matches = checks.iter().map(FileCheck::matches).collect::<Vec<_>>();
+        assert_eq!(matches, [true, false, false]);
+        assert_eq!(checks[2].actual, None);
+        Ok(())
+    }
+}
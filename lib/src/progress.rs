@@ -1,6 +1,12 @@
 //! Progress reporting abstractions for the sendme library.
 
-use iroh_blobs::Hash;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use iroh_blobs::{ticket::BlobTicket, Hash};
 use serde::{Deserialize, Serialize};
 
 /// Unified progress event type sent through channels.
@@ -14,6 +20,15 @@ pub enum ProgressEvent {
     Download(DownloadProgress),
     /// Connection status events.
     Connection(ConnectionStatus),
+    /// The data is imported and can be fetched with `ticket`.
+    ///
+    /// Sent once per send, before the provider starts serving, so a progress
+    /// consumer can show the ticket without waiting for the send to return.
+    SendReady {
+        ticket: BlobTicket,
+        hash: Hash,
+        total_size: u64,
+    },
 }
 
 /// Progress events for import operations.
@@ -27,8 +42,20 @@ pub enum ImportProgress {
     FileProgress { name: String, offset: u64 },
     /// A file import completed.
     FileCompleted { name: String },
+    /// The size or modification time of a file changed while it was imported,
+    /// so the imported data may be a mix of old and new content. Sent after
+    /// [`ImportProgress::FileCompleted`], see [`crate::SendArgs::reimport_changed`].
+    FileChanged { name: String },
+    /// A file was left out because its name is not valid UTF-8, see
+    /// [`crate::SendArgs::non_utf8_names`]. The name is shown lossily.
+    FileSkipped { name: String },
+    /// Number of files imported so far, for imports with too many files to
+    /// report each of them, see [`crate::CommonConfig::import_batch_threshold`].
+    BatchProgress { completed: usize, total: usize },
     /// Entire import completed.
     Completed { total_size: u64 },
+    /// The import was cancelled, and the data imported so far removed.
+    Cancelled,
 }
 
 /// Progress events for export operations.
@@ -49,6 +76,14 @@ pub enum ExportProgress {
 /// Progress events for download operations.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DownloadProgress {
+    /// The endpoint was prewarmed, see [`crate::ReceiveArgs::prewarm`]. It is
+    /// online, or `online` is false if that took too long. Sent before
+    /// [`DownloadProgress::Connecting`].
+    EndpointReady { online: bool },
+    /// The download is limited to `bytes_per_sec`, see
+    /// [`crate::CommonConfig::max_download_bps`]. Sent before
+    /// [`DownloadProgress::Connecting`].
+    Throttled { bytes_per_sec: u64 },
     /// Connecting to the sender.
     Connecting,
     /// Getting sizes of blobs to download.
@@ -68,13 +103,35 @@ pub enum DownloadProgress {
     },
     /// Downloading data.
     Downloading { offset: u64, total: u64 },
+    /// Checking that all data of the download is there, before the export.
+    /// The data was verified against its hashes while it arrived, this
+    /// confirms that nothing is missing. `offset` bytes of `total` were
+    /// checked. Not sent when the data was already there before the receive.
+    Verifying { offset: u64, total: u64 },
     /// Download completed.
     Completed,
+    /// An earlier partial download could not be resumed and was discarded,
+    /// so the download starts over.
+    Restarted { reason: String },
+    /// The connection to the sender was lost mid-download. The download
+    /// reconnects after a backoff, this is reconnect number `attempt`.
+    Stalled { attempt: u32, reason: String },
+    /// Reconnecting after [`DownloadProgress::Stalled`], the download
+    /// continues from the data that was already received.
+    Resumed { attempt: u32 },
 }
 
 /// Connection status events for provider side.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConnectionStatus {
+    /// The endpoint was prewarmed, see [`crate::SendArgs::prewarm`]. It is
+    /// online, or `online` is false if that took too long. Sent before the
+    /// import starts.
+    EndpointReady { online: bool },
+    /// The sender has an address receivers can connect to, or none could be
+    /// found in time if `reachable` is false. Sent once, after
+    /// [`ProgressEvent::SendReady`].
+    ServerReady { reachable: bool },
     /// A client connected.
     ClientConnected {
         endpoint_id: String,
@@ -97,6 +154,24 @@ pub enum ConnectionStatus {
     },
     /// A transfer request completed.
     RequestCompleted { connection_id: u64, request_id: u64 },
+    /// A transfer request was refused, because it asked for data that is not
+    /// part of the shared collection.
+    RequestRejected {
+        connection_id: u64,
+        request_id: u64,
+        hash: Hash,
+    },
+}
+
+/// The state of one ticket of [`crate::receive::receive_many`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TicketState {
+    /// Waiting for one of the parallel downloads to finish.
+    Queued,
+    /// Downloading and exporting.
+    Active,
+    /// Received, or failed.
+    Done,
 }
 
 /// Channel sender type for progress events.
@@ -104,3 +179,402 @@ pub type ProgressSenderTx = tokio::sync::mpsc::Sender<ProgressEvent>;
 
 /// Channel receiver type for progress events.
 pub type ProgressReceiverRx = tokio::sync::mpsc::Receiver<ProgressEvent>;
+
+/// Options of a [`ProgressBroadcast`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgressOptions {
+    /// Start each new subscription with the last [`DownloadProgress::Metadata`]
+    /// and the last event after it, so a UI that subscribes late still gets
+    /// the file names and the current phase.
+    pub latch_metadata: bool,
+}
+
+/// Hands the progress events of a transfer to any number of subscribers,
+/// which can come and go while the transfer runs.
+///
+/// A slow subscriber holds back the others and the transfer, like a single
+/// progress channel does.
+#[derive(Debug, Clone)]
+pub struct ProgressBroadcast {
+    state: Arc<Mutex<BroadcastState>>,
+}
+
+#[derive(Debug, Default)]
+struct BroadcastState {
+    subscribers: Vec<ProgressSenderTx>,
+    metadata: Option<ProgressEvent>,
+    latest: Option<ProgressEvent>,
+    /// The transfer dropped its sender, so no more events come.
+    closed: bool,
+}
+
+impl BroadcastState {
+    fn latch(&mut self, event: &ProgressEvent) {
+        if let ProgressEvent::Download(DownloadProgress::Metadata { .. }) = event {
+            self.metadata = Some(event.clone());
+            self.latest = None;
+        } else if self.metadata.is_some() {
+            self.latest = Some(event.clone());
+        }
+    }
+}
+
+impl ProgressBroadcast {
+    /// Create a broadcast, and the sender to pass to the transfer, e.g. to
+    /// [`crate::receive_with_progress`].
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn new(options: ProgressOptions) -> (Self, ProgressSenderTx) {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let state = Arc::new(Mutex::new(BroadcastState::default()));
+        tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Some(event) = rx.recv().await {
+                    let subscribers = {
+                        let mut state = state.lock().unwrap();
+                        if options.latch_metadata {
+                            state.latch(&event);
+                        }
+                        state.subscribers.retain(|tx| !tx.is_closed());
+                        state.subscribers.clone()
+                    };
+                    for tx in subscribers {
+                        let _ = tx.send(event.clone()).await;
+                    }
+                }
+                let mut state = state.lock().unwrap();
+                state.closed = true;
+                state.subscribers.clear();
+            }
+        });
+        (Self { state }, tx)
+    }
+
+    /// Subscribe to the events from now on, starting with the latched ones,
+    /// see [`ProgressOptions::latch_metadata`].
+    ///
+    /// The receiver ends once the transfer is done.
+    pub fn subscribe(&self) -> ProgressReceiverRx {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let mut state = self.state.lock().unwrap();
+        for event in state.metadata.iter().chain(&state.latest) {
+            // a new channel has room for both
+            let _ = tx.try_send(event.clone());
+        }
+        if !state.closed {
+            state.subscribers.push(tx);
+        }
+        rx
+    }
+}
+
+/// A summary of the progress of a transfer, accumulated from [`ProgressEvent`]s.
+///
+/// This allows showing the current state of a transfer without having to
+/// observe every single event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+    /// Bytes transferred so far.
+    pub bytes: u64,
+    /// Total bytes to transfer, if known.
+    pub total_bytes: u64,
+    /// Files completed so far.
+    pub files: u64,
+    /// Total number of files, if known.
+    pub total_files: u64,
+    /// Completion in percent, from 0 to 100.
+    pub percent: f64,
+    /// Average transfer rate since the first event.
+    pub bytes_per_sec: u64,
+    /// Time of the first event, used to compute the rate.
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl ProgressSnapshot {
+    /// Update the snapshot with an event.
+    pub fn update(&mut self, event: &ProgressEvent) {
+        self.update_at(event, Instant::now());
+    }
+
+    /// Update the snapshot with an event that happened at `now`.
+    pub fn update_at(&mut self, event: &ProgressEvent, now: Instant) {
+        let started = *self.started.get_or_insert(now);
+        match event {
+            ProgressEvent::Import(_, progress) => match progress {
+                ImportProgress::Started { total_files } => {
+                    self.total_files = *total_files as u64;
+                    self.files = 0;
+                }
+                ImportProgress::FileCompleted { .. } => self.files += 1,
+                ImportProgress::Completed { total_size } => {
+                    self.bytes = *total_size;
+                    self.total_bytes = *total_size;
+                    self.files = self.total_files;
+                }
+                _ => {}
+            },
+            ProgressEvent::Export(_, progress) => match progress {
+                ExportProgress::Started { total_files } => {
+                    self.total_files = *total_files as u64;
+                    self.files = 0;
+                }
+                ExportProgress::FileCompleted { .. } => self.files += 1,
+                ExportProgress::Completed => self.files = self.total_files,
+                _ => {}
+            },
+            ProgressEvent::Download(progress) => match progress {
+                DownloadProgress::Metadata {
+                    total_size,
+                    file_count,
+                    ..
+                } => {
+                    self.total_bytes = *total_size;
+                    self.total_files = *file_count;
+                }
+                DownloadProgress::Downloading { offset, total } => {
+                    self.bytes = *offset;
+                    self.total_bytes = *total;
+                }
+                DownloadProgress::Completed => self.bytes = self.total_bytes,
+                _ => {}
+            },
+            ProgressEvent::Connection(_) | ProgressEvent::SendReady { .. } => {}
+        }
+
+        let percent = if self.total_bytes > 0 {
+            self.bytes as f64 / self.total_bytes as f64 * 100.0
+        } else if self.total_files > 0 {
+            self.files as f64 / self.total_files as f64 * 100.0
+        } else {
+            0.0
+        };
+        self.percent = percent.min(100.0);
+        let elapsed = now.duration_since(started).as_secs_f64();
+        if elapsed > 0.0 {
+            self.bytes_per_sec = (self.bytes as f64 / elapsed) as u64;
+        }
+    }
+}
+
+/// Tracks the peak transfer rate over a rolling window of byte counts.
+///
+/// Transfers shorter than the window have no full window to measure, so their
+/// peak is the average over all samples.
+#[derive(Debug, Clone)]
+pub struct ThroughputTracker {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+    first: Option<(Instant, u64)>,
+    peak: Option<f64>,
+}
+
+impl ThroughputTracker {
+    /// Window used for the peak rate of transfers.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(1);
+
+    /// Create a tracker that measures the rate over `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            first: None,
+            peak: None,
+        }
+    }
+
+    /// Record the total number of bytes transferred so far.
+    pub fn record(&mut self, bytes: u64) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    /// Record the total number of bytes transferred at `now`.
+    pub fn record_at(&mut self, bytes: u64, now: Instant) {
+        self.first.get_or_insert((now, bytes));
+        self.samples.push_back((now, bytes));
+        // keep the newest sample that is at least a window old as the start
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+        let (start, start_bytes) = self.samples[0];
+        let span = now.duration_since(start);
+        if span >= self.window {
+            let rate = bytes.saturating_sub(start_bytes) as f64 / span.as_secs_f64();
+            self.peak = Some(self.peak.map_or(rate, |peak| peak.max(rate)));
+        }
+    }
+
+    /// The highest rate in bytes per second seen over any window.
+    pub fn peak(&self) -> f64 {
+        if let Some(peak) = self.peak {
+            return peak;
+        }
+        match (self.first, self.samples.back()) {
+            (Some((start, start_bytes)), Some(&(end, bytes))) if end > start => {
+                bytes.saturating_sub(start_bytes) as f64 / end.duration_since(start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// When the last number of bytes was recorded.
+    pub fn last_record(&self) -> Option<Instant> {
+        self.samples.back().map(|(at, _)| *at)
+    }
+}
+
+impl Default for ThroughputTracker {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_from_events() {
+        let t0 = Instant::now();
+        let mut snapshot = ProgressSnapshot::default();
+        snapshot.update_at(
+            &ProgressEvent::Download(DownloadProgress::Metadata {
+                total_size: 1000,
+                file_count: 2,
+                names: vec!["a".to_string(), "b".to_string()],
+            }),
+            t0,
+        );
+        assert_eq!(snapshot.total_bytes, 1000);
+        assert_eq!(snapshot.total_files, 2);
+        assert_eq!(snapshot.percent, 0.0);
+
+        snapshot.update_at(
+            &ProgressEvent::Download(DownloadProgress::Downloading {
+                offset: 250,
+                total: 1000,
+            }),
+            t0 + Duration::from_secs(1),
+        );
+        assert_eq!(snapshot.bytes, 250);
+        assert_eq!(snapshot.percent, 25.0);
+        assert_eq!(snapshot.bytes_per_sec, 250);
+
+        snapshot.update_at(
+            &ProgressEvent::Download(DownloadProgress::Completed),
+            t0 + Duration::from_secs(2),
+        );
+        assert_eq!(snapshot.percent, 100.0);
+        assert_eq!(snapshot.bytes_per_sec, 500);
+
+        let t2 = t0 + Duration::from_secs(2);
+        let export = |progress| ProgressEvent::Export(String::new(), progress);
+        snapshot.update_at(&export(ExportProgress::Started { total_files: 2 }), t2);
+        snapshot.update_at(
+            &export(ExportProgress::FileCompleted {
+                name: "a".to_string(),
+            }),
+            t2,
+        );
+        assert_eq!(snapshot.files, 1);
+        assert_eq!(snapshot.percent, 100.0);
+    }
+
+    #[test]
+    fn test_peak_throughput() {
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+        let mut tracker = ThroughputTracker::new(Duration::from_secs(1));
+        assert_eq!(tracker.peak(), 0.0);
+
+        // 100 bytes/s, then a burst of 1000 bytes/s, then slow again
+        tracker.record_at(0, at(0));
+        tracker.record_at(50, at(500));
+        tracker.record_at(100, at(1000));
+        assert_eq!(tracker.peak(), 100.0);
+        tracker.record_at(600, at(1500));
+        tracker.record_at(1100, at(2000));
+        tracker.record_at(1150, at(2500));
+        tracker.record_at(1200, at(3000));
+        assert_eq!(tracker.peak(), 1000.0);
+    }
+
+    #[test]
+    fn test_peak_throughput_short_transfer() {
+        let t0 = Instant::now();
+        let mut tracker = ThroughputTracker::new(Duration::from_secs(1));
+        tracker.record_at(0, t0);
+        assert_eq!(tracker.peak(), 0.0);
+        tracker.record_at(300, t0 + Duration::from_millis(250));
+        assert_eq!(tracker.peak(), 1200.0);
+    }
+
+    #[tokio::test]
+    async fn test_latch_metadata() {
+        let (broadcast, tx) = ProgressBroadcast::new(ProgressOptions {
+            latch_metadata: true,
+        });
+        let mut first = broadcast.subscribe();
+        let events = [
+            DownloadProgress::Connecting,
+            DownloadProgress::Metadata {
+                total_size: 10,
+                file_count: 2,
+                names: vec!["a".to_string(), "b".to_string()],
+            },
+            DownloadProgress::Downloading {
+                offset: 4,
+                total: 10,
+            },
+            DownloadProgress::Downloading {
+                offset: 6,
+                total: 10,
+            },
+        ];
+        for event in events {
+            tx.send(ProgressEvent::Download(event)).await.unwrap();
+        }
+        // once the first subscriber has them, they were latched
+        for _ in 0..4 {
+            first.recv().await.unwrap();
+        }
+
+        let mut late = broadcast.subscribe();
+        let Some(ProgressEvent::Download(DownloadProgress::Metadata { names, .. })) =
+            late.recv().await
+        else {
+            panic!("expected the latched metadata");
+        };
+        assert_eq!(names, ["a", "b"]);
+        assert!(matches!(
+            late.recv().await,
+            Some(ProgressEvent::Download(DownloadProgress::Downloading {
+                offset: 6,
+                ..
+            }))
+        ));
+        tx.send(ProgressEvent::Download(DownloadProgress::Completed))
+            .await
+            .unwrap();
+        drop(tx);
+        for rx in [&mut first, &mut late] {
+            assert!(matches!(
+                rx.recv().await,
+                Some(ProgressEvent::Download(DownloadProgress::Completed))
+            ));
+            assert!(rx.recv().await.is_none());
+        }
+
+        // without latching, a late subscriber only gets new events
+        let (broadcast, tx) = ProgressBroadcast::new(ProgressOptions::default());
+        let mut first = broadcast.subscribe();
+        tx.send(ProgressEvent::Download(DownloadProgress::Connecting))
+            .await
+            .unwrap();
+        first.recv().await.unwrap();
+        let mut late = broadcast.subscribe();
+        drop(tx);
+        assert!(late.recv().await.is_none());
+    }
+}
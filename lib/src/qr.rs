@@ -0,0 +1,138 @@
+//! Checks that tickets make QR codes that are easy to scan.
+//!
+//! QR codes get denser as the encoded data grows. Tickets with several relay
+//! URLs and IPv6 addresses make codes that phone cameras struggle with,
+//! especially when shown in a terminal.
+
+use derive_more::{Display, FromStr};
+use iroh_blobs::ticket::BlobTicket;
+use serde::{Deserialize, Serialize};
+
+use crate::types::AddrInfoOptions;
+
+/// The largest QR code version that still scans reliably, 77x77 modules.
+pub const COMFORTABLE_VERSION: u8 = 15;
+
+/// The error correction level of a QR code. Higher levels survive more
+/// damage, but hold less data.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Display, FromStr, Serialize, Deserialize)]
+pub enum Ecl {
+    /// Recovers about 7% of the code.
+    Low,
+    /// Recovers about 15% of the code.
+    #[default]
+    Medium,
+    /// Recovers about 25% of the code.
+    Quartile,
+    /// Recovers about 30% of the code.
+    High,
+}
+
+/// How many bytes fit in a QR code of [`COMFORTABLE_VERSION`].
+pub fn capacity(ecl: Ecl) -> usize {
+    // byte mode capacities of version 15
+    match ecl {
+        Ecl::Low => 520,
+        Ecl::Medium => 412,
+        Ecl::Quartile => 292,
+        Ecl::High => 220,
+    }
+}
+
+/// Whether `data` fits in a QR code that is still easy to scan.
+pub fn fits_comfortably(data: &str, ecl: Ecl) -> bool {
+    data.len() <= capacity(ecl)
+}
+
+/// The ticket with the most address information that still fits in a QR
+/// code that is easy to scan.
+///
+/// Relay URLs are kept over direct addresses, since they work across
+/// networks. A ticket with just the node id always fits.
+pub fn fitting_ticket(ticket: &BlobTicket, ecl: Ecl) -> BlobTicket {
+    [
+        AddrInfoOptions::RelayAndAddresses,
+        AddrInfoOptions::Relay,
+        AddrInfoOptions::Addresses,
+    ]
+    .into_iter()
+    .map(|opts| crate::links::convert_ticket(ticket, opts))
+    .find(|ticket| fits_comfortably(&ticket.to_string(), ecl))
+    .unwrap_or_else(|| crate::links::convert_ticket(ticket, AddrInfoOptions::Id))
+}
+
+/// A short caption for the QR code of a share, so a printed or forwarded code
+/// tells what it is, e.g. `photos, 12.3 MiB, relay ticket`.
+pub fn caption(name: &str, total_size: u64, ticket_type: AddrInfoOptions) -> String {
+    let kind = match ticket_type {
+        AddrInfoOptions::Id => "id ticket",
+        AddrInfoOptions::Relay => "relay ticket",
+        AddrInfoOptions::Addresses => "direct ticket",
+        AddrInfoOptions::RelayAndAddresses => "relay and direct ticket",
+    };
+    format!("{name}, {}, {kind}", format_size(total_size))
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::{EndpointAddr, SecretKey, TransportAddr};
+    use iroh_blobs::{BlobFormat, Hash};
+
+    use super::*;
+
+    fn ticket(addrs: impl IntoIterator<Item = TransportAddr>) -> BlobTicket {
+        let addr = EndpointAddr {
+            id: SecretKey::generate(&mut rand::rng()).public(),
+            addrs: addrs.into_iter().collect(),
+        };
+        BlobTicket::new(addr, Hash::new("qr"), BlobFormat::HashSeq)
+    }
+
+    #[test]
+    fn test_fits_comfortably() {
+        let short = ticket([TransportAddr::Ip("192.168.1.2:1234".parse().unwrap())]);
+        assert!(fits_comfortably(&short.to_string(), Ecl::High));
+        assert_eq!(fitting_ticket(&short, Ecl::High), short);
+
+        let relays = (0..3).map(|i| {
+            let url = format!("https://relay-{i}.example.com");
+            TransportAddr::Relay(url.parse().unwrap())
+        });
+        let ips = (0..8).map(|i| {
+            let addr = format!("[2001:db8:85a3::8a2e:370:{i}]:41234");
+            TransportAddr::Ip(addr.parse().unwrap())
+        });
+        let long = ticket(relays.chain(ips));
+        assert!(!fits_comfortably(&long.to_string(), Ecl::Medium));
+
+        let fitting = fitting_ticket(&long, Ecl::Medium);
+        assert!(fits_comfortably(&fitting.to_string(), Ecl::Medium));
+        assert_eq!(fitting.addr().id, long.addr().id);
+        assert_eq!(fitting.hash(), long.hash());
+        // the relays alone fit, so they are kept
+        assert_eq!(fitting.addr().relay_urls().count(), 3);
+        assert_eq!(fitting.addr().ip_addrs().count(), 0);
+    }
+
+    #[test]
+    fn test_caption() {
+        let caption = caption("holiday.jpg", 3 * 1024 * 1024 + 512 * 1024, AddrInfoOptions::Relay);
+        assert_eq!(caption, "holiday.jpg, 3.5 MiB, relay ticket");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.0 KiB");
+    }
+}
@@ -1,15 +1,43 @@
 //! Receive functionality - downloading files.
 
-use iroh::{discovery::dns::DnsDiscovery, Endpoint};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use fs4::fs_std::FileExt;
+use iroh::{
+    discovery::dns::DnsDiscovery, endpoint::Connection, Endpoint, EndpointAddr, RelayUrl,
+    TransportAddr,
+};
 use iroh_blobs::{
+    api::{
+        blobs::{AddPathOptions, BlobStatus, ImportMode},
+        Store, TempTag,
+    },
     format::collection::Collection,
     get::{request::get_hash_seq_and_sizes, GetError, Stats},
-    store::fs::FsStore,
+    hashseq::HashSeq,
+    protocol::GetRequest,
+    store::{fs::FsStore, mem::MemStore},
+    ticket::BlobTicket,
+    BlobFormat, Hash, HashAndFormat,
 };
 
 use n0_future::StreamExt;
+use tokio::task::JoinHandle;
 
-use crate::{export, get_or_create_secret, progress::*, ReceiveArgs, ReceiveResult};
+use crate::{
+    archive, export, get_export_path, get_or_create_secret, metrics, progress::*, restrict_relays,
+    throttle::RateLimiter, BlobStore, CommonConfig, NameRewrite, ReceiveArgs, ReceivePhases,
+    ReceiveResult, RelaySelection, StoreBackend,
+};
+
+/// The default for [`ReceiveArgs::stream_reconnects`].
+pub const DEFAULT_STREAM_RECONNECTS: u32 = 3;
 
 /// Receive a file or directory.
 ///
@@ -32,31 +60,79 @@ pub async fn receive_with_progress(
     receive_internal(args, Some(progress_tx)).await
 }
 
+/// Receive several tickets, at most `parallel` of them at a time. The
+/// others are queued in the order they were given.
+///
+/// The state of each ticket is sent to `states` with its index in `args`. The
+/// results are in the same order as `args`, and a ticket that fails doesn't
+/// stop the others.
+pub async fn receive_many(
+    args: Vec<ReceiveArgs>,
+    parallel: usize,
+    states: Option<tokio::sync::mpsc::Sender<(usize, TicketState)>>,
+) -> Vec<anyhow::Result<ReceiveResult>> {
+    let slots = Arc::new(tokio::sync::Semaphore::new(parallel.max(1)));
+    let mut tasks = Vec::with_capacity(args.len());
+    for (index, args) in args.into_iter().enumerate() {
+        if let Some(ref tx) = states {
+            let _ = tx.send((index, TicketState::Queued)).await;
+        }
+        let slots = slots.clone();
+        let states = states.clone();
+        tasks.push(tokio::spawn(async move {
+            let _slot = slots.acquire_owned().await?;
+            if let Some(ref tx) = states {
+                let _ = tx.send((index, TicketState::Active)).await;
+            }
+            let result = receive(args).await;
+            // before the slot is released, so at most `parallel` are active
+            if let Some(ref tx) = states {
+                let _ = tx.send((index, TicketState::Done)).await;
+            }
+            result
+        }));
+    }
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await.map_err(anyhow::Error::from).and_then(|result| result));
+    }
+    results
+}
+
 async fn receive_internal(
     args: ReceiveArgs,
     progress_tx: Option<ProgressSenderTx>,
 ) -> anyhow::Result<ReceiveResult> {
+    anyhow::ensure!(
+        args.common.max_download_bps != Some(0),
+        "the download rate must not be zero"
+    );
+    let _metrics = metrics::TransferGuard::new(metrics::Direction::Receive);
+    let (progress_tx, throughput, forwarder) = track_throughput(progress_tx);
+    let progress_tx = Some(progress_tx);
     let ticket = args.ticket;
-    let addr = ticket.addr().clone();
-    let secret_key = get_or_create_secret(args.common.show_secret)?;
-    let mut builder = Endpoint::builder()
-        .alpns(vec![])
-        .secret_key(secret_key)
-        .relay_mode(args.common.relay.into());
-
-    if ticket.addr().relay_urls().next().is_none() && ticket.addr().ip_addrs().next().is_none() {
-        builder = builder.discovery(DnsDiscovery::n0_dns());
+    if let Some(ref code) = args.expect_code {
+        crate::links::check_verification_code(&ticket, code)?;
     }
-
-    if let Some(addr) = args.common.magic_ipv4_addr {
-        builder = builder.bind_addr_v4(addr);
+    let mut addr = ticket.addr().clone();
+    if let Some(ref allowed) = args.allowed_relays {
+        restrict_relays(&mut addr, allowed)?;
     }
-    if let Some(addr) = args.common.magic_ipv6_addr {
-        builder = builder.bind_addr_v6(addr);
+    select_relay(&mut addr, args.relay_selection).await;
+    let endpoint = bind_endpoint(&ticket, &args.common).await?;
+    let bound_addrs = endpoint.bound_sockets();
+    let mut phases = ReceivePhases::default();
+    if args.prewarm {
+        let t0 = Instant::now();
+        let online = crate::prewarm(&endpoint, &args.common).await;
+        phases.prewarm = t0.elapsed();
+        if let Some(ref tx) = progress_tx {
+            let _ = tx
+                .send(ProgressEvent::Download(DownloadProgress::EndpointReady { online }))
+                .await;
+        }
     }
 
-    let endpoint = builder.bind().await?;
-
     // Determine the base directory for temp files
     // Use temp_dir from args if provided (required for Android/macOS sandbox),
     // otherwise fall back to current directory
@@ -69,228 +145,1244 @@ async fn receive_internal(
 
     tracing::info!("📁 Using base directory for temp storage: {:?}", base_dir);
 
-    let dir_name = format!(".sendme-recv-{}", ticket.hash().to_hex());
-    let iroh_data_dir = base_dir.join(&dir_name);
+    let hash_and_format = ticket.hash_and_format();
+    // An in-memory store has no data directory to lock, resume or clean up
+    let mut _lock = None;
+    let (db, iroh_data_dir) = match args.common.store_backend {
+        StoreBackend::Fs => {
+            let dir_name = args.common.temp_name("recv", &ticket.hash().to_hex());
+            let iroh_data_dir = base_dir.join(&dir_name);
 
-    tracing::info!("📂 Creating/loading FsStore at: {:?}", iroh_data_dir);
+            tracing::info!("📂 Creating/loading FsStore at: {:?}", iroh_data_dir);
 
-    // Verify parent directory exists and is writable
-    if !base_dir.exists() {
-        tracing::error!("❌ Base directory does not exist: {:?}", base_dir);
-        anyhow::bail!("Base directory does not exist: {:?}", base_dir);
-    }
+            // Verify parent directory exists and is writable
+            if !base_dir.exists() {
+                tracing::error!("❌ Base directory does not exist: {:?}", base_dir);
+                anyhow::bail!("Base directory does not exist: {:?}", base_dir);
+            }
 
-    // Test write permissions by creating the temp directory
-    std::fs::create_dir_all(&iroh_data_dir).map_err(|e| {
-        tracing::error!(
-            "❌ Failed to create temp directory {:?}: {}",
-            iroh_data_dir,
-            e
-        );
-        anyhow::anyhow!(
-            "Failed to create temp directory {:?}: {}. Check write permissions.",
-            iroh_data_dir,
-            e
-        )
-    })?;
+            // Receives of the same ticket into the same base directory share the data
+            // directory, so they must not run at the same time.
+            let lock = ReceiveLock::acquire(&iroh_data_dir).await.map_err(|e| {
+                if e.is::<std::io::Error>() {
+                    storage_error(&iroh_data_dir, e)
+                } else {
+                    e
+                }
+            })?;
+            _lock = Some(lock);
 
-    tracing::info!("✅ Temp directory created/verified");
+            // Test write permissions by creating the temp directory
+            std::fs::create_dir_all(&iroh_data_dir).map_err(|e| {
+                tracing::error!("❌ Failed to create temp directory {:?}: {}", iroh_data_dir, e);
+                storage_error(&iroh_data_dir, e.into())
+            })?;
 
-    let db = FsStore::load(&iroh_data_dir).await.map_err(|e| {
-        tracing::error!("❌ Failed to load FsStore: {}", e);
-        anyhow::anyhow!("Failed to load FsStore: {}", e)
-    })?;
+            tracing::info!("✅ Temp directory created/verified");
 
-    tracing::info!("✅ FsStore loaded successfully");
+            let db = open_store(&iroh_data_dir, hash_and_format, &progress_tx)
+                .await
+                .map_err(|e| storage_error(&iroh_data_dir, e))?;
 
-    let hash_and_format = ticket.hash_and_format();
-    let local = db.remote().local(hash_and_format).await?;
+            tracing::info!("✅ FsStore loaded successfully");
+            (BlobStore::Fs(db), Some(iroh_data_dir))
+        }
+        StoreBackend::Mem => (BlobStore::Mem(MemStore::new()), None),
+    };
+
+    let result = async {
+        // Use export_dir from args if provided, otherwise export to base_dir
+        let export_dir = args.export_dir.as_ref().unwrap_or(&base_dir);
+        let local = db.remote().local(hash_and_format).await?;
+        // the data found in the export directory, kept until the receive is done
+        let mut seeded = Vec::new();
+
+        let (stats, total_files, payload_size, cached, streamed) = if !local.is_complete() {
+            // shared by all attempts, so reconnecting doesn't reset the rate
+            let limiter = args.common.max_download_bps.map(RateLimiter::new);
+            if let (Some(bytes_per_sec), Some(tx)) = (args.common.max_download_bps, &progress_tx) {
+                let throttled = DownloadProgress::Throttled { bytes_per_sec };
+                let _ = tx.send(ProgressEvent::Download(throttled)).await;
+            }
+            let mut attempt = 0;
+            loop {
+                let download = download(
+                    &endpoint,
+                    addr.clone(),
+                    &db,
+                    hash_and_format,
+                    &args,
+                    export_dir,
+                    &mut phases,
+                    &mut seeded,
+                    limiter.as_ref(),
+                    &progress_tx,
+                );
+                let result = match args.common.timeout {
+                    Some(timeout) => tokio::select! {
+                        result = download => result,
+                        timed_out = stall_timeout(timeout, &throughput) => Err(timed_out.into()),
+                    },
+                    None => download.await,
+                };
+                match result {
+                    Ok(result) => break result,
+                    Err(e) if attempt < args.stream_reconnects && is_transient(&e) => {
+                        attempt += 1;
+                        let delay = reconnect_delay(attempt);
+                        tracing::warn!(
+                            "⏳ Download stalled, reconnecting in {:?}: {:#}",
+                            delay,
+                            e
+                        );
+                        if let Some(ref tx) = progress_tx {
+                            let _ = tx
+                                .send(ProgressEvent::Download(DownloadProgress::Stalled {
+                                    attempt,
+                                    reason: format!("{e:#}"),
+                                }))
+                                .await;
+                        }
+                        tokio::time::sleep(delay).await;
+                        if let Some(ref tx) = progress_tx {
+                            let resumed = DownloadProgress::Resumed { attempt };
+                            let _ = tx.send(ProgressEvent::Download(resumed)).await;
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        } else {
+            // Collection already cached locally
+            let mut total_files = local.children().unwrap() - 1;
+            // Use local_bytes as an approximation for total size (includes some metadata overhead)
+            let payload_bytes = local.local_bytes();
+
+            // Load collection and emit metadata event
+            let mut collection = Collection::load(hash_and_format.hash, &db).await?;
+            if let Some(ref only) = args.only_files {
+                collection = select_entries(&collection, only)?;
+                total_files = collection.len() as u64;
+            }
+            let names: Vec<String> = collection
+                .iter()
+                .map(|(name, _hash)| name.to_string())
+                .collect();
+
+            if let Some(ref tx) = progress_tx {
+                let _ = tx
+                    .send(ProgressEvent::Download(DownloadProgress::Metadata {
+                        total_size: payload_bytes,
+                        file_count: total_files,
+                        names,
+                    }))
+                    .await;
+            }
+
+            (
+                Stats::default(),
+                total_files,
+                payload_bytes,
+                Some(collection),
+                false,
+            )
+        };
+
+        // Use cached collection if available, otherwise load it
+        let collection = match cached {
+            Some(col) => col,
+            None => Collection::load(hash_and_format.hash, &db).await?,
+        };
+        // data that was already there was checked by the receive that got it,
+        // and streamed data is already exported
+        if !local.is_complete() && !streamed {
+            verify_complete(&db, &collection, payload_size, &progress_tx).await?;
+        }
+
+        tracing::info!("📤 Starting export to base_dir: {:?}", base_dir);
+        let t0 = Instant::now();
+        let fifo = match streamed {
+            // the file was written into the pipe as it arrived
+            true => None,
+            false => export::fifo_target(
+                args.fifo.as_deref(),
+                &collection,
+                export_dir,
+                args.name_rewrite.as_ref(),
+            )?,
+        };
+        let piped = streamed || fifo.is_some();
+        let report = if let Some(fifo) = fifo {
+            let mut pipe = export::open_fifo(&fifo).await?;
+            export::export_to_writer(&db, collection.clone(), &mut pipe, progress_tx.clone())
+                .await
+                .map_err(|e| export::pipe_error(e, &fifo))?;
+            export::ExportReport::default()
+        } else if streamed {
+            export::ExportReport::default()
+        } else if let Some(ref staging) = args.staging_dir {
+            tokio::fs::create_dir_all(staging).await?;
+            export::export_staged(
+                &db,
+                collection.clone(),
+                progress_tx.clone(),
+                staging,
+                export_dir,
+                args.name_rewrite.as_ref(),
+                &args.export_options(),
+            )
+            .await?
+        } else {
+            export::export(
+                &db,
+                collection.clone(),
+                progress_tx.clone(),
+                Some(export_dir),
+                args.name_rewrite.as_ref(),
+                &args.export_options(),
+            )
+            .await?
+        };
+        if args.write_manifest && !piped {
+            export::write_manifest(&collection, export_dir, &report).await?;
+        }
+        let failed_files = report.failed;
+        if args.auto_extract && !piped && failed_files.is_empty() {
+            extract_archive(&collection, export_dir, args.name_rewrite.as_ref()).await?;
+        }
+        phases.export = t0.elapsed();
 
-    let (stats, total_files, payload_size, metadata_collection) = if !local.is_complete() {
         if let Some(ref tx) = progress_tx {
             let _ = tx
-                .send(ProgressEvent::Download(DownloadProgress::Connecting))
+                .send(ProgressEvent::Download(DownloadProgress::Completed))
                 .await;
         }
+        anyhow::Ok((stats, total_files, payload_size, collection, failed_files))
+    }
+    .await;
+    // Shut the store down whether or not this worked, so the data of a failed
+    // download is kept and the next receive of the ticket resumes from it
+    db.shutdown().await.ok();
+    let (stats, total_files, payload_size, collection, failed_files) = result?;
 
-        let connection = endpoint.connect(addr, iroh_blobs::protocol::ALPN).await?;
+    // Clean up the temp directory, only once everything was exported
+    if let Some(dir) = iroh_data_dir {
+        tokio::fs::remove_dir_all(dir).await?;
+    }
+    metrics::bytes_received(stats.total_bytes_read());
+    // let the forwarder record the last events before reading the peak
+    drop(progress_tx);
+    forwarder.await.ok();
 
+    Ok(ReceiveResult {
+        collection,
+        total_files,
+        payload_size,
+        stats,
+        sender: ticket.addr().id,
+        phases,
+        failed_files,
+        bound_addrs,
+        peak_throughput: throughput.lock().unwrap().peak(),
+    })
+}
+
+/// Check that every file of `collection` is complete in `db`, reporting
+/// [`DownloadProgress::Verifying`] with `total` bytes to check.
+async fn verify_complete(
+    db: &Store,
+    collection: &Collection,
+    total: u64,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<()> {
+    let verifying = |offset| ProgressEvent::Download(DownloadProgress::Verifying { offset, total });
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(verifying(0)).await;
+    }
+    let mut offset = 0;
+    for (name, hash) in collection.iter() {
+        match db.status(*hash).await? {
+            BlobStatus::Complete { size } => offset += size,
+            _ => anyhow::bail!("{} is incomplete after the download", name),
+        }
         if let Some(ref tx) = progress_tx {
-            let _ = tx
-                .send(ProgressEvent::Download(DownloadProgress::GettingSizes))
-                .await;
+            let _ = tx.send(verifying(offset)).await;
         }
+    }
+    Ok(())
+}
 
-        let (hash_seq, sizes) =
-            get_hash_seq_and_sizes(&connection, &hash_and_format.hash, 1024 * 1024 * 32, None)
-                .await
-                .map_err(|e| show_get_error(e))?;
+/// Extract the file of a single-file `collection` in `export_dir` if it is
+/// an archive, see [`ReceiveArgs::auto_extract`].
+async fn extract_archive(
+    collection: &Collection,
+    export_dir: &Path,
+    name_rewrite: Option<&NameRewrite>,
+) -> anyhow::Result<()> {
+    if collection.len() != 1 {
+        return Ok(());
+    }
+    let Some((name, _)) = collection.iter().next() else {
+        return Ok(());
+    };
+    let name = match name_rewrite {
+        Some(rewrite) => rewrite.apply(name)?,
+        None => name.clone(),
+    };
+    let Some(kind) = archive::ArchiveKind::from_name(&name) else {
+        return Ok(());
+    };
+    let path = get_export_path(export_dir, &name)?;
+    let files = tokio::task::spawn_blocking({
+        let path = path.clone();
+        let root = export_dir.to_path_buf();
+        move || archive::extract(&path, kind, &root)
+    })
+    .await??;
+    tracing::info!("📦 Extracted {} files from {}", files.len(), path.display());
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
 
-        let total_size = sizes.iter().copied().sum::<u64>();
-        let payload_size = sizes.iter().skip(2).copied().sum::<u64>();
-        let total_files = (sizes.len().saturating_sub(1)) as u64;
+/// Connect to the sender and download what is missing from `db`.
+///
+/// Everything already in the store, e.g. from an earlier attempt, is kept, so
+/// this can be called again after a failure to continue where it stopped.
+///
+/// The last element of the result tells whether the file was already streamed
+/// into a named pipe, see [`ReceiveArgs::fifo`].
+#[allow(clippy::too_many_arguments)]
+async fn download(
+    endpoint: &Endpoint,
+    addr: EndpointAddr,
+    db: &Store,
+    hash_and_format: HashAndFormat,
+    args: &ReceiveArgs,
+    export_dir: &Path,
+    phases: &mut ReceivePhases,
+    seeded: &mut Vec<TempTag>,
+    limiter: Option<&RateLimiter>,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<(Stats, u64, u64, Option<Collection>, bool)> {
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::Download(DownloadProgress::Connecting))
+            .await;
+    }
+
+    let t0 = Instant::now();
+    let connection = endpoint.connect(addr, iroh_blobs::protocol::ALPN).await?;
+    phases.connect = t0.elapsed();
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::Download(DownloadProgress::GettingSizes))
+            .await;
+    }
+
+    let t0 = Instant::now();
+    let (hash_seq, sizes) =
+        get_hash_seq_and_sizes(&connection, &hash_and_format.hash, 1024 * 1024 * 32, None)
+            .await
+            .map_err(|e| show_get_error(e))?;
+    phases.sizes = t0.elapsed();
 
+    let t0 = Instant::now();
+    if args.merge_with_existing {
+        let collection =
+            download_metadata(db, &connection, hash_and_format.hash, &hash_seq).await?;
+        let (reused, tags) = seed_from_existing(
+            db,
+            &collection,
+            &hash_seq,
+            &sizes,
+            export_dir,
+            args.name_rewrite.as_ref(),
+        )
+        .await?;
+        seeded.extend(tags);
+        tracing::info!("♻️ Reusing {} bytes already in {:?}", reused, export_dir);
+    }
+    // a single file can be streamed into a named pipe while it downloads. Its
+    // name is needed to find the pipe, so the metadata is only fetched early
+    // if there could be one.
+    let single_file = hash_seq.iter().count() == 2 && args.only_files.is_none();
+    if args.fifo.is_some() && !single_file {
+        anyhow::bail!("can only stream a single file into a pipe");
+    }
+    if single_file && (args.fifo.is_some() || export::has_fifo(export_dir)) {
+        let collection =
+            download_metadata(db, &connection, hash_and_format.hash, &hash_seq).await?;
+        let fifo = export::fifo_target(
+            args.fifo.as_deref(),
+            &collection,
+            export_dir,
+            args.name_rewrite.as_ref(),
+        )?;
+        if let Some(fifo) = fifo {
+            let (stats, total_files, payload_size, collection) = download_streaming(
+                db,
+                connection,
+                collection,
+                &hash_seq,
+                &sizes,
+                &fifo,
+                limiter,
+                progress_tx,
+            )
+            .await
+            // the reader already got part of the file, so this can't be retried
+            .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+            phases.download = t0.elapsed();
+            return Ok((stats, total_files, payload_size, collection, true));
+        }
+    }
+
+    // seeding or fetching the metadata may have added data
+    let local = db.remote().local(hash_and_format).await?;
+    let (stats, total_files, payload_size, collection) = if let Some(ref only) = args.only_files {
+        download_selected(
+            db,
+            connection,
+            hash_and_format.hash,
+            &hash_seq,
+            &sizes,
+            only,
+            limiter,
+            progress_tx,
+        )
+        .await?
+    } else if local.is_complete() {
+        // everything was in the export directory, or fetched by an earlier attempt
+        let collection = Collection::load(hash_and_format.hash, db).await?;
+        let payload_size = sizes.iter().skip(2).copied().sum::<u64>();
         if let Some(ref tx) = progress_tx {
             let _ = tx
-                .send(ProgressEvent::Download(DownloadProgress::Downloading {
-                    offset: 0,
-                    total: total_size,
+                .send(ProgressEvent::Download(DownloadProgress::Metadata {
+                    total_size: payload_size,
+                    file_count: collection.len() as u64,
+                    names: collection.iter().map(|(name, _)| name.clone()).collect(),
                 }))
                 .await;
         }
+        let total_files = (sizes.len().saturating_sub(1)) as u64;
+        (Stats::default(), total_files, payload_size, Some(collection))
+    } else {
+        download_all(
+            db,
+            connection,
+            local.local_bytes(),
+            local.missing(),
+            hash_and_format.hash,
+            &hash_seq,
+            &sizes,
+            limiter,
+            progress_tx,
+        )
+        .await?
+    };
+    phases.download = t0.elapsed();
+    Ok((stats, total_files, payload_size, collection, false))
+}
 
-        let local_size = local.local_bytes();
-        let get = db.remote().execute_get(connection, local.missing());
-        let mut stream = get.stream();
-        let mut stats = Stats::default();
-        let mut metadata_sent = false;
-        let mut metadata_collection: Option<Collection> = None;
-        let mut progress_count = 0u32;
+/// Whether a failed download may succeed on a new connection.
+fn is_transient(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<GetError>() {
+        Some(e) => !matches!(e, GetError::BadRequest { .. } | GetError::LocalFailure { .. }),
+        None => {
+            e.downcast_ref::<iroh::endpoint::ConnectError>().is_some()
+                || e.downcast_ref::<iroh::endpoint::ConnectionError>().is_some()
+                || matches!(e.downcast_ref(), Some(ReceiveError::TimedOut { .. }))
+        }
+    }
+}
 
-        while let Some(item) = stream.next().await {
-            match item {
-                iroh_blobs::api::remote::GetProgressItem::Progress(offset) => {
-                    // Try to load collection metadata as soon as it's available
-                    // Try on first event and then every 10th event thereafter (events 1, 11, 21...) to avoid excessive load attempts
-                    if !metadata_sent {
-                        progress_count += 1;
-                        if (progress_count - 1) % 10 == 0 {
-                            if let Ok(collection) =
-                                Collection::load(hash_and_format.hash, db.as_ref()).await
-                            {
-                                // Calculate actual payload size from collection files
-                                let mut actual_payload_size = 0u64;
-                                for (name, file_hash) in collection.iter() {
-                                    // Find the size for this file hash in the hash_seq
-                                    if let Some(idx) = hash_seq.iter().position(|h| h == *file_hash)
-                                    {
-                                        if idx < sizes.len() {
-                                            actual_payload_size += sizes[idx];
-                                            tracing::debug!(
-                                                "File {}: hash at index {}, size {}",
-                                                name,
-                                                idx,
-                                                sizes[idx]
-                                            );
-                                        }
-                                    } else {
-                                        tracing::warn!("File {} hash not found in hash_seq", name);
+/// Complete once no bytes were recorded in `throughput` for `timeout`,
+/// counting from when this is called, see [`CommonConfig::timeout`].
+async fn stall_timeout(timeout: Duration, throughput: &Mutex<ThroughputTracker>) -> ReceiveError {
+    let start = Instant::now();
+    loop {
+        let last = throughput.lock().unwrap().last_record();
+        let idle = last.map_or(start, |last| last.max(start)).elapsed();
+        if idle >= timeout {
+            return ReceiveError::TimedOut { timeout };
+        }
+        tokio::time::sleep(timeout - idle).await;
+    }
+}
+
+/// How long to wait before reconnecting for the given attempt, doubling from
+/// half a second up to 30 seconds.
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_millis(500 << attempt.saturating_sub(1).min(6)).min(Duration::from_secs(30))
+}
+
+/// How long to wait for a relay to answer a latency probe.
+const RELAY_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Remove all but one relay from `addr` as chosen by `selection`, see
+/// [`ReceiveArgs::relay_selection`].
+pub(crate) async fn select_relay(addr: &mut EndpointAddr, selection: RelaySelection) {
+    if selection == RelaySelection::LowestLatency {
+        prefer_fastest_relay(addr, relay_rtt).await;
+    }
+}
+
+/// Keep only the relay of `addr` with the lowest round trip time as measured
+/// by `probe`. Nothing changes if there is only one relay, or none answers.
+async fn prefer_fastest_relay<F, Fut>(addr: &mut EndpointAddr, probe: F)
+where
+    F: Fn(RelayUrl) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Duration>>,
+{
+    let relays: Vec<RelayUrl> = addr.relay_urls().cloned().collect();
+    if relays.len() < 2 {
+        return;
+    }
+    let rtts = futures_buffered::join_all(relays.iter().map(|url| probe(url.clone()))).await;
+    let mut fastest = None;
+    for (url, rtt) in relays.iter().zip(rtts) {
+        match rtt {
+            Ok(rtt) if fastest.is_none_or(|(_, best)| rtt < best) => fastest = Some((url, rtt)),
+            Ok(_) => {}
+            Err(e) => tracing::debug!("relay {} did not answer: {:#}", url, e),
+        }
+    }
+    let Some((fastest, rtt)) = fastest else {
+        tracing::warn!("⚠️ No relay answered, leaving the choice to iroh");
+        return;
+    };
+    tracing::info!("📡 Using relay {} with a round trip time of {:?}", fastest, rtt);
+    let fastest = fastest.clone();
+    addr.addrs.retain(|addr| match addr {
+        TransportAddr::Relay(url) => *url == fastest,
+        _ => true,
+    });
+}
+
+/// The time it takes to open a TCP connection to a relay.
+async fn relay_rtt(url: RelayUrl) -> anyhow::Result<Duration> {
+    let host = url.host_str().context("relay URL has no host")?;
+    let port = url.port_or_known_default().context("relay URL has no port")?;
+    let t0 = Instant::now();
+    tokio::time::timeout(RELAY_PROBE_TIMEOUT, tokio::net::TcpStream::connect((host, port)))
+        .await
+        .context("timed out")??;
+    Ok(t0.elapsed())
+}
+
+/// Forward progress events to `progress_tx`, recording the download progress
+/// in a [`ThroughputTracker`] on the way.
+///
+/// This works whether or not the caller listens for progress. The returned
+/// task ends once the returned sender and all its clones are dropped.
+fn track_throughput(
+    progress_tx: Option<ProgressSenderTx>,
+) -> (
+    ProgressSenderTx,
+    Arc<Mutex<ThroughputTracker>>,
+    JoinHandle<()>,
+) {
+    let tracker = Arc::new(Mutex::new(ThroughputTracker::default()));
+    let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+    let tracker2 = tracker.clone();
+    let forwarder = tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if let ProgressEvent::Download(DownloadProgress::Downloading { offset, .. }) = event {
+                tracker2.lock().unwrap().record(offset);
+            }
+            if let Some(ref tx) = progress_tx {
+                let _ = tx.send(event).await;
+            }
+        }
+    });
+    (tx, tracker, forwarder)
+}
+
+/// Open the data directory of a receive, resuming an earlier partial download.
+///
+/// If the store can't be opened, or the data for `content` in it is corrupt,
+/// the directory is wiped and the download starts over instead of failing.
+async fn open_store(
+    dir: &Path,
+    content: HashAndFormat,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<FsStore> {
+    let error = match FsStore::load(dir).await {
+        Ok(db) => match check_resumable(&db, content).await {
+            Ok(()) => return Ok(db),
+            Err(e) => {
+                db.shutdown().await.ok();
+                e
+            }
+        },
+        Err(e) => anyhow::anyhow!("failed to load store: {}", e),
+    };
+
+    tracing::warn!("♻️ Can not resume from {:?}, starting over: {:#}", dir, error);
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::Download(DownloadProgress::Restarted {
+                reason: format!("{error:#}"),
+            }))
+            .await;
+    }
+    tokio::fs::remove_dir_all(dir).await?;
+    tokio::fs::create_dir_all(dir).await?;
+    FsStore::load(dir).await.map_err(|e| {
+        tracing::error!("❌ Failed to load FsStore: {}", e);
+        anyhow::anyhow!("Failed to load FsStore: {}", e)
+    })
+}
+
+/// Errors of a receive that frontends may want to handle specially.
+///
+/// They are returned inside the [`anyhow::Error`] of a receive, and can be
+/// checked for with `downcast_ref`.
+#[derive(Debug)]
+pub enum ReceiveError {
+    /// The directory for the transfer data can't be written, e.g. because the
+    /// volume is read-only or the app has no access to it. Choosing another
+    /// [`CommonConfig::temp_dir`] usually helps.
+    StorageUnavailable {
+        /// The data directory, resolved as far as it exists.
+        path: PathBuf,
+        /// Free space on the volume, if known.
+        available_space: Option<u64>,
+        /// Why writing failed.
+        reason: String,
+    },
+    /// No data arrived for [`CommonConfig::timeout`].
+    TimedOut {
+        /// The configured timeout.
+        timeout: Duration,
+    },
+}
+
+impl std::fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StorageUnavailable {
+                path,
+                available_space,
+                reason,
+            } => {
+                write!(f, "can not store the transfer in {}: {}", path.display(), reason)?;
+                if let Some(space) = available_space {
+                    write!(f, " ({space} bytes available)")?;
+                }
+                Ok(())
+            }
+            Self::TimedOut { timeout } => {
+                write!(f, "no data received for {:.1}s", timeout.as_secs_f64())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReceiveError {}
+
+/// What is known about the volume of a data directory, for error reports.
+#[derive(Debug)]
+struct StorageReport {
+    /// The directory, resolved as far as it exists.
+    path: PathBuf,
+    available_space: Option<u64>,
+    /// The result of writing a probe file.
+    writable: std::io::Result<()>,
+}
+
+impl StorageReport {
+    /// Check the volume of `dir`. If `dir` doesn't exist, its closest existing
+    /// ancestor is checked instead.
+    fn probe(dir: &Path) -> Self {
+        let existing = dir.ancestors().find(|dir| dir.is_dir()).unwrap_or(dir);
+        let path = match (existing.canonicalize(), dir.strip_prefix(existing)) {
+            (Ok(resolved), Ok(rest)) if !rest.as_os_str().is_empty() => resolved.join(rest),
+            (Ok(resolved), _) => resolved,
+            (Err(_), _) => dir.to_path_buf(),
+        };
+        let available_space = fs4::available_space(existing).ok();
+        let probe = existing.join(format!(".sendme-probe-{}", std::process::id()));
+        let writable = std::fs::write(&probe, b"probe").and_then(|()| std::fs::remove_file(&probe));
+        Self {
+            path,
+            available_space,
+            writable,
+        }
+    }
+}
+
+impl std::fmt::Display for StorageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "path {}", self.path.display())?;
+        match self.available_space {
+            Some(space) => write!(f, ", {space} bytes available")?,
+            None => write!(f, ", available space unknown")?,
+        }
+        match &self.writable {
+            Ok(()) => write!(f, ", writable"),
+            Err(e) => write!(f, ", not writable: {e}"),
+        }
+    }
+}
+
+/// Add the details of the volume to an error setting up the store in `dir`,
+/// so bug reports from restricted mobile volumes are actionable.
+///
+/// If `dir` can't be written at all, the error becomes
+/// [`ReceiveError::StorageUnavailable`].
+fn storage_error(dir: &Path, error: anyhow::Error) -> anyhow::Error {
+    let report = StorageReport::probe(dir);
+    tracing::error!("❌ Failed to set up the store: {:#} ({})", error, report);
+    match &report.writable {
+        Err(e) => ReceiveError::StorageUnavailable {
+            reason: e.to_string(),
+            path: report.path,
+            available_space: report.available_space,
+        }
+        .into(),
+        Ok(()) => anyhow::anyhow!("{error:#} ({report})"),
+    }
+}
+
+/// Check that the partial data for `content` in `db` can be resumed.
+///
+/// Blobs are verified as they are written, so only the structure is checked:
+/// a complete collection root must be a hash sequence, and complete metadata
+/// must load as a collection.
+async fn check_resumable(db: &FsStore, content: HashAndFormat) -> anyhow::Result<()> {
+    let complete = |status: BlobStatus| matches!(status, BlobStatus::Complete { .. });
+    if content.format != BlobFormat::HashSeq || !complete(db.status(content.hash).await?) {
+        return Ok(());
+    }
+    let hash_seq = HashSeq::try_from(db.get_bytes(content.hash).await?)?;
+    let meta_hash = hash_seq
+        .iter()
+        .next()
+        .context("collection has no metadata blob")?;
+    if complete(db.status(meta_hash).await?) {
+        Collection::load(content.hash, db).await?;
+    }
+    Ok(())
+}
+
+/// Receives in progress in this process, keyed by their data directory.
+static ACTIVE_RECEIVES: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Exclusive access to the data directory of a receive.
+///
+/// A second receive of the same ticket in this process waits for the first one
+/// to finish. A receive in another process is detected by an advisory lock on a
+/// file next to the data directory, and fails instead of corrupting the store.
+/// The operating system releases the lock when its process exits, so a crashed
+/// receive never blocks the next one.
+struct ReceiveLock {
+    data_dir: PathBuf,
+    lock_file: PathBuf,
+    _file: std::fs::File,
+    guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl ReceiveLock {
+    async fn acquire(data_dir: &Path) -> anyhow::Result<Self> {
+        let mutex = ACTIVE_RECEIVES
+            .lock()
+            .unwrap()
+            .entry(data_dir.to_path_buf())
+            .or_default()
+            .clone();
+        let guard = mutex.lock_owned().await;
+
+        let lock_file = data_dir.with_extension("lock");
+        let file = match lock_file_exclusive(&lock_file) {
+            Ok(file) => file,
+            Err(e) => {
+                forget_receive(data_dir, &guard);
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            data_dir: data_dir.to_path_buf(),
+            lock_file,
+            _file: file,
+            guard,
+        })
+    }
+}
+
+impl Drop for ReceiveLock {
+    fn drop(&mut self) {
+        // Removed while still locked; the lock is released when the file closes.
+        std::fs::remove_file(&self.lock_file).ok();
+        forget_receive(&self.data_dir, &self.guard);
+    }
+}
+
+/// Remove `data_dir` from [`ACTIVE_RECEIVES`] unless another receive in this
+/// process waits for it: the map and `guard` are then its only owners.
+fn forget_receive(data_dir: &Path, guard: &tokio::sync::OwnedMutexGuard<()>) {
+    let mut active = ACTIVE_RECEIVES.lock().unwrap();
+    if Arc::strong_count(tokio::sync::OwnedMutexGuard::mutex(guard)) <= 2 {
+        active.remove(data_dir);
+    }
+}
+
+/// Open and lock `path`, failing if another process holds the lock.
+fn lock_file_exclusive(path: &Path) -> anyhow::Result<std::fs::File> {
+    loop {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        anyhow::ensure!(
+            file.try_lock_exclusive()?,
+            "already receiving this ticket in another sendme process ({} is locked)",
+            path.display()
+        );
+        // The previous owner removes the file before unlocking it. If that
+        // happened after we opened it, we locked a file nobody else will see,
+        // so start over with the one now at the path.
+        if is_current_file(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+/// Whether `file` is still the file at `path`.
+///
+/// Elsewhere than on Unix an open file can not be removed, so it always is.
+fn is_current_file(file: &std::fs::File, path: &Path) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let open = file.metadata()?;
+        match std::fs::metadata(path) {
+            Ok(current) => Ok(open.dev() == current.dev() && open.ino() == current.ino()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file, path);
+        Ok(true)
+    }
+}
+
+/// Create the endpoint used to connect to the sender of a ticket.
+pub(crate) async fn bind_endpoint(
+    ticket: &BlobTicket,
+    common: &CommonConfig,
+) -> anyhow::Result<Endpoint> {
+    let secret_key = get_or_create_secret(common.show_secret)?;
+    let id_only =
+        ticket.addr().relay_urls().next().is_none() && ticket.addr().ip_addrs().next().is_none();
+    let builder = || {
+        let builder = Endpoint::builder()
+            .alpns(vec![])
+            .secret_key(secret_key.clone())
+            .relay_mode(common.relay.clone().into());
+        if id_only {
+            builder.discovery(DnsDiscovery::n0_dns())
+        } else {
+            builder
+        }
+    };
+    crate::bind_with_config(common, builder).await
+}
+
+/// Download the collection root and its metadata blob, so that the
+/// collection can be loaded from `store` without fetching any file content.
+pub(crate) async fn download_metadata(
+    store: &Store,
+    connection: &Connection,
+    hash: Hash,
+    hash_seq: &HashSeq,
+) -> anyhow::Result<Collection> {
+    let meta_hash = hash_seq
+        .iter()
+        .next()
+        .context("collection has no metadata blob")?;
+    for blob in [hash, meta_hash] {
+        run_get(store, connection, blob, None, None).await?;
+    }
+    Collection::load(hash, store).await
+}
+
+/// Download a single blob, optionally reporting progress as `base + offset` of `total`.
+pub(crate) async fn run_get(
+    store: &Store,
+    connection: &Connection,
+    hash: Hash,
+    progress: Option<(&ProgressSenderTx, u64, u64)>,
+    limiter: Option<&RateLimiter>,
+) -> anyhow::Result<Stats> {
+    let get = store
+        .remote()
+        .execute_get(connection.clone(), GetRequest::blob(hash));
+    let mut stream = get.stream();
+    let mut paced = 0;
+    while let Some(item) = stream.next().await {
+        match item {
+            iroh_blobs::api::remote::GetProgressItem::Progress(offset) => {
+                pace(limiter, &mut paced, offset).await;
+                if let Some((tx, base, total)) = progress {
+                    let _ = tx
+                        .send(ProgressEvent::Download(DownloadProgress::Downloading {
+                            offset: base + offset,
+                            total,
+                        }))
+                        .await;
+                }
+            }
+            iroh_blobs::api::remote::GetProgressItem::Done(stats) => return Ok(stats),
+            iroh_blobs::api::remote::GetProgressItem::Error(cause) => {
+                anyhow::bail!(show_get_error(cause));
+            }
+        }
+    }
+    anyhow::bail!("get stream ended without completing")
+}
+
+/// Wait until the bytes a get stream received since `paced` may be taken,
+/// see [`CommonConfig::max_download_bps`].
+///
+/// The stream is not read while this waits, so the sender is held back by
+/// flow control rather than the data piling up here.
+async fn pace(limiter: Option<&RateLimiter>, paced: &mut u64, offset: u64) {
+    if let Some(limiter) = limiter {
+        limiter.acquire(offset.saturating_sub(*paced)).await;
+    }
+    *paced = offset;
+}
+
+/// Add the files in `export_dir` that have the name, size and content of a
+/// collection entry to `db`, so they don't need to be downloaded.
+///
+/// Returns the number of bytes that were found, and the tags that protect
+/// them in `db`. They must be kept until the download is done.
+async fn seed_from_existing(
+    db: &Store,
+    collection: &Collection,
+    hash_seq: &HashSeq,
+    sizes: &[u64],
+    export_dir: &Path,
+    name_rewrite: Option<&NameRewrite>,
+) -> anyhow::Result<(u64, Vec<TempTag>)> {
+    let mut reused = 0;
+    let mut tags = Vec::new();
+    for (name, hash) in collection.iter() {
+        let Some(size) = hash_seq
+            .iter()
+            .position(|h| h == *hash)
+            .and_then(|idx| sizes.get(idx).copied())
+        else {
+            continue;
+        };
+        let name = match name_rewrite {
+            Some(rewrite) => rewrite.apply(name)?,
+            None => name.clone(),
+        };
+        let path = get_export_path(export_dir, &name)?;
+        // only hash files that can match, and aren't in the store yet
+        match tokio::fs::metadata(&path).await {
+            Ok(meta) if meta.is_file() && meta.len() == size => {}
+            _ => continue,
+        }
+        if matches!(db.status(*hash).await?, BlobStatus::Complete { .. }) {
+            continue;
+        }
+        // Copy rather than reference the file, since the export replaces it
+        let tag = db
+            .add_path_with_opts(AddPathOptions {
+                path,
+                mode: ImportMode::Copy,
+                format: BlobFormat::Raw,
+            })
+            .temp_tag()
+            .await?;
+        if tag.hash() == *hash {
+            reused += size;
+            tags.push(tag);
+        } else {
+            tracing::debug!("{} changed, downloading it again", name);
+        }
+    }
+    Ok((reused, tags))
+}
+
+/// Restrict a collection to the given entry names.
+///
+/// Fails if any of the names is not part of the collection.
+pub(crate) fn select_entries(
+    collection: &Collection,
+    only: &[String],
+) -> anyhow::Result<Collection> {
+    for name in only {
+        anyhow::ensure!(
+            collection.iter().any(|(n, _)| n == name),
+            "{} is not part of the collection",
+            name
+        );
+    }
+    Ok(collection
+        .iter()
+        .filter(|(name, _)| only.contains(name))
+        .map(|(name, hash)| (name.clone(), *hash))
+        .collect())
+}
+
+/// Download all missing blobs of the collection.
+#[allow(clippy::too_many_arguments)]
+async fn download_all(
+    db: &Store,
+    connection: Connection,
+    local_size: u64,
+    missing: GetRequest,
+    hash: Hash,
+    hash_seq: &HashSeq,
+    sizes: &[u64],
+    limiter: Option<&RateLimiter>,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<(Stats, u64, u64, Option<Collection>)> {
+    let total_size = sizes.iter().copied().sum::<u64>();
+    let payload_size = sizes.iter().skip(2).copied().sum::<u64>();
+    let total_files = (sizes.len().saturating_sub(1)) as u64;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::Download(DownloadProgress::Downloading {
+                offset: 0,
+                total: total_size,
+            }))
+            .await;
+    }
+
+    let get = db.remote().execute_get(connection, missing);
+    let mut stream = get.stream();
+    let mut stats = Stats::default();
+    let mut metadata_sent = false;
+    let mut metadata_collection: Option<Collection> = None;
+    let mut progress_count = 0u32;
+    let mut paced = 0;
+
+    while let Some(item) = stream.next().await {
+        match item {
+            iroh_blobs::api::remote::GetProgressItem::Progress(offset) => {
+                pace(limiter, &mut paced, offset).await;
+                // Try to load collection metadata as soon as it's available
+                // Try on first event and then every 10th event thereafter (events 1, 11, 21...)
+                // to avoid excessive load attempts
+                if !metadata_sent {
+                    progress_count += 1;
+                    if (progress_count - 1) % 10 == 0 {
+                        if let Ok(collection) = Collection::load(hash, db).await {
+                            // Calculate actual payload size from collection files
+                            let mut actual_payload_size = 0u64;
+                            for (name, file_hash) in collection.iter() {
+                                // Find the size for this file hash in the hash_seq
+                                if let Some(idx) = hash_seq.iter().position(|h| h == *file_hash) {
+                                    if idx < sizes.len() {
+                                        actual_payload_size += sizes[idx];
+                                        tracing::debug!(
+                                            "File {}: hash at index {}, size {}",
+                                            name,
+                                            idx,
+                                            sizes[idx]
+                                        );
                                     }
+                                } else {
+                                    tracing::warn!("File {} hash not found in hash_seq", name);
                                 }
+                            }
 
-                                tracing::info!(
-                                    "Metadata: {} files, total size: {}",
-                                    collection.iter().count(),
-                                    actual_payload_size
-                                );
-
-                                let names: Vec<String> = collection
-                                    .iter()
-                                    .map(|(name, _hash)| name.to_string())
-                                    .collect();
-
-                                if let Some(ref tx) = progress_tx {
-                                    let _ = tx
-                                        .send(ProgressEvent::Download(DownloadProgress::Metadata {
-                                            total_size: actual_payload_size,
-                                            file_count: total_files,
-                                            names,
-                                        }))
-                                        .await;
-                                }
-                                metadata_sent = true;
-                                metadata_collection = Some(collection);
+                            tracing::info!(
+                                "Metadata: {} files, total size: {}",
+                                collection.iter().count(),
+                                actual_payload_size
+                            );
+
+                            let names: Vec<String> = collection
+                                .iter()
+                                .map(|(name, _hash)| name.to_string())
+                                .collect();
+
+                            if let Some(ref tx) = progress_tx {
+                                let _ = tx
+                                    .send(ProgressEvent::Download(DownloadProgress::Metadata {
+                                        total_size: actual_payload_size,
+                                        file_count: total_files,
+                                        names,
+                                    }))
+                                    .await;
                             }
+                            metadata_sent = true;
+                            metadata_collection = Some(collection);
                         }
                     }
-
-                    if let Some(ref tx) = progress_tx {
-                        let _ = tx
-                            .send(ProgressEvent::Download(DownloadProgress::Downloading {
-                                offset: local_size + offset,
-                                total: total_size,
-                            }))
-                            .await;
-                    }
-                }
-                iroh_blobs::api::remote::GetProgressItem::Done(value) => {
-                    stats = value;
-                    break;
                 }
-                iroh_blobs::api::remote::GetProgressItem::Error(cause) => {
-                    anyhow::bail!(show_get_error(cause));
+
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx
+                        .send(ProgressEvent::Download(DownloadProgress::Downloading {
+                            offset: local_size + offset,
+                            total: total_size,
+                        }))
+                        .await;
                 }
             }
+            iroh_blobs::api::remote::GetProgressItem::Done(value) => {
+                stats = value;
+                break;
+            }
+            iroh_blobs::api::remote::GetProgressItem::Error(cause) => {
+                anyhow::bail!(show_get_error(cause));
+            }
         }
+    }
 
-        (stats, total_files, payload_size, metadata_collection)
-    } else {
-        // Collection already cached locally
-        let total_files = local.children().unwrap() - 1;
-        // Use local_bytes as an approximation for total size (includes some metadata overhead)
-        let payload_bytes = local.local_bytes();
-
-        // Load collection and emit metadata event
-        let collection = Collection::load(hash_and_format.hash, db.as_ref()).await?;
-        let names: Vec<String> = collection
-            .iter()
-            .map(|(name, _hash)| name.to_string())
-            .collect();
+    Ok((stats, total_files, payload_size, metadata_collection))
+}
 
-        if let Some(ref tx) = progress_tx {
-            let _ = tx
-                .send(ProgressEvent::Download(DownloadProgress::Metadata {
-                    total_size: payload_bytes,
-                    file_count: total_files,
-                    names,
-                }))
-                .await;
-        }
+/// Download the collection metadata and only the selected files.
+#[allow(clippy::too_many_arguments)]
+async fn download_selected(
+    db: &Store,
+    connection: Connection,
+    hash: Hash,
+    hash_seq: &HashSeq,
+    sizes: &[u64],
+    only: &[String],
+    limiter: Option<&RateLimiter>,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<(Stats, u64, u64, Option<Collection>)> {
+    let t0 = Instant::now();
+    let collection = download_metadata(db, &connection, hash, hash_seq).await?;
+    let selected = select_entries(&collection, only)?;
 
-        (
-            Stats::default(),
-            total_files,
-            payload_bytes,
-            Some(collection),
-        )
+    let size_of = |file_hash: &Hash| {
+        hash_seq
+            .iter()
+            .position(|h| h == *file_hash)
+            .and_then(|idx| sizes.get(idx).copied())
+            .unwrap_or_default()
     };
+    let total_size = selected.iter().map(|(_, h)| size_of(h)).sum::<u64>();
 
-    // Use cached collection if available, otherwise load it
-    let collection = match metadata_collection {
-        Some(col) => col,
-        None => Collection::load(hash_and_format.hash, db.as_ref()).await?,
-    };
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::Download(DownloadProgress::Metadata {
+                total_size,
+                file_count: selected.len() as u64,
+                names: selected.iter().map(|(name, _)| name.clone()).collect(),
+            }))
+            .await;
+    }
 
-    tracing::info!("📤 Starting export to base_dir: {:?}", base_dir);
-    // Use export_dir from args if provided, otherwise export to base_dir
-    let export_dir = args.export_dir.as_ref().unwrap_or(&base_dir);
-    export::export(
-        &db,
-        collection.clone(),
-        progress_tx.clone(),
-        Some(export_dir),
-    )
-    .await?;
+    let mut offset = 0u64;
+    for (name, file_hash) in selected.iter() {
+        if matches!(db.status(*file_hash).await?, BlobStatus::Complete { .. }) {
+            // e.g. reused with `merge_with_existing`
+            offset += size_of(file_hash);
+            continue;
+        }
+        tracing::debug!("Downloading selected file {}", name);
+        let progress = progress_tx.as_ref().map(|tx| (tx, offset, total_size));
+        run_get(db, &connection, *file_hash, progress, limiter).await?;
+        offset += size_of(file_hash);
+    }
+
+    let stats = Stats {
+        elapsed: t0.elapsed(),
+        ..Default::default()
+    };
+    Ok((stats, selected.len() as u64, total_size, Some(selected)))
+}
 
+/// Download the single file of `collection` and write it into the named pipe
+/// `fifo` as it arrives, see [`ReceiveArgs::fifo`].
+#[allow(clippy::too_many_arguments)]
+async fn download_streaming(
+    db: &Store,
+    connection: Connection,
+    collection: Collection,
+    hash_seq: &HashSeq,
+    sizes: &[u64],
+    fifo: &Path,
+    limiter: Option<&RateLimiter>,
+    progress_tx: &Option<ProgressSenderTx>,
+) -> anyhow::Result<(Stats, u64, u64, Option<Collection>)> {
+    let t0 = Instant::now();
+    let (name, file_hash) = collection.iter().next().context("collection is empty")?;
+    let file_hash = *file_hash;
+    let size = hash_seq
+        .iter()
+        .position(|h| h == file_hash)
+        .and_then(|idx| sizes.get(idx).copied())
+        .unwrap_or_default();
     if let Some(ref tx) = progress_tx {
         let _ = tx
-            .send(ProgressEvent::Download(DownloadProgress::Completed))
+            .send(ProgressEvent::Download(DownloadProgress::Metadata {
+                total_size: size,
+                file_count: 1,
+                names: vec![name.clone()],
+            }))
             .await;
     }
 
-    // Clean up temp directory
-    tokio::fs::remove_dir_all(iroh_data_dir).await?;
-
-    Ok(ReceiveResult {
-        collection,
-        total_files,
-        payload_size,
-        stats,
-    })
+    let complete = matches!(db.status(file_hash).await?, BlobStatus::Complete { .. });
+    let (verified_tx, verified_rx) = tokio::sync::watch::channel(if complete { size } else { 0 });
+    let download = async {
+        if complete {
+            return anyhow::Ok(Stats::default());
+        }
+        let get = db
+            .remote()
+            .execute_get(connection.clone(), GetRequest::blob(file_hash));
+        let mut stream = get.stream();
+        let mut paced = 0;
+        while let Some(item) = stream.next().await {
+            match item {
+                iroh_blobs::api::remote::GetProgressItem::Progress(offset) => {
+                    pace(limiter, &mut paced, offset).await;
+                    verified_tx.send_replace(offset);
+                    if let Some(ref tx) = progress_tx {
+                        let _ = tx
+                            .send(ProgressEvent::Download(DownloadProgress::Downloading {
+                                offset,
+                                total: size,
+                            }))
+                            .await;
+                    }
+                }
+                iroh_blobs::api::remote::GetProgressItem::Done(stats) => {
+                    verified_tx.send_replace(size);
+                    return Ok(stats);
+                }
+                iroh_blobs::api::remote::GetProgressItem::Error(cause) => {
+                    anyhow::bail!(show_get_error(cause));
+                }
+            }
+        }
+        anyhow::bail!("get stream ended without completing")
+    };
+    let write = async {
+        let mut pipe = export::open_fifo(fifo).await?;
+        export::stream_to_fifo(db, file_hash, size, &mut pipe, verified_rx)
+            .await
+            .map_err(|e| export::pipe_error(e, fifo))
+    };
+    let (mut stats, _) = tokio::try_join!(download, write)?;
+    stats.elapsed = t0.elapsed();
+    Ok((stats, 1, size, Some(collection)))
 }
 
 /// Show get error with context.
-fn show_get_error(e: GetError) -> GetError {
+pub(crate) fn show_get_error(e: GetError) -> GetError {
     match &e {
         GetError::InitialNext { .. } => {
             tracing::error!("initial connection error: {:?}", e);
@@ -319,3 +1411,690 @@ fn show_get_error(e: GetError) -> GetError {
     }
     e
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::LoopbackTransfer, RelayModeOption, SendArgs};
+
+    #[tokio::test]
+    async fn test_concurrent_receives_of_one_ticket() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data/a.txt", "first")?;
+        t.write("data/b.txt", "second file")?;
+        let sent = crate::send(t.send_args("data")).await?;
+
+        let export_dirs = [tempfile::tempdir()?, tempfile::tempdir()?];
+        let args = |export_dir: &Path| ReceiveArgs {
+            export_dir: Some(export_dir.to_path_buf()),
+            ..t.receive_args(sent.ticket.clone())
+        };
+        let (a, b) = tokio::join!(
+            receive(args(export_dirs[0].path())),
+            receive(args(export_dirs[1].path())),
+        );
+        assert_eq!(a?.total_files, 2);
+        assert_eq!(b?.total_files, 2);
+
+        for dir in &export_dirs {
+            let root = dir.path().join("data");
+            assert_eq!(std::fs::read_to_string(root.join("a.txt"))?, "first");
+            assert_eq!(std::fs::read_to_string(root.join("b.txt"))?, "second file");
+        }
+        let leftovers = std::fs::read_dir(t.recv_dir.path())?.count();
+        assert_eq!(leftovers, 0, "lock file or data directory left behind");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_lock() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data_dir = dir.path().join(".sendme-recv-test");
+        let lock_file = data_dir.with_extension("lock");
+
+        // A lock file left behind by a crashed receive is not locked.
+        std::fs::write(&lock_file, "")?;
+        drop(ReceiveLock::acquire(&data_dir).await?);
+        assert!(!lock_file.exists());
+
+        // A lock held through another open file, as by another process, is.
+        let other = std::fs::File::create(&lock_file)?;
+        assert!(other.try_lock_exclusive()?);
+        let err = ReceiveLock::acquire(&data_dir).await.err().unwrap();
+        assert!(err.to_string().contains("already receiving"), "{err}");
+        assert!(!ACTIVE_RECEIVES.lock().unwrap().contains_key(&data_dir));
+        drop(other);
+
+        let lock = ReceiveLock::acquire(&data_dir).await?;
+        assert!(ACTIVE_RECEIVES.lock().unwrap().contains_key(&data_dir));
+        drop(lock);
+        assert!(!ACTIVE_RECEIVES.lock().unwrap().contains_key(&data_dir));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_note() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data.bin", "payload")?;
+        let sent = crate::send(SendArgs {
+            attach_note: Some("licensed under CC0".to_string()),
+            ..t.send_args("data.bin")
+        })
+        .await?;
+        let names = sent.collection.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![crate::import::NOTE_NAME, "data.bin"]);
+        assert_eq!(sent.total_size, 7 + 18);
+
+        let received = receive(t.receive_args(sent.ticket)).await?;
+        assert_eq!(received.total_files, 2);
+        let note = t.export_dir.path().join(crate::import::NOTE_NAME);
+        assert_eq!(std::fs::read_to_string(note)?, "licensed under CC0");
+        assert_eq!(std::fs::read_to_string(t.export_dir.path().join("data.bin"))?, "payload");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_multiple_paths() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("first/notes.txt", "first")?;
+        t.write("second/notes.txt", "second")?;
+        let sent = crate::send(SendArgs {
+            paths: vec![t.source.path().join("second/notes.txt")],
+            double_read_verify: true,
+            ..t.send_args("first/notes.txt")
+        })
+        .await?;
+        let names = sent.collection.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["2-notes.txt", "notes.txt"]);
+
+        let received = receive(t.receive_args(sent.ticket)).await?;
+        assert_eq!(received.total_files, 2);
+        let read = |name: &str| std::fs::read_to_string(t.export_dir.path().join(name));
+        assert_eq!(read("notes.txt")?, "first");
+        assert_eq!(read("2-notes.txt")?, "second");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_resume_store_restarts() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("data.bin", "fresh data").await?;
+
+        // leave a store behind as an interrupted receive would, then corrupt
+        // its files
+        let data_dir = t.recv_dir.path().join(format!(".sendme-recv-{}", sent.hash.to_hex()));
+        let db = FsStore::load(&data_dir).await?;
+        db.add_bytes(b"unrelated".to_vec()).await?;
+        db.shutdown().await?;
+        for entry in std::fs::read_dir(&data_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                std::fs::write(entry.path(), b"garbage")?;
+            }
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let restarted = tokio::spawn(async move {
+            let mut restarted = false;
+            while let Some(event) = rx.recv().await {
+                restarted |= matches!(
+                    event,
+                    ProgressEvent::Download(DownloadProgress::Restarted { .. })
+                );
+            }
+            restarted
+        });
+        let received = receive_with_progress(t.receive_args(sent.ticket), tx).await?;
+        assert_eq!(received.total_files, 1);
+        assert!(restarted.await?);
+        let data = std::fs::read_to_string(t.export_dir.path().join("data.bin"))?;
+        assert_eq!(data, "fresh data");
+        assert!(!data_dir.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_with_existing() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        let unchanged = vec![7u8; 256 * 1024];
+        t.write("data/big.bin", &unchanged)?;
+        t.write("data/new.txt", "new file")?;
+        t.write("data/edited.txt", "edited")?;
+
+        // an earlier receive left these behind
+        let existing = t.export_dir.path().join("data");
+        std::fs::create_dir(&existing)?;
+        std::fs::write(existing.join("big.bin"), &unchanged)?;
+        std::fs::write(existing.join("edited.txt"), "unedit")?;
+
+        let sent = crate::send(t.send_args("data")).await?;
+        let received = receive(ReceiveArgs {
+            merge_with_existing: true,
+            ..t.receive_args(sent.ticket)
+        })
+        .await?;
+        assert_eq!(received.total_files, 3);
+        // only the metadata and the small files were transferred
+        let read = received.stats.total_bytes_read();
+        assert!(read < unchanged.len() as u64, "read {read} bytes");
+        assert_eq!(std::fs::read(existing.join("big.bin"))?, unchanged);
+        assert_eq!(std::fs::read_to_string(existing.join("new.txt"))?, "new file");
+        assert_eq!(std::fs::read_to_string(existing.join("edited.txt"))?, "edited");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_after_dropped_connection() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data.bin", vec![3u8; 64 * 1024])?;
+
+        // drop the first connection, as a flaky network would
+        let attempts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let on_connect = crate::OnConnect::new(move |_| {
+            match counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => crate::ConnectDecision::Reject,
+                _ => crate::ConnectDecision::Accept,
+            }
+        });
+        let sent = crate::send(SendArgs {
+            on_connect: Some(on_connect),
+            ..t.send_args("data.bin")
+        })
+        .await?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let events = tokio::spawn(async move {
+            let (mut stalled, mut resumed) = (false, false);
+            while let Some(event) = rx.recv().await {
+                match event {
+                    ProgressEvent::Download(DownloadProgress::Stalled { attempt: 1, .. }) => {
+                        stalled = true
+                    }
+                    ProgressEvent::Download(DownloadProgress::Resumed { attempt: 1 }) => {
+                        resumed = true
+                    }
+                    _ => {}
+                }
+            }
+            (stalled, resumed)
+        });
+        let received = receive_with_progress(
+            ReceiveArgs {
+                stream_reconnects: 2,
+                ..t.receive_args(sent.ticket)
+            },
+            tx,
+        )
+        .await?;
+        assert_eq!(received.total_files, 1);
+        assert_eq!(events.await?, (true, true));
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 2);
+        let data = std::fs::read(t.export_dir.path().join("data.bin"))?;
+        assert_eq!(data, vec![3u8; 64 * 1024]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_download() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        for i in 0..8u8 {
+            t.write(&format!("data/{i}.bin"), vec![i; 1024 * 1024])?;
+        }
+        let total = 8 * 1024 * 1024;
+        let sent = crate::send(t.send_args("data")).await?;
+
+        // Stop reading progress halfway, which holds the download there until
+        // the sender drops the connection
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let args = t.receive_args(sent.ticket.clone());
+        let interrupted = tokio::spawn(receive_with_progress(args, tx));
+        while let Some(event) = rx.recv().await {
+            if let ProgressEvent::Download(DownloadProgress::Downloading { offset, .. }) = event {
+                if offset >= total / 2 {
+                    break;
+                }
+            }
+        }
+        for conn in sent.handle.connections() {
+            sent.handle.disconnect(conn.connection_id);
+        }
+        drop(rx);
+        assert!(interrupted.await?.is_err());
+        let data_dir = t.recv_dir.path().join(format!(".sendme-recv-{}", sent.hash.to_hex()));
+        assert!(data_dir.exists());
+
+        let received = receive(t.receive_args(sent.ticket)).await?;
+        assert_eq!(received.total_files, 8);
+        assert!(received.stats.total_bytes_read() < total);
+        for i in 0..8u8 {
+            let file = std::fs::read(t.export_dir.path().join(format!("data/{i}.bin")))?;
+            assert_eq!(file, vec![i; 1024 * 1024]);
+        }
+        assert!(!data_dir.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stalled_sender_times_out() -> anyhow::Result<()> {
+        let recv_dir = tempfile::tempdir()?;
+        // a sender that accepts the connection, but never answers a request
+        let sender = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .alpns(vec![iroh_blobs::ALPN.to_vec()])
+            .bind()
+            .await?;
+        let ticket = BlobTicket::new(sender.addr(), Hash::new("stalled"), BlobFormat::HashSeq);
+        let accept = sender.clone();
+        let _stalled = tokio::spawn(async move {
+            let _connection = accept.accept().await.context("closed")?.accept()?.await?;
+            std::future::pending::<anyhow::Result<()>>().await
+        });
+
+        let receive = receive(ReceiveArgs {
+            common: CommonConfig {
+                relay: RelayModeOption::Disabled,
+                temp_dir: Some(recv_dir.path().to_path_buf()),
+                timeout: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+            stream_reconnects: 0,
+            ..ReceiveArgs::new(ticket)
+        });
+        let Err(err) = tokio::time::timeout(Duration::from_secs(10), receive).await? else {
+            anyhow::bail!("receiving from a stalled sender succeeded");
+        };
+        assert!(
+            matches!(err.downcast_ref(), Some(ReceiveError::TimedOut { .. })),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefer_fastest_relay() -> anyhow::Result<()> {
+        let fast: RelayUrl = "https://fast.relay.example".parse()?;
+        let slow: RelayUrl = "https://slow.relay.example".parse()?;
+        let direct = TransportAddr::Ip("192.168.1.2:1234".parse()?);
+        let addr = EndpointAddr {
+            id: iroh::SecretKey::generate(&mut rand::rng()).public(),
+            addrs: [
+                direct.clone(),
+                TransportAddr::Relay(slow.clone()),
+                TransportAddr::Relay(fast.clone()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        // mock relays that answer after a delay depending on their name
+        let mock = |url: RelayUrl| async move {
+            let delay = if url.as_str().contains("fast") { 10 } else { 200 };
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            anyhow::Ok(Duration::from_millis(delay))
+        };
+
+        let mut chosen = addr.clone();
+        prefer_fastest_relay(&mut chosen, mock).await;
+        assert_eq!(chosen.relay_urls().collect::<Vec<_>>(), [&fast]);
+        assert!(chosen.addrs.contains(&direct));
+
+        // if no relay answers, all of them are kept
+        let mut unchanged = addr.clone();
+        let unreachable = |_| async { Err::<Duration, _>(anyhow::anyhow!("unreachable")) };
+        prefer_fastest_relay(&mut unchanged, unreachable).await;
+        assert_eq!(unchanged, addr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mem_store_backend() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data.bin", "kept in memory")?;
+        let mut send_args = t.send_args("data.bin");
+        send_args.common.store_backend = StoreBackend::Mem;
+        let sent = crate::send(send_args).await?;
+        let mut args = t.receive_args(sent.ticket);
+        args.common.store_backend = StoreBackend::Mem;
+        let received = receive(args).await?;
+
+        assert_eq!(received.total_files, 1);
+        let data = std::fs::read_to_string(t.export_dir.path().join("data.bin"))?;
+        assert_eq!(data, "kept in memory");
+        // neither side created a data directory
+        assert_eq!(std::fs::read_dir(t.send_dir.path())?.count(), 0);
+        assert_eq!(std::fs::read_dir(t.recv_dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_many_in_parallel() -> anyhow::Result<()> {
+        let recv_dir = tempfile::tempdir()?;
+
+        let mut transfers = Vec::new();
+        let mut args = Vec::new();
+        for i in 0..4 {
+            let t = LoopbackTransfer::new()?;
+            let name = format!("file-{i}.bin");
+            let sent = t.send(&name, vec![i as u8; 64 * 1024]).await?;
+            // all tickets are received into the same directory
+            args.push(ReceiveArgs {
+                common: CommonConfig::loopback(recv_dir.path()),
+                export_dir: Some(recv_dir.path().to_path_buf()),
+                ..t.receive_args(sent.ticket)
+            });
+            transfers.push(t);
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let states = tokio::spawn(async move {
+            let (mut active, mut max_active, mut done) = (0, 0, 0);
+            while let Some((_, state)) = rx.recv().await {
+                match state {
+                    TicketState::Queued => {}
+                    TicketState::Active => active += 1,
+                    TicketState::Done => {
+                        active -= 1;
+                        done += 1;
+                    }
+                }
+                max_active = max_active.max(active);
+            }
+            (max_active, done)
+        });
+        let results = receive_many(args, 2, Some(tx)).await;
+        let (max_active, done) = states.await?;
+        assert!(max_active <= 2, "{max_active} tickets were active at once");
+        assert_eq!(done, 4);
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result?.total_files, 1);
+            let data = std::fs::read(recv_dir.path().join(format!("file-{i}.bin")))?;
+            assert_eq!(data, vec![i as u8; 64 * 1024]);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prewarm() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data.bin", "warm")?;
+        let sent = crate::send(SendArgs {
+            prewarm: true,
+            ..t.send_args("data.bin")
+        })
+        .await?;
+        assert!(!sent.ticket.addr().addrs.is_empty());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let events = tokio::spawn(async move {
+            let (mut ready, mut connecting) = (None, false);
+            while let Some(event) = rx.recv().await {
+                match event {
+                    ProgressEvent::Download(DownloadProgress::EndpointReady { online }) => {
+                        ready = Some((online, connecting))
+                    }
+                    ProgressEvent::Download(DownloadProgress::Connecting) => connecting = true,
+                    _ => {}
+                }
+            }
+            ready
+        });
+        let received = receive_with_progress(
+            ReceiveArgs {
+                prewarm: true,
+                ..t.receive_args(sent.ticket)
+            },
+            tx,
+        )
+        .await?;
+        let (online, connecting) = events.await?.expect("no EndpointReady event");
+        assert!(online);
+        // the endpoint is ready before connecting
+        assert!(!connecting, "EndpointReady came after Connecting");
+        assert!(!received.phases.prewarm.is_zero());
+        assert_eq!(std::fs::read_to_string(t.export_dir.path().join("data.bin"))?, "warm");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_receive_into_fifo() -> anyhow::Result<()> {
+        use std::{io::Read, os::unix::fs::FileTypeExt};
+
+        let t = LoopbackTransfer::new()?;
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mkfifo = |path: &Path| {
+            let status = std::process::Command::new("mkfifo").arg(path).status()?;
+            anyhow::ensure!(status.success(), "mkfifo failed");
+            anyhow::Ok(())
+        };
+
+        let sent = t.send("movie.bin", &data).await?;
+        let args = |fifo: Option<PathBuf>| ReceiveArgs {
+            fifo,
+            ..t.receive_args(sent.ticket.clone())
+        };
+
+        // a pipe at the export path is detected and read while downloading
+        let target = t.export_dir.path().join("movie.bin");
+        mkfifo(&target)?;
+        let reader = {
+            let target = target.clone();
+            std::thread::spawn(move || std::fs::read(target))
+        };
+        let received = receive(args(None)).await?;
+        assert_eq!(received.total_files, 1);
+        assert_eq!(reader.join().unwrap()?, data);
+        assert!(std::fs::metadata(&target)?.file_type().is_fifo());
+
+        // a reader that goes away early fails the receive cleanly
+        let pipe = t.export_dir.path().join("player");
+        mkfifo(&pipe)?;
+        let reader = {
+            let pipe = pipe.clone();
+            std::thread::spawn(move || {
+                let mut head = [0u8; 16];
+                std::fs::File::open(pipe)?.read_exact(&mut head)?;
+                std::io::Result::Ok(head)
+            })
+        };
+        let err = receive(args(Some(pipe))).await.unwrap_err();
+        assert!(err.to_string().contains("closed"), "{err:#}");
+        assert_eq!(reader.join().unwrap()?, data[..16]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_storage_unavailable() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = tempfile::tempdir()?;
+        let read_only = base.path().join("read-only");
+        std::fs::create_dir(&read_only)?;
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555))?;
+        if std::fs::write(read_only.join("probe"), "x").is_ok() {
+            // permissions are not enforced, e.g. when running as root
+            return Ok(());
+        }
+
+        let addr = EndpointAddr::new(iroh::SecretKey::generate(&mut rand::rng()).public());
+        let ticket = BlobTicket::new(addr, Hash::new("storage"), BlobFormat::HashSeq);
+        let err = receive(ReceiveArgs {
+            common: CommonConfig {
+                relay: RelayModeOption::Disabled,
+                temp_dir: Some(read_only.clone()),
+                ..Default::default()
+            },
+            stream_reconnects: 0,
+            ..ReceiveArgs::new(ticket)
+        })
+        .await
+        .unwrap_err();
+
+        let Some(ReceiveError::StorageUnavailable { path, reason, .. }) = err.downcast_ref() else {
+            panic!("unexpected error: {err:#}");
+        };
+        assert!(path.starts_with(read_only.canonicalize()?), "{}", path.display());
+        assert!(reason.contains("ermission denied"), "{reason}");
+        assert!(err.to_string().contains(".sendme-recv-"), "{err}");
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_extract() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        use zip::write::SimpleFileOptions;
+
+        let t = LoopbackTransfer::new()?;
+        let path = t.source.path().join("tree.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path)?);
+        for (name, data) in [("a.txt", "a"), ("sub/b.txt", "b")] {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(data.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let sent = crate::send(t.send_args("tree.zip")).await?;
+        receive(ReceiveArgs {
+            auto_extract: true,
+            ..t.receive_args(sent.ticket)
+        })
+        .await?;
+
+        let root = t.export_dir.path();
+        assert_eq!(std::fs::read_to_string(root.join("a.txt"))?, "a");
+        assert_eq!(std::fs::read_to_string(root.join("sub/b.txt"))?, "b");
+        assert!(!root.join("tree.zip").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receive_from_node_addr() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("known.txt", "known out of band").await?;
+
+        let addr = sent.ticket.addr().clone();
+        assert!(ReceiveArgs::from_node_addr(addr.clone(), sent.hash, BlobFormat::Raw).is_err());
+        let mut args = ReceiveArgs::from_node_addr(addr, sent.hash, BlobFormat::HashSeq)?;
+        args.common = CommonConfig::loopback(t.recv_dir.path());
+        args.export_dir = Some(t.export_dir.path().to_path_buf());
+        let received = receive(args).await?;
+        assert_eq!(received.total_files, 1);
+        let exported = std::fs::read_to_string(t.export_dir.path().join("known.txt"))?;
+        assert_eq!(exported, "known out of band");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_download_rate() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        let size = 384 * 1024;
+        let max_rate = 128 * 1024;
+        let sent = t.send("data.bin", vec![7u8; size]).await?;
+
+        let mut args = t.receive_args(sent.ticket.clone());
+        args.common.max_download_bps = Some(max_rate);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let throttled = tokio::spawn(async move {
+            let mut throttled = false;
+            while let Some(event) = rx.recv().await {
+                throttled |= matches!(
+                    event,
+                    ProgressEvent::Download(DownloadProgress::Throttled { bytes_per_sec })
+                        if bytes_per_sec == max_rate
+                );
+            }
+            throttled
+        });
+        let t0 = Instant::now();
+        let received = receive_with_progress(args, tx).await?;
+        let elapsed = t0.elapsed();
+        assert_eq!(received.payload_size, size as u64);
+        assert!(throttled.await?);
+        // at most a second's worth of bytes can come in a burst
+        let min = crate::throttle::transfer_time(size as u64, max_rate) - Duration::from_secs(1);
+        assert!(elapsed >= min, "took {elapsed:?}");
+
+        let mut args = t.receive_args(sent.ticket);
+        args.common.max_download_bps = Some(0);
+        let err = receive(args).await.unwrap_err();
+        assert!(err.to_string().contains("must not be zero"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verifying_progress() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data/a.bin", vec![1u8; 1000])?;
+        t.write("data/b.bin", vec![2u8; 3000])?;
+        let sent = crate::send(t.send_args("data")).await?;
+
+        let receive = |export_dir: PathBuf| {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+            let verified = tokio::spawn(async move {
+                let mut verified = Vec::new();
+                while let Some(event) = rx.recv().await {
+                    if let ProgressEvent::Download(DownloadProgress::Verifying { offset, total }) =
+                        event
+                    {
+                        verified.push((offset, total));
+                    }
+                }
+                verified
+            });
+            let args = ReceiveArgs {
+                export_dir: Some(export_dir),
+                ..t.receive_args(sent.ticket.clone())
+            };
+            (receive_with_progress(args, tx), verified)
+        };
+
+        // the export fails, so the downloaded data stays in the store
+        let blocked = t.export_dir.path().join("blocked");
+        std::fs::write(&blocked, "a file, not a directory")?;
+        let (received, verified) = receive(blocked);
+        assert!(received.await.is_err());
+        let verified = verified.await?;
+        assert_eq!(verified.len(), 3);
+        assert_eq!(verified.first(), Some(&(0, 4000)));
+        assert_eq!(verified.last(), Some(&(4000, 4000)));
+
+        // the data is there already, so there is nothing to verify
+        let (received, verified) = receive(t.export_dir.path().to_path_buf());
+        assert_eq!(received.await?.total_files, 2);
+        assert!(verified.await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_manifest() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("data/a.txt", "first")?;
+        t.write("data/sub/b.txt", "second file")?;
+        let sent = crate::send(t.send_args("data")).await?;
+        let received = receive(ReceiveArgs {
+            write_manifest: true,
+            ..t.receive_args(sent.ticket)
+        })
+        .await?;
+
+        let manifest = std::fs::read_to_string(t.export_dir.path().join(export::MANIFEST))?;
+        let expected: String = received
+            .collection
+            .iter()
+            .map(|(name, hash)| format!("{}  {}\n", hash.to_hex(), name))
+            .collect();
+        assert_eq!(manifest, expected);
+        assert_eq!(manifest.lines().count(), 2);
+        let hash_of = |name: &str| {
+            let (_, hash) = received.collection.iter().find(|(n, _)| n == name).unwrap();
+            hash.to_hex().to_string()
+        };
+        assert!(manifest.contains(&format!("{}  data/sub/b.txt\n", hash_of("data/sub/b.txt"))));
+        let first = blake3::hash(b"first").to_hex().to_string();
+        assert_eq!(hash_of("data/a.txt"), first);
+        Ok(())
+    }
+}
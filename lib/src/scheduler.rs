@@ -0,0 +1,138 @@
+//! Sharing the import/export worker budget between concurrent transfers.
+//!
+//! Every file that is imported or exported holds a permit from a process-wide
+//! [`WorkerPool`] with `num_cpus` permits. When permits are scarce, waiters
+//! with a higher [`Priority`] are served first, so a large low-priority send
+//! can't starve interactive transfers.
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{LazyLock, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+use crate::types::Priority;
+
+static GLOBAL: LazyLock<WorkerPool> = LazyLock::new(|| WorkerPool::new(num_cpus::get()));
+
+/// The pool shared by all transfers in this process.
+pub fn global() -> &'static WorkerPool {
+    &GLOBAL
+}
+
+/// A fixed number of worker permits, handed out by priority.
+#[derive(Debug)]
+pub struct WorkerPool {
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    available: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    tx: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // highest priority first, then first come first served
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl WorkerPool {
+    /// Create a pool with `size` permits. A size of zero is treated as one.
+    pub fn new(size: usize) -> Self {
+        Self {
+            state: Mutex::new(PoolState {
+                available: size.max(1),
+                next_seq: 0,
+                waiters: BinaryHeap::new(),
+            }),
+        }
+    }
+
+    /// Wait for a permit. The permit is returned to the pool when dropped.
+    pub async fn acquire(&self, priority: Priority) -> WorkerPermit<'_> {
+        let mut pending = {
+            let mut state = self.state.lock().unwrap();
+            if state.available > 0 && state.waiters.is_empty() {
+                state.available -= 1;
+                return WorkerPermit { pool: self };
+            }
+            let (tx, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter { priority, seq, tx });
+            Pending { pool: self, rx }
+        };
+        // the sender is only dropped after a hand-off, so this can't fail
+        (&mut pending.rx).await.ok();
+        WorkerPermit { pool: self }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        // hand the permit directly to the best waiter that is still waiting
+        while let Some(waiter) = state.waiters.pop() {
+            if waiter.tx.send(()).is_ok() {
+                return;
+            }
+        }
+        state.available += 1;
+    }
+}
+
+/// A queued [`WorkerPool::acquire`] call.
+///
+/// If the call is cancelled after a permit was handed to it, the permit is
+/// passed on instead of being lost.
+struct Pending<'a> {
+    pool: &'a WorkerPool,
+    rx: oneshot::Receiver<()>,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if self.rx.try_recv().is_ok() {
+            self.pool.release();
+        }
+    }
+}
+
+/// A permit to run one import or export job. See [`WorkerPool::acquire`].
+#[derive(Debug)]
+pub struct WorkerPermit<'a> {
+    pool: &'a WorkerPool,
+}
+
+impl Drop for WorkerPermit<'_> {
+    fn drop(&mut self) {
+        self.pool.release();
+    }
+}
@@ -1,25 +1,54 @@
 //! Send functionality - hosting files for transfer.
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant, SystemTime},
 };
 
-use iroh::{discovery::pkarr::PkarrPublisher, Endpoint, RelayMode};
+use iroh::{discovery::pkarr::PkarrPublisher, Endpoint, EndpointAddr, RelayMode, Watcher};
 use iroh_blobs::{
-    provider::events::{ConnectMode, EventMask, EventSender, ProviderMessage, RequestMode},
-    store::fs::FsStore,
-    BlobFormat, BlobsProtocol,
+    provider::events::{
+        AbortReason, ConnectMode, EventMask, EventSender, ProviderMessage, RequestMode,
+        ThrottleMode,
+    },
+    api::Store,
+    hashseq::HashSeq,
+    BlobFormat, BlobsProtocol, Hash,
 };
 
 use n0_future::StreamExt;
 use tokio::select;
+use tokio_util::sync::CancellationToken;
 
-use crate::{apply_options, get_or_create_secret, progress::*, types::*, SendArgs, SendResult};
+use crate::{
+    apply_options, get_or_create_secret,
+    import::{ImportEntry, ImportFilter, ImportOptions},
+    progress::*,
+    throttle::RateLimiter,
+    types::*,
+    BlobStore, SendArgs, SendResult,
+};
 
 use rand::Rng;
 
+/// How long to wait for the endpoint to have an address before reporting
+/// [`ConnectionStatus::ServerReady`] as unreachable.
+const SERVER_READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The error of a send that was cancelled while it imported the data, see
+/// [`SendArgs::cancel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled during import")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
 /// Send a file or directory.
 ///
 /// This function creates a temporary iroh node that serves the content in the
@@ -28,7 +57,7 @@ use rand::Rng;
 /// The provider will run until it is terminated. On termination, it will delete
 /// the temporary directory.
 pub async fn send(args: SendArgs) -> anyhow::Result<SendResult> {
-    send_internal(args, None).await
+    send_internal(args, None, None).await
 }
 
 /// Send a file or directory with progress reporting.
@@ -36,41 +65,210 @@ pub async fn send_with_progress(
     args: SendArgs,
     progress_tx: ProgressSenderTx,
 ) -> anyhow::Result<SendResult> {
-    send_internal(args, Some(progress_tx)).await
+    send_internal(args, None, Some(progress_tx)).await
+}
+
+/// Send a file or directory from a persistent store in `store_dir`.
+///
+/// Unlike [`send`], the files are referenced in place rather than copied
+/// where possible, and the store is kept when the provider terminates. Serving
+/// the same unchanged path from the same store again skips the import, so it
+/// starts right away even for large data sets.
+///
+/// The files must not be modified while they are served. A changed file can't
+/// be served anymore, and is only picked up by importing it again.
+///
+/// Only a single path can be served this way, so [`SendArgs::paths`] must be
+/// empty.
+pub async fn send_serve_dir(
+    store_dir: std::path::PathBuf,
+    args: SendArgs,
+) -> anyhow::Result<SendResult> {
+    send_internal(args, Some(store_dir), None).await
+}
+
+/// Send a file or directory from a persistent store, with progress reporting.
+/// See [`send_serve_dir`].
+pub async fn send_serve_dir_with_progress(
+    store_dir: std::path::PathBuf,
+    args: SendArgs,
+    progress_tx: ProgressSenderTx,
+) -> anyhow::Result<SendResult> {
+    send_internal(args, Some(store_dir), Some(progress_tx)).await
+}
+
+/// Send several files as a single collection.
+pub async fn send_many(args: SendManyArgs) -> anyhow::Result<SendResult> {
+    let source = Source::Entries {
+        entries: args.entries,
+        root: args.root,
+    };
+    let options = SendOptions::new(args.ticket_type, args.common, args.on_connect);
+    send_source(source, options, None).await
+}
+
+/// Send several files as a single collection, with progress reporting.
+///
+/// This is used for files picked together on mobile platforms, which don't
+/// share a parent directory and may not be accessible by path.
+pub async fn send_many_with_progress(
+    args: SendManyArgs,
+    progress_tx: ProgressSenderTx,
+) -> anyhow::Result<SendResult> {
+    let source = Source::Entries {
+        entries: args.entries,
+        root: args.root,
+    };
+    let options = SendOptions::new(args.ticket_type, args.common, args.on_connect);
+    send_source(source, options, Some(progress_tx)).await
+}
+
+/// Send `data` as a single file named `name`, e.g. a generated report, without
+/// writing it to a file first.
+pub async fn send_bytes(
+    name: String,
+    data: Vec<u8>,
+    args: SendBytesArgs,
+) -> anyhow::Result<SendResult> {
+    send_bytes_internal(name, data, args, None).await
+}
+
+/// Send in-memory data with progress reporting, see [`send_bytes`].
+pub async fn send_bytes_with_progress(
+    name: String,
+    data: Vec<u8>,
+    args: SendBytesArgs,
+    progress_tx: ProgressSenderTx,
+) -> anyhow::Result<SendResult> {
+    send_bytes_internal(name, data, args, Some(progress_tx)).await
+}
+
+async fn send_bytes_internal(
+    name: String,
+    data: Vec<u8>,
+    args: SendBytesArgs,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<SendResult> {
+    let options = SendOptions::new(args.ticket_type, args.common, args.on_connect);
+    send_source(Source::Bytes { name, data }, options, progress_tx).await
+}
+
+/// What to import before serving.
+enum Source {
+    Path {
+        paths: Vec<std::path::PathBuf>,
+        unwrap_single_file: bool,
+        /// A persistent store to serve from, see [`send_serve_dir`].
+        store_dir: Option<std::path::PathBuf>,
+        double_read_verify: bool,
+        import: ImportOptions,
+    },
+    Entries {
+        entries: Vec<ImportEntry>,
+        root: Option<String>,
+    },
+    /// A single file that is already in memory, see [`send_bytes`].
+    Bytes { name: String, data: Vec<u8> },
 }
 
 async fn send_internal(
     args: SendArgs,
+    store_dir: Option<std::path::PathBuf>,
     progress_tx: Option<ProgressSenderTx>,
 ) -> anyhow::Result<SendResult> {
-    let secret_key = get_or_create_secret(args.common.show_secret)?;
-    let relay_mode: RelayMode = args.common.relay.into();
+    anyhow::ensure!(
+        store_dir.is_none() || args.paths.is_empty(),
+        "a store directory can only serve a single path"
+    );
+    let mut paths = vec![args.path];
+    paths.extend(args.paths);
+    let source = Source::Path {
+        paths,
+        unwrap_single_file: args.unwrap_single_file,
+        store_dir,
+        double_read_verify: args.double_read_verify,
+        import: ImportOptions {
+            reimport_changed: args.reimport_changed,
+            non_utf8_names: args.non_utf8_names,
+            filter: ImportFilter {
+                gitignore: args.respect_gitignore,
+                exclude: args.exclude,
+                follow_symlinks: args.follow_symlinks,
+            },
+            record_metadata: args.send_metadata,
+            ..ImportOptions::from_common(&args.common)
+        },
+    };
+    let options = SendOptions {
+        note: args.attach_note,
+        shortlink_service: args.shortlink_service,
+        cancel: args.cancel,
+        prewarm: args.prewarm,
+        strict_ticket_type: args.strict_ticket_type,
+        ..SendOptions::new(args.ticket_type, args.common, args.on_connect)
+    };
+    send_source(source, options, progress_tx).await
+}
 
-    let mut builder = Endpoint::builder()
-        .alpns(vec![iroh_blobs::protocol::ALPN.to_vec()])
-        .secret_key(secret_key)
-        .relay_mode(relay_mode.clone());
+/// How [`send_source`] serves its source: the options of [`SendArgs`] that
+/// don't concern the import.
+struct SendOptions {
+    ticket_type: AddrInfoOptions,
+    common: CommonConfig,
+    on_connect: Option<OnConnect>,
+    note: Option<String>,
+    shortlink_service: Option<String>,
+    cancel: Option<CancellationToken>,
+    prewarm: bool,
+    strict_ticket_type: bool,
+}
 
-    if args.ticket_type == AddrInfoOptions::Id {
-        builder = builder.discovery(PkarrPublisher::n0_dns());
+impl SendOptions {
+    /// The given options, and the defaults of [`SendArgs`] for all others.
+    fn new(
+        ticket_type: AddrInfoOptions,
+        common: CommonConfig,
+        on_connect: Option<OnConnect>,
+    ) -> Self {
+        Self {
+            ticket_type,
+            common,
+            on_connect,
+            note: None,
+            shortlink_service: None,
+            cancel: None,
+            prewarm: false,
+            strict_ticket_type: false,
+        }
     }
+}
 
-    if let Some(addr) = args.common.magic_ipv4_addr {
-        builder = builder.bind_addr_v4(addr);
-    }
-    if let Some(addr) = args.common.magic_ipv6_addr {
-        builder = builder.bind_addr_v6(addr);
-    }
+async fn send_source(
+    source: Source,
+    options: SendOptions,
+    progress_tx: Option<ProgressSenderTx>,
+) -> anyhow::Result<SendResult> {
+    let SendOptions {
+        ticket_type,
+        common,
+        on_connect,
+        note,
+        shortlink_service,
+        cancel,
+        prewarm,
+        strict_ticket_type,
+    } = options;
+    anyhow::ensure!(
+        common.max_upload_bps != Some(0),
+        "the upload rate must not be zero"
+    );
+    let builder = endpoint_builder(&common, ticket_type)?;
 
     // Create temporary directory for blob storage
     let suffix = rand::rng().random::<[u8; 16]>();
 
     // Use custom temp_dir if provided (required for macOS sandbox), otherwise use cwd
-    let temp_dir_for_borrow = args
-        .common
-        .temp_dir
-        .as_ref()
-        .map(std::path::PathBuf::as_path);
+    let temp_dir_for_borrow = common.temp_dir.as_ref().map(std::path::PathBuf::as_path);
     let base_dir = match temp_dir_for_borrow {
         Some(path) => path,
         None => {
@@ -79,80 +277,190 @@ async fn send_internal(
         }
     };
 
-    let blobs_data_dir = base_dir.join(format!(
-        ".sendme-send-{}",
-        data_encoding::HEXLOWER.encode(&suffix)
-    ));
-
-    if blobs_data_dir.exists() {
-        anyhow::bail!(
-            "can not share twice from the same directory: {}",
-            base_dir.display()
-        );
-    }
+    let persistent_dir = match &source {
+        Source::Path { store_dir, .. } => store_dir.clone(),
+        Source::Entries { .. } | Source::Bytes { .. } => None,
+    };
+    anyhow::ensure!(
+        persistent_dir.is_none() || common.store_backend == StoreBackend::Fs,
+        "serving from a persistent store needs the fs store backend"
+    );
+    let persistent = persistent_dir.is_some();
+    // An in-memory store has no data directory
+    let blobs_data_dir = match (persistent_dir, common.store_backend) {
+        (Some(dir), _) => Some(dir),
+        (None, StoreBackend::Mem) => None,
+        (None, StoreBackend::Fs) => {
+            let suffix = data_encoding::HEXLOWER.encode(&suffix);
+            let dir = base_dir.join(common.temp_name("send", &suffix));
+            if dir.exists() {
+                anyhow::bail!(
+                    "can not share twice from the same directory: {}",
+                    base_dir.display()
+                );
+            }
+            Some(dir)
+        }
+    };
 
     // Check if trying to share from current directory
-    if args.common.temp_dir.is_none() {
+    if let (None, Source::Path { paths, .. }) = (&common.temp_dir, &source) {
         let cwd = std::env::current_dir()?;
-        if cwd.join(&args.path) == cwd {
+        if paths.iter().any(|path| cwd.join(path) == cwd) {
             anyhow::bail!("can not share from the current directory");
         }
     }
 
-    let path = args.path;
     let blobs_data_dir2 = blobs_data_dir.clone();
-    let _ticket_type = args.ticket_type;
     let progress_tx2 = progress_tx.clone();
+    let priority = common.priority;
+    let batch_threshold = common.import_batch_threshold;
+    let bind_config = common.clone();
+    let cancel = cancel.unwrap_or_default();
 
     let setup = async move {
         let t0 = Instant::now();
-        tokio::fs::create_dir_all(&blobs_data_dir2).await?;
-
-        let endpoint = builder.bind().await?;
-        let store = FsStore::load(&blobs_data_dir2).await?;
-
-        let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(32);
-        let blobs = BlobsProtocol::new(
-            &store,
-            Some(EventSender::new(
-                event_tx,
-                EventMask {
-                    connected: ConnectMode::Notify,
-                    get: RequestMode::NotifyLog,
-                    ..EventMask::DEFAULT
-                },
-            )),
-        );
-
-        // Spawn progress handler if channel provided
-        if let Some(ref tx) = progress_tx2 {
-            tokio::task::spawn(handle_provider_progress(tx.clone(), event_rx));
-        } else {
-            // Still consume the events to prevent blocking
-            tokio::spawn(async move { while event_rx.recv().await.is_some() {} });
+        let mut phases = SendPhases::default();
+        if let Some(ref dir) = blobs_data_dir2 {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let endpoint = crate::bind_with_config(&bind_config, builder).await?;
+        phases.bind = t0.elapsed();
+        if prewarm {
+            let t_prewarm = Instant::now();
+            let online = crate::prewarm(&endpoint, &bind_config).await;
+            phases.prewarm = t_prewarm.elapsed();
+            if let Some(ref tx) = progress_tx2 {
+                let status = ConnectionStatus::EndpointReady { online };
+                let _ = tx.send(ProgressEvent::Connection(status)).await;
+            }
         }
+        let store = BlobStore::load(blobs_data_dir2.as_deref()).await?;
 
-        let import_result = crate::import::import(path, &store, progress_tx2).await?;
+        let (blobs, served, handle) =
+            provider(&store, on_connect, bind_config.max_upload_bps, progress_tx2.clone());
+        let t_import = Instant::now();
+        // Cancelling stops the import, and the store is shut down before it is removed
+        let import = async {
+            let mut import_result = match source {
+                Source::Path {
+                    paths,
+                    unwrap_single_file,
+                    store_dir,
+                    double_read_verify,
+                    import,
+                } => {
+                    let (hash, size, collection) = match store_dir {
+                        Some(store_dir) => {
+                            let (path, tx) = (paths[0].clone(), progress_tx2);
+                            crate::import::import_indexed(path, &store, &store_dir, &import, tx)
+                                .await?
+                        }
+                        None => {
+                            crate::import::import(paths.clone(), &store, &import, progress_tx2)
+                                .await?
+                        }
+                    };
+                    if double_read_verify {
+                        crate::import::verify_reads(&paths, &collection, &import).await?;
+                    }
+                    if unwrap_single_file {
+                        crate::import::unwrap_single_file(&store, hash, size, collection).await?
+                    } else {
+                        (hash, size, collection)
+                    }
+                }
+                Source::Entries { entries, root } => {
+                    let (tx, threshold) = (progress_tx2, batch_threshold);
+                    crate::import::import_many(entries, root, &store, priority, threshold, tx)
+                        .await?
+                }
+                Source::Bytes { name, data } => {
+                    crate::import::import_from_bytes(name, data, &store, progress_tx2).await?
+                }
+            };
+            if let Some(note) = note {
+                let (_, size, collection) = import_result;
+                import_result = crate::import::attach_note(&store, collection, size, note).await?;
+            }
+            // Nothing is served before the router is spawned below, so no events are missed.
+            let hashes = served_hashes(&store, import_result.0).await?;
+            *served.lock().unwrap() = hashes;
+            anyhow::Ok(import_result)
+        };
+        let import_result = select! {
+            result = import => result,
+            _ = cancel.cancelled() => Err(Cancelled.into()),
+            _ = tokio::signal::ctrl_c() => Err(Cancelled.into()),
+        };
+        let import_result = match import_result {
+            Ok(import_result) => import_result,
+            Err(e) => {
+                store.shutdown().await.ok();
+                return Err(e);
+            }
+        };
+        phases.import = t_import.elapsed();
+        crate::metrics::import_finished(phases.import);
         let dt = t0.elapsed();
 
         let router = iroh::protocol::Router::builder(endpoint)
             .accept(iroh_blobs::ALPN, blobs.clone())
             .spawn();
 
-        anyhow::Ok((router, import_result, dt))
+        anyhow::Ok((router, import_result, dt, phases, handle))
     };
 
-    let (router, (hash, size, collection), dt) = select! {
-        x = setup => x?,
-        _ = tokio::signal::ctrl_c() => {
-            std::process::exit(130);
+    let (router, (hash, size, collection), dt, phases, handle) = match setup.await {
+        Ok(setup) => setup,
+        Err(e) => {
+            // the data of a send that never started is of no use
+            if let Some(dir) = blobs_data_dir.filter(|_| !persistent) {
+                if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+                    tracing::warn!("failed to remove {}: {}", dir.display(), err);
+                }
+            }
+            if let (Some(tx), true) = (&progress_tx, e.is::<Cancelled>()) {
+                let event = ProgressEvent::Import("".to_string(), ImportProgress::Cancelled);
+                let _ = tx.send(event).await;
+            }
+            return Err(e);
         }
     };
 
     // Make a ticket
     let mut addr = router.endpoint().addr();
-    apply_options(&mut addr, args.ticket_type);
+    let ticket_type = if strict_ticket_type {
+        ticket_type
+    } else {
+        usable_ticket_type(ticket_type, &addr, direct_likely(router.endpoint()))
+    };
+    apply_options(&mut addr, ticket_type);
     let ticket = iroh_blobs::ticket::BlobTicket::new(addr, hash, BlobFormat::HashSeq);
+    // Before the router is kept alive below, so it is closed if this fails
+    let short_link = match shortlink_service {
+        #[cfg(feature = "url")]
+        Some(service) => Some(crate::shortlink::shorten(&service, &ticket).await?),
+        #[cfg(not(feature = "url"))]
+        Some(_) => anyhow::bail!("short links need sendme-lib with the url feature"),
+        None => None,
+    };
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx
+            .send(ProgressEvent::SendReady {
+                ticket: ticket.clone(),
+                hash,
+                total_size: size,
+            })
+            .await;
+    }
+    if let Some(ref tx) = progress_tx {
+        tokio::spawn(report_server_ready(router.endpoint().clone(), tx.clone()));
+    }
+
+    let bound_addrs = router.endpoint().bound_sockets();
 
     // Spawn a task to keep the router alive for connections
     tokio::spawn(async move {
@@ -166,15 +474,289 @@ async fn send_internal(
         total_size: size,
         import_duration: dt,
         ticket,
+        ticket_type,
+        phases,
+        bound_addrs,
+        handle,
+        short_link,
+    })
+}
+
+/// The ticket type to use instead of `requested`, so receivers can connect.
+///
+/// A ticket with only direct addresses is useless if hole punching fails, so
+/// relay URLs are added to it if a direct connection is not `direct_likely`.
+fn usable_ticket_type(
+    requested: AddrInfoOptions,
+    addr: &EndpointAddr,
+    direct_likely: bool,
+) -> AddrInfoOptions {
+    let has_relay = addr.relay_urls().next().is_some();
+    if requested == AddrInfoOptions::Addresses && !direct_likely && has_relay {
+        tracing::warn!("⚠️ Direct connections are unlikely, adding relays to the ticket");
+        AddrInfoOptions::RelayAndAddresses
+    } else {
+        requested
+    }
+}
+
+/// Whether receivers are likely to connect to `endpoint` directly, judging
+/// by its latest network report. Without a report that is assumed.
+fn direct_likely(endpoint: &Endpoint) -> bool {
+    let Some(report) = endpoint.net_report().get() else {
+        return true;
+    };
+    // behind a symmetric NAT, the port a receiver needs to punch is unknown
+    let symmetric = report.mapping_varies_by_dest_ipv4 == Some(true);
+    (report.udp_v4 && !symmetric) || report.udp_v6
+}
+
+/// The builder of an endpoint that serves blobs, for [`crate::bind_with_config`].
+pub(crate) fn endpoint_builder(
+    common: &CommonConfig,
+    ticket_type: AddrInfoOptions,
+) -> anyhow::Result<impl Fn() -> iroh::endpoint::Builder + Send + Sync + 'static> {
+    let secret_key = get_or_create_secret(common.show_secret)?;
+    let relay_mode: RelayMode = common.relay.clone().into();
+
+    Ok(move || {
+        let builder = Endpoint::builder()
+            .alpns(vec![iroh_blobs::protocol::ALPN.to_vec()])
+            .secret_key(secret_key.clone())
+            .relay_mode(relay_mode.clone());
+        if ticket_type == AddrInfoOptions::Id {
+            builder.discovery(PkarrPublisher::n0_dns())
+        } else {
+            builder
+        }
     })
 }
 
+/// The blobs protocol for serving `store`, with its events handled by
+/// [`handle_provider_progress`].
+///
+/// Only the hashes in the returned set are served. It starts out empty.
+pub(crate) fn provider(
+    store: &Store,
+    on_connect: Option<OnConnect>,
+    max_upload_bps: Option<u64>,
+    progress_tx: Option<ProgressSenderTx>,
+) -> (BlobsProtocol, Arc<Mutex<HashSet<Hash>>>, SendHandle) {
+    let (event_tx, event_rx) = tokio::sync::mpsc::channel(32);
+    let connected = if on_connect.is_some() {
+        ConnectMode::Intercept
+    } else {
+        ConnectMode::Notify
+    };
+    let blobs = BlobsProtocol::new(
+        store,
+        Some(EventSender::new(
+            event_tx,
+            EventMask {
+                connected,
+                get: RequestMode::InterceptLog,
+                // lets SendHandle::disconnect abort running requests, and
+                // paces the data for CommonConfig::max_upload_bps
+                throttle: ThrottleMode::Intercept,
+                ..EventMask::DEFAULT
+            },
+        )),
+    );
+
+    // Always handle the provider events, so connections can be approved.
+    // Without a progress channel, the progress is discarded.
+    let progress_tx = match progress_tx {
+        Some(tx) => tx,
+        None => {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+            tokio::spawn(async move { while rx.recv().await.is_some() {} });
+            tx
+        }
+    };
+    let served = Arc::new(Mutex::new(HashSet::new()));
+    let handle = SendHandle::default();
+    tokio::task::spawn(handle_provider_progress(
+        progress_tx,
+        event_rx,
+        on_connect,
+        served.clone(),
+        handle.connections.clone(),
+        handle.changed.clone(),
+        max_upload_bps.map(|rate| Arc::new(RateLimiter::new(rate))),
+    ));
+    (blobs, served, handle)
+}
+
+/// Send [`ConnectionStatus::ServerReady`] once the endpoint has a relay or
+/// direct address, or as unreachable if it has none in time.
+async fn report_server_ready(endpoint: Endpoint, progress_tx: ProgressSenderTx) {
+    let mut addrs = endpoint.watch_addr().stream();
+    let has_addr = async {
+        while let Some(addr) = addrs.next().await {
+            if !addr.addrs.is_empty() {
+                return true;
+            }
+        }
+        false
+    };
+    let reachable = tokio::time::timeout(SERVER_READY_TIMEOUT, has_addr)
+        .await
+        .unwrap_or(false);
+    let _ = progress_tx
+        .send(ProgressEvent::Connection(ConnectionStatus::ServerReady { reachable }))
+        .await;
+}
+
+/// The hashes receivers may request: the collection and every blob in it.
+///
+/// The store can hold other data, e.g. an earlier version of the collection,
+/// which must not be served to a peer that happens to know its hash.
+pub(crate) async fn served_hashes(store: &Store, hash: Hash) -> anyhow::Result<HashSet<Hash>> {
+    let hash_seq = HashSeq::try_from(store.get_bytes(hash).await?)?;
+    Ok(std::iter::once(hash).chain(hash_seq.iter()).collect())
+}
+
+/// The receivers connected to a running send.
+#[derive(Debug, Clone, Default)]
+pub struct SendHandle {
+    connections: Arc<Mutex<BTreeMap<u64, ConnectionInfo>>>,
+    /// Notified whenever a receiver connects or disconnects.
+    changed: Arc<tokio::sync::Notify>,
+}
+
+/// A receiver connected to a send, see [`SendHandle::connections`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionSnapshot {
+    /// ID of the connection, as used in [`ConnectionStatus`] events.
+    pub connection_id: u64,
+    /// Endpoint ID of the receiver, if known.
+    pub node_id: Option<iroh::EndpointId>,
+    /// Bytes sent to the receiver so far.
+    pub bytes_served: u64,
+    /// When the receiver connected.
+    pub started_at: SystemTime,
+}
+
+impl ConnectionSnapshot {
+    /// The average rate in bytes per second since the receiver connected.
+    pub fn rate(&self) -> f64 {
+        let elapsed = self.started_at.elapsed().unwrap_or_default().as_secs_f64();
+        if elapsed > 0.0 {
+            self.bytes_served as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+impl SendHandle {
+    /// How long no receiver may be connected before [`Self::wait_idle`]
+    /// considers the send idle.
+    pub const IDLE_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+    /// The receivers that are currently connected, ordered by connection id.
+    pub fn connections(&self) -> Vec<ConnectionSnapshot> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, conn)| !conn.disconnected)
+            .map(|(id, conn)| ConnectionSnapshot {
+                connection_id: *id,
+                node_id: conn.node_id,
+                bytes_served: conn.bytes_served,
+                started_at: conn.started_at,
+            })
+            .collect()
+    }
+
+    /// A summary of the connected receivers, one line each, e.g. to log
+    /// on request when there is no terminal to show progress in.
+    pub fn status(&self) -> String {
+        let connections = self.connections();
+        let mut status = format!("{} active transfers", connections.len());
+        for conn in connections {
+            let peer = conn.node_id.map_or_else(|| "unknown".to_string(), |id| id.to_string());
+            status.push_str(&format!(
+                "\n  connection {} to {}: {} bytes sent, {:.0} B/s",
+                conn.connection_id,
+                peer,
+                conn.bytes_served,
+                conn.rate()
+            ));
+        }
+        status
+    }
+
+    /// Stop serving a receiver. Its running requests are aborted and new ones
+    /// are rejected, other receivers are not affected.
+    ///
+    /// Returns false if there is no such connection.
+    pub fn disconnect(&self, connection_id: u64) -> bool {
+        match self.connections.lock().unwrap().get_mut(&connection_id) {
+            Some(conn) => {
+                tracing::info!("🔌 Disconnecting {}", connection_id);
+                conn.disconnected = true;
+                self.changed.notify_waiters();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wait until no receiver was connected for [`Self::IDLE_GRACE_PERIOD`],
+    /// e.g. to replace the served data without cutting off a download.
+    ///
+    /// A receiver that connects during the grace period starts it over once it
+    /// is gone. Returns false if the send is not idle within `timeout`.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // register before looking, so a change right after is not missed
+            changed.as_mut().enable();
+            let grace = if self.connections().is_empty() {
+                Self::IDLE_GRACE_PERIOD
+            } else {
+                timeout
+            };
+            let idle = tokio::time::Instant::now() + grace;
+            select! {
+                _ = &mut changed => {}
+                _ = tokio::time::sleep_until(idle.min(deadline)) => {
+                    return idle <= deadline && self.connections().is_empty();
+                }
+            }
+        }
+    }
+}
+
+/// Whether a connection was disconnected with [`SendHandle::disconnect`].
+fn is_disconnected(connections: &Mutex<BTreeMap<u64, ConnectionInfo>>, connection_id: u64) -> bool {
+    connections
+        .lock()
+        .unwrap()
+        .get(&connection_id)
+        .is_some_and(|conn| conn.disconnected)
+}
+
 /// Handle provider progress events and forward them to the progress channel.
+///
+/// If `on_connect` is set, incoming connections are intercepted and only
+/// served if the callback accepts them. Requests for anything but the
+/// `served` hashes, and any data for disconnected `connections`, are rejected.
+/// With a `limiter`, each chunk of data waits for its bytes before it is sent.
+/// The served hashes can change while the provider runs.
 async fn handle_provider_progress(
     progress_tx: ProgressSenderTx,
     mut recv: tokio::sync::mpsc::Receiver<ProviderMessage>,
+    on_connect: Option<OnConnect>,
+    served: Arc<Mutex<HashSet<Hash>>>,
+    connections: Arc<Mutex<BTreeMap<u64, ConnectionInfo>>>,
+    changed: Arc<tokio::sync::Notify>,
+    limiter: Option<Arc<RateLimiter>>,
 ) -> anyhow::Result<()> {
-    let connections = Arc::new(Mutex::new(BTreeMap::new()));
     let mut tasks = n0_future::FuturesUnordered::new();
 
     loop {
@@ -186,28 +768,33 @@ async fn handle_provider_progress(
                 };
 
                 match item {
+                    ProviderMessage::ClientConnected(msg) => {
+                        let info = ConnectInfo {
+                            node_id: msg.endpoint_id,
+                            connection_id: msg.connection_id,
+                        };
+                        let decision = match on_connect {
+                            Some(ref on_connect) => on_connect.decide(info),
+                            None => ConnectDecision::Accept,
+                        };
+                        if decision == ConnectDecision::Reject {
+                            tracing::info!("🚫 Rejected connection {}", msg.connection_id);
+                            msg.tx.send(Err(AbortReason::Permission)).await.ok();
+                            continue;
+                        }
+                        msg.tx.send(Ok(())).await.ok();
+                        let id = msg.endpoint_id;
+                        add_connection(&connections, &progress_tx, id, msg.connection_id).await;
+                        changed.notify_waiters();
+                    }
                     ProviderMessage::ClientConnectedNotify(msg) => {
-                        let endpoint_id = msg
-                            .endpoint_id
-                            .map(|id| id.fmt_short().to_string())
-                            .unwrap_or_else(|| "?".to_string());
-                        let connection_id = msg.connection_id;
-                        connections.lock().unwrap().insert(
-                            connection_id,
-                            ConnectionInfo {
-                                requests: BTreeMap::new(),
-                                endpoint_id: endpoint_id.clone(),
-                            },
-                        );
-                        let _ = progress_tx
-                            .send(ProgressEvent::Connection(ConnectionStatus::ClientConnected {
-                                endpoint_id,
-                                connection_id,
-                            }))
-                            .await;
+                        let id = msg.endpoint_id;
+                        add_connection(&connections, &progress_tx, id, msg.connection_id).await;
+                        changed.notify_waiters();
                     }
                     ProviderMessage::ConnectionClosed(msg) => {
                         if connections.lock().unwrap().remove(&msg.connection_id).is_some() {
+                            changed.notify_waiters();
                             let _ = progress_tx
                                 .send(ProgressEvent::Connection(ConnectionStatus::ConnectionClosed {
                                     connection_id: msg.connection_id,
@@ -215,16 +802,43 @@ async fn handle_provider_progress(
                                 .await;
                         }
                     }
-                    ProviderMessage::GetRequestReceivedNotify(msg) => {
+                    ProviderMessage::GetRequestReceived(msg) => {
                         let request_id = msg.request_id;
                         let connection_id = msg.connection_id;
+                        let hash = msg.request.hash;
+                        let allowed = served.lock().unwrap().contains(&hash);
+                        if !allowed || is_disconnected(&connections, connection_id) {
+                            tracing::info!(
+                                "🚫 Rejected request {} for {}",
+                                request_id,
+                                hash.fmt_short()
+                            );
+                            msg.tx.send(Err(AbortReason::Permission)).await.ok();
+                            let _ = progress_tx
+                                .send(ProgressEvent::Connection(ConnectionStatus::RequestRejected {
+                                    connection_id,
+                                    request_id,
+                                    hash,
+                                }))
+                                .await;
+                            continue;
+                        }
+                        msg.tx.send(Ok(())).await.ok();
                         let connections = connections.clone();
                         let progress_tx = progress_tx.clone();
                         tasks.push(tokio::task::spawn(async move {
                             let mut rx = msg.rx;
+                            let mut _transfer = None;
+                            let mut size = 0;
                             while let Ok(Some(msg)) = rx.recv().await {
                                 match msg {
                                     iroh_blobs::provider::events::RequestUpdate::Started(msg) => {
+                                        if let Some(conn) = connections.lock().unwrap().get_mut(&connection_id) {
+                                            conn.requests.insert(request_id, 0);
+                                        }
+                                        let direction = crate::metrics::Direction::Send;
+                                        _transfer = Some(crate::metrics::TransferGuard::new(direction));
+                                        size = msg.size;
                                         let _ = progress_tx
                                             .send(ProgressEvent::Connection(ConnectionStatus::RequestStarted {
                                                 connection_id,
@@ -235,6 +849,10 @@ async fn handle_provider_progress(
                                             .await;
                                     }
                                     iroh_blobs::provider::events::RequestUpdate::Progress(msg) => {
+                                        if let Some(conn) = connections.lock().unwrap().get_mut(&connection_id) {
+                                            let offset = conn.requests.insert(request_id, msg.end_offset).unwrap_or(0);
+                                            conn.bytes_served += msg.end_offset.saturating_sub(offset);
+                                        }
                                         let _ = progress_tx
                                             .send(ProgressEvent::Connection(ConnectionStatus::RequestProgress {
                                                 connection_id,
@@ -244,6 +862,7 @@ async fn handle_provider_progress(
                                             .await;
                                     }
                                     iroh_blobs::provider::events::RequestUpdate::Completed(_) => {
+                                        crate::metrics::bytes_sent(size);
                                         if let Some(conn) = connections.lock().unwrap().get_mut(&connection_id) {
                                             let _ = conn.requests.remove(&request_id);
                                         }
@@ -265,6 +884,20 @@ async fn handle_provider_progress(
                             }
                         }));
                     }
+                    ProviderMessage::Throttle(msg) => {
+                        if is_disconnected(&connections, msg.connection_id) {
+                            msg.tx.send(Err(AbortReason::Permission)).await.ok();
+                        } else if let Some(ref limiter) = limiter {
+                            // wait in a task, so other events are handled meanwhile
+                            let limiter = limiter.clone();
+                            tasks.push(tokio::task::spawn(async move {
+                                limiter.acquire(msg.size).await;
+                                msg.tx.send(Ok(())).await.ok();
+                            }));
+                        } else {
+                            msg.tx.send(Ok(())).await.ok();
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -275,9 +908,506 @@ async fn handle_provider_progress(
     Ok(())
 }
 
+/// Track a new connection and report it.
+async fn add_connection(
+    connections: &Mutex<BTreeMap<u64, ConnectionInfo>>,
+    progress_tx: &ProgressSenderTx,
+    endpoint_id: Option<iroh::EndpointId>,
+    connection_id: u64,
+) {
+    connections.lock().unwrap().insert(
+        connection_id,
+        ConnectionInfo {
+            node_id: endpoint_id,
+            started_at: SystemTime::now(),
+            bytes_served: 0,
+            requests: BTreeMap::new(),
+            disconnected: false,
+        },
+    );
+    let endpoint_id = endpoint_id
+        .map(|id| id.fmt_short().to_string())
+        .unwrap_or_else(|| "?".to_string());
+    let _ = progress_tx
+        .send(ProgressEvent::Connection(ConnectionStatus::ClientConnected {
+            endpoint_id,
+            connection_id,
+        }))
+        .await;
+}
+
 #[derive(Debug)]
 struct ConnectionInfo {
-    #[allow(dead_code)]
-    endpoint_id: String,
-    requests: BTreeMap<u64, ()>,
+    node_id: Option<iroh::EndpointId>,
+    started_at: SystemTime,
+    bytes_served: u64,
+    /// The offsets reached by the running requests, by request id.
+    requests: BTreeMap<u64, u64>,
+    /// Set by [`SendHandle::disconnect`].
+    disconnected: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LoopbackTransfer;
+
+    #[tokio::test]
+    async fn test_send_ready_event() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("hello.txt", "hello ticket")?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let ready = tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let ProgressEvent::SendReady {
+                    ticket,
+                    hash,
+                    total_size,
+                } = event
+                {
+                    return Some((ticket, hash, total_size));
+                }
+            }
+            None
+        });
+
+        let result = send_with_progress(t.send_args("hello.txt"), tx).await?;
+
+        let (ticket, hash, total_size) = ready.await?.expect("no SendReady event");
+        assert_eq!(ticket, result.ticket);
+        assert_eq!(hash, result.hash);
+        assert_eq!(total_size, result.total_size);
+        Ok(())
+    }
+
+    async fn send_on_port(dir: &std::path::Path, port: u16) -> anyhow::Result<SendResult> {
+        let path = dir.join("hello.txt");
+        std::fs::write(&path, "hello port")?;
+        send(SendArgs {
+            ticket_type: AddrInfoOptions::Addresses,
+            common: CommonConfig {
+                bind_port: Some(port),
+                ..CommonConfig::loopback(dir)
+            },
+            ..SendArgs::new(path)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_server_ready_event() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("hello.txt", "hello receiver")?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let events = tokio::spawn(async move {
+            let mut send_ready = false;
+            while let Some(event) = rx.recv().await {
+                match event {
+                    ProgressEvent::SendReady { .. } => send_ready = true,
+                    ProgressEvent::Connection(ConnectionStatus::ServerReady { reachable }) => {
+                        return Some((send_ready, reachable));
+                    }
+                    _ => {}
+                }
+            }
+            None
+        });
+
+        // without a relay, the loopback addresses are all there is
+        send_with_progress(t.send_args("hello.txt"), tx).await?;
+
+        let ready = tokio::time::timeout(SERVER_READY_TIMEOUT, events).await??;
+        let (send_ready, reachable) = ready.expect("no ServerReady event");
+        assert!(send_ready, "ServerReady came before SendReady");
+        assert!(reachable);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_port() -> anyhow::Result<()> {
+        // find a free port
+        let port = std::net::UdpSocket::bind("0.0.0.0:0")?.local_addr()?.port();
+        let dir = tempfile::tempdir()?;
+        let result = send_on_port(dir.path(), port).await?;
+        assert!(result.bound_addrs.iter().any(|addr| addr.port() == port));
+
+        // a taken port falls back to an ephemeral one
+        let taken = std::net::UdpSocket::bind("0.0.0.0:0")?;
+        let port = taken.local_addr()?.port();
+        let dir = tempfile::tempdir()?;
+        let result = send_on_port(dir.path(), port).await?;
+        assert!(!result.bound_addrs.is_empty());
+        assert!(result.bound_addrs.iter().all(|addr| addr.port() != port));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_temp_prefix() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let send_dir = tempfile::tempdir()?;
+        let path = source.path().join("data.bin");
+        std::fs::write(&path, "payload")?;
+        let common = CommonConfig {
+            relay: RelayModeOption::Disabled,
+            temp_dir: Some(send_dir.path().to_path_buf()),
+            temp_prefix: Some("my-app-".to_string()),
+            ..Default::default()
+        };
+        let _sent = send(SendArgs {
+            ticket_type: AddrInfoOptions::Addresses,
+            common: common.clone(),
+            ..SendArgs::new(path)
+        })
+        .await?;
+        let names = |dir: &std::path::Path| {
+            let mut names = std::fs::read_dir(dir)?
+                .map(|entry| anyhow::Ok(entry?.file_name().to_string_lossy().into_owned()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            names.sort();
+            anyhow::Ok(names)
+        };
+        let created = names(send_dir.path())?;
+        assert_eq!(created.len(), 1);
+        assert!(created[0].starts_with("my-app-send-"), "{created:?}");
+
+        // only the directories with the prefix are cleaned up
+        let other = tempfile::tempdir()?;
+        for name in ["my-app-recv-1", "my-app-send-2", "my-app-notes", ".sendme-recv-3"] {
+            std::fs::create_dir(other.path().join(name))?;
+        }
+        let common = CommonConfig {
+            temp_dir: Some(other.path().to_path_buf()),
+            ..common
+        };
+        let removed = crate::remove_temp_dirs(&common)?;
+        assert_eq!(removed.len(), 2);
+        assert_eq!(names(other.path())?, vec![".sendme-recv-3", "my-app-notes"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_only_collection_is_served() -> anyhow::Result<()> {
+        use iroh_blobs::{format::collection::Collection, store::mem::MemStore};
+
+        let t = LoopbackTransfer::new()?;
+        t.write("hello.txt", "hello collection")?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let rejected = tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let ProgressEvent::Connection(ConnectionStatus::RequestRejected { hash, .. }) =
+                    event
+                {
+                    return Some(hash);
+                }
+            }
+            None
+        });
+        let sent = send_with_progress(
+            SendArgs {
+                attach_note: Some("a note".to_string()),
+                ..t.send_args("hello.txt")
+            },
+            tx,
+        )
+        .await?;
+
+        // the collection from before the note was attached is still in the
+        // sender's store, but not part of what was shared
+        let local = MemStore::new();
+        let unrelated = sent
+            .collection
+            .iter()
+            .filter(|(name, _)| name != crate::import::NOTE_NAME)
+            .cloned()
+            .collect::<Collection>()
+            .store(local.as_ref())
+            .await?
+            .hash();
+
+        let endpoint = Endpoint::builder().relay_mode(RelayMode::Disabled).bind().await?;
+        let addr = sent.ticket.addr().clone();
+        let connection = endpoint.connect(addr.clone(), iroh_blobs::ALPN).await?;
+        let result =
+            crate::receive::run_get(local.as_ref(), &connection, unrelated, None, None).await;
+        assert!(result.is_err());
+        let rejected = tokio::time::timeout(std::time::Duration::from_secs(5), rejected).await??;
+        assert_eq!(rejected, Some(unrelated));
+
+        let connection = endpoint.connect(addr, iroh_blobs::ALPN).await?;
+        crate::receive::run_get(local.as_ref(), &connection, sent.hash, None, None).await?;
+        for (_, hash) in sent.collection.iter() {
+            crate::receive::run_get(local.as_ref(), &connection, *hash, None, None).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unwrap_single_file() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        t.write("photos/cat.jpg", "meow")?;
+        let names = |unwrap_single_file| {
+            let args = SendArgs {
+                unwrap_single_file,
+                ..t.send_args("photos")
+            };
+            async move {
+                let result = send(args).await?;
+                let names = result.collection.iter().map(|(name, _)| name.clone());
+                anyhow::Ok(names.collect::<Vec<_>>())
+            }
+        };
+        assert_eq!(names(false).await?, vec!["photos/cat.jpg"]);
+        assert_eq!(names(true).await?, vec!["cat.jpg"]);
+
+        // with more than one file the structure is kept
+        t.write("photos/dog.jpg", "woof")?;
+        assert_eq!(names(true).await?, vec!["photos/cat.jpg", "photos/dog.jpg"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_one_receiver() -> anyhow::Result<()> {
+        use iroh_blobs::store::mem::MemStore;
+
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("data.bin", vec![3u8; 256 * 1024]).await?;
+
+        let endpoint = Endpoint::builder().relay_mode(RelayMode::Disabled).bind().await?;
+        let connection = endpoint.connect(sent.ticket.addr().clone(), iroh_blobs::ALPN).await?;
+        let kicked = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Some(conn) = sent.handle.connections().pop() {
+                    return conn;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert_eq!(kicked.node_id, Some(endpoint.id()));
+        assert_eq!(kicked.bytes_served, 0);
+
+        assert!(sent.handle.disconnect(kicked.connection_id));
+        assert!(!sent.handle.disconnect(u64::MAX));
+        assert!(sent.handle.connections().is_empty());
+        let local = MemStore::new();
+        let result =
+            crate::receive::run_get(local.as_ref(), &connection, sent.hash, None, None).await;
+        assert!(result.is_err());
+
+        // another receiver is still served
+        let received = crate::receive(t.receive_args(sent.ticket.clone())).await?;
+        assert_eq!(received.total_files, 1);
+        assert_eq!(std::fs::read(t.export_dir.path().join("data.bin"))?.len(), 256 * 1024);
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay_fallback() {
+        let addr = EndpointAddr {
+            id: iroh::SecretKey::generate(&mut rand::rng()).public(),
+            addrs: [
+                iroh::TransportAddr::Ip("192.168.1.2:1234".parse().unwrap()),
+                iroh::TransportAddr::Relay("https://relay.example.com".parse().unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let addresses = AddrInfoOptions::Addresses;
+        assert_eq!(usable_ticket_type(addresses, &addr, true), addresses);
+        // direct connections are deemed impossible, so the relay is added
+        let fallback = usable_ticket_type(addresses, &addr, false);
+        assert_eq!(fallback, AddrInfoOptions::RelayAndAddresses);
+        let mut ticket_addr = addr.clone();
+        apply_options(&mut ticket_addr, fallback);
+        assert_eq!(ticket_addr.relay_urls().count(), 1);
+
+        // other ticket types are kept, and without a relay there is nothing to add
+        let id = AddrInfoOptions::Id;
+        assert_eq!(usable_ticket_type(id, &addr, false), id);
+        let mut direct_only = addr.clone();
+        apply_options(&mut direct_only, addresses);
+        assert_eq!(usable_ticket_type(addresses, &direct_only, false), addresses);
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle() -> anyhow::Result<()> {
+        use iroh_blobs::store::mem::MemStore;
+
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("data.bin", vec![5u8; 64 * 1024]).await?;
+
+        let endpoint = Endpoint::builder().relay_mode(RelayMode::Disabled).bind().await?;
+        let connection = endpoint.connect(sent.ticket.addr().clone(), iroh_blobs::ALPN).await?;
+        let local = MemStore::new();
+        crate::receive::run_get(local.as_ref(), &connection, sent.hash, None, None).await?;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sent.handle.connections().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        // still connected, so not idle
+        assert!(!sent.handle.wait_idle(Duration::from_millis(200)).await);
+
+        connection.close(0u32.into(), b"done");
+        let t0 = Instant::now();
+        assert!(sent.handle.wait_idle(Duration::from_secs(5)).await);
+        assert!(t0.elapsed() >= SendHandle::IDLE_GRACE_PERIOD);
+        assert!(sent.handle.connections().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_upload_rate() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        let size = 384 * 1024;
+        let max_rate = 128 * 1024;
+        t.write("data.bin", vec![9u8; size])?;
+        let mut args = t.send_args("data.bin");
+        args.common.max_upload_bps = Some(max_rate);
+        let sent = send(args).await?;
+
+        let t0 = Instant::now();
+        let received = crate::receive(t.receive_args(sent.ticket)).await?;
+        let elapsed = t0.elapsed();
+        assert_eq!(received.payload_size, size as u64);
+        // at most a second's worth of bytes can go out in a burst
+        let min = crate::throttle::transfer_time(size as u64, max_rate) - Duration::from_secs(1);
+        assert!(elapsed >= min, "took {elapsed:?}");
+
+        let mut args = t.send_args("data.bin");
+        args.common.max_upload_bps = Some(0);
+        let err = send(args).await.unwrap_err();
+        assert!(err.to_string().contains("must not be zero"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_status_of_active_transfers() -> anyhow::Result<()> {
+        use iroh_blobs::store::mem::MemStore;
+
+        let t = LoopbackTransfer::new()?;
+        let sent = t.send("data.bin", vec![5u8; 128 * 1024]).await?;
+
+        // two receivers that keep their connections open, one got the data
+        let mut receivers = Vec::new();
+        for _ in 0..2 {
+            let endpoint = Endpoint::builder().relay_mode(RelayMode::Disabled).bind().await?;
+            let connection = endpoint.connect(sent.ticket.addr().clone(), iroh_blobs::ALPN).await?;
+            receivers.push((endpoint, connection));
+        }
+        let local = MemStore::new();
+        crate::receive::run_get(local.as_ref(), &receivers[0].1, sent.hash, None, None).await?;
+        let connections = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let connections = sent.handle.connections();
+                let done = connections.iter().any(|c| c.bytes_served >= 128 * 1024);
+                if connections.len() == 2 && done {
+                    return connections;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+
+        let status = sent.handle.status();
+        let mut lines = status.lines();
+        assert_eq!(lines.next(), Some("2 active transfers"));
+        for conn in &connections {
+            let line = lines.next().unwrap();
+            assert!(line.contains(&format!("connection {} ", conn.connection_id)));
+            assert!(line.contains(&conn.node_id.unwrap().to_string()));
+            assert!(conn.bytes_served <= sent.total_size + 64 * 1024);
+        }
+        assert!(lines.next().is_none());
+        let peers: Vec<_> = receivers.iter().map(|(endpoint, _)| Some(endpoint.id())).collect();
+        assert!(connections.iter().all(|conn| peers.contains(&conn.node_id)));
+        let served = connections.iter().find(|conn| conn.bytes_served > 0).unwrap();
+        assert!(served.rate() > 0.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_import() -> anyhow::Result<()> {
+        let t = LoopbackTransfer::new()?;
+        for dir in 0..20 {
+            for file in 0..25 {
+                let name = format!("tree/dir-{dir}/{file}.bin");
+                t.write(&name, vec![file as u8; 16 * 1024])?;
+            }
+        }
+
+        // cancel as soon as the import starts
+        let cancel = CancellationToken::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let events = tokio::spawn({
+            let cancel = cancel.clone();
+            async move {
+                while let Some(event) = rx.recv().await {
+                    match event {
+                        ProgressEvent::Import(_, ImportProgress::Started { .. }) => cancel.cancel(),
+                        ProgressEvent::Import(_, ImportProgress::Cancelled) => return true,
+                        _ => {}
+                    }
+                }
+                false
+            }
+        });
+
+        let result = send_with_progress(
+            SendArgs {
+                cancel: Some(cancel),
+                ..t.send_args("tree")
+            },
+            tx,
+        )
+        .await;
+        let Err(err) = result else {
+            panic!("the send was not cancelled");
+        };
+        assert!(err.is::<Cancelled>(), "{err:#}");
+        assert!(events.await?);
+        // the temporary store is gone
+        assert_eq!(std::fs::read_dir(t.send_dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_bytes() -> anyhow::Result<()> {
+        let recv_dir = tempfile::tempdir()?;
+        let export_dir = tempfile::tempdir()?;
+        let report: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let common = CommonConfig {
+            relay: RelayModeOption::Disabled,
+            store_backend: StoreBackend::Mem,
+            ..Default::default()
+        };
+        let sent = send_bytes(
+            "reports/daily.bin".to_string(),
+            report.clone(),
+            SendBytesArgs {
+                ticket_type: AddrInfoOptions::Addresses,
+                common: common.clone(),
+                on_connect: None,
+            },
+        )
+        .await?;
+        assert_eq!(sent.total_size, report.len() as u64);
+
+        let addr = sent.ticket.addr().clone();
+        let mut args = crate::ReceiveArgs::from_node_addr(addr, sent.hash, BlobFormat::HashSeq)?;
+        args.common = CommonConfig {
+            temp_dir: Some(recv_dir.path().to_path_buf()),
+            ..common
+        };
+        args.export_dir = Some(export_dir.path().to_path_buf());
+        let received = crate::receive(args).await?;
+        assert_eq!(received.total_files, 1);
+        let exported = std::fs::read(export_dir.path().join("reports/daily.bin"))?;
+        assert_eq!(exported, report);
+        Ok(())
+    }
 }
@@ -0,0 +1,279 @@
+//! A long-running provider that serves many paths on one endpoint.
+//!
+//! [`crate::send`] serves a single collection and lives as long as the
+//! process. A [`Server`] keeps one endpoint and store, so paths can be added
+//! and removed over time without binding a new endpoint for each of them.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use iroh::protocol::Router;
+use iroh_blobs::{store::fs::FsStore, ticket::BlobTicket, BlobFormat, Hash};
+
+use crate::{
+    apply_options,
+    progress::{ProgressEvent, ProgressSenderTx},
+    send::{endpoint_builder, provider, served_hashes, SendHandle},
+    types::{AddrInfoOptions, CommonConfig},
+    ServerArgs,
+};
+
+/// A path served by a [`Server`], see [`Server::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedPath {
+    /// The path that was added.
+    pub path: PathBuf,
+    /// Hash of the collection.
+    pub hash: Hash,
+    /// Total size of the files.
+    pub total_size: u64,
+    /// The ticket to receive the path.
+    pub ticket: BlobTicket,
+}
+
+/// Serves any number of paths from a persistent store on one endpoint.
+///
+/// Receivers can only get the paths that are currently added, even if the
+/// store still holds data of paths that were removed.
+pub struct Server {
+    router: Router,
+    store: FsStore,
+    ticket_type: AddrInfoOptions,
+    common: CommonConfig,
+    served: Arc<Mutex<HashSet<Hash>>>,
+    paths: Mutex<BTreeMap<Hash, (ServedPath, HashSet<Hash>)>>,
+    handle: SendHandle,
+}
+
+impl Server {
+    /// Bind the endpoint and open the store. Nothing is served until a path
+    /// is added with [`Self::add_path`].
+    pub async fn spawn(args: ServerArgs) -> anyhow::Result<Self> {
+        Self::spawn_internal(args, None).await
+    }
+
+    /// Like [`Self::spawn`], with the connection events sent to `progress_tx`.
+    pub async fn spawn_with_progress(
+        args: ServerArgs,
+        progress_tx: ProgressSenderTx,
+    ) -> anyhow::Result<Self> {
+        Self::spawn_internal(args, Some(progress_tx)).await
+    }
+
+    async fn spawn_internal(
+        args: ServerArgs,
+        progress_tx: Option<ProgressSenderTx>,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            args.common.max_upload_bps != Some(0),
+            "the upload rate must not be zero"
+        );
+        let builder = endpoint_builder(&args.common, args.ticket_type)?;
+        tokio::fs::create_dir_all(&args.store_dir).await?;
+        let endpoint = crate::bind_with_config(&args.common, builder).await?;
+        let store = FsStore::load(&args.store_dir).await?;
+        let (blobs, served, handle) =
+            provider(&store, args.on_connect, args.common.max_upload_bps, progress_tx);
+        let router = Router::builder(endpoint)
+            .accept(iroh_blobs::ALPN, blobs)
+            .spawn();
+        tracing::info!("🗄️ Serving from {}", args.store_dir.display());
+        Ok(Self {
+            router,
+            store,
+            ticket_type: args.ticket_type,
+            common: args.common,
+            served,
+            paths: Mutex::new(BTreeMap::new()),
+            handle,
+        })
+    }
+
+    /// Import a file or directory and start serving it.
+    ///
+    /// Returns the ticket to receive it. Adding a path with the same content
+    /// again replaces the earlier entry.
+    pub async fn add_path(&self, path: impl Into<PathBuf>) -> anyhow::Result<BlobTicket> {
+        self.add_path_internal(path.into(), None).await
+    }
+
+    /// Like [`Self::add_path`], with the import progress and
+    /// [`ProgressEvent::SendReady`] sent to `progress_tx`.
+    pub async fn add_path_with_progress(
+        &self,
+        path: impl Into<PathBuf>,
+        progress_tx: ProgressSenderTx,
+    ) -> anyhow::Result<BlobTicket> {
+        self.add_path_internal(path.into(), Some(progress_tx)).await
+    }
+
+    async fn add_path_internal(
+        &self,
+        path: PathBuf,
+        progress_tx: Option<ProgressSenderTx>,
+    ) -> anyhow::Result<BlobTicket> {
+        let options = crate::import::ImportOptions::from_common(&self.common);
+        let paths = vec![path.clone()];
+        let (hash, total_size, _) =
+            crate::import::import(paths, &self.store, &options, progress_tx.clone()).await?;
+        let hashes = served_hashes(&self.store, hash).await?;
+
+        let mut addr = self.router.endpoint().addr();
+        apply_options(&mut addr, self.ticket_type);
+        let ticket = BlobTicket::new(addr, hash, BlobFormat::HashSeq);
+        let entry = ServedPath {
+            path,
+            hash,
+            total_size,
+            ticket: ticket.clone(),
+        };
+        {
+            // under the paths lock, so a concurrent remove can't recompute
+            // the served set between the two updates
+            let mut paths = self.paths.lock().unwrap();
+            self.served.lock().unwrap().extend(hashes.iter().copied());
+            paths.insert(hash, (entry, hashes));
+        }
+        if let Some(tx) = progress_tx {
+            let _ = tx
+                .send(ProgressEvent::SendReady {
+                    ticket: ticket.clone(),
+                    hash,
+                    total_size,
+                })
+                .await;
+        }
+        Ok(ticket)
+    }
+
+    /// Stop serving the collection `hash`. Receivers that are downloading it
+    /// can't request more of it.
+    ///
+    /// Blobs that are also part of another served path are still served.
+    /// The data stays in the store: the server keeps no tag for the paths it
+    /// serves, so there is none to delete. Returns false if no such path was
+    /// added.
+    pub fn remove(&self, hash: Hash) -> bool {
+        let mut paths = self.paths.lock().unwrap();
+        if paths.remove(&hash).is_none() {
+            return false;
+        }
+        *self.served.lock().unwrap() = paths
+            .values()
+            .flat_map(|(_, hashes)| hashes.iter().copied())
+            .collect();
+        true
+    }
+
+    /// The paths that are served, ordered by hash.
+    pub fn list(&self) -> Vec<ServedPath> {
+        let paths = self.paths.lock().unwrap();
+        paths.values().map(|(entry, _)| entry.clone()).collect()
+    }
+
+    /// The receivers connected to the server.
+    pub fn handle(&self) -> &SendHandle {
+        &self.handle
+    }
+
+    /// Close the endpoint and the store.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.router.shutdown().await?;
+        self.store.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{receive, ReceiveArgs};
+
+    #[tokio::test]
+    async fn test_serve_several_paths() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let store_dir = tempfile::tempdir()?;
+        let recv_dir = tempfile::tempdir()?;
+        let first = source.path().join("first.txt");
+        std::fs::write(&first, "first file")?;
+        let second = source.path().join("second");
+        std::fs::create_dir(&second)?;
+        std::fs::write(second.join("b.txt"), "second file")?;
+
+        let server = Server::spawn(ServerArgs {
+            store_dir: store_dir.path().to_path_buf(),
+            ticket_type: AddrInfoOptions::Addresses,
+            common: CommonConfig::loopback(store_dir.path()),
+            on_connect: None,
+        })
+        .await?;
+        let first_ticket = server.add_path(&first).await?;
+        let second_ticket = server.add_path(&second).await?;
+        assert_eq!(first_ticket.addr().id, second_ticket.addr().id);
+        assert_eq!(server.list().len(), 2);
+
+        let args = |ticket: &BlobTicket, export_dir: &Path| ReceiveArgs {
+            common: CommonConfig::loopback(recv_dir.path()),
+            export_dir: Some(export_dir.to_path_buf()),
+            stream_reconnects: 0,
+            ..ReceiveArgs::new(ticket.clone())
+        };
+        let out = tempfile::tempdir()?;
+        receive(args(&first_ticket, out.path())).await?;
+        receive(args(&second_ticket, out.path())).await?;
+        assert_eq!(std::fs::read_to_string(out.path().join("first.txt"))?, "first file");
+        let b = out.path().join("second/b.txt");
+        assert_eq!(std::fs::read_to_string(b)?, "second file");
+
+        assert!(server.remove(first_ticket.hash()));
+        assert!(!server.remove(first_ticket.hash()));
+        let listed = server.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, second);
+
+        let again = tempfile::tempdir()?;
+        assert!(receive(args(&first_ticket, again.path())).await.is_err());
+        receive(args(&second_ticket, again.path())).await?;
+        server.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sends_share_the_endpoint() -> anyhow::Result<()> {
+        let source = tempfile::tempdir()?;
+        let store_dir = tempfile::tempdir()?;
+        let server = Server::spawn(ServerArgs {
+            store_dir: store_dir.path().to_path_buf(),
+            ticket_type: AddrInfoOptions::Addresses,
+            common: CommonConfig::loopback(store_dir.path()),
+            on_connect: None,
+        })
+        .await?;
+
+        // like two sends from the TUI, each with its own progress channel
+        let mut ids = Vec::new();
+        for name in ["one.txt", "two.txt"] {
+            let path = source.path().join(name);
+            std::fs::write(&path, name)?;
+            let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+            let ticket = server.add_path_with_progress(&path, tx).await?;
+            let mut ready = None;
+            while let Ok(event) = rx.try_recv() {
+                if let ProgressEvent::SendReady { ticket, .. } = event {
+                    ready = Some(ticket);
+                }
+            }
+            assert_eq!(ready.as_ref(), Some(&ticket));
+            ids.push(ticket.addr().id);
+        }
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(server.router.endpoint().id(), ids[0]);
+        server.shutdown().await?;
+        Ok(())
+    }
+}
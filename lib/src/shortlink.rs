@@ -0,0 +1,150 @@
+//! Short links for tickets, from a self-hosted redirect service.
+//!
+//! Tickets are long, which makes them painful to type or read out. A short
+//! link service maps them to short URLs with a plain HTTP API:
+//!
+//! - `POST <service>` with a ticket as the text body returns the short URL as
+//!   the text body.
+//! - `GET <short URL>` returns the ticket, or any share string of
+//!   [`crate::links`], as the text body.
+
+use anyhow::Context;
+use iroh_blobs::ticket::BlobTicket;
+use reqwest::Url;
+
+/// Register `ticket` with the short link service at `service`, returning
+/// the short URL.
+pub async fn shorten(service: &str, ticket: &BlobTicket) -> anyhow::Result<String> {
+    let service = http_url(service)?;
+    let response = reqwest::Client::new()
+        .post(service.clone())
+        .header(reqwest::header::CONTENT_TYPE, "text/plain")
+        .body(ticket.to_string())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to shorten the ticket with {service}"))?;
+    let short = response.text().await?;
+    let short = http_url(short.trim())
+        .with_context(|| format!("{service} did not return a short link"))?;
+    Ok(short.to_string())
+}
+
+/// Fetch the ticket behind a short link.
+pub async fn resolve(short_link: &str) -> anyhow::Result<BlobTicket> {
+    let url = http_url(short_link)?;
+    let body = reqwest::get(url.clone())
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to resolve {url}"))?
+        .text()
+        .await?;
+    crate::links::parse_ticket(&body).with_context(|| format!("{url} is not a link to a ticket"))
+}
+
+/// Parse a ticket or share string like [`crate::links::parse_ticket`], and
+/// resolve anything else that is an HTTP(S) URL as a short link.
+pub async fn parse_or_resolve(s: &str) -> anyhow::Result<BlobTicket> {
+    match crate::links::parse_ticket(s) {
+        Ok(ticket) => Ok(ticket),
+        Err(_) if http_url(s.trim()).is_ok() => resolve(s.trim()).await,
+        Err(e) => Err(e),
+    }
+}
+
+fn http_url(s: &str) -> anyhow::Result<Url> {
+    let url = Url::parse(s).with_context(|| format!("invalid URL {s}"))?;
+    anyhow::ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "unsupported URL scheme {}",
+        url.scheme()
+    );
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use iroh::{EndpointAddr, SecretKey};
+    use iroh_blobs::{BlobFormat, Hash};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    use super::*;
+
+    /// A short link service that keeps the links in memory.
+    async fn serve_shortener() -> anyhow::Result<String> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let base = format!("http://{}", listener.local_addr()?);
+        let links = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+        let service = base.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let mut socket = BufReader::new(socket);
+                let mut request_line = String::new();
+                socket.read_line(&mut request_line).await?;
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    socket.read_line(&mut line).await?;
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse()?;
+                        }
+                    }
+                }
+                let mut body = vec![0u8; content_length];
+                socket.read_exact(&mut body).await?;
+
+                let mut parts = request_line.split_whitespace();
+                let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+                let response = match method {
+                    "POST" => {
+                        let mut links = links.lock().unwrap();
+                        let code = format!("s{}", links.len());
+                        links.insert(format!("/{code}"), String::from_utf8(body)?);
+                        Some(format!("{base}/{code}"))
+                    }
+                    _ => links.lock().unwrap().get(path).cloned(),
+                };
+                let (status, body) = match response {
+                    Some(body) => ("200 OK", body),
+                    None => ("404 Not Found", String::new()),
+                };
+                let header = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let socket = socket.get_mut();
+                socket.write_all(header.as_bytes()).await?;
+                socket.write_all(body.as_bytes()).await?;
+            }
+            anyhow::Ok(())
+        });
+        Ok(service)
+    }
+
+    #[tokio::test]
+    async fn test_short_link_round_trip() -> anyhow::Result<()> {
+        let service = serve_shortener().await?;
+        let addr = EndpointAddr::new(SecretKey::generate(&mut rand::rng()).public());
+        let ticket = BlobTicket::new(addr, Hash::new("short"), BlobFormat::HashSeq);
+
+        let short = shorten(&service, &ticket).await?;
+        assert!(short.starts_with(&service));
+        assert!(short.len() < ticket.to_string().len());
+        assert_eq!(resolve(&short).await?, ticket);
+        assert_eq!(parse_or_resolve(&short).await?, ticket);
+        assert_eq!(parse_or_resolve(&ticket.to_string()).await?, ticket);
+
+        assert!(resolve(&format!("{service}/unknown")).await.is_err());
+        assert!(shorten("ftp://example.com", &ticket).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Limiting the upload rate of a send and the download rate of a receive.
+//!
+//! The blobs provider asks before it sends each chunk of data, see
+//! [`iroh_blobs::provider::events::ThrottleMode`]. With
+//! [`crate::CommonConfig::max_upload_bps`], the answer is delayed by a
+//! [`RateLimiter`] shared by all receivers of the send.
+//!
+//! A receive with [`crate::CommonConfig::max_download_bps`] waits on its own
+//! [`RateLimiter`] before it reads on after each chunk it got.
+
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Context;
+use tokio::time::Instant;
+
+/// A token bucket that hands out bytes at a fixed rate.
+///
+/// The bucket starts out empty, so the rate is never exceeded from the
+/// start of a transfer. After a pause, up to a second's worth of bytes can be
+/// sent at once.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that can be sent right away. Negative while senders wait.
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter for `bytes_per_sec`, which must not be zero.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "rate must not be zero");
+        Self {
+            bytes_per_sec: bytes_per_sec as f64,
+            state: Mutex::new(Bucket {
+                tokens: 0.0,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until `bytes` may be sent.
+    ///
+    /// Callers are served in the order they call this, each one waiting for
+    /// the bytes of all callers before it.
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.state.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last).as_secs_f64() * self.bytes_per_sec;
+            bucket.tokens = (bucket.tokens + refill).min(self.bytes_per_sec);
+            bucket.last = now;
+            bucket.tokens -= bytes as f64;
+            Duration::from_secs_f64((-bucket.tokens).max(0.0) / self.bytes_per_sec)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Parse a rate in bytes per second, e.g. `500000`, `500KB`, `2MB` or
+/// `1.5MiB/s`.
+///
+/// `K`, `M` and `G` are powers of 1000, `Ki`, `Mi` and `Gi` powers of 1024.
+/// Case is ignored.
+pub fn parse_rate(s: &str) -> anyhow::Result<u64> {
+    let lower = s.trim().to_ascii_lowercase();
+    let lower = lower.strip_suffix("/s").unwrap_or(&lower);
+    let lower = lower.strip_suffix('b').unwrap_or(lower);
+    let split = lower
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("invalid rate {s:?}, expected e.g. 2MB"))?;
+    let factor: u64 = match unit.trim() {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1000 * 1000,
+        "g" => 1000 * 1000 * 1000,
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        "gi" => 1 << 30,
+        unit => anyhow::bail!("invalid unit {unit:?} in rate {s:?}"),
+    };
+    let rate = (number * factor as f64).round() as u64;
+    anyhow::ensure!(rate > 0, "rate {s:?} must be more than zero");
+    Ok(rate)
+}
+
+/// How long sending `size` bytes takes at `bytes_per_sec`, ignoring
+/// connection setup and protocol overhead.
+///
+/// A rate of zero never gets there, and gives [`Duration::MAX`].
+pub fn transfer_time(size: u64, bytes_per_sec: u64) -> Duration {
+    if bytes_per_sec == 0 {
+        return Duration::MAX;
+    }
+    Duration::from_secs_f64(size as f64 / bytes_per_sec as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate() -> anyhow::Result<()> {
+        assert_eq!(parse_rate("500000")?, 500_000);
+        assert_eq!(parse_rate("2MB")?, 2_000_000);
+        assert_eq!(parse_rate("2mb/s")?, 2_000_000);
+        assert_eq!(parse_rate("1.5MiB")?, 3 << 19);
+        assert_eq!(parse_rate("64 KiB")?, 64 * 1024);
+        assert_eq!(parse_rate("1G")?, 1_000_000_000);
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("2TB").is_err());
+        assert!(parse_rate("0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_time() -> anyhow::Result<()> {
+        assert_eq!(transfer_time(300_000_000, parse_rate("1MB")?), Duration::from_secs(300));
+        assert_eq!(transfer_time(3 << 19, parse_rate("1MiB/s")?), Duration::from_millis(1500));
+        assert_eq!(transfer_time(0, 1000), Duration::ZERO);
+        assert_eq!(transfer_time(1000, 0), Duration::MAX);
+        assert!(parse_rate("-1MB").is_err());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let t0 = Instant::now();
+        for _ in 0..10 {
+            limiter.acquire(500).await;
+        }
+        // the bucket starts empty, so 5000 bytes take five seconds
+        assert_eq!(t0.elapsed(), Duration::from_secs(5));
+
+        // after a pause, a second's worth goes out right away
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let t0 = Instant::now();
+        limiter.acquire(1000).await;
+        assert_eq!(t0.elapsed(), Duration::ZERO);
+        limiter.acquire(1000).await;
+        assert_eq!(t0.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -1,6 +1,9 @@
 //! Core types for the sendme library.
 
-use std::{fmt::Display, net::SocketAddrV4, net::SocketAddrV6, path::PathBuf, str::FromStr};
+use std::{
+    fmt::Display, net::SocketAddrV4, net::SocketAddrV6, path::PathBuf, str::FromStr, sync::Arc,
+    time::Duration,
+};
 
 use derive_more::{Display, FromStr};
 use iroh::{RelayMode, RelayUrl, TransportAddr};
@@ -78,6 +81,23 @@ pub fn apply_options(addr: &mut iroh::EndpointAddr, opts: AddrInfoOptions) {
     }
 }
 
+/// Remove relay URLs that are not in `allowed` from an endpoint address.
+///
+/// Fails if the address contains relay URLs, but none of them is allowed and
+/// there are no direct addresses to connect to instead.
+pub fn restrict_relays(addr: &mut iroh::EndpointAddr, allowed: &[RelayUrl]) -> anyhow::Result<()> {
+    let had_relays = addr.relay_urls().next().is_some();
+    addr.addrs.retain(|addr| match addr {
+        TransportAddr::Relay(url) => allowed.contains(url),
+        _ => true,
+    });
+    anyhow::ensure!(
+        !had_relays || addr.relay_urls().next().is_some() || addr.ip_addrs().next().is_some(),
+        "refusing to connect: the ticket only contains untrusted relays and no direct addresses"
+    );
+    Ok(())
+}
+
 /// Relay mode configuration.
 #[derive(Clone, Debug)]
 pub enum RelayModeOption {
@@ -121,6 +141,43 @@ impl From<RelayModeOption> for RelayMode {
     }
 }
 
+/// How urgently a transfer should get import/export workers when several
+/// transfers run in the same process.
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    Debug,
+    Display,
+    FromStr,
+    Serialize,
+    Deserialize,
+)]
+pub enum Priority {
+    /// Background work that may wait for everything else.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// Interactive transfers that should not wait behind large background ones.
+    High,
+}
+
+/// Where a transfer keeps its data while it runs.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Display, FromStr, Serialize, Deserialize)]
+pub enum StoreBackend {
+    /// A store in a temporary directory. Interrupted receives can be resumed.
+    #[default]
+    Fs,
+    /// A store in memory. Nothing is written to disk besides the exported
+    /// files, but all data must fit in memory.
+    Mem,
+}
+
 /// Common configuration for send/receive operations.
 #[derive(Clone, Debug)]
 pub struct CommonConfig {
@@ -137,6 +194,66 @@ pub struct CommonConfig {
     /// Optional custom temp directory for blob storage.
     /// If None, uses current working directory (not compatible with macOS sandbox).
     pub temp_dir: Option<PathBuf>,
+    /// Priority of the transfer when competing for import/export workers.
+    pub priority: Priority,
+    /// Fixed UDP port to bind on all IPv4 and IPv6 interfaces, e.g. for a
+    /// forwarded firewall port.
+    ///
+    /// `magic_ipv4_addr` / `magic_ipv6_addr` take precedence for their address
+    /// family. If the port is taken, an ephemeral port is used instead.
+    pub bind_port: Option<u16>,
+    /// Normalize the names of imported files to Unicode NFC, so the same tree
+    /// produces the same collection on macOS and Linux. On by default.
+    pub normalize_names: bool,
+    /// Imports of more files than this report their progress in batches,
+    /// instead of sending events for every file.
+    ///
+    /// Files of at least [`crate::import::LARGE_FILE_SIZE`] still report their
+    /// own progress.
+    pub import_batch_threshold: usize,
+    /// Where to keep the data of the transfer. With [`StoreBackend::Mem`],
+    /// no temporary directory is created.
+    pub store_backend: StoreBackend,
+    /// Fail a receive with [`crate::receive::ReceiveError::TimedOut`] if no
+    /// data arrives for this long. A slow transfer is not affected as long as
+    /// it makes progress. Retried like a dropped connection, see
+    /// [`ReceiveArgs::stream_reconnects`].
+    pub timeout: Option<Duration>,
+    /// Limit the rate at which a send serves data to all its receivers
+    /// together, in bytes per second. Ignored by receives. A send with a rate
+    /// of zero fails.
+    pub max_upload_bps: Option<u64>,
+    /// Limit the rate at which a receive downloads, in bytes per second,
+    /// across reconnects. Ignored by sends. A receive with a rate of zero
+    /// fails.
+    pub max_download_bps: Option<u64>,
+    /// Prefix of the temporary directories in [`Self::temp_dir`], followed by
+    /// `send-` or `recv-`. Defaults to [`DEFAULT_TEMP_PREFIX`]. Embedders can
+    /// set their own, so [`crate::remove_temp_dirs`] only removes theirs.
+    pub temp_prefix: Option<String>,
+}
+
+/// The default for [`CommonConfig::temp_prefix`].
+pub const DEFAULT_TEMP_PREFIX: &str = ".sendme-";
+
+impl CommonConfig {
+    /// The name of the temporary directory of a transfer, e.g.
+    /// `.sendme-recv-<hash>`.
+    pub(crate) fn temp_name(&self, kind: &str, id: &str) -> String {
+        let prefix = self.temp_prefix.as_deref().unwrap_or(DEFAULT_TEMP_PREFIX);
+        format!("{prefix}{kind}-{id}")
+    }
+
+    /// A config for tests that connect over the local network only, with the
+    /// data of the transfer kept in `temp_dir`.
+    #[cfg(test)]
+    pub(crate) fn loopback(temp_dir: &std::path::Path) -> Self {
+        Self {
+            relay: RelayModeOption::Disabled,
+            temp_dir: Some(temp_dir.to_path_buf()),
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for CommonConfig {
@@ -148,6 +265,15 @@ impl Default for CommonConfig {
             relay: RelayModeOption::Default,
             show_secret: false,
             temp_dir: None,
+            priority: Priority::default(),
+            bind_port: None,
+            normalize_names: true,
+            import_batch_threshold: crate::import::DEFAULT_BATCH_THRESHOLD,
+            store_backend: StoreBackend::default(),
+            timeout: None,
+            max_upload_bps: None,
+            max_download_bps: None,
+            temp_prefix: None,
         }
     }
 }
@@ -157,10 +283,193 @@ impl Default for CommonConfig {
 pub struct SendArgs {
     /// Path to the file or directory to send.
     pub path: PathBuf,
+    /// More files or directories to send in the same collection as `path`.
+    /// Each one is added under its own name. If two have the same name, the
+    /// later one gets its position as a prefix, e.g. `2-notes.txt`.
+    pub paths: Vec<PathBuf>,
     /// What type of ticket to use.
     pub ticket_type: AddrInfoOptions,
     /// Common configuration.
     pub common: CommonConfig,
+    /// Optional approval of incoming connections.
+    /// If not set, all connections are accepted.
+    pub on_connect: Option<OnConnect>,
+    /// Optional text, e.g. a description or license, sent along with the data
+    /// as [`crate::import::NOTE_NAME`].
+    pub attach_note: Option<String>,
+    /// If `path` is a directory with exactly one file, send that file under its
+    /// bare name instead of `dir/file`.
+    pub unwrap_single_file: bool,
+    /// URL of a short link service to register the ticket with, see
+    /// [`crate::shortlink`]. Needs the `url` feature.
+    pub shortlink_service: Option<String>,
+    /// Read every file again after importing it, and fail if it doesn't hash
+    /// the same. This catches flaky reads from a failing disk before bad data
+    /// is shared, at the cost of reading everything twice.
+    pub double_read_verify: bool,
+    /// Cancel the send while it imports the data. The send then fails with
+    /// [`crate::send::Cancelled`], and its temporary store is removed.
+    pub cancel: Option<tokio_util::sync::CancellationToken>,
+    /// Wait for the endpoint to be online before importing, see
+    /// [`crate::prewarm`], so the ticket has the relay address right away.
+    pub prewarm: bool,
+    /// Use [`Self::ticket_type`] as is. Otherwise relay URLs are added to an
+    /// [`AddrInfoOptions::Addresses`] ticket when the network report says
+    /// that receivers are unlikely to connect directly, e.g. behind a
+    /// symmetric NAT. [`SendResult::ticket_type`] tells what was used.
+    pub strict_ticket_type: bool,
+    /// Import a file once more if it changed while it was imported, e.g.
+    /// because it is still being written. Changed files are always reported
+    /// as [`crate::ImportProgress::FileChanged`].
+    pub reimport_changed: bool,
+    /// What to do with files whose names are not valid UTF-8.
+    pub non_utf8_names: NonUtf8Policy,
+    /// Leave out what the `.gitignore` files in the sent directories ignore,
+    /// with git's rules: nested `.gitignore` files apply to their own
+    /// directory, and `!pattern` includes a file again. `.git` directories
+    /// are always left out. Sending fails if nothing is left of a directory
+    /// that had files.
+    pub respect_gitignore: bool,
+    /// Leave out files whose name in the collection matches one of these
+    /// globs. Names include the sent directory, so `data/cache/**` or
+    /// `**/*.tmp` match `data/cache/x.bin` and `data/a.tmp`. As with
+    /// [`Self::respect_gitignore`], nothing being left is an error.
+    pub exclude: Vec<String>,
+    /// Send the targets of symlinks, named like the links, and walk linked
+    /// directories. By default symlinks are left out. A link to a directory
+    /// above it, or to nothing, is left out with a warning.
+    pub follow_symlinks: bool,
+    /// Record the permissions and modification times of all files, as
+    /// [`crate::import::FILE_METADATA`], so receivers can restore them with
+    /// [`ReceiveArgs::apply_sender_metadata`]. Without it, only the
+    /// permissions of executable files are recorded.
+    pub send_metadata: bool,
+}
+
+impl SendArgs {
+    /// Send `path` with the default options, which can be changed on the
+    /// result.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            paths: Vec::new(),
+            ticket_type: AddrInfoOptions::default(),
+            common: CommonConfig::default(),
+            on_connect: None,
+            attach_note: None,
+            unwrap_single_file: false,
+            shortlink_service: None,
+            double_read_verify: false,
+            cancel: None,
+            prewarm: false,
+            strict_ticket_type: false,
+            reimport_changed: false,
+            non_utf8_names: NonUtf8Policy::default(),
+            respect_gitignore: false,
+            exclude: Vec::new(),
+            follow_symlinks: false,
+            send_metadata: false,
+        }
+    }
+}
+
+/// What to do with a file whose name is not valid UTF-8, which collection
+/// names must be. Such names are common on Linux file systems.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Display, FromStr, Serialize, Deserialize)]
+pub enum NonUtf8Policy {
+    /// Fail the import.
+    #[default]
+    Error,
+    /// Leave the file out, and report it as
+    /// [`crate::ImportProgress::FileSkipped`].
+    Skip,
+    /// Replace the invalid bytes with U+FFFD. The original names are added
+    /// to the collection as [`crate::import::ORIGINAL_NAMES`], and restored on
+    /// export on unix.
+    Lossy,
+}
+
+/// Information about a receiver that is connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectInfo {
+    /// Endpoint ID of the receiver, if known.
+    pub node_id: Option<iroh::EndpointId>,
+    /// ID of the connection, as used in [`crate::ConnectionStatus`] events.
+    pub connection_id: u64,
+}
+
+/// Whether to accept an incoming connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectDecision {
+    /// Serve data on the connection.
+    Accept,
+    /// Close the connection.
+    Reject,
+}
+
+/// A function to approve incoming connections on the sender.
+#[derive(Clone)]
+pub struct OnConnect(Arc<dyn Fn(ConnectInfo) -> ConnectDecision + Send + Sync>);
+
+impl OnConnect {
+    /// Create an approval callback from a function.
+    pub fn new(f: impl Fn(ConnectInfo) -> ConnectDecision + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Decide whether to accept a connection.
+    pub fn decide(&self, info: ConnectInfo) -> ConnectDecision {
+        (self.0)(info)
+    }
+}
+
+impl std::fmt::Debug for OnConnect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OnConnect(..)")
+    }
+}
+
+/// Arguments for sending several files as a single collection.
+#[derive(Clone, Debug)]
+pub struct SendManyArgs {
+    /// The files to send.
+    pub entries: Vec<crate::import::ImportEntry>,
+    /// Put all files in a folder with this name, so the receiver gets a
+    /// single directory instead of loose files.
+    pub root: Option<String>,
+    /// What type of ticket to use.
+    pub ticket_type: AddrInfoOptions,
+    /// Common configuration.
+    pub common: CommonConfig,
+    /// Optional approval of incoming connections.
+    pub on_connect: Option<OnConnect>,
+}
+
+/// Arguments for sending in-memory data with [`crate::send_bytes`].
+#[derive(Clone, Debug)]
+pub struct SendBytesArgs {
+    /// What type of ticket to use.
+    pub ticket_type: AddrInfoOptions,
+    /// Common configuration. With [`StoreBackend::Mem`], the send writes
+    /// nothing to disk.
+    pub common: CommonConfig,
+    /// Optional approval of incoming connections.
+    pub on_connect: Option<OnConnect>,
+}
+
+/// Arguments for a long-running [`crate::server::Server`].
+#[derive(Clone, Debug)]
+pub struct ServerArgs {
+    /// Directory of the store that holds the served data. It is kept when
+    /// the server stops.
+    pub store_dir: PathBuf,
+    /// What type of ticket to use.
+    pub ticket_type: AddrInfoOptions,
+    /// Common configuration. [`CommonConfig::store_backend`] is ignored, the
+    /// server always uses a store on disk.
+    pub common: CommonConfig,
+    /// Optional approval of incoming connections.
+    pub on_connect: Option<OnConnect>,
 }
 
 /// Arguments for receiving data.
@@ -173,6 +482,198 @@ pub struct ReceiveArgs {
     /// Optional export directory for final file location.
     /// If not set, files will be exported to temp_dir.
     pub export_dir: Option<PathBuf>,
+    /// Only download and export these collection entries.
+    /// If not set, the whole collection is received.
+    pub only_files: Option<Vec<String>>,
+    /// Optional function to rename collection entries on export.
+    pub name_rewrite: Option<NameRewrite>,
+    /// If set, only connect through these relays.
+    ///
+    /// Other relays in the ticket are ignored. Receiving fails if this leaves
+    /// no way to reach the sender.
+    pub allowed_relays: Option<Vec<RelayUrl>>,
+    /// Skip files that can't be exported instead of failing the receive.
+    ///
+    /// The skipped files are listed in [`ReceiveResult::failed_files`].
+    pub continue_on_export_error: bool,
+    /// The sender's [`crate::links::verification_code`], as told to the
+    /// receiver over a trusted channel.
+    ///
+    /// If set, the receive fails before connecting if the ticket's code is
+    /// different.
+    pub expect_code: Option<String>,
+    /// Reuse files that are already in the export directory.
+    ///
+    /// Before downloading, existing files with the name and content of a
+    /// collection entry are added to the local store, so only new and changed
+    /// files are transferred.
+    pub merge_with_existing: bool,
+    /// How many times to reconnect when the connection to the sender drops
+    /// mid-download, with an exponential backoff between attempts.
+    ///
+    /// Data that was already received is kept, so each attempt only fetches
+    /// what is missing. Zero fails on the first dropped connection.
+    pub stream_reconnects: u32,
+    /// Stream the file of a single-file transfer into this named pipe as it
+    /// downloads, so a reader like a media player can start right away.
+    ///
+    /// If not set, a named pipe that already exists at the export path of the
+    /// file, directly in the export directory, is used the same way. Named
+    /// pipes are only supported on unix.
+    pub fifo: Option<PathBuf>,
+    /// Wait for the endpoint to be online before connecting, see
+    /// [`crate::prewarm`]. The wait is reported as [`ReceivePhases::prewarm`],
+    /// so it doesn't count as connecting.
+    pub prewarm: bool,
+    /// Export into this directory first, and move the files to the export
+    /// directory only after they were checked against their hashes.
+    ///
+    /// If the check fails, the export directory is left untouched, see
+    /// [`crate::export::export_staged`]. Ignored when streaming into a pipe.
+    pub staging_dir: Option<PathBuf>,
+    /// Extract a received single file that is a `.tar` or `.zip` archive into
+    /// the export directory, and remove the archive.
+    ///
+    /// Archives with entries outside of the export directory are rejected,
+    /// see [`crate::archive::extract`].
+    pub auto_extract: bool,
+    /// Sync each exported file and its directory entry to disk before it is
+    /// reported as completed, so it survives a crash or power loss. Off by
+    /// default, since it makes exports of many small files much slower.
+    pub fsync: bool,
+    /// Which of the relays listed in the ticket to connect through.
+    pub relay_selection: RelaySelection,
+    /// Give exported files the modification times and permissions the sender
+    /// recorded with [`SendArgs::send_metadata`], instead of the time of the
+    /// export. Without it, only the permissions of executable files are
+    /// restored.
+    pub apply_sender_metadata: bool,
+    /// What to do with a file that already exists in the export directory.
+    pub on_conflict: ExportConflict,
+    /// After the export, write [`crate::export::MANIFEST`] into the export
+    /// directory, listing the BLAKE3 hash of each file.
+    pub write_manifest: bool,
+}
+
+/// How a receiver picks a relay when the ticket lists more than one.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Display, FromStr, Serialize, Deserialize)]
+pub enum RelaySelection {
+    /// Leave the choice to iroh.
+    #[default]
+    First,
+    /// Measure the round trip time to each relay before connecting, and only
+    /// use the fastest one. If no relay answers, this is the same as
+    /// [`RelaySelection::First`].
+    LowestLatency,
+}
+
+/// What an export does with a file that already exists at the target path.
+///
+/// Files are always written under a temporary name first and renamed into
+/// place once complete, so an interrupted export never leaves partial files.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Display, FromStr, Serialize, Deserialize)]
+pub enum ExportConflict {
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Keep the existing file, and leave the received one out.
+    Skip,
+    /// Export the received file next to the existing one, with a number
+    /// added to its name, e.g. `notes (1).txt`.
+    Rename,
+}
+
+impl ReceiveArgs {
+    /// Receive `ticket` into the current directory with the default options,
+    /// which can be changed on the result.
+    pub fn new(ticket: BlobTicket) -> Self {
+        Self {
+            ticket,
+            common: CommonConfig::default(),
+            export_dir: None,
+            only_files: None,
+            name_rewrite: None,
+            allowed_relays: None,
+            continue_on_export_error: false,
+            expect_code: None,
+            merge_with_existing: false,
+            stream_reconnects: crate::receive::DEFAULT_STREAM_RECONNECTS,
+            fifo: None,
+            prewarm: false,
+            staging_dir: None,
+            auto_extract: false,
+            fsync: false,
+            relay_selection: RelaySelection::First,
+            apply_sender_metadata: false,
+            on_conflict: ExportConflict::Overwrite,
+            write_manifest: false,
+        }
+    }
+
+    /// Receive the collection `hash` from the endpoint at `addr`, without a
+    /// ticket, e.g. when the address is known from elsewhere.
+    ///
+    /// An address without relay URL or direct addresses is looked up like an
+    /// endpoint ID ticket. All options have their defaults and can be changed
+    /// on the result.
+    pub fn from_node_addr(
+        addr: iroh::EndpointAddr,
+        hash: iroh_blobs::Hash,
+        format: iroh_blobs::BlobFormat,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            format == iroh_blobs::BlobFormat::HashSeq,
+            "only collections can be received, not {:?} blobs",
+            format
+        );
+        Ok(Self::new(BlobTicket::new(addr, hash, format)))
+    }
+
+    /// The options for exporting the received collection.
+    pub(crate) fn export_options(&self) -> crate::export::ExportOptions {
+        crate::export::ExportOptions {
+            priority: self.common.priority,
+            continue_on_error: self.continue_on_export_error,
+            fsync: self.fsync,
+            apply_metadata: self.apply_sender_metadata,
+            on_conflict: self.on_conflict,
+        }
+    }
+}
+
+/// A function to rename collection entries on export.
+///
+/// The rewritten names are validated, so a rewrite can not be used to write
+/// outside of the export directory.
+#[derive(Clone)]
+pub struct NameRewrite(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl NameRewrite {
+    /// Create a rewrite from a function mapping collection names to export names.
+    pub fn new(f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Rewrite a name, failing if the result is not a safe relative path.
+    pub fn apply(&self, name: &str) -> anyhow::Result<String> {
+        let rewritten = (self.0)(name);
+        for part in rewritten.split('/') {
+            crate::validate_path_component(part)?;
+            anyhow::ensure!(
+                !matches!(part, "" | "." | "..") && !part.contains('\\'),
+                "invalid rewrite of {} to {}",
+                name,
+                rewritten
+            );
+        }
+        Ok(rewritten)
+    }
+}
+
+impl std::fmt::Debug for NameRewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NameRewrite(..)")
+    }
 }
 
 /// Result from a send operation.
@@ -188,6 +689,83 @@ pub struct SendResult {
     pub import_duration: std::time::Duration,
     /// Ticket for receiving the data.
     pub ticket: BlobTicket,
+    /// What type of ticket was created.
+    pub ticket_type: AddrInfoOptions,
+    /// Time spent in each phase of the send.
+    pub phases: SendPhases,
+    /// Local socket addresses the endpoint is bound to.
+    pub bound_addrs: Vec<std::net::SocketAddr>,
+    /// The connected receivers, see [`crate::send::SendHandle`].
+    pub handle: crate::send::SendHandle,
+    /// The short link for the ticket, if [`SendArgs::shortlink_service`] was set.
+    pub short_link: Option<String>,
+}
+
+impl SendResult {
+    /// A short code to confirm the ticket out of band, see
+    /// [`crate::links::verification_code`].
+    pub fn verification_code(&self) -> String {
+        crate::links::verification_code(&self.ticket)
+    }
+
+    /// How long receiving all files takes at `bytes_per_sec`, see
+    /// [`crate::throttle::transfer_time`].
+    pub fn estimated_transfer_time(&self, bytes_per_sec: u64) -> std::time::Duration {
+        crate::throttle::transfer_time(self.total_size, bytes_per_sec)
+    }
+
+    /// Machine readable statistics about the send.
+    pub fn to_stats_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "operation": "send",
+            "hash": self.hash.to_string(),
+            "files": self.collection.len(),
+            "total_size": self.total_size,
+            "addr": self.addr_summary(),
+            "bound_addrs": self.bound_addrs,
+            "phases": {
+                "bind_secs": self.phases.bind.as_secs_f64(),
+                "prewarm_secs": self.phases.prewarm.as_secs_f64(),
+                "import_secs": self.phases.import.as_secs_f64(),
+            },
+        })
+    }
+
+    /// Summarize the addressing information embedded in the ticket.
+    pub fn addr_summary(&self) -> AddrSummary {
+        let addr = self.ticket.addr();
+        AddrSummary {
+            node_id: addr.id.to_string(),
+            relay_urls: addr.relay_urls().map(|url| url.to_string()).collect(),
+            direct_addrs: addr.ip_addrs().map(|addr| addr.to_string()).collect(),
+            ticket_type: self.ticket_type,
+        }
+    }
+}
+
+/// Addressing information of a ticket, for display purposes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddrSummary {
+    /// Endpoint ID of the sender.
+    pub node_id: String,
+    /// Relay URLs a receiver can connect through.
+    pub relay_urls: Vec<String>,
+    /// Direct addresses a receiver can connect to.
+    pub direct_addrs: Vec<String>,
+    /// What type of ticket was created.
+    pub ticket_type: AddrInfoOptions,
+}
+
+impl AddrSummary {
+    /// Whether a receiver on the same network can connect directly.
+    pub fn has_direct_addrs(&self) -> bool {
+        !self.direct_addrs.is_empty()
+    }
+
+    /// Whether the ticket can only be used through a relay.
+    pub fn is_relay_only(&self) -> bool {
+        self.direct_addrs.is_empty() && !self.relay_urls.is_empty()
+    }
 }
 
 /// Result from a receive operation.
@@ -201,4 +779,286 @@ pub struct ReceiveResult {
     pub payload_size: u64,
     /// Statistics about the transfer.
     pub stats: iroh_blobs::get::Stats,
+    /// Endpoint ID of the sender.
+    pub sender: iroh::EndpointId,
+    /// Time spent in each phase of the receive.
+    pub phases: ReceivePhases,
+    /// Files that could not be exported, with `continue_on_export_error`.
+    pub failed_files: Vec<String>,
+    /// Local socket addresses the endpoint was bound to.
+    pub bound_addrs: Vec<std::net::SocketAddr>,
+    /// Highest download rate in bytes per second, measured over one second
+    /// windows. See [`crate::ThroughputTracker`].
+    pub peak_throughput: f64,
+}
+
+impl ReceiveResult {
+    /// Average download rate in bytes per second, or zero if nothing was
+    /// downloaded.
+    pub fn average_throughput(&self) -> f64 {
+        bytes_per_sec(self.stats.total_bytes_read(), self.stats.elapsed)
+    }
+
+    /// Machine readable statistics about the receive.
+    pub fn to_stats_json(&self) -> serde_json::Value {
+        let bytes_read = self.stats.total_bytes_read();
+        let download_secs = self.phases.download.as_secs_f64();
+        serde_json::json!({
+            "operation": "receive",
+            "sender": self.sender.to_string(),
+            "files": self.total_files,
+            "payload_size": self.payload_size,
+            "bytes_read": bytes_read,
+            "throughput_bytes_per_sec": self.average_throughput(),
+            "peak_throughput_bytes_per_sec": self.peak_throughput,
+            "bound_addrs": self.bound_addrs,
+            "phases": {
+                "prewarm_secs": self.phases.prewarm.as_secs_f64(),
+                "connect_secs": self.phases.connect.as_secs_f64(),
+                "sizes_secs": self.phases.sizes.as_secs_f64(),
+                "download_secs": download_secs,
+                "export_secs": self.phases.export.as_secs_f64(),
+            },
+            "failed_files": self.failed_files,
+        })
+    }
+}
+
+/// The rate of transferring `bytes` in `elapsed`, or zero if no time passed.
+fn bytes_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        bytes as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+/// Time spent in each phase of a receive.
+///
+/// Phases that were skipped, e.g. because the data was already cached, are zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReceivePhases {
+    /// Waiting for the endpoint to be online, see [`ReceiveArgs::prewarm`].
+    pub prewarm: Duration,
+    /// Connecting to the sender.
+    pub connect: Duration,
+    /// Getting the sizes of the blobs.
+    pub sizes: Duration,
+    /// Downloading the data.
+    pub download: Duration,
+    /// Exporting the files.
+    pub export: Duration,
+}
+
+/// Time spent in each phase of a send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendPhases {
+    /// Binding the endpoint.
+    pub bind: Duration,
+    /// Waiting for the endpoint to be online, see [`SendArgs::prewarm`].
+    pub prewarm: Duration,
+    /// Importing the files.
+    pub import: Duration,
+}
+
+/// The directories of a transfer over the local network only, for tests of
+/// both sides. Files are sent from `source` and received into `export_dir`.
+#[cfg(test)]
+pub(crate) struct LoopbackTransfer {
+    pub source: tempfile::TempDir,
+    pub send_dir: tempfile::TempDir,
+    pub recv_dir: tempfile::TempDir,
+    pub export_dir: tempfile::TempDir,
+}
+
+#[cfg(test)]
+impl LoopbackTransfer {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            source: tempfile::tempdir()?,
+            send_dir: tempfile::tempdir()?,
+            recv_dir: tempfile::tempdir()?,
+            export_dir: tempfile::tempdir()?,
+        })
+    }
+
+    /// Write `data` to `name` in `source`, creating its parent directories.
+    pub fn write(&self, name: &str, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+        let path = self.source.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, data)
+    }
+
+    /// The args to send `name` from `source`.
+    pub fn send_args(&self, name: &str) -> SendArgs {
+        SendArgs {
+            ticket_type: AddrInfoOptions::Addresses,
+            common: CommonConfig::loopback(self.send_dir.path()),
+            ..SendArgs::new(self.source.path().join(name))
+        }
+    }
+
+    /// Write `data` to `name` and send it with the default args.
+    pub async fn send(&self, name: &str, data: impl AsRef<[u8]>) -> anyhow::Result<SendResult> {
+        self.write(name, data)?;
+        crate::send(self.send_args(name)).await
+    }
+
+    /// The args to receive `ticket` into `export_dir`, without reconnecting.
+    pub fn receive_args(&self, ticket: BlobTicket) -> ReceiveArgs {
+        ReceiveArgs {
+            common: CommonConfig::loopback(self.recv_dir.path()),
+            export_dir: Some(self.export_dir.path().to_path_buf()),
+            stream_reconnects: 0,
+            ..ReceiveArgs::new(ticket)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_addr() -> iroh::EndpointAddr {
+        let id = iroh::SecretKey::generate(&mut rand::rng()).public();
+        let relay: RelayUrl = "https://relay.example.com".parse().unwrap();
+        iroh::EndpointAddr {
+            id,
+            addrs: [
+                TransportAddr::Relay(relay),
+                TransportAddr::Ip("192.168.1.2:1234".parse().unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn test_addr_summary_matches_ticket() {
+        for ticket_type in [
+            AddrInfoOptions::Id,
+            AddrInfoOptions::RelayAndAddresses,
+            AddrInfoOptions::Relay,
+            AddrInfoOptions::Addresses,
+        ] {
+            let mut addr = test_addr();
+            apply_options(&mut addr, ticket_type);
+            let hash = iroh_blobs::Hash::new("collection");
+            let ticket = BlobTicket::new(addr.clone(), hash, iroh_blobs::BlobFormat::HashSeq);
+            let result = SendResult {
+                hash,
+                collection: Default::default(),
+                total_size: 0,
+                import_duration: Duration::ZERO,
+                ticket,
+                ticket_type,
+                phases: SendPhases::default(),
+                bound_addrs: vec![],
+                handle: Default::default(),
+                short_link: None,
+            };
+
+            let stats = result.to_stats_json();
+            assert_eq!(stats["addr"]["node_id"], addr.id.to_string());
+            for phase in ["bind_secs", "import_secs"] {
+                assert!(stats["phases"][phase].as_f64().unwrap() >= 0.0);
+            }
+
+            let summary = result.addr_summary();
+            assert_eq!(summary.node_id, addr.id.to_string());
+            assert_eq!(summary.ticket_type, ticket_type);
+            let relay_urls = addr.relay_urls().map(|u| u.to_string()).collect::<Vec<_>>();
+            let direct_addrs = addr.ip_addrs().map(|a| a.to_string()).collect::<Vec<_>>();
+            assert_eq!(summary.relay_urls, relay_urls);
+            assert_eq!(summary.direct_addrs, direct_addrs);
+            assert_eq!(summary.is_relay_only(), ticket_type == AddrInfoOptions::Relay);
+            assert_eq!(
+                summary.has_direct_addrs(),
+                matches!(
+                    ticket_type,
+                    AddrInfoOptions::RelayAndAddresses | AddrInfoOptions::Addresses
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_on_connect_decision() {
+        let blocked = iroh::SecretKey::generate(&mut rand::rng()).public();
+        let other = iroh::SecretKey::generate(&mut rand::rng()).public();
+        let on_connect = OnConnect::new(move |info| {
+            if info.node_id == Some(blocked) {
+                ConnectDecision::Reject
+            } else {
+                ConnectDecision::Accept
+            }
+        });
+        let info = |node_id| ConnectInfo {
+            node_id: Some(node_id),
+            connection_id: 0,
+        };
+        assert_eq!(on_connect.decide(info(blocked)), ConnectDecision::Reject);
+        assert_eq!(on_connect.decide(info(other)), ConnectDecision::Accept);
+    }
+
+    #[test]
+    fn test_restrict_relays() {
+        let trusted: RelayUrl = "https://trusted.example.com".parse().unwrap();
+
+        let mut addr = test_addr();
+        apply_options(&mut addr, AddrInfoOptions::Relay);
+        let err = restrict_relays(&mut addr, std::slice::from_ref(&trusted)).unwrap_err();
+        assert!(err.to_string().contains("untrusted relays"));
+
+        let mut addr = test_addr();
+        restrict_relays(&mut addr, std::slice::from_ref(&trusted)).unwrap();
+        assert_eq!(addr.relay_urls().count(), 0);
+        assert_eq!(addr.ip_addrs().count(), 1);
+
+        let mut addr = test_addr();
+        let relay = addr.relay_urls().next().unwrap().clone();
+        restrict_relays(&mut addr, &[relay.clone(), trusted]).unwrap();
+        assert_eq!(addr.relay_urls().collect::<Vec<_>>(), vec![&relay]);
+    }
+
+    #[test]
+    fn test_receive_stats_json() {
+        let result = ReceiveResult {
+            collection: Default::default(),
+            total_files: 2,
+            payload_size: 1000,
+            stats: Default::default(),
+            sender: iroh::SecretKey::generate(&mut rand::rng()).public(),
+            phases: ReceivePhases {
+                prewarm: Duration::ZERO,
+                connect: Duration::from_millis(20),
+                sizes: Duration::from_millis(5),
+                download: Duration::from_secs(1),
+                export: Duration::from_millis(10),
+            },
+            failed_files: vec![],
+            bound_addrs: vec![],
+            peak_throughput: 1500.0,
+        };
+        let json = result.to_stats_json();
+        assert_eq!(json["operation"], "receive");
+        assert_eq!(json["payload_size"], 1000);
+        for phase in ["connect_secs", "sizes_secs", "download_secs", "export_secs"] {
+            let secs = json["phases"][phase].as_f64().unwrap();
+            assert!(secs >= 0.0, "{phase} is negative");
+        }
+        assert_eq!(json["phases"]["download_secs"], 1.0);
+        assert_eq!(json["phases"]["prewarm_secs"], 0.0);
+        // nothing was read in no time
+        assert_eq!(json["throughput_bytes_per_sec"], 0.0);
+        assert_eq!(json["peak_throughput_bytes_per_sec"], 1500.0);
+    }
+
+    #[test]
+    fn test_bytes_per_sec() {
+        assert_eq!(bytes_per_sec(1000, Duration::from_millis(500)), 2000.0);
+        assert_eq!(bytes_per_sec(1000, Duration::ZERO), 0.0);
+        assert_eq!(bytes_per_sec(0, Duration::from_secs(1)), 0.0);
+    }
 }
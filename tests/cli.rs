@@ -5,6 +5,7 @@ use std::{
 };
 
 use iroh_blobs::ticket::BlobTicket;
+use sendme_lib::progress::{DownloadProgress, ExportProgress, ProgressEvent};
 
 // binary path
 fn sendme_bin() -> &'static str {
@@ -136,3 +137,174 @@ fn send_recv_dir() {
         }
     }
 }
+
+#[test]
+fn send_json_output_is_plain() {
+    let name = "somefile.bin";
+    let src_dir = tempfile::tempdir().unwrap();
+    let src_file = src_dir.path().join(name);
+    std::fs::write(&src_file, vec![0u8; 100]).unwrap();
+    let mut send_cmd = duct::cmd(
+        sendme_bin(),
+        [
+            "send",
+            src_file.as_os_str().to_str().unwrap(),
+            "--no-qr",
+            "--no-progress",
+            "--json",
+        ],
+    )
+    .dir(src_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_null()
+    .reader()
+    .unwrap();
+    let output = read_ascii_lines(1, &mut send_cmd).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(!output.contains('\x1b'), "unexpected escape in {output:?}");
+    let json: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!(json["name"], name);
+    assert_eq!(json["size"], 100);
+    let ticket = json["ticket"].as_str().unwrap();
+    BlobTicket::from_str(ticket).unwrap();
+}
+
+#[test]
+fn send_recv_plan() {
+    // create src and tgt dir, and two src files
+    let src_dir = tempfile::tempdir().unwrap();
+    let tgt_dir = tempfile::tempdir().unwrap();
+    let src_data_dir = src_dir.path().join("data");
+    std::fs::create_dir_all(&src_data_dir).unwrap();
+    std::fs::write(src_data_dir.join("a.txt"), b"first").unwrap();
+    std::fs::write(src_data_dir.join("b.txt"), b"second file").unwrap();
+    let mut send_cmd = duct::cmd(
+        sendme_bin(),
+        ["send", src_data_dir.as_os_str().to_str().unwrap()],
+    )
+    .dir(src_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_to_stdout()
+    .reader()
+    .unwrap();
+    let output = read_ascii_lines(3, &mut send_cmd).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let ticket = output.split_ascii_whitespace().last().unwrap();
+    let ticket = BlobTicket::from_str(ticket).unwrap();
+
+    // write the plan, without downloading any data
+    let plan_file = tgt_dir.path().join("plan.json");
+    let plan_output = duct::cmd(
+        sendme_bin(),
+        [
+            "receive",
+            &ticket.to_string(),
+            "--plan-only",
+            plan_file.as_os_str().to_str().unwrap(),
+        ],
+    )
+    .dir(tgt_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_to_stdout()
+    .run()
+    .unwrap();
+    assert!(plan_output.status.success());
+    assert!(!tgt_dir.path().join("data").exists());
+    let plan: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&plan_file).unwrap()).unwrap();
+    assert_eq!(plan["total_size"], 16);
+    let names = plan["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["data/a.txt", "data/b.txt"]);
+
+    // execute a filtered subset of the plan
+    let receive_output = duct::cmd(
+        sendme_bin(),
+        [
+            "receive",
+            "--from-plan",
+            plan_file.as_os_str().to_str().unwrap(),
+            "--only",
+            "data/a.txt",
+        ],
+    )
+    .dir(tgt_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_to_stdout()
+    .run()
+    .unwrap();
+    assert!(receive_output.status.success());
+    let tgt_data_dir = tgt_dir.path().join("data");
+    assert_eq!(std::fs::read(tgt_data_dir.join("a.txt")).unwrap(), b"first");
+    assert!(!tgt_data_dir.join("b.txt").exists());
+}
+
+#[test]
+fn recv_progress_json() {
+    let name = "somefile.bin";
+    let src_dir = tempfile::tempdir().unwrap();
+    let tgt_dir = tempfile::tempdir().unwrap();
+    let src_file = src_dir.path().join(name);
+    std::fs::write(&src_file, vec![0u8; 100]).unwrap();
+    let mut send_cmd = duct::cmd(
+        sendme_bin(),
+        [
+            "send",
+            src_file.as_os_str().to_str().unwrap(),
+            "--no-qr",
+            "--no-progress",
+            "--json",
+        ],
+    )
+    .dir(src_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_null()
+    .reader()
+    .unwrap();
+    let output = read_ascii_lines(1, &mut send_cmd).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let ticket = json["ticket"].as_str().unwrap();
+
+    // progress events are written without progress bars
+    let progress_file = tgt_dir.path().join("progress.jsonl");
+    let receive_output = duct::cmd(
+        sendme_bin(),
+        [
+            "receive",
+            ticket,
+            "--no-progress",
+            "--progress-json",
+            progress_file.as_os_str().to_str().unwrap(),
+        ],
+    )
+    .dir(tgt_dir.path())
+    .env_remove("RUST_LOG") // disable tracing
+    .stderr_to_stdout()
+    .run()
+    .unwrap();
+    assert!(receive_output.status.success());
+    let events = std::fs::read_to_string(&progress_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<ProgressEvent>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        events.first(),
+        Some(ProgressEvent::Download(DownloadProgress::Connecting))
+    ));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ProgressEvent::Download(DownloadProgress::Metadata { file_count: 1, .. })
+    )));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, ProgressEvent::Export(_, ExportProgress::Completed))));
+    assert!(matches!(
+        events.last(),
+        Some(ProgressEvent::Download(DownloadProgress::Completed))
+    ));
+}